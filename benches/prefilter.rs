@@ -0,0 +1,89 @@
+//! Measures the literal prefilter on a synthetic log corpus.
+//!
+//! Each pattern is matched against every line twice: once with the plain
+//! regex, and once with `Matcher`, which first looks for a literal every
+//! match must contain and only runs the regex on lines that have it.
+//!
+//! ```text
+//! cargo bench --bench prefilter
+//! ```
+
+use regex::bytes::Regex;
+use searcher_cli_starter::Matcher;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const LINES: usize = 500_000;
+const ITERATIONS: u32 = 5;
+
+/// Builds a log where roughly one line in a thousand is a timeout.
+fn corpus() -> Vec<Vec<u8>> {
+    let levels = ["INFO", "DEBUG", "WARN", "INFO", "TRACE"];
+    let services = ["api", "db", "cache", "auth", "queue", "billing"];
+    (0..LINES)
+        .map(|i| {
+            let level = levels[i % levels.len()];
+            let service = services[i % services.len()];
+            let message = if i % 997 == 0 {
+                format!("request {} timeout after {}ms", i, i % 5000)
+            } else {
+                format!("request {} handled in {}ms status=200", i, i % 300)
+            };
+            format!(
+                "2024-03-01T12:{:02}:{:02} {level} [{service}] user={} {message}",
+                i / 60 % 60,
+                i % 60,
+                i % 10_000
+            )
+            .into_bytes()
+        })
+        .collect()
+}
+
+fn time(mut search: impl FnMut() -> usize) -> (Duration, usize) {
+    let matches = search();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(search());
+    }
+    (start.elapsed() / ITERATIONS, matches)
+}
+
+fn main() {
+    let lines = corpus();
+    let bytes: usize = lines.iter().map(|line| line.len() + 1).sum();
+    println!("Corpus: {} lines, {:.1} MB", LINES, bytes as f64 / 1e6);
+    println!(
+        "{:<32} {:>12} {:>12} {:>8}",
+        "PATTERN", "REGEX", "PREFILTER", "SPEEDUP"
+    );
+
+    for pattern in [
+        r"timeout after \d+ms",
+        r"\[billing\] user=\d+ request \d+ timeout",
+        r"user=42\b",
+        r"status=5\d\d",
+        // `ms` is too short to prefilter on, so both sides run the regex
+        r"\d{4}ms",
+    ] {
+        let regex = Regex::new(pattern).unwrap();
+        let matcher = Matcher::new(pattern, false, true).unwrap();
+
+        let (plain, expected) = time(|| lines.iter().filter(|line| regex.is_match(line)).count());
+        let (filtered, found) = time(|| {
+            lines
+                .iter()
+                .filter(|line| matcher.is_match_bytes(line))
+                .count()
+        });
+        assert_eq!(expected, found, "`{pattern}` matched different lines");
+
+        println!(
+            "{:<32} {:>9.2} ms {:>9.2} ms {:>7.2}x",
+            pattern,
+            plain.as_secs_f64() * 1000.0,
+            filtered.as_secs_f64() * 1000.0,
+            plain.as_secs_f64() / filtered.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Criterion benchmarks of the main search paths on generated corpora.
+//!
+//! Each corpus stands for input searcher sees in practice: plain ASCII
+//! logs, logs full of multi-byte UTF-8, minified files with very long
+//! lines, and a tree of many small files. Literal, case-insensitive, regex,
+//! and multi-pattern searches run over the in-memory corpora, and the tree
+//! and one large file are searched serially and on several threads.
+//!
+//! ```text
+//! cargo bench --bench search
+//! cargo bench --bench search -- utf8        # only the UTF-8 group
+//! cargo bench --bench search -- --save-baseline main
+//! cargo bench --bench search -- --baseline main
+//! ```
+//!
+//! The corpora are built the same way every run, so results can be compared
+//! between commits.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use searcher_cli_starter::multi::PatternSet;
+use searcher_cli_starter::split::{Split, search_path_split};
+use searcher_cli_starter::threads::Threads;
+use searcher_cli_starter::walk::{Walk, each_file_in, each_file_parallel};
+use searcher_cli_starter::{Matcher, SearchOptions, search_path, search_reader};
+use std::fs;
+use std::hint::black_box;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// Lines in each in-memory corpus.
+const LINES: usize = 100_000;
+/// Files in the many-small-files tree.
+const FILES: usize = 2_000;
+
+/// A log of ASCII lines where roughly one line in a thousand is a timeout.
+fn ascii_log() -> Vec<u8> {
+    let levels = ["INFO", "DEBUG", "WARN", "INFO", "TRACE"];
+    let services = ["api", "db", "cache", "auth", "queue", "billing"];
+    let mut log = Vec::new();
+    for i in 0..LINES {
+        let message = if i % 997 == 0 {
+            format!("request {} timeout after {}ms", i, i % 5000)
+        } else {
+            format!("request {} handled in {}ms status=200", i, i % 300)
+        };
+        log.extend_from_slice(
+            format!(
+                "2024-03-01T12:{:02}:{:02} {} [{}] user={} {}\n",
+                i / 60 % 60,
+                i % 60,
+                levels[i % levels.len()],
+                services[i % services.len()],
+                i % 10_000,
+                message
+            )
+            .as_bytes(),
+        );
+    }
+    log
+}
+
+/// A log whose messages are mostly Cyrillic, Greek, CJK, and emoji, so
+/// nearly every byte is part of a multi-byte character.
+fn utf8_log() -> Vec<u8> {
+    let messages = [
+        "Запрос обработан успешно",
+        "Αίτημα ολοκληρώθηκε κανονικά",
+        "请求已成功处理",
+        "リクエストは正常に処理されました",
+        "요청이 성공적으로 처리되었습니다 ✅",
+    ];
+    let mut log = Vec::new();
+    for i in 0..LINES {
+        let message = if i % 997 == 0 {
+            "ОШИБКА: превышено время ожидания ⏱"
+        } else {
+            messages[i % messages.len()]
+        };
+        log.extend_from_slice(
+            format!("{} пользователь={} {}\n", i, i % 10_000, message).as_bytes(),
+        );
+    }
+    log
+}
+
+/// Minified-looking lines of about 64 KiB each, one in eight with a
+/// timeout near its end.
+fn long_lines() -> Vec<u8> {
+    let mut text = Vec::new();
+    for i in 0..64 {
+        for j in 0..2_000 {
+            text.extend_from_slice(format!("{{\"k{}\":{}}},", j, i * j).as_bytes());
+        }
+        if i % 8 == 0 {
+            text.extend_from_slice(b"\"error\":\"timeout after 30000ms\"");
+        }
+        text.push(b'\n');
+    }
+    text
+}
+
+/// Writes a tree of many small log files, 100 per directory, below the
+/// temporary directory, returning its root.
+fn small_files() -> PathBuf {
+    let root = std::env::temp_dir().join("searcher_bench_small_files");
+    fs::remove_dir_all(&root).ok();
+    for i in 0..FILES {
+        let dir = root.join(format!("{:02}", i / 100));
+        fs::create_dir_all(&dir).unwrap();
+        let mut text = String::new();
+        for line in 0..40 {
+            let message = if (i + line) % 97 == 0 {
+                "timeout after 30000ms"
+            } else {
+                "handled in 12ms status=200"
+            };
+            text.push_str(&format!("{} worker={} {}\n", line, i, message));
+        }
+        fs::write(dir.join(format!("{}.log", i)), text).unwrap();
+    }
+    root
+}
+
+/// Writes the ASCII log to a file eight times over, for splitting.
+fn large_file(log: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join("searcher_bench_large.log");
+    fs::write(&path, log.repeat(8)).unwrap();
+    path
+}
+
+/// Benchmarks literal, case-insensitive, regex, and multi-pattern searches
+/// of `corpus`.
+fn search_corpus(
+    c: &mut Criterion,
+    name: &str,
+    corpus: &[u8],
+    literal: &str,
+    regex: &str,
+    patterns: &[&str],
+) {
+    let options = SearchOptions::default();
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Bytes(corpus.len() as u64));
+    for (mode, matcher) in [
+        ("literal", Matcher::new(literal, false, false).unwrap()),
+        ("ignore_case", Matcher::new(literal, true, false).unwrap()),
+        ("regex", Matcher::new(regex, false, true).unwrap()),
+    ] {
+        group.bench_function(mode, |b| {
+            b.iter(|| search_reader(black_box(corpus), &matcher, &options).unwrap())
+        });
+    }
+    let set = PatternSet::new(patterns, false, false).unwrap();
+    group.bench_with_input(
+        BenchmarkId::new("multi_pattern", patterns.len()),
+        &set,
+        |b, set| b.iter(|| set.search_reader(black_box(corpus), &options).unwrap()),
+    );
+    group.finish();
+}
+
+fn ascii(c: &mut Criterion) {
+    search_corpus(
+        c,
+        "ascii",
+        &ascii_log(),
+        "TIMEOUT",
+        r"timeout after \d+ms",
+        &["timeout", "status=500", "[billing]", "user=42 "],
+    );
+}
+
+fn utf8(c: &mut Criterion) {
+    search_corpus(
+        c,
+        "utf8",
+        &utf8_log(),
+        "ошибка",
+        r"ОШИБКА: \w+",
+        &["ОШИБКА", "请求", "✅", "Αίτημα"],
+    );
+}
+
+fn long(c: &mut Criterion) {
+    search_corpus(
+        c,
+        "long_lines",
+        &long_lines(),
+        "TIMEOUT",
+        r"timeout after \d+ms",
+        &["timeout", "\"k1999\":0", "null"],
+    );
+}
+
+/// Counts the matching lines below `root`, on `threads` if given.
+fn search_tree(root: &Path, matcher: &Matcher, threads: Option<&Threads>) -> usize {
+    let options = SearchOptions::default();
+    let search = |path: &Path| search_path(path, matcher, &options);
+    let walk = Walk::new([root]);
+    match threads {
+        Some(threads) => thread::scope(|scope| {
+            each_file_parallel(scope, walk, threads, search, |error| panic!("{:#}", error))
+                .map(|(_, matches)| matches.len())
+                .sum()
+        }),
+        None => each_file_in(walk, search, |error| panic!("{:#}", error))
+            .map(|(_, matches)| matches.len())
+            .sum(),
+    }
+}
+
+fn parallel(c: &mut Criterion) {
+    let matcher = Matcher::new("timeout", false, false).unwrap();
+    let threads = Threads::available();
+
+    let root = small_files();
+    let mut group = c.benchmark_group("many_small_files");
+    group.throughput(Throughput::Elements(FILES as u64));
+    group.bench_function("serial", |b| b.iter(|| search_tree(&root, &matcher, None)));
+    group.bench_function(BenchmarkId::new("parallel", threads.count), |b| {
+        b.iter(|| search_tree(&root, &matcher, Some(&threads)))
+    });
+    group.finish();
+
+    let path = large_file(&ascii_log());
+    let options = SearchOptions::default();
+    let split = Split {
+        threads: threads.clone(),
+        range_size: 4 * 1024 * 1024,
+    };
+    let mut group = c.benchmark_group("large_file");
+    group.throughput(Throughput::Bytes(fs::metadata(&path).unwrap().len()));
+    group.sample_size(20);
+    group.bench_function("whole", |b| {
+        b.iter(|| search_path(&path, &matcher, &options).unwrap())
+    });
+    group.bench_function(BenchmarkId::new("split", threads.count), |b| {
+        b.iter(|| search_path_split(&path, &matcher, &options, &split).unwrap())
+    });
+    group.finish();
+
+    fs::remove_dir_all(root).ok();
+    fs::remove_file(path).ok();
+}
+
+criterion_group!(benches, ascii, utf8, long, parallel);
+criterion_main!(benches);
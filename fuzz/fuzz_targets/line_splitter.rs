@@ -0,0 +1,125 @@
+//! Splits arbitrary input into lines with the block-based readers and
+//! searches it, comparing each with splitting the input in one go.
+//!
+//! The input is handed to `LineReader` in chunks of arbitrary sizes and may
+//! be pushed past the end of the first 64 KiB block by a run of padding, so
+//! lines are cut at every kind of boundary. `ReverseLineReader` must give
+//! the same lines backwards, `count_lines` their number, and `search_reader`
+//! the lines the matcher matches, with their line numbers.
+//!
+//! ```text
+//! cargo +nightly fuzz run line_splitter
+//! ```
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use searcher_cli_starter::lines::{LineReader, ReverseLineReader, count_lines};
+use searcher_cli_starter::{LineTerminator, Matcher, SearchOptions, search_reader};
+use std::io::{self, Cursor, Read};
+
+#[derive(Arbitrary, Debug)]
+enum Terminator {
+    Lf,
+    Crlf,
+    Nul,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    terminator: Terminator,
+    /// Bytes of padding before `data`, in units of 2
+    padding: u16,
+    /// The sizes of the reads `LineReader` gets, in turn
+    chunks: Vec<u8>,
+    pattern: String,
+    data: Vec<u8>,
+}
+
+/// Hands out its input in reads of the given sizes, in turn.
+struct Chunked<'a> {
+    input: &'a [u8],
+    chunks: &'a [u8],
+    next: usize,
+}
+
+impl Read for Chunked<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let size = match self.chunks {
+            [] => buffer.len(),
+            chunks => usize::from(chunks[self.next % chunks.len()]).max(1),
+        };
+        self.next += 1;
+        let read = size.min(buffer.len()).min(self.input.len());
+        buffer[..read].copy_from_slice(&self.input[..read]);
+        self.input = &self.input[read..];
+        Ok(read)
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let line_terminator = match input.terminator {
+        Terminator::Lf => LineTerminator::Lf,
+        Terminator::Crlf => LineTerminator::Crlf,
+        Terminator::Nul => LineTerminator::Nul,
+    };
+    let terminator = line_terminator.byte();
+    let mut data = vec![b'x'; usize::from(input.padding) * 2];
+    data.extend_from_slice(&input.data);
+    let expected: Vec<&[u8]> = data.split_inclusive(|&byte| byte == terminator).collect();
+
+    let mut reader = LineReader::new(
+        Chunked {
+            input: &data,
+            chunks: &input.chunks,
+            next: 0,
+        },
+        terminator,
+    );
+    let mut lines = Vec::new();
+    while let Some(line) = reader.next_line().unwrap() {
+        lines.push(line.to_vec());
+    }
+    assert_eq!(lines, expected, "LineReader split the input differently");
+
+    let mut reader = ReverseLineReader::new(Cursor::new(&data), terminator).unwrap();
+    let mut lines = Vec::new();
+    while let Some(line) = reader.prev_line().unwrap() {
+        lines.push(line.to_vec());
+    }
+    lines.reverse();
+    assert_eq!(
+        lines, expected,
+        "ReverseLineReader split the input differently"
+    );
+
+    assert_eq!(count_lines(&data[..], terminator).unwrap(), expected.len());
+
+    let Ok(matcher) = Matcher::new(&input.pattern, false, false) else {
+        return;
+    };
+    let options = SearchOptions {
+        line_terminator,
+        ..SearchOptions::default()
+    };
+    let found: Vec<(usize, Vec<u8>)> = search_reader(&data[..], &matcher, &options)
+        .unwrap()
+        .into_iter()
+        .map(|search_match| (search_match.line_number, search_match.content))
+        .collect();
+    let wanted: Vec<(usize, Vec<u8>)> = expected
+        .iter()
+        .map(|line| {
+            let line = line.strip_suffix(&[terminator]).unwrap_or(line);
+            match line_terminator {
+                LineTerminator::Crlf => line.strip_suffix(b"\r").unwrap_or(line),
+                _ => line,
+            }
+        })
+        .enumerate()
+        .filter(|(_, line)| matcher.is_match_bytes(line))
+        .map(|(index, line)| (index + 1, line.to_vec()))
+        .collect();
+    assert_eq!(found, wanted, "search_reader matched different lines");
+});
@@ -0,0 +1,59 @@
+//! Builds a matcher from an arbitrary pattern and matches it against an
+//! arbitrary line.
+//!
+//! Besides panics, this checks that the `&str` and `&[u8]` entry points
+//! agree on valid UTF-8, and that the match ranges of a line lie within it,
+//! in order, and only on lines that match.
+//!
+//! ```text
+//! cargo +nightly fuzz run matcher
+//! ```
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use searcher_cli_starter::Matcher;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    pattern: String,
+    ignore_case: bool,
+    regex: bool,
+    line: Vec<u8>,
+}
+
+fuzz_target!(|input: Input| {
+    let Ok(matcher) = Matcher::new(&input.pattern, input.ignore_case, input.regex) else {
+        return;
+    };
+    let line = &input.line[..];
+    let matched = matcher.is_match_bytes(line);
+    if let Ok(text) = std::str::from_utf8(line) {
+        assert_eq!(
+            matcher.is_match(text),
+            matched,
+            "is_match and is_match_bytes disagree"
+        );
+    }
+
+    let ranges = matcher.match_ranges(line);
+    assert!(
+        matched || ranges.is_empty(),
+        "ranges on a line that does not match"
+    );
+    let mut end = 0;
+    for range in &ranges {
+        assert!(
+            range.start <= range.end && range.end <= line.len(),
+            "range {:?} out of bounds",
+            range
+        );
+        assert!(
+            range.start >= end,
+            "range {:?} overlaps the one before",
+            range
+        );
+        end = range.end;
+    }
+});
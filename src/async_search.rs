@@ -0,0 +1,537 @@
+//! Async search for services built on tokio.
+//!
+//! Available with the `async` cargo feature. The functions here mirror the
+//! blocking ones in the crate root but read through tokio's [`AsyncRead`],
+//! so searching an upload, a socket, or a directory tree never blocks an
+//! executor thread. Matching itself is CPU work and runs inline, one line at
+//! a time, between reads.
+//!
+//! [`SearchStream`] goes one step further and yields matches as a
+//! [`Stream`], reading only as fast as the consumer polls.
+//!
+//! ```
+//! use searcher_cli_starter::Matcher;
+//! use searcher_cli_starter::async_search::search_lines_async;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let body: &[u8] = b"GET /health 200\nGET /login 500\n";
+//! let matcher = Matcher::new(" 500", false, false)?;
+//! let results = search_lines_async(body, &matcher).await?;
+//! assert_eq!(results[0].line_number, 2);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::diagnostic::FileContext;
+use crate::walk::FileError;
+use crate::{
+    Matcher, SearchMatch, SearchMatchBytes, SearchOptions, cancel::Cancelled, check_line,
+    file_error, filter::Dedupe, last_requested_line,
+};
+use anyhow::{Context as _, Result};
+use futures_core::Stream;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, ready};
+use tokio::fs::{self, File};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, BufReader};
+
+/// The matches found in one file by [`search_paths_async`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileMatches {
+    /// The file that was searched
+    pub path: PathBuf,
+    /// Its matching lines, in order
+    pub matches: Vec<SearchMatchBytes>,
+}
+
+/// Everything [`search_paths_async`] found, including what it had to skip.
+#[derive(Debug, Default)]
+pub struct PathSearch {
+    /// Every file that was searched, in walk order (including files without matches)
+    pub files: Vec<FileMatches>,
+    /// Files and directories that could not be read
+    pub errors: Vec<anyhow::Error>,
+}
+
+/// Async counterpart of [`search_lines`](crate::search_lines).
+///
+/// Lines must be valid UTF-8; `\n` and `\r\n` are stripped.
+///
+/// # Errors
+///
+/// Returns an error if reading fails or a line is not valid UTF-8.
+pub async fn search_lines_async<R: AsyncRead + Unpin>(
+    reader: R,
+    matcher: &Matcher,
+) -> Result<Vec<SearchMatch>> {
+    let mut lines = BufReader::new(reader).lines();
+    let mut matches = Vec::new();
+    let mut line_number = 0;
+
+    while let Some(content) = lines.next_line().await? {
+        line_number += 1;
+        if matcher.is_match(&content) {
+            matches.push(SearchMatch {
+                line_number,
+                content,
+            });
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Async counterpart of [`search_reader`](crate::search_reader).
+///
+/// # Errors
+///
+/// Returns an error if reading from the input source fails.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::{LineTerminator, Matcher, SearchOptions};
+/// use searcher_cli_starter::async_search::search_reader_async;
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let options = SearchOptions { line_terminator: LineTerminator::Crlf, ..Default::default() };
+/// let matcher = Matcher::new("done", false, false)?;
+/// let results = search_reader_async(&b"work\r\ndone\r\n"[..], &matcher, &options).await?;
+/// assert_eq!(results[0].content, b"done");
+/// # Ok(())
+/// # }
+/// ```
+pub async fn search_reader_async<R: AsyncRead + Unpin>(
+    reader: R,
+    matcher: &Matcher,
+    options: &SearchOptions,
+) -> Result<Vec<SearchMatchBytes>> {
+    let terminator = options.line_terminator.byte();
+    let last_line = last_requested_line(&options.line_ranges);
+    let mut buf_reader = BufReader::new(reader);
+    let mut matches = Vec::new();
+    let mut line = Vec::new();
+    let mut line_number = options.line_offset;
+    let deadline = options.deadline();
+    let dedupe = options.dedupe.as_ref().map(Dedupe::for_input);
+
+    loop {
+        if last_line.is_some_and(|last| line_number >= last) {
+            break;
+        }
+        options.check_cancelled()?;
+        options.check_deadline(deadline)?;
+        line.clear();
+        if buf_reader.read_until(terminator, &mut line).await? == 0 {
+            break;
+        }
+        line_number += 1;
+        if let Some(content) = check_line(&line, line_number, matcher, options, None)
+            && dedupe.as_ref().is_none_or(|dedupe| dedupe.insert(content))
+        {
+            matches.push(SearchMatchBytes {
+                line_number,
+                content: content.to_vec(),
+            });
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Async counterpart of [`search_path`](crate::search_path).
+///
+/// # Errors
+///
+/// Returns an error naming the file if it cannot be opened or read, or
+/// [`Cancelled`] if `options.cancel` is cancelled.
+pub async fn search_path_async(
+    path: &Path,
+    matcher: &Matcher,
+    options: &SearchOptions,
+) -> Result<Vec<SearchMatchBytes>> {
+    options.check_cancelled()?;
+    let file = File::open(path)
+        .await
+        .with_context(|| FileContext::read(path))?;
+    search_reader_async(file, matcher, options)
+        .await
+        .map_err(|error| file_error(path, error))
+}
+
+/// Searches files and directory trees without blocking the executor.
+///
+/// Paths are expanded the same way as [`Walk`](crate::walk::Walk): in the
+/// order given, directories recursively in file name order, following
+/// symbolic links to files but not to directories. A file or directory that
+/// cannot be read is recorded in [`PathSearch::errors`] and the search goes
+/// on with the rest. If `options.cancel` is cancelled, the search stops with
+/// a [`Cancelled`] error as the last entry of `errors`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use searcher_cli_starter::{Matcher, SearchOptions};
+/// use searcher_cli_starter::async_search::search_paths_async;
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let matcher = Matcher::new("TODO", false, false)?;
+/// let search = search_paths_async(&["src".into()], &matcher, &SearchOptions::default()).await;
+/// for file in &search.files {
+///     println!("{}: {} matches", file.path.display(), file.matches.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn search_paths_async(
+    paths: &[PathBuf],
+    matcher: &Matcher,
+    options: &SearchOptions,
+) -> PathSearch {
+    let mut search = PathSearch::default();
+
+    for path in paths {
+        // Command-line style arguments are followed even when they are symlinks
+        let is_dir = fs::metadata(path).await.is_ok_and(|m| m.is_dir());
+        let mut stack = vec![(path.clone(), is_dir)];
+
+        while let Some((path, is_dir)) = stack.pop() {
+            if let Err(error) = options.check_cancelled() {
+                search.errors.push(error);
+                return search;
+            }
+            if !is_dir {
+                match search_path_async(&path, matcher, options).await {
+                    Ok(matches) => search.files.push(FileMatches { path, matches }),
+                    Err(error) if error.is::<Cancelled>() => {
+                        search.errors.push(error);
+                        return search;
+                    }
+                    Err(error) => search.errors.push(error),
+                }
+                continue;
+            }
+            match read_dir_sorted(&path).await {
+                // Reversed so that popping visits entries in name order
+                Ok(entries) => stack.extend(entries.into_iter().rev()),
+                Err(error) => search.errors.push(FileError { path, error }.into()),
+            }
+        }
+    }
+
+    search
+}
+
+/// A [`Stream`] of the matching lines of an async reader.
+///
+/// Unlike [`search_reader_async`], nothing is collected: the stream reads
+/// from its source only while it is being polled, so a slow consumer (a
+/// client reading an HTTP response, a bounded channel) naturally throttles
+/// how fast a large remote object is pulled in. Memory use is bounded by the
+/// reader's buffer plus the longest line.
+///
+/// Lines are split and selected according to [`SearchOptions`], matched as
+/// bytes, and yielded as [`SearchMatch`]. A matching line that is not valid
+/// UTF-8 is yielded as an error; the stream ends after the first error.
+/// [`SearchOptions::timeout`] is ignored, since how long a stream lives
+/// depends on its consumer.
+///
+/// # Examples
+///
+/// ```
+/// use futures_core::Stream;
+/// use searcher_cli_starter::Matcher;
+/// use searcher_cli_starter::async_search::SearchStream;
+/// use std::pin::Pin;
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let body: &[u8] = b"ok\nerror: disk full\nok\n";
+/// let mut stream = SearchStream::new(body, Matcher::new("error", false, false)?);
+///
+/// let first = std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await;
+/// assert_eq!(first.unwrap()?.line_number, 2);
+/// # Ok(())
+/// # }
+/// ```
+pub struct SearchStream<R> {
+    reader: BufReader<R>,
+    matcher: Arc<Matcher>,
+    options: SearchOptions,
+    last_line: Option<usize>,
+    line: Vec<u8>,
+    line_number: usize,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> SearchStream<R> {
+    /// Creates a stream over `reader` using the default [`SearchOptions`].
+    ///
+    /// The matcher can be passed by value or shared between streams as an
+    /// `Arc<Matcher>`.
+    pub fn new(reader: R, matcher: impl Into<Arc<Matcher>>) -> Self {
+        Self::with_options(reader, matcher, SearchOptions::default())
+    }
+
+    /// Creates a stream over `reader` with custom [`SearchOptions`].
+    pub fn with_options(
+        reader: R,
+        matcher: impl Into<Arc<Matcher>>,
+        mut options: SearchOptions,
+    ) -> Self {
+        options.dedupe = options.dedupe.as_ref().map(Dedupe::for_input);
+        SearchStream {
+            reader: BufReader::new(reader),
+            matcher: matcher.into(),
+            last_line: last_requested_line(&options.line_ranges),
+            line_number: options.line_offset,
+            options,
+            line: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Checks the line in `self.line`, returning it if it matches.
+    fn finish_line(&mut self) -> Option<Result<SearchMatch>> {
+        self.line_number += 1;
+        let Some(content) = check_line(
+            &self.line,
+            self.line_number,
+            &self.matcher,
+            &self.options,
+            None,
+        )
+        .filter(|content| {
+            let dedupe = self.options.dedupe.as_ref();
+            dedupe.is_none_or(|dedupe| dedupe.insert(content))
+        }) else {
+            self.line.clear();
+            return None;
+        };
+        // The content is the line minus its terminator
+        let len = content.len();
+        self.line.truncate(len);
+        let line_number = self.line_number;
+        Some(match String::from_utf8(std::mem::take(&mut self.line)) {
+            Ok(content) => Ok(SearchMatch {
+                line_number,
+                content,
+            }),
+            Err(_) => {
+                self.done = true;
+                Err(anyhow::anyhow!("Line {} is not valid UTF-8", line_number))
+            }
+        })
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for SearchStream<R> {
+    type Item = Result<SearchMatch>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let terminator = this.options.line_terminator.byte();
+
+        loop {
+            if this.done || this.last_line.is_some_and(|last| this.line_number >= last) {
+                return Poll::Ready(None);
+            }
+            if let Err(error) = this.options.check_cancelled() {
+                this.done = true;
+                return Poll::Ready(Some(Err(error)));
+            }
+
+            let buf = match ready!(Pin::new(&mut this.reader).poll_fill_buf(cx)) {
+                Ok(buf) => buf,
+                Err(error) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(error.into())));
+                }
+            };
+            if buf.is_empty() {
+                // End of input: a final line without a terminator still counts
+                this.done = true;
+                if this.line.is_empty() {
+                    return Poll::Ready(None);
+                }
+            } else {
+                let (taken, complete) = match memchr::memchr(terminator, buf) {
+                    Some(index) => (index + 1, true),
+                    None => (buf.len(), false),
+                };
+                this.line.extend_from_slice(&buf[..taken]);
+                Pin::new(&mut this.reader).consume(taken);
+                if !complete {
+                    continue;
+                }
+            }
+
+            if let Some(item) = this.finish_line() {
+                return Poll::Ready(Some(item));
+            }
+        }
+    }
+}
+
+/// Lists the entries of `dir` to visit, sorted by name, with whether each is a directory.
+async fn read_dir_sorted(dir: &Path) -> std::io::Result<Vec<(PathBuf, bool)>> {
+    let mut entries = Vec::new();
+    let mut read_dir = fs::read_dir(dir).await?;
+    while let Some(entry) = read_dir.next_entry().await? {
+        let file_type = entry.file_type().await?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            entries.push((path, true));
+        } else if file_type.is_symlink() {
+            // Follow links to files only; a link to a directory could loop
+            if fs::metadata(&path).await.is_ok_and(|m| m.is_file()) {
+                entries.push((path, false));
+            }
+        } else if file_type.is_file() {
+            entries.push((path, false));
+        }
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancel::CancellationToken;
+    use crate::{LineRange, LineTerminator};
+
+    #[tokio::test]
+    async fn test_search_lines_async() {
+        let matcher = Matcher::new("rust", true, false).unwrap();
+        let results = search_lines_async(&b"Rust\r\nGo\nrust"[..], &matcher)
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].content, "Rust");
+        assert_eq!(results[1].line_number, 3);
+    }
+
+    #[tokio::test]
+    async fn test_search_lines_async_invalid_utf8() {
+        let matcher = Matcher::new("x", false, false).unwrap();
+        assert!(
+            search_lines_async(&b"x\n\xFF\n"[..], &matcher)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_reader_async_matches_sync() {
+        let input: &[u8] = b"a1\nb2\n\xFF a3\na4\n";
+        let matcher = Matcher::new(r"a\d", false, true).unwrap();
+        let options = SearchOptions {
+            line_ranges: vec!["2-3".parse::<LineRange>().unwrap()],
+            ..SearchOptions::default()
+        };
+
+        let expected = crate::search_reader(input, &matcher, &options).unwrap();
+        let actual = search_reader_async(input, &matcher, &options)
+            .await
+            .unwrap();
+
+        assert_eq!(actual, expected);
+        assert_eq!(actual[0].line_number, 3);
+    }
+
+    async fn next<R: AsyncRead + Unpin>(
+        stream: &mut SearchStream<R>,
+    ) -> Option<Result<SearchMatch>> {
+        std::future::poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+    }
+
+    #[tokio::test]
+    async fn test_search_stream_yields_matches() {
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let mut stream = SearchStream::new(&b"x1\ny\nx3"[..], matcher);
+
+        assert_eq!(next(&mut stream).await.unwrap().unwrap().content, "x1");
+        let last = next(&mut stream).await.unwrap().unwrap();
+        assert_eq!((last.line_number, last.content.as_str()), (3, "x3"));
+        assert!(next(&mut stream).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_search_stream_does_not_wait_for_eof() {
+        let (mut writer, reader) = tokio::io::duplex(16);
+        let matcher = Arc::new(Matcher::new("hit", false, false).unwrap());
+        let mut stream = SearchStream::new(reader, Arc::clone(&matcher));
+
+        tokio::io::AsyncWriteExt::write_all(&mut writer, b"miss\nhit one\n")
+            .await
+            .unwrap();
+        let first = next(&mut stream).await.unwrap().unwrap();
+        assert_eq!(first.content, "hit one");
+
+        drop(writer);
+        assert!(next(&mut stream).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_search_stream_options_and_invalid_utf8() {
+        let options = SearchOptions {
+            line_terminator: LineTerminator::Crlf,
+            line_ranges: vec!["2-".parse::<LineRange>().unwrap()],
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("a", false, false).unwrap();
+        let mut stream =
+            SearchStream::with_options(&b"a\r\na\r\n\xFFa\r\na\r\n"[..], matcher, options);
+
+        let second = next(&mut stream).await.unwrap().unwrap();
+        assert_eq!((second.line_number, second.content.as_str()), (2, "a"));
+        assert!(next(&mut stream).await.unwrap().is_err());
+        assert!(next(&mut stream).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_search_paths_async_walks_and_collects_errors() {
+        let root = std::env::temp_dir().join("searcher_async_paths");
+        std::fs::remove_dir_all(&root).ok();
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("b.txt"), "error\n").unwrap();
+        std::fs::write(root.join("sub/a.txt"), "ok\nerror here\n").unwrap();
+
+        let matcher = Matcher::new("error", false, false).unwrap();
+        let paths = [root.join("missing.txt"), root.clone()];
+        let search = search_paths_async(&paths, &matcher, &SearchOptions::default()).await;
+
+        let found: Vec<_> = search
+            .files
+            .iter()
+            .map(|file| (file.path.strip_prefix(&root).unwrap(), file.matches.len()))
+            .collect();
+        assert_eq!(
+            found,
+            [(Path::new("b.txt"), 1), (Path::new("sub/a.txt"), 1)]
+        );
+        assert_eq!(search.errors.len(), 1);
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    #[tokio::test]
+    async fn test_search_paths_async_stops_when_cancelled() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let options = SearchOptions {
+            cancel: Some(token),
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("Rust", false, false).unwrap();
+
+        let search = search_paths_async(&["tests/fixtures".into()], &matcher, &options).await;
+
+        assert!(search.files.is_empty());
+        assert_eq!(search.errors.len(), 1);
+        assert!(search.errors[0].is::<Cancelled>());
+    }
+}
@@ -0,0 +1,310 @@
+//! Throughput measurement for the `searcher bench` subcommand.
+//!
+//! [`run`] searches an in-memory copy of the input repeatedly and collects
+//! timing, throughput, and allocation figures in a [`BenchReport`]. Keeping
+//! the data in memory takes disk caching out of the picture, so runs with
+//! different flags (literal vs regex, case-insensitive, ...) are comparable.
+//!
+//! Allocation counts come from [`CountingAllocator`]. A library cannot choose
+//! the global allocator for its users, so the counters only move when the
+//! final binary installs it, as `searcher` does when built with the
+//! `bench-alloc` feature; otherwise a report has no allocation figures:
+//!
+//! ```
+//! use searcher_cli_starter::bench::CountingAllocator;
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: CountingAllocator = CountingAllocator;
+//! # fn main() {}
+//! ```
+//!
+//! With the `profiling` feature it also keeps track of the heap memory in
+//! use, for [`profile`](crate::profile). That costs an atomic operation on
+//! every deallocation too, so it is left out otherwise.
+
+use crate::{Matcher, SearchOptions, search_reader};
+use anyhow::Result;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt;
+use std::io::Cursor;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+#[cfg(feature = "profiling")]
+static HEAP_BYTES: AtomicU64 = AtomicU64::new(0);
+#[cfg(feature = "profiling")]
+static PEAK_HEAP_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// A global allocator that counts allocations before delegating to [`System`].
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        // SAFETY: the caller upholds `GlobalAlloc::alloc`'s contract, which
+        // is passed through to the system allocator unchanged.
+        let ptr = unsafe { System.alloc(layout) };
+        #[cfg(feature = "profiling")]
+        if !ptr.is_null() {
+            let size = layout.size() as u64;
+            let heap = HEAP_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+            PEAK_HEAP_BYTES.fetch_max(heap, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        #[cfg(feature = "profiling")]
+        HEAP_BYTES.fetch_sub(layout.size() as u64, Ordering::Relaxed);
+        // SAFETY: `ptr` was allocated by `System` in `alloc` above.
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+/// Returns the number of allocations and bytes allocated so far.
+///
+/// Both values stay at zero unless [`CountingAllocator`] is installed as the
+/// global allocator.
+pub fn allocation_counts() -> (u64, u64) {
+    (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        ALLOCATED_BYTES.load(Ordering::Relaxed),
+    )
+}
+
+/// Whether [`CountingAllocator`] is installed as the global allocator, found
+/// by allocating once and seeing if the count moves.
+pub fn counting_allocations() -> bool {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    drop(std::hint::black_box(Box::new(0_u8)));
+    ALLOCATIONS.load(Ordering::Relaxed) != before
+}
+
+/// Returns the bytes of heap memory in use now, and the most in use at once
+/// since the last [`reset_peak_heap`].
+///
+/// Both values stay at zero unless [`CountingAllocator`] is installed as the
+/// global allocator.
+#[cfg(feature = "profiling")]
+pub fn heap_bytes() -> (u64, u64) {
+    (
+        HEAP_BYTES.load(Ordering::Relaxed),
+        PEAK_HEAP_BYTES.load(Ordering::Relaxed),
+    )
+}
+
+/// Starts measuring the most heap memory in use at once afresh, from what
+/// is in use now.
+#[cfg(feature = "profiling")]
+pub fn reset_peak_heap() {
+    PEAK_HEAP_BYTES.store(HEAP_BYTES.load(Ordering::Relaxed), Ordering::Relaxed);
+}
+
+/// Results of a benchmark run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BenchReport {
+    /// How many times the input was searched
+    pub iterations: u32,
+    /// Size of the input in bytes
+    pub bytes: u64,
+    /// Number of lines in the input
+    pub lines: u64,
+    /// Matching lines found by each iteration
+    pub matches: usize,
+    /// Wall-clock time for all iterations together
+    pub elapsed: Duration,
+    /// Allocations made across all iterations, unless
+    /// [`CountingAllocator`] is not installed to count them
+    pub allocations: Option<u64>,
+    /// Bytes allocated across all iterations, where counted
+    pub allocated_bytes: Option<u64>,
+}
+
+impl BenchReport {
+    /// Average time for one search of the input.
+    pub fn mean(&self) -> Duration {
+        self.elapsed / self.iterations.max(1)
+    }
+
+    /// Input processed per second, in megabytes (10^6 bytes).
+    pub fn megabytes_per_sec(&self) -> f64 {
+        self.per_second(self.bytes) / 1_000_000.0
+    }
+
+    /// Input lines processed per second.
+    pub fn lines_per_sec(&self) -> f64 {
+        self.per_second(self.lines)
+    }
+
+    /// Average allocations made by one search, where counted.
+    pub fn allocations_per_iteration(&self) -> Option<u64> {
+        Some(self.allocations? / u64::from(self.iterations.max(1)))
+    }
+
+    fn per_second(&self, amount: u64) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds == 0.0 {
+            return 0.0;
+        }
+        (amount as f64 * f64::from(self.iterations)) / seconds
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Input:        {} bytes, {} lines",
+            self.bytes, self.lines
+        )?;
+        writeln!(f, "Iterations:   {}", self.iterations)?;
+        writeln!(f, "Matches:      {} per run", self.matches)?;
+        writeln!(
+            f,
+            "Mean time:    {:.3} ms",
+            self.mean().as_secs_f64() * 1000.0
+        )?;
+        writeln!(f, "Throughput:   {:.1} MB/s", self.megabytes_per_sec())?;
+        writeln!(f, "Lines/sec:    {:.0}", self.lines_per_sec())?;
+        match (self.allocations_per_iteration(), self.allocated_bytes) {
+            (Some(allocations), Some(bytes)) => write!(
+                f,
+                "Allocations:  {} per run ({} bytes)",
+                allocations,
+                bytes / u64::from(self.iterations.max(1))
+            ),
+            _ => write!(
+                f,
+                "Allocations:  unavailable (not built with `bench-alloc`)"
+            ),
+        }
+    }
+}
+
+/// Searches `data` `iterations` times and reports how fast it went.
+///
+/// # Errors
+///
+/// Returns an error if a search fails, which can only happen if the
+/// underlying reader fails.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::{Matcher, SearchOptions};
+/// use searcher_cli_starter::bench;
+///
+/// let data = b"error one\nok\nerror two\n";
+/// let matcher = Matcher::new("error", false, false).unwrap();
+/// let report = bench::run(data, &matcher, &SearchOptions::default(), 5).unwrap();
+///
+/// assert_eq!(report.iterations, 5);
+/// assert_eq!(report.lines, 3);
+/// assert_eq!(report.matches, 2);
+/// ```
+pub fn run(
+    data: &[u8],
+    matcher: &Matcher,
+    options: &SearchOptions,
+    iterations: u32,
+) -> Result<BenchReport> {
+    let terminator = options.line_terminator.byte();
+    let lines = memchr::memchr_iter(terminator, data).count() as u64
+        + u64::from(!data.is_empty() && data.last() != Some(&terminator));
+
+    let counting = counting_allocations();
+    let (allocations_before, bytes_before) = allocation_counts();
+    let start = Instant::now();
+    let mut matches = 0;
+    for _ in 0..iterations {
+        matches = search_reader(Cursor::new(data), matcher, options)?.len();
+    }
+    let elapsed = start.elapsed();
+    let (allocations_after, bytes_after) = allocation_counts();
+
+    Ok(BenchReport {
+        iterations,
+        bytes: data.len() as u64,
+        lines,
+        matches,
+        elapsed,
+        allocations: counting.then(|| allocations_after - allocations_before),
+        allocated_bytes: counting.then(|| bytes_after - bytes_before),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(elapsed: Duration) -> BenchReport {
+        BenchReport {
+            iterations: 4,
+            bytes: 2_000_000,
+            lines: 1000,
+            matches: 3,
+            elapsed,
+            allocations: Some(40),
+            allocated_bytes: Some(4096),
+        }
+    }
+
+    #[test]
+    fn test_report_rates() {
+        let report = report(Duration::from_secs(2));
+
+        assert_eq!(report.mean(), Duration::from_millis(500));
+        assert_eq!(report.megabytes_per_sec(), 4.0);
+        assert_eq!(report.lines_per_sec(), 2000.0);
+        assert_eq!(report.allocations_per_iteration(), Some(10));
+        assert!(
+            report
+                .to_string()
+                .ends_with("Allocations:  10 per run (1024 bytes)")
+        );
+    }
+
+    #[test]
+    fn test_report_without_counting_allocator() {
+        // The library's tests run without `CountingAllocator` installed
+        assert!(!counting_allocations());
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let report = run(b"x\n", &matcher, &SearchOptions::default(), 2).unwrap();
+
+        assert_eq!(report.allocations, None);
+        assert_eq!(report.allocations_per_iteration(), None);
+        assert!(
+            report
+                .to_string()
+                .ends_with("Allocations:  unavailable (not built with `bench-alloc`)")
+        );
+    }
+
+    #[test]
+    fn test_report_zero_elapsed() {
+        let report = report(Duration::ZERO);
+        assert_eq!(report.megabytes_per_sec(), 0.0);
+    }
+
+    #[test]
+    fn test_run_counts_lines_without_trailing_newline() {
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let report = run(b"x\ny\nx", &matcher, &SearchOptions::default(), 1).unwrap();
+
+        assert_eq!(report.lines, 3);
+        assert_eq!(report.matches, 2);
+    }
+
+    #[test]
+    fn test_run_empty_input() {
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let report = run(b"", &matcher, &SearchOptions::default(), 3).unwrap();
+
+        assert_eq!(report.lines, 0);
+        assert_eq!(report.matches, 0);
+    }
+}
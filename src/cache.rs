@@ -0,0 +1,379 @@
+//! A persistent cache of per-file search results.
+//!
+//! Re-running the same audit over a tree where few files changed repeats
+//! almost all of its work. A [`ResultCache`] remembers the matches of every
+//! file it searches, keyed by a hash of the file's content, in a directory
+//! for one search configuration (the `key` passed to [`ResultCache::open`]).
+//! The next search of an unchanged file reads its matches back instead of
+//! matching every line again; a file whose content changed hashes
+//! differently and is searched for real.
+//!
+//! The cache never goes stale on its own:
+//!
+//! - Entries are found by content, not path or modification time, so an
+//!   edited file simply misses.
+//! - The `key` stands for everything else that decides the matches, such as
+//!   the [`Matcher::fingerprint`] and the search options, so a different
+//!   search uses a different directory. Callers should fold their own
+//!   version into it.
+//! - Entries that cannot be read back, including those written by an
+//!   incompatible version, count as misses and are overwritten.
+//!
+//! Entries for contents that no longer exist are not removed either;
+//! [`ResultCache::clear`] deletes a whole cache.
+//!
+//! # Layout
+//!
+//! ```text
+//! DIR/CACHEDIR.TAG                  marks DIR as a cache for backup tools
+//! DIR/<key>/<content hash>-<size>   one entry per file content
+//! ```
+//!
+//! An entry holds `b"SRCHRES1"`, a `u32` count of matches, then per match a
+//! `u64` line number and a `u32` length followed by the line's bytes, all
+//! little-endian.
+
+use crate::diagnostic::FileContext;
+use crate::scope::Language;
+use crate::{
+    Matcher, SearchMatchBytes, SearchOptions, file_error, open_window, search_path, search_source,
+};
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The first bytes of every cache entry.
+const MAGIC: &[u8; 8] = b"SRCHRES1";
+
+/// The file that marks a directory as a cache, following the Cache
+/// Directory Tagging Specification.
+const TAG_FILE: &str = "CACHEDIR.TAG";
+
+/// The contents of [`TAG_FILE`].
+const TAG: &str = "Signature: 8a477f597d28d172789f06886806bc55\n\
+    # This file marks a searcher result cache; see `searcher cache clear`.\n";
+
+/// A stable 64-bit hash of `data`, the same on every platform and in every
+/// version of this crate.
+///
+/// It is fast rather than cryptographic: fine for telling file contents
+/// apart, not for resisting someone crafting collisions.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::cache::hash_bytes;
+///
+/// assert_eq!(hash_bytes(b"TODO"), hash_bytes(b"TODO"));
+/// assert_ne!(hash_bytes(b"TODO"), hash_bytes(b"TODO "));
+/// ```
+pub fn hash_bytes(data: &[u8]) -> u64 {
+    const MULTIPLIER: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut hash = 0xcbf2_9ce4_8422_2325 ^ data.len() as u64;
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap_or_default());
+        hash = (hash ^ word).wrapping_mul(MULTIPLIER);
+        hash ^= hash >> 32;
+    }
+    for &byte in chunks.remainder() {
+        hash = (hash ^ u64::from(byte)).wrapping_mul(MULTIPLIER);
+    }
+    // Spread the last bytes over the whole hash
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash
+}
+
+/// Search results remembered in a directory, for one search configuration.
+#[derive(Debug)]
+pub struct ResultCache {
+    /// The directory of this configuration's entries
+    dir: PathBuf,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl ResultCache {
+    /// Opens the cache in `root` for the searches identified by `key`,
+    /// creating the directory if needed.
+    ///
+    /// `key` must change whenever anything but the file content would change
+    /// the matches: the matcher (see [`Matcher::fingerprint`]), the search
+    /// options, and the version of the code doing the search.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be created, or if `root`
+    /// already exists, is not empty, and is not a searcher cache.
+    pub fn open(root: &Path, key: u64) -> Result<Self> {
+        let context = || format!("Could not use cache directory `{}`", root.display());
+        fs::create_dir_all(root).with_context(context)?;
+        let tag = root.join(TAG_FILE);
+        if !tag.exists() {
+            if fs::read_dir(root).with_context(context)?.next().is_some() {
+                bail!(
+                    "`{}` is not empty and not a searcher cache; choose another `--cache-dir`",
+                    root.display()
+                );
+            }
+            fs::write(&tag, TAG).with_context(context)?;
+        }
+        let dir = root.join(format!("{:016x}", key));
+        fs::create_dir_all(&dir).with_context(context)?;
+        Ok(ResultCache {
+            dir,
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        })
+    }
+
+    /// Searches the file at `path` like [`search_path`], reading its matches
+    /// from the cache when a file with the same content was searched before
+    /// and remembering them otherwise.
+    ///
+    /// The whole file, or the part of it `options.byte_window` keeps, is
+    /// read into memory to hash it. Files that go through
+    /// `options.preprocessor` and searches with
+    /// [`Dedupe::across_inputs`](crate::filter::Dedupe::across_inputs), whose
+    /// matches do not only depend on the file's content, skip the cache.
+    /// Errors, including timeouts and cancellation, are never cached. Failing
+    /// to write an entry is not an error either: the search result is
+    /// returned all the same.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`search_path`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::cache::ResultCache;
+    /// use searcher_cli_starter::{Matcher, SearchOptions};
+    ///
+    /// let root = std::env::temp_dir().join(format!("searcher_cache_doc_{}", std::process::id()));
+    /// let cache = ResultCache::open(&root, 1).unwrap();
+    /// let matcher = Matcher::new("Rust", false, false).unwrap();
+    /// let path = "tests/fixtures/sample.txt".as_ref();
+    ///
+    /// let first = cache.search_path(path, &matcher, &SearchOptions::default()).unwrap();
+    /// let second = cache.search_path(path, &matcher, &SearchOptions::default()).unwrap();
+    /// assert_eq!(first, second);
+    /// assert_eq!((cache.misses(), cache.hits()), (1, 1));
+    /// # ResultCache::clear(&root).unwrap();
+    /// ```
+    pub fn search_path(
+        &self,
+        path: &Path,
+        matcher: &Matcher,
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchMatchBytes>> {
+        let preprocessed = options
+            .preprocessor
+            .as_ref()
+            .is_some_and(|preprocessor| preprocessor.applies_to(path));
+        let across_inputs = options
+            .dedupe
+            .as_ref()
+            .is_some_and(|dedupe| dedupe.is_across_inputs());
+        if preprocessed || across_inputs {
+            return search_path(path, matcher, options);
+        }
+
+        options.check_cancelled()?;
+        // Only the part of the file `options.byte_window` keeps is searched
+        let mut data = Vec::new();
+        open_window(path, options)?
+            .read_to_end(&mut data)
+            .with_context(|| FileContext::read(path))?;
+        let mut hash = hash_bytes(&data);
+        if options.scope.is_some() {
+            // Scoped searches also depend on the language the path implies
+            let language = Language::from_path(path).map_or("", |language| language.name);
+            hash ^= hash_bytes(language.as_bytes()).rotate_left(1);
+        }
+        let entry = self.dir.join(format!("{:016x}-{:x}", hash, data.len()));
+
+        if let Ok(matches) = read_entry(&entry) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(matches);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let matches = search_source(path, data.as_slice(), matcher, options)
+            .map_err(|error| file_error(path, error))?;
+        // A cache that cannot be written only makes the next run slower
+        let _ = write_entry(&entry, &matches);
+        Ok(matches)
+    }
+
+    /// How many searches were answered from the cache.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// How many searches had to read and match the file.
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Deletes the cache in `root`, returning how many entries it held.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `root` is not a searcher cache, so that a
+    /// mistyped path cannot delete anything else, or if deleting fails.
+    pub fn clear(root: &Path) -> Result<usize> {
+        let tag = fs::read_to_string(root.join(TAG_FILE)).unwrap_or_default();
+        if !tag.starts_with(TAG.lines().next().unwrap_or_default()) {
+            bail!("`{}` is not a searcher cache", root.display());
+        }
+        let context = || format!("Could not clear cache `{}`", root.display());
+        let mut entries = 0;
+        for dir in fs::read_dir(root).with_context(context)? {
+            let dir = dir.with_context(context)?.path();
+            if dir.is_dir() {
+                entries += fs::read_dir(&dir).with_context(context)?.count();
+            }
+        }
+        fs::remove_dir_all(root).with_context(context)?;
+        Ok(entries)
+    }
+}
+
+/// Reads the matches stored in the entry at `path`.
+fn read_entry(path: &Path) -> Result<Vec<SearchMatchBytes>> {
+    let data = fs::read(path)?;
+    let mut input = data.as_slice();
+    let mut magic = [0; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        bail!("not a searcher cache entry (or one from an incompatible version)");
+    }
+    let count = read_u32(&mut input)?;
+    let mut matches = Vec::new();
+    for _ in 0..count {
+        let line_number = read_u64(&mut input)? as usize;
+        let len = read_u32(&mut input)? as usize;
+        if len > input.len() {
+            bail!("truncated cache entry");
+        }
+        let (content, rest) = input.split_at(len);
+        matches.push(SearchMatchBytes {
+            line_number,
+            content: content.to_vec(),
+        });
+        input = rest;
+    }
+    Ok(matches)
+}
+
+/// Stores `matches` as the entry at `path`, through a temporary file so that
+/// a concurrent search never reads half an entry.
+fn write_entry(path: &Path, matches: &[SearchMatchBytes]) -> io::Result<()> {
+    let mut data = Vec::from(*MAGIC);
+    data.write_all(&(matches.len() as u32).to_le_bytes())?;
+    for search_match in matches {
+        data.write_all(&(search_match.line_number as u64).to_le_bytes())?;
+        data.write_all(&(search_match.content.len() as u32).to_le_bytes())?;
+        data.write_all(&search_match.content)?;
+    }
+    let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&temporary, data)?;
+    fs::rename(&temporary, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temporary);
+    })
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory below the temporary directory.
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("searcher_cache_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_changed_content_misses() {
+        let root = scratch("changed");
+        let file = scratch("changed_file");
+        fs::write(&file, "one TODO\ntwo\n").unwrap();
+        let cache = ResultCache::open(&root, 7).unwrap();
+        let matcher = Matcher::new("TODO", false, false).unwrap();
+        let options = SearchOptions::default();
+
+        assert_eq!(
+            cache.search_path(&file, &matcher, &options).unwrap().len(),
+            1
+        );
+        assert_eq!(
+            cache.search_path(&file, &matcher, &options).unwrap().len(),
+            1
+        );
+        fs::write(&file, "one TODO\ntwo TODO\n").unwrap();
+        let matches = cache.search_path(&file, &matcher, &options).unwrap();
+        assert_eq!(matches[1].line_number, 2);
+        assert_eq!((cache.hits(), cache.misses()), (1, 2));
+
+        // Another key starts out empty
+        let other = ResultCache::open(&root, 8).unwrap();
+        other.search_path(&file, &matcher, &options).unwrap();
+        assert_eq!(other.misses(), 1);
+
+        assert_eq!(ResultCache::clear(&root).unwrap(), 3);
+        assert!(!root.exists());
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_entries_are_misses() {
+        let root = scratch("corrupt");
+        let cache = ResultCache::open(&root, 1).unwrap();
+        let matcher = Matcher::new("Rust", false, false).unwrap();
+        let path = Path::new("tests/fixtures/sample.txt");
+        let expected = search_path(path, &matcher, &SearchOptions::default()).unwrap();
+
+        cache
+            .search_path(path, &matcher, &SearchOptions::default())
+            .unwrap();
+        for entry in fs::read_dir(&cache.dir).unwrap() {
+            fs::write(entry.unwrap().path(), b"SRCHRES1\xff\xff").unwrap();
+        }
+        let matches = cache
+            .search_path(path, &matcher, &SearchOptions::default())
+            .unwrap();
+        assert_eq!(matches, expected);
+        assert_eq!(cache.misses(), 2);
+        ResultCache::clear(&root).unwrap();
+    }
+
+    #[test]
+    fn test_only_caches_are_cleared_or_opened() {
+        let root = scratch("foreign");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("notes.txt"), "keep me").unwrap();
+
+        assert!(ResultCache::clear(&root).is_err());
+        assert!(ResultCache::open(&root, 1).is_err());
+        assert!(root.join("notes.txt").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
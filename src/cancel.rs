@@ -0,0 +1,142 @@
+//! Cooperative cancellation of long-running searches.
+//!
+//! A [`CancellationToken`] set in [`SearchOptions::cancel`] is checked before
+//! each file and between lines. Once it is cancelled, searches return a
+//! [`Cancelled`] error at the next check instead of running to the end, and
+//! [`walk::each_file`] stops walking.
+//!
+//! [`SearchOptions::timeout`] puts a time limit on each input instead; an
+//! input that takes longer fails with [`TimedOut`] and other files are still
+//! searched.
+//!
+//! ```
+//! use searcher_cli_starter::cancel::{CancellationToken, Cancelled};
+//! use searcher_cli_starter::{Matcher, SearchOptions, search_reader};
+//!
+//! let token = CancellationToken::new();
+//! let options = SearchOptions {
+//!     cancel: Some(token.clone()),
+//!     ..SearchOptions::default()
+//! };
+//! let matcher = Matcher::new("x", false, false).unwrap();
+//!
+//! // Typically called from another thread or a signal handler
+//! token.cancel();
+//!
+//! let error = search_reader(&b"x\nx\n"[..], &matcher, &options).unwrap_err();
+//! assert!(error.is::<Cancelled>());
+//! ```
+//!
+//! [`SearchOptions::cancel`]: crate::SearchOptions::cancel
+//! [`SearchOptions::timeout`]: crate::SearchOptions::timeout
+//! [`walk::each_file`]: crate::walk::each_file
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// A shared flag requesting that searches stop early.
+///
+/// Clones share the same flag, so one clone can be handed to the search and
+/// another kept to cancel it. Two tokens are equal when they share a flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    flag: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests cancellation of every search using this token.
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether cancellation has been requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    /// The underlying flag, for APIs that set an `AtomicBool` directly,
+    /// such as `signal_hook::flag::register`.
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.flag)
+    }
+}
+
+impl From<Arc<AtomicBool>> for CancellationToken {
+    fn from(flag: Arc<AtomicBool>) -> Self {
+        CancellationToken { flag }
+    }
+}
+
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.flag, &other.flag)
+    }
+}
+
+impl Eq for CancellationToken {}
+
+/// The error returned by a search that was cancelled.
+///
+/// Use `error.is::<Cancelled>()` to tell it apart from I/O errors; it is
+/// found even when the error has context attached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "search cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// The error returned when searching one input took longer than
+/// [`SearchOptions::timeout`](crate::SearchOptions::timeout).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedOut {
+    /// The time limit that was exceeded
+    pub limit: Duration,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "search exceeded the time limit of {:?}", self.limit)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_the_flag() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+
+        token.cancel();
+
+        assert!(clone.is_cancelled());
+        assert_eq!(token, clone);
+        assert_ne!(token, CancellationToken::new());
+    }
+
+    #[test]
+    fn test_from_atomic_bool() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let token = CancellationToken::from(Arc::clone(&flag));
+
+        flag.store(true, Ordering::Relaxed);
+
+        assert!(token.is_cancelled());
+    }
+}
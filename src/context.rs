@@ -0,0 +1,300 @@
+//! Matches together with the lines around them.
+//!
+//! [`search_lines_with_context`] returns each matching line with up to
+//! `before` lines preceding it and `after` lines following it, as
+//! `grep -B` / `-A` would show them, so that an editor or GUI can display
+//! context without reading the input a second time.
+//!
+//! ```
+//! use searcher_cli_starter::Matcher;
+//! use searcher_cli_starter::context::search_lines_with_context;
+//!
+//! let input = "start\nconnecting\nerror: refused\nretrying\ndone\n";
+//! let matcher = Matcher::new("error", false, false).unwrap();
+//! let results = search_lines_with_context(input.as_bytes(), &matcher, 1, 2).unwrap();
+//!
+//! assert_eq!(results[0].before, ["connecting"]);
+//! assert_eq!(results[0].matched.line_number, 3);
+//! assert_eq!(results[0].after, ["retrying", "done"]);
+//! ```
+//!
+//! [`search_lines_with_section`] instead gives each match the lines of its
+//! section: everything since the last line matching a delimiter such as
+//! `^fn ` or `^\[.*\]$`, so the context is the enclosing function or INI
+//! section however long it is. [`Section`] tracks those lines for callers
+//! reading the input themselves.
+
+use crate::lines::LineReader;
+use crate::{Matcher, SearchMatch, for_each_line};
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::io::Read;
+use std::ops::ControlFlow;
+
+/// A matching line with the lines around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchWithContext {
+    /// The lines right before the match, oldest first
+    pub before: Vec<String>,
+    /// The matching line
+    pub matched: SearchMatch,
+    /// The lines right after the match
+    pub after: Vec<String>,
+}
+
+impl MatchWithContext {
+    /// The line number of the first line in `before`, or of the match if
+    /// there is none.
+    pub fn first_line_number(&self) -> usize {
+        self.matched.line_number - self.before.len()
+    }
+}
+
+/// Searches through a reader like [`search_lines`](crate::search_lines),
+/// returning each match with up to `before` lines preceding it and `after`
+/// lines following it.
+///
+/// Fewer lines are returned near the start or end of the input. Each match
+/// gets its own context, so when matches are close together a line can
+/// appear in the context of several matches, matching lines included.
+///
+/// # Errors
+///
+/// Returns an error if reading from `reader` fails or a line is not valid
+/// UTF-8.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::Matcher;
+/// use searcher_cli_starter::context::search_lines_with_context;
+///
+/// let matcher = Matcher::new("x", false, false).unwrap();
+/// let results = search_lines_with_context(&b"x1\nx2\ny\n"[..], &matcher, 2, 1).unwrap();
+///
+/// assert_eq!(results.len(), 2);
+/// assert!(results[0].before.is_empty());
+/// assert_eq!(results[0].after, ["x2"]);
+/// assert_eq!(results[1].before, ["x1"]);
+/// assert_eq!(results[1].first_line_number(), 1);
+/// ```
+pub fn search_lines_with_context<R: Read>(
+    reader: R,
+    matcher: &Matcher,
+    before: usize,
+    after: usize,
+) -> Result<Vec<MatchWithContext>> {
+    // Grown as lines come, as `before` may be far more than the input has
+    let mut recent: VecDeque<String> = VecDeque::new();
+    let mut results: Vec<MatchWithContext> = Vec::new();
+    // Matches from this index on still want lines after them
+    let mut waiting = 0;
+
+    for_each_line(
+        &mut LineReader::new(reader, b'\n'),
+        |line_number, content| {
+            // Earlier matches fill up first
+            while results
+                .get(waiting)
+                .is_some_and(|result| result.after.len() == after)
+            {
+                waiting += 1;
+            }
+            for result in &mut results[waiting..] {
+                result.after.push(content.to_string());
+            }
+
+            if matcher.is_match(content) {
+                results.push(MatchWithContext {
+                    before: recent.iter().cloned().collect(),
+                    matched: SearchMatch {
+                        line_number,
+                        content: content.to_string(),
+                    },
+                    after: Vec::new(),
+                });
+            }
+            if before > 0 {
+                if recent.len() == before {
+                    recent.pop_front();
+                }
+                recent.push_back(content.to_string());
+            }
+            ControlFlow::Continue(())
+        },
+    )?;
+
+    Ok(results)
+}
+
+/// The lines of the section being read: those from the last line matching
+/// a delimiter, such as a function signature or an INI header, up to the
+/// latest line.
+///
+/// The lines of a section are kept until the next delimiter line, so a
+/// long section takes as much memory as its lines.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::Matcher;
+/// use searcher_cli_starter::context::Section;
+///
+/// let delimiter = Matcher::new(r"^\[.*\]$", false, true).unwrap();
+/// let mut section = Section::new(&delimiter);
+/// for (line_number, line) in ["a=1", "[db]", "host=x", "port=1"].iter().enumerate() {
+///     section.push(line_number + 1, line.as_bytes());
+/// }
+///
+/// let before: Vec<_> = section.lines_before(4).collect();
+/// assert_eq!(before, [(2, &b"[db]"[..]), (3, &b"host=x"[..])]);
+/// ```
+pub struct Section<'m> {
+    delimiter: &'m Matcher,
+    lines: Vec<(usize, Vec<u8>)>,
+}
+
+impl<'m> Section<'m> {
+    /// Creates a section tracker starting a new section at each line
+    /// `delimiter` matches.
+    pub fn new(delimiter: &'m Matcher) -> Self {
+        Section {
+            delimiter,
+            lines: Vec::new(),
+        }
+    }
+
+    /// Reads the next line. A line matching the delimiter starts a new
+    /// section; any other line is added to the current one, or dropped if
+    /// no delimiter line has been read yet.
+    pub fn push(&mut self, line_number: usize, line: &[u8]) {
+        if self.delimiter.is_match_bytes(line) {
+            self.lines.clear();
+        } else if self.lines.is_empty() {
+            return;
+        }
+        self.lines.push((line_number, line.to_vec()));
+    }
+
+    /// The lines of the current section before line `line_number`, with
+    /// their line numbers, starting with the delimiter line.
+    pub fn lines_before(&self, line_number: usize) -> impl Iterator<Item = (usize, &[u8])> {
+        self.lines
+            .iter()
+            .take_while(move |(number, _)| *number < line_number)
+            .map(|(number, line)| (*number, line.as_slice()))
+    }
+}
+
+/// Searches through a reader like [`search_lines`](crate::search_lines),
+/// returning each match with the lines of its [`Section`] before it as
+/// `before`: those from the last line matching `delimiter` on.
+///
+/// A match with no delimiter line before it, or that is one itself, gets no
+/// lines before it. `after` is always empty.
+///
+/// # Errors
+///
+/// Returns an error if reading from `reader` fails or a line is not valid
+/// UTF-8.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::Matcher;
+/// use searcher_cli_starter::context::search_lines_with_section;
+///
+/// let input = "fn open() {\n    let f = 1;\n    todo!()\n}\nfn close() {\n    todo!()\n}\n";
+/// let matcher = Matcher::new("todo!", false, false).unwrap();
+/// let delimiter = Matcher::new("^fn ", false, true).unwrap();
+/// let results = search_lines_with_section(input.as_bytes(), &matcher, &delimiter).unwrap();
+///
+/// assert_eq!(results[0].before, ["fn open() {", "    let f = 1;"]);
+/// assert_eq!(results[1].before, ["fn close() {"]);
+/// assert_eq!(results[1].first_line_number(), 5);
+/// ```
+pub fn search_lines_with_section<R: Read>(
+    reader: R,
+    matcher: &Matcher,
+    delimiter: &Matcher,
+) -> Result<Vec<MatchWithContext>> {
+    let mut section = Section::new(delimiter);
+    let mut results: Vec<MatchWithContext> = Vec::new();
+
+    for_each_line(
+        &mut LineReader::new(reader, b'\n'),
+        |line_number, content| {
+            section.push(line_number, content.as_bytes());
+            if matcher.is_match(content) {
+                results.push(MatchWithContext {
+                    before: section
+                        .lines_before(line_number)
+                        .map(|(_, line)| String::from_utf8_lossy(line).into_owned())
+                        .collect(),
+                    matched: SearchMatch {
+                        line_number,
+                        content: content.to_string(),
+                    },
+                    after: Vec::new(),
+                });
+            }
+            ControlFlow::Continue(())
+        },
+    )?;
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_is_cut_off_at_the_edges() {
+        let matcher = Matcher::new("m", false, false).unwrap();
+        let input = "m1\na\nb\nm2\r\nc\n";
+        let results = search_lines_with_context(input.as_bytes(), &matcher, 2, 3).unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].before.is_empty());
+        assert_eq!(results[0].after, ["a", "b", "m2"]);
+        assert_eq!(results[1].before, ["a", "b"]);
+        assert_eq!(results[1].matched.content, "m2");
+        assert_eq!(results[1].after, ["c"]);
+        assert_eq!(results[1].first_line_number(), 2);
+
+        let bare = search_lines_with_context(input.as_bytes(), &matcher, 0, 0).unwrap();
+        let lines: Vec<usize> = bare.iter().map(|m| m.matched.line_number).collect();
+        assert_eq!(lines, [1, 4]);
+        assert!(
+            bare.iter()
+                .all(|m| m.before.is_empty() && m.after.is_empty())
+        );
+    }
+
+    #[test]
+    fn test_huge_context_is_not_allocated_up_front() {
+        let matcher = Matcher::new("m", false, false).unwrap();
+        let results =
+            search_lines_with_context(&b"a\nm\n"[..], &matcher, usize::MAX, usize::MAX).unwrap();
+        assert_eq!(results[0].before, ["a"]);
+        assert!(results[0].after.is_empty());
+    }
+
+    #[test]
+    fn test_section_starts_at_each_delimiter() {
+        let matcher = Matcher::new("key", false, false).unwrap();
+        let delimiter = Matcher::new(r"^\[.*\]$", false, true).unwrap();
+        let input = "key=0\n[a]\nx=1\nkey=1\nkey=2\n[key]\n[b]\nkey=3\n";
+        let results = search_lines_with_section(input.as_bytes(), &matcher, &delimiter).unwrap();
+
+        let before: Vec<&[String]> = results.iter().map(|m| &m.before[..]).collect();
+        assert_eq!(before.len(), 5);
+        assert!(before[0].is_empty());
+        assert_eq!(before[1], ["[a]", "x=1"]);
+        assert_eq!(before[2], ["[a]", "x=1", "key=1"]);
+        assert!(before[3].is_empty());
+        assert_eq!(before[4], ["[b]"]);
+    }
+}
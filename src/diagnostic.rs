@@ -0,0 +1,218 @@
+//! Telling why a file could not be searched.
+//!
+//! Errors from walking and searching are [`anyhow::Error`]s whose message is
+//! meant for people, e.g. ``Could not read file `a.log`: No such file or
+//! directory``. Tools that run searches need to act on the kind of failure
+//! and the file it happened to without parsing that text. [`Diagnostic`]
+//! takes both out of an error: the path from the [`FileError`] of a walk or
+//! the [`FileContext`] the file searches add, and the kind from the I/O
+//! error or [`TimedOut`] behind it. This is what `searcher find
+//! --error-format json` prints.
+//!
+//! ```
+//! use searcher_cli_starter::diagnostic::{Diagnostic, DiagnosticKind};
+//! use searcher_cli_starter::{Matcher, SearchOptions, search_path};
+//! use std::path::Path;
+//!
+//! let matcher = Matcher::new("x", false, false).unwrap();
+//! let error = search_path(Path::new("missing.log"), &matcher, &SearchOptions::default()).unwrap_err();
+//!
+//! let diagnostic = Diagnostic::new(&error);
+//! assert_eq!(diagnostic.kind, DiagnosticKind::NotFound);
+//! assert_eq!(diagnostic.path.as_deref(), Some(Path::new("missing.log")));
+//! assert!(diagnostic.message.starts_with("Could not read file `missing.log`"));
+//! ```
+
+use crate::cancel::TimedOut;
+use crate::walk::FileError;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The context added to an error from reading or searching a file, naming
+/// the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileContext {
+    /// The file that failed
+    pub path: PathBuf,
+    /// Whether the search took longer than its time limit, rather than
+    /// failing to read the file
+    pub timed_out: bool,
+}
+
+impl FileContext {
+    /// The context of failing to open or read the file at `path`.
+    pub fn read(path: &Path) -> Self {
+        FileContext {
+            path: path.to_path_buf(),
+            timed_out: false,
+        }
+    }
+
+    /// The context of searching the file at `path` taking too long.
+    pub fn timed_out(path: &Path) -> Self {
+        FileContext {
+            path: path.to_path_buf(),
+            timed_out: true,
+        }
+    }
+}
+
+impl fmt::Display for FileContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.timed_out {
+            write!(f, "Timed out searching `{}`", self.path.display())
+        } else {
+            write!(f, "Could not read file `{}`", self.path.display())
+        }
+    }
+}
+
+/// Why a file could not be searched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// The file or directory does not exist
+    NotFound,
+    /// There is no permission to read it
+    PermissionDenied,
+    /// It is larger than the walk allows
+    TooLarge,
+    /// Its contents could not be decoded, e.g. invalid UTF-8 where text was
+    /// needed
+    Encoding,
+    /// The search took longer than its time limit
+    TimedOut,
+    /// Any other error reading it
+    Io,
+    /// An error that did not come from reading
+    Other,
+}
+
+impl DiagnosticKind {
+    /// The name of the kind in `snake_case`, e.g. `not_found`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DiagnosticKind::NotFound => "not_found",
+            DiagnosticKind::PermissionDenied => "permission_denied",
+            DiagnosticKind::TooLarge => "too_large",
+            DiagnosticKind::Encoding => "encoding",
+            DiagnosticKind::TimedOut => "timed_out",
+            DiagnosticKind::Io => "io",
+            DiagnosticKind::Other => "other",
+        }
+    }
+
+    fn of_io(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => DiagnosticKind::NotFound,
+            io::ErrorKind::PermissionDenied => DiagnosticKind::PermissionDenied,
+            io::ErrorKind::FileTooLarge => DiagnosticKind::TooLarge,
+            io::ErrorKind::InvalidData => DiagnosticKind::Encoding,
+            _ => DiagnosticKind::Io,
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An error taken apart for tools rather than people.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Why it failed
+    pub kind: DiagnosticKind,
+    /// The file or directory it failed on, if the error names one
+    pub path: Option<PathBuf>,
+    /// The whole message, with its causes, as `{:#}` writes it
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Finds the kind and the path of `error` among its causes.
+    pub fn new(error: &anyhow::Error) -> Self {
+        let mut kind = None;
+        let mut path = error
+            .downcast_ref::<FileContext>()
+            .map(|context| context.path.clone());
+        for cause in error.chain() {
+            if let Some(error) = cause.downcast_ref::<FileError>() {
+                path.get_or_insert_with(|| error.path.clone());
+                kind.get_or_insert(DiagnosticKind::of_io(&error.error));
+            } else if let Some(error) = cause.downcast_ref::<io::Error>() {
+                kind.get_or_insert(DiagnosticKind::of_io(error));
+            } else if cause.is::<TimedOut>() {
+                kind.get_or_insert(DiagnosticKind::TimedOut);
+            } else if cause.is::<std::str::Utf8Error>() || cause.is::<std::string::FromUtf8Error>()
+            {
+                kind.get_or_insert(DiagnosticKind::Encoding);
+            }
+        }
+        Diagnostic {
+            kind: kind.unwrap_or(DiagnosticKind::Other),
+            path,
+            message: format!("{:#}", error),
+        }
+    }
+
+    /// The diagnostic as a JSON `error` object, with the fields of the
+    /// errors `searcher serve` answers with and its `kind`:
+    ///
+    /// ```json
+    /// {"kind":"not_found","message":"Could not read file `a.log`: ...","path":"a.log","type":"error"}
+    /// ```
+    ///
+    /// Paths are written as by the server, with `path_base64` for those that
+    /// are not valid UTF-8. Available with the `server` feature.
+    #[cfg(feature = "server")]
+    pub fn to_json(&self) -> serde_json::Value {
+        let value = serde_json::json!({
+            "type": "error",
+            "kind": self.kind.as_str(),
+            "message": self.message,
+        });
+        match &self.path {
+            Some(path) => crate::server::with_path(value, path),
+            None => value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_kind_and_path_come_from_the_causes() {
+        let error = anyhow::Error::from(FileError {
+            path: PathBuf::from("logs/private"),
+            error: io::Error::from(io::ErrorKind::PermissionDenied),
+        });
+        let diagnostic = Diagnostic::new(&error);
+        assert_eq!(diagnostic.kind, DiagnosticKind::PermissionDenied);
+        assert_eq!(diagnostic.path, Some(PathBuf::from("logs/private")));
+
+        let error = Err::<(), _>(TimedOut {
+            limit: std::time::Duration::from_secs(1),
+        })
+        .context(FileContext::timed_out(Path::new("big.log")))
+        .context("while checking")
+        .unwrap_err();
+        let diagnostic = Diagnostic::new(&error);
+        assert_eq!(diagnostic.kind, DiagnosticKind::TimedOut);
+        assert_eq!(diagnostic.path, Some(PathBuf::from("big.log")));
+        assert_eq!(
+            diagnostic.message,
+            "while checking: Timed out searching `big.log`: search exceeded the time limit of 1s"
+        );
+
+        let diagnostic = Diagnostic::new(&anyhow::anyhow!("bad pattern"));
+        assert_eq!(
+            (diagnostic.kind, diagnostic.path),
+            (DiagnosticKind::Other, None)
+        );
+    }
+}
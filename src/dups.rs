@@ -0,0 +1,150 @@
+//! Finding the lines, or matched values, that repeat.
+//!
+//! Much of the noise in a log is the same line written over and over.
+//! [`Duplicates`] counts how often each line occurs, or each value a
+//! pattern matches, and remembers where, so the repeated ones can be
+//! reported with their locations. This is what `searcher dups` prints.
+//!
+//! ```
+//! use searcher_cli_starter::dups::Duplicates;
+//! use std::path::Path;
+//!
+//! let mut duplicates = Duplicates::new();
+//! for (line_number, line) in ["retrying", "ok", "retrying"].into_iter().enumerate() {
+//!     duplicates.add(Path::new("app.log"), line_number + 1, line.as_bytes());
+//! }
+//!
+//! let repeated = duplicates.repeated(2);
+//! assert_eq!(repeated.len(), 1);
+//! assert_eq!(repeated[0].value, b"retrying");
+//! assert_eq!(repeated[0].locations, [(Path::new("app.log"), 1), (Path::new("app.log"), 3)]);
+//! ```
+
+use crate::Matcher;
+use crate::histogram::match_values;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Counts values and remembers where they occur.
+///
+/// Only the first [`Duplicates::with_max_locations`] locations of each value
+/// are kept, so memory grows with the number of distinct values rather than
+/// with the input.
+#[derive(Debug, Clone, Default)]
+pub struct Duplicates {
+    /// The files values were found in, so locations refer to them by index
+    files: Vec<PathBuf>,
+    values: HashMap<Vec<u8>, Occurrences>,
+    max_locations: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Occurrences {
+    count: usize,
+    /// The index in `files` and the line number of each kept occurrence
+    locations: Vec<(usize, usize)>,
+}
+
+/// A value that occurred at least the number of times asked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicate<'d> {
+    /// The line, or the matched value
+    pub value: &'d [u8],
+    /// How often it occurred
+    pub count: usize,
+    /// The file and line number of each occurrence kept, in the order they
+    /// were added
+    pub locations: Vec<(&'d Path, usize)>,
+}
+
+impl Duplicates {
+    /// Creates an empty count that keeps every location.
+    pub fn new() -> Self {
+        Duplicates::default()
+    }
+
+    /// Keeps at most `max` locations of each value; the count still covers
+    /// all of them.
+    pub fn with_max_locations(mut self, max: usize) -> Self {
+        self.max_locations = Some(max);
+        self
+    }
+
+    /// Counts `value`, found on line `line_number` of the file at `path`.
+    /// Empty values are not counted.
+    pub fn add(&mut self, path: &Path, line_number: usize, value: &[u8]) {
+        if value.is_empty() {
+            return;
+        }
+        if self.files.last().is_none_or(|last| last != path) {
+            self.files.push(path.to_path_buf());
+        }
+        let file = self.files.len() - 1;
+        let occurrences = match self.values.get_mut(value) {
+            Some(occurrences) => occurrences,
+            None => self.values.entry(value.to_vec()).or_default(),
+        };
+        occurrences.count += 1;
+        if self
+            .max_locations
+            .is_none_or(|max| occurrences.locations.len() < max)
+        {
+            occurrences.locations.push((file, line_number));
+        }
+    }
+
+    /// Counts the value of every match of `matcher` in `line`: its group
+    /// named `key`, else its first capture group, else the whole match, as
+    /// [`DistinctValues`](crate::histogram::DistinctValues) takes them.
+    pub fn add_matches(&mut self, path: &Path, line_number: usize, matcher: &Matcher, line: &[u8]) {
+        for value in match_values(matcher, line) {
+            self.add(path, line_number, value.as_bytes());
+        }
+    }
+
+    /// The values that occurred at least `min_count` times, most frequent
+    /// first; values that occur equally often are sorted by value.
+    pub fn repeated(&self, min_count: usize) -> Vec<Duplicate<'_>> {
+        let mut repeated: Vec<Duplicate<'_>> = self
+            .values
+            .iter()
+            .filter(|(_, occurrences)| occurrences.count >= min_count)
+            .map(|(value, occurrences)| Duplicate {
+                value,
+                count: occurrences.count,
+                locations: occurrences
+                    .locations
+                    .iter()
+                    .map(|&(file, line_number)| (self.files[file].as_path(), line_number))
+                    .collect(),
+            })
+            .collect();
+        repeated.sort_by(|a, b| b.count.cmp(&a.count).then(a.value.cmp(b.value)));
+        repeated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locations_are_capped_but_counts_are_not() {
+        let mut duplicates = Duplicates::new().with_max_locations(2);
+        let matcher = Matcher::new(r"id=(\d+)", false, true).unwrap();
+        duplicates.add_matches(Path::new("a.log"), 1, &matcher, b"id=1 id=2");
+        duplicates.add_matches(Path::new("a.log"), 2, &matcher, b"id=1");
+        duplicates.add_matches(Path::new("b.log"), 7, &matcher, b"id=1 id=3");
+        duplicates.add(Path::new("b.log"), 8, b"");
+
+        let repeated = duplicates.repeated(2);
+        assert_eq!(repeated.len(), 1);
+        assert_eq!(repeated[0].value, b"1");
+        assert_eq!(repeated[0].count, 3);
+        assert_eq!(
+            repeated[0].locations,
+            [(Path::new("a.log"), 1), (Path::new("a.log"), 2)]
+        );
+        assert_eq!(duplicates.repeated(1).len(), 3);
+    }
+}
@@ -0,0 +1,247 @@
+//! Search results as a stream of events, for GUIs.
+//!
+//! A desktop app cannot wait for [`search_path`](crate::search_path) to
+//! finish every file before showing anything. [`spawn_search`] runs the
+//! search on a background thread instead and sends a [`SearchEvent`] over a
+//! channel as each file is started, each match is found, and each file is
+//! done, so results can be rendered as they come in. The search stops early
+//! when the receiver is dropped or `options.cancel` is cancelled.
+//!
+//! ```no_run
+//! use searcher_cli_starter::events::{SearchEvent, spawn_search};
+//! use searcher_cli_starter::{Matcher, SearchOptions};
+//!
+//! let matcher = Matcher::new("TODO", false, false).unwrap();
+//! let events = spawn_search(vec!["src".into()], matcher, SearchOptions::default());
+//! for event in events {
+//!     match event {
+//!         SearchEvent::Match { path, search_match } => {
+//!             println!("{}:{}", path.display(), search_match.line_number)
+//!         }
+//!         SearchEvent::Done { files, matches } => println!("{} in {} files", matches, files),
+//!         _ => {}
+//!     }
+//! }
+//! ```
+
+use crate::cancel::Cancelled;
+use crate::walk::Walk;
+use crate::{Matcher, SearchMatchBytes, SearchOptions, search_path_with};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// Something that happened during a search started by [`spawn_search`] or
+/// [`search_with_events`].
+///
+/// The events of one file come in order: `FileStart`, its `Match`es, then
+/// `FileEnd`, or `Error` in place of `FileEnd` if the file could not be
+/// searched. Directories that cannot be listed only get an `Error`. `Done`
+/// is always the last event.
+#[derive(Debug)]
+pub enum SearchEvent {
+    /// A file is about to be searched
+    FileStart {
+        /// The file's path
+        path: PathBuf,
+    },
+    /// A matching line was found
+    Match {
+        /// The file the line is in
+        path: PathBuf,
+        /// The matching line
+        search_match: SearchMatchBytes,
+    },
+    /// A file was searched to the end
+    FileEnd {
+        /// The file's path
+        path: PathBuf,
+        /// How many matching lines it had
+        matches: usize,
+    },
+    /// A file or directory could not be searched; the search goes on
+    Error {
+        /// The path that failed
+        path: PathBuf,
+        /// What went wrong
+        error: anyhow::Error,
+    },
+    /// The search is over, because every file was searched or it was cancelled
+    Done {
+        /// How many files were searched to the end
+        files: usize,
+        /// How many matching lines they had in total
+        matches: usize,
+    },
+}
+
+/// Searches the files below `paths` on a new thread, returning the channel
+/// its [`SearchEvent`]s arrive on.
+///
+/// The matcher can be passed by value or shared as an `Arc<Matcher>`. The
+/// thread stops once it has sent [`SearchEvent::Done`], or as soon as the
+/// receiver is dropped.
+pub fn spawn_search(
+    paths: Vec<PathBuf>,
+    matcher: impl Into<Arc<Matcher>>,
+    options: SearchOptions,
+) -> Receiver<SearchEvent> {
+    let matcher = matcher.into();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || search_with_events(Walk::new(&paths), &matcher, &options, &sender));
+    receiver
+}
+
+/// Searches the files of `walk` on the current thread, sending a
+/// [`SearchEvent`] to `sender` for each step, for callers that manage their
+/// own threads.
+///
+/// Stops early, still sending [`SearchEvent::Done`], when `options.cancel`
+/// is cancelled, and without sending anything more once the receiver is
+/// dropped.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::events::{SearchEvent, search_with_events};
+/// use searcher_cli_starter::walk::Walk;
+/// use searcher_cli_starter::{Matcher, SearchOptions};
+/// use std::sync::mpsc;
+///
+/// let matcher = Matcher::new("Rust", false, false).unwrap();
+/// let walk = Walk::new(["tests/fixtures/sample.txt", "missing.txt"]);
+/// let (sender, receiver) = mpsc::channel();
+/// search_with_events(walk, &matcher, &SearchOptions::default(), &sender);
+///
+/// let events: Vec<SearchEvent> = receiver.try_iter().collect();
+/// assert!(matches!(events[0], SearchEvent::FileStart { .. }));
+/// assert!(matches!(events.last(), Some(SearchEvent::Done { files: 1, matches: 3 })));
+/// ```
+pub fn search_with_events(
+    walk: Walk,
+    matcher: &Matcher,
+    options: &SearchOptions,
+    sender: &Sender<SearchEvent>,
+) {
+    let mut files = 0;
+    let mut total = 0;
+
+    for entry in walk {
+        let path = match entry {
+            Ok(path) => path,
+            Err(error) => {
+                let path = error.path.clone();
+                let error = anyhow::Error::from(error);
+                if sender.send(SearchEvent::Error { path, error }).is_err() {
+                    return;
+                }
+                continue;
+            }
+        };
+        if sender
+            .send(SearchEvent::FileStart { path: path.clone() })
+            .is_err()
+        {
+            return;
+        }
+
+        let mut matches = 0;
+        let mut disconnected = false;
+        let searched = search_path_with(&path, matcher, options, |search_match| {
+            matches += 1;
+            let event = SearchEvent::Match {
+                path: path.clone(),
+                search_match,
+            };
+            if sender.send(event).is_err() {
+                disconnected = true;
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        });
+        if disconnected {
+            return;
+        }
+        let event = match searched {
+            Err(error) if error.is::<Cancelled>() => break,
+            Err(error) => SearchEvent::Error { path, error },
+            Ok(()) => {
+                files += 1;
+                total += matches;
+                SearchEvent::FileEnd { path, matches }
+            }
+        };
+        if sender.send(event).is_err() {
+            return;
+        }
+    }
+
+    let _ = sender.send(SearchEvent::Done {
+        files,
+        matches: total,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancel::CancellationToken;
+
+    /// The events of a search, with paths and matches reduced to strings.
+    fn describe(events: Receiver<SearchEvent>) -> Vec<String> {
+        events
+            .into_iter()
+            .map(|event| match event {
+                SearchEvent::FileStart { path } => format!("start {}", path.display()),
+                SearchEvent::Match { search_match, .. } => {
+                    format!("match {}", search_match.line_number)
+                }
+                SearchEvent::FileEnd { path, matches } => {
+                    format!("end {} {}", path.display(), matches)
+                }
+                SearchEvent::Error { path, .. } => format!("error {}", path.display()),
+                SearchEvent::Done { files, matches } => format!("done {} {}", files, matches),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_spawn_search_sends_events_in_order() {
+        let matcher = Matcher::new("Rust", false, false).unwrap();
+        let paths = vec![
+            PathBuf::from("tests/fixtures/sample.txt"),
+            PathBuf::from("missing.txt"),
+        ];
+        let events = spawn_search(paths, matcher, SearchOptions::default());
+
+        assert_eq!(
+            describe(events),
+            [
+                "start tests/fixtures/sample.txt",
+                "match 2",
+                "match 3",
+                "match 5",
+                "end tests/fixtures/sample.txt 3",
+                "start missing.txt",
+                "error missing.txt",
+                "done 1 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cancelled_search_is_done_early() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let options = SearchOptions {
+            cancel: Some(token),
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("Rust", false, false).unwrap();
+        let events = spawn_search(vec!["tests/fixtures".into()], matcher, options);
+
+        assert_eq!(describe(events).last().unwrap(), "done 0 0");
+    }
+}
@@ -0,0 +1,763 @@
+//! Hooks that decide which lines are searched and which matches are kept.
+//!
+//! Two kinds of filters plug into [`SearchOptions`]:
+//!
+//! - A [`LineFilter`] runs before matching, after
+//!   [`SearchOptions::line_ranges`], and is meant for cheap checks that skip
+//!   lines the matcher never needs to see.
+//! - A [`MatchFilter`] runs after matching, on matching lines only, so it can
+//!   afford costlier business rules, such as looking up a captured value.
+//!
+//! A line is reported only if every filter of both kinds keeps it:
+//!
+//! ```
+//! use searcher_cli_starter::{Matcher, SearchOptions, search_reader};
+//!
+//! let options = SearchOptions::default()
+//!     // Don't search comments
+//!     .with_filter(|_line_number: usize, line: &[u8]| !line.starts_with(b"#"))
+//!     // Ignore ports that are allowed
+//!     .with_match_filter(|_line_number: usize, line: &[u8], matcher: &Matcher| {
+//!         matcher.captures_bytes(line)[0][0].value.as_deref() != Some("443")
+//!     });
+//!
+//! let matcher = Matcher::new(r"port = (\d+)", false, true).unwrap();
+//! let input = "# port = 80\nport = 443\nport = 8080\n";
+//! let matches = search_reader(input.as_bytes(), &matcher, &options).unwrap();
+//! assert_eq!(matches.len(), 1);
+//! assert_eq!(matches[0].line_number, 3);
+//! ```
+//!
+//! With the `timestamps` feature, [`TimeRange`] keeps the log lines whose
+//! timestamp falls inside a time window; it is what `--since` and `--until`
+//! use.
+//!
+//! [`Dedupe`], set as [`SearchOptions::dedupe`], drops matching lines that
+//! repeat one already reported, for `--dedupe`.
+//!
+//! [`Sample`] keeps one line in every N, for `--sample`.
+//!
+//! A [`LineTransform`] rewrites what the matcher sees instead, such as
+//! [`NormalizeWhitespace`] for `--normalize-ws`, so that `connection
+//! refused` matches a line with a tab between the words. Filters and the
+//! reported match still get the line as it was read:
+//!
+//! ```
+//! use searcher_cli_starter::filter::NormalizeWhitespace;
+//! use searcher_cli_starter::{Matcher, SearchOptions, search_reader};
+//!
+//! let options = SearchOptions::default().with_transform(NormalizeWhitespace);
+//! let matcher = Matcher::new("connection refused", false, false).unwrap();
+//! let matches = search_reader(&b"  connection \t refused\n"[..], &matcher, &options).unwrap();
+//! assert_eq!(matches[0].content, b"  connection \t refused");
+//! ```
+//!
+//! [`SearchOptions`]: crate::SearchOptions
+//! [`SearchOptions::line_ranges`]: crate::SearchOptions::line_ranges
+//! [`SearchOptions::dedupe`]: crate::SearchOptions::dedupe
+
+use crate::Matcher;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Decides whether a line is searched.
+///
+/// Implemented for closures taking the 1-based line number and the line
+/// (without its terminator), so most filters need no type of their own.
+pub trait LineFilter: Send + Sync {
+    /// Whether line `line_number`, whose content is `line`, is searched.
+    fn keep(&self, line_number: usize, line: &[u8]) -> bool;
+}
+
+impl<F> LineFilter for F
+where
+    F: Fn(usize, &[u8]) -> bool + Send + Sync,
+{
+    fn keep(&self, line_number: usize, line: &[u8]) -> bool {
+        self(line_number, line)
+    }
+}
+
+/// Decides whether a matching line is reported.
+///
+/// Implemented for closures taking the 1-based line number, the line
+/// (without its terminator), and the matcher that matched it, e.g. to
+/// inspect its [`Matcher::captures_bytes`].
+pub trait MatchFilter: Send + Sync {
+    /// Whether the match on line `line_number`, whose content is `line`, is
+    /// kept.
+    fn keep(&self, line_number: usize, line: &[u8], matcher: &Matcher) -> bool;
+}
+
+impl<F> MatchFilter for F
+where
+    F: Fn(usize, &[u8], &Matcher) -> bool + Send + Sync,
+{
+    fn keep(&self, line_number: usize, line: &[u8], matcher: &Matcher) -> bool {
+        self(line_number, line, matcher)
+    }
+}
+
+/// Rewrites a line before it is matched.
+pub trait LineTransform: Send + Sync {
+    /// `line` (without its terminator) as the matcher should see it,
+    /// borrowed if nothing changes.
+    fn transform<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]>;
+}
+
+/// Trims whitespace off both ends of each line and turns every run of
+/// whitespace inside it into a single space, so a pattern written with one
+/// space matches however the words are spaced. Only ASCII whitespace counts.
+///
+/// ```
+/// use searcher_cli_starter::filter::{LineTransform, NormalizeWhitespace};
+///
+/// assert_eq!(NormalizeWhitespace.transform(b"  a\t\tb  c \r"), &b"a b c"[..]);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NormalizeWhitespace;
+
+impl LineTransform for NormalizeWhitespace {
+    fn transform<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        let normal = !line.first().is_some_and(u8::is_ascii_whitespace)
+            && !line.last().is_some_and(u8::is_ascii_whitespace)
+            && line
+                .windows(2)
+                .all(|pair| pair[0] != b' ' || !pair[1].is_ascii_whitespace())
+            && line
+                .iter()
+                .all(|&byte| byte == b' ' || !byte.is_ascii_whitespace());
+        if normal {
+            return Cow::Borrowed(line);
+        }
+
+        let mut normalized = Vec::with_capacity(line.len());
+        for word in line
+            .split(|byte| byte.is_ascii_whitespace())
+            .filter(|word| !word.is_empty())
+        {
+            if !normalized.is_empty() {
+                normalized.push(b' ');
+            }
+            normalized.extend_from_slice(word);
+        }
+        Cow::Owned(normalized)
+    }
+}
+
+/// A [`LineFilter`] keeping one line in every `n`: lines 1, `n + 1`,
+/// `2n + 1`, and so on, to probe huge files quickly with `--sample`.
+///
+/// ```
+/// use searcher_cli_starter::filter::{LineFilter, Sample};
+/// use std::num::NonZeroUsize;
+///
+/// let sample = Sample::new(NonZeroUsize::new(3).unwrap());
+/// let kept: Vec<usize> = (1..=7).filter(|&line| sample.keep(line, b"")).collect();
+/// assert_eq!(kept, [1, 4, 7]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    every: NonZeroUsize,
+}
+
+impl Sample {
+    /// Keeps one line in every `every`, starting with the first.
+    pub fn new(every: NonZeroUsize) -> Self {
+        Sample { every }
+    }
+
+    /// How many lines there are per line kept.
+    pub fn every(&self) -> NonZeroUsize {
+        self.every
+    }
+}
+
+impl LineFilter for Sample {
+    fn keep(&self, line_number: usize, _line: &[u8]) -> bool {
+        line_number.saturating_sub(1) % self.every == 0
+    }
+}
+
+/// A list of hooks of one kind: filters, all of which must keep a line, or
+/// transforms, applied one after the other.
+///
+/// Clones share the same hooks. Two lists are equal when they hold the
+/// same hooks in the same order.
+pub struct Filters<F: ?Sized> {
+    filters: Vec<Arc<F>>,
+}
+
+/// The [`LineFilter`]s of a search.
+pub type LineFilters = Filters<dyn LineFilter>;
+
+/// The [`MatchFilter`]s of a search.
+pub type MatchFilters = Filters<dyn MatchFilter>;
+
+/// The [`LineTransform`]s of a search.
+pub type LineTransforms = Filters<dyn LineTransform>;
+
+impl<F: ?Sized> Filters<F> {
+    /// Creates an empty list, which keeps every line.
+    pub fn new() -> Self {
+        Filters {
+            filters: Vec::new(),
+        }
+    }
+
+    /// Whether there are no filters.
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+}
+
+impl LineFilters {
+    /// Adds a filter, run after the ones already added.
+    pub fn push(&mut self, filter: impl LineFilter + 'static) {
+        self.filters.push(Arc::new(filter));
+    }
+
+    /// Whether every filter keeps the line.
+    pub fn keep(&self, line_number: usize, line: &[u8]) -> bool {
+        self.filters
+            .iter()
+            .all(|filter| filter.keep(line_number, line))
+    }
+}
+
+impl MatchFilters {
+    /// Adds a filter, run after the ones already added.
+    pub fn push(&mut self, filter: impl MatchFilter + 'static) {
+        self.filters.push(Arc::new(filter));
+    }
+
+    /// Whether every filter keeps the match.
+    pub fn keep(&self, line_number: usize, line: &[u8], matcher: &Matcher) -> bool {
+        self.filters
+            .iter()
+            .all(|filter| filter.keep(line_number, line, matcher))
+    }
+}
+
+impl LineTransforms {
+    /// Adds a transform, run on the output of the ones already added.
+    pub fn push(&mut self, transform: impl LineTransform + 'static) {
+        self.filters.push(Arc::new(transform));
+    }
+
+    /// `line` as rewritten by every transform in turn.
+    pub fn apply<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        let mut line = Cow::Borrowed(line);
+        for transform in &self.filters {
+            line = match line {
+                Cow::Borrowed(line) => transform.transform(line),
+                Cow::Owned(line) => Cow::Owned(transform.transform(&line).into_owned()),
+            };
+        }
+        line
+    }
+}
+
+impl<F: ?Sized> Default for Filters<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: ?Sized> Clone for Filters<F> {
+    fn clone(&self) -> Self {
+        Filters {
+            filters: self.filters.clone(),
+        }
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for Filters<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Filters({} filters)", self.filters.len())
+    }
+}
+
+impl<F: ?Sized> PartialEq for Filters<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.filters.len() == other.filters.len()
+            && self
+                .filters
+                .iter()
+                .zip(&other.filters)
+                .all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+}
+
+impl<F: ?Sized> Eq for Filters<F> {}
+
+/// Remembers the matching lines reported so far, to drop repeats such as the
+/// same entry in several rotated log files.
+///
+/// Lines are compared after [`Dedupe::normalize`]. Clones share what was
+/// seen; the search functions start over for each input unless the `Dedupe`
+/// was created with [`Dedupe::across_inputs`].
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::filter::Dedupe;
+/// use searcher_cli_starter::{Matcher, SearchOptions, search_reader};
+///
+/// let options = SearchOptions {
+///     dedupe: Some(Dedupe::across_inputs()),
+///     ..SearchOptions::default()
+/// };
+/// let matcher = Matcher::new("error", false, false).unwrap();
+/// let first = search_reader(&b"error: disk\nerror:  disk \n"[..], &matcher, &options).unwrap();
+/// assert_eq!(first.len(), 1);
+/// let second = search_reader(&b"error: disk\nerror: net\n"[..], &matcher, &options).unwrap();
+/// assert_eq!(second.len(), 1);
+/// assert_eq!(second[0].content, b"error: net");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Dedupe {
+    across_inputs: bool,
+    /// The normalized lines seen so far
+    seen: Arc<Mutex<HashSet<Vec<u8>>>>,
+}
+
+impl Dedupe {
+    /// Drops lines repeated within one input.
+    pub fn per_input() -> Self {
+        Dedupe::default()
+    }
+
+    /// Drops lines repeated anywhere in the inputs searched with this
+    /// `Dedupe` (or its clones).
+    pub fn across_inputs() -> Self {
+        Dedupe {
+            across_inputs: true,
+            ..Dedupe::default()
+        }
+    }
+
+    /// Whether repeats are dropped across inputs.
+    pub fn is_across_inputs(&self) -> bool {
+        self.across_inputs
+    }
+
+    /// The `Dedupe` to use for the next input: `self` when deduplicating
+    /// across inputs, otherwise a fresh one.
+    pub fn for_input(&self) -> Self {
+        if self.across_inputs {
+            self.clone()
+        } else {
+            Dedupe::per_input()
+        }
+    }
+
+    /// Records `line` and returns whether it is the first of its kind.
+    pub fn insert(&self, line: &[u8]) -> bool {
+        self.seen
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(Self::normalize(line))
+    }
+
+    /// `line` without leading or trailing whitespace and with every run of
+    /// whitespace inside it turned into a single space.
+    ///
+    /// ```
+    /// use searcher_cli_starter::filter::Dedupe;
+    ///
+    /// assert_eq!(Dedupe::normalize(b"  a\t\tb  c \r"), b"a b c");
+    /// ```
+    pub fn normalize(line: &[u8]) -> Vec<u8> {
+        NormalizeWhitespace.transform(line).into_owned()
+    }
+}
+
+impl PartialEq for Dedupe {
+    fn eq(&self, other: &Self) -> bool {
+        self.across_inputs == other.across_inputs && Arc::ptr_eq(&self.seen, &other.seen)
+    }
+}
+
+impl Eq for Dedupe {}
+
+#[cfg(feature = "timestamps")]
+pub use timestamps::TimeRange;
+#[cfg(feature = "timestamps")]
+pub(crate) use timestamps::parse_bound;
+
+#[cfg(feature = "timestamps")]
+mod timestamps {
+    use super::LineFilter;
+    use anyhow::{Context, Result, bail};
+    use regex::bytes::Regex;
+    use time::format_description::{self, OwnedFormatItem};
+    use time::{Date, PrimitiveDateTime};
+
+    /// Finds ISO 8601 timestamps such as `2024-05-01T12:30:00` or
+    /// `2024-05-01 12:30:00`.
+    const DEFAULT_PATTERN: &str = r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}";
+
+    /// Reads the timestamps found by [`DEFAULT_PATTERN`].
+    const DEFAULT_FORMAT: &str = "[year]-[month]-[day][first [T] [ ]][hour]:[minute]:[second]";
+
+    /// Keeps the lines whose timestamp is inside a time window.
+    ///
+    /// The timestamp of a line is the first match of a regex (or its first
+    /// capture group, if it has one), read with a
+    /// [`time` format description](https://time-rs.github.io/book/api/format-description.html).
+    /// Both default to ISO 8601 date-times, e.g. `2024-05-01T12:30:00` or
+    /// `2024-05-01 12:30:00`. Lines without a readable timestamp are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::filter::TimeRange;
+    /// use searcher_cli_starter::{Matcher, SearchOptions, search_reader};
+    ///
+    /// let log = "2024-05-01 09:59:59 ERROR a\n2024-05-01 10:00:00 ERROR b\n";
+    /// let mut options = SearchOptions::default();
+    /// options.filters.push(TimeRange::new(None, None).unwrap().since("2024-05-01 10:00").unwrap());
+    ///
+    /// let matcher = Matcher::new("ERROR", false, false).unwrap();
+    /// let matches = search_reader(log.as_bytes(), &matcher, &options).unwrap();
+    /// assert_eq!(matches.len(), 1);
+    /// assert_eq!(matches[0].line_number, 2);
+    /// ```
+    #[derive(Debug, Clone)]
+    pub struct TimeRange {
+        pattern: Regex,
+        format: OwnedFormatItem,
+        since: Option<PrimitiveDateTime>,
+        until: Option<PrimitiveDateTime>,
+    }
+
+    impl TimeRange {
+        /// Creates a window with no bounds, which keeps every line with a
+        /// timestamp. `pattern` and `format` override how timestamps are
+        /// found and read.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if `pattern` is not a valid regex or `format` is
+        /// not a valid format description.
+        pub fn new(pattern: Option<&str>, format: Option<&str>) -> Result<Self> {
+            let pattern = Regex::new(pattern.unwrap_or(DEFAULT_PATTERN))
+                .context("Invalid timestamp pattern")?;
+            let format = format_description::parse_owned::<2>(format.unwrap_or(DEFAULT_FORMAT))
+                .context("Invalid timestamp format")?;
+            Ok(TimeRange {
+                pattern,
+                format,
+                since: None,
+                until: None,
+            })
+        }
+
+        /// Keeps only lines at or after `time`.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if `time` is not written as `YYYY-MM-DD`,
+        /// `YYYY-MM-DD HH:MM`, or `YYYY-MM-DD HH:MM:SS` (a `T` may replace
+        /// the space).
+        pub fn since(mut self, time: &str) -> Result<Self> {
+            self.since = Some(parse_bound(time)?);
+            Ok(self)
+        }
+
+        /// Keeps only lines at or before `time`, written like for
+        /// [`TimeRange::since`].
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if `time` cannot be read.
+        pub fn until(mut self, time: &str) -> Result<Self> {
+            self.until = Some(parse_bound(time)?);
+            Ok(self)
+        }
+
+        /// The timestamp of `line`, if it has one that can be read.
+        pub fn timestamp(&self, line: &[u8]) -> Option<PrimitiveDateTime> {
+            let captures = self.pattern.captures(line)?;
+            let text = captures.get(1).or_else(|| captures.get(0))?;
+            let text = std::str::from_utf8(text.as_bytes()).ok()?;
+            PrimitiveDateTime::parse(text, &self.format)
+                .ok()
+                .or_else(|| Some(Date::parse(text, &self.format).ok()?.midnight()))
+        }
+    }
+
+    impl LineFilter for TimeRange {
+        fn keep(&self, _line_number: usize, line: &[u8]) -> bool {
+            let Some(timestamp) = self.timestamp(line) else {
+                return false;
+            };
+            self.since.is_none_or(|since| timestamp >= since)
+                && self.until.is_none_or(|until| timestamp <= until)
+        }
+    }
+
+    /// Reads a `--since` / `--until` bound, down to the day, minute, or second.
+    pub(crate) fn parse_bound(time: &str) -> Result<PrimitiveDateTime> {
+        let normalized = time.trim().replacen('T', " ", 1);
+        let formats = [
+            "[year]-[month]-[day] [hour]:[minute]:[second]",
+            "[year]-[month]-[day] [hour]:[minute]",
+        ];
+        for format in formats {
+            let format = format_description::parse_owned::<2>(format)?;
+            if let Ok(parsed) = PrimitiveDateTime::parse(&normalized, &format) {
+                return Ok(parsed);
+            }
+        }
+        let format = format_description::parse_owned::<2>("[year]-[month]-[day]")?;
+        match Date::parse(&normalized, &format) {
+            Ok(date) => Ok(date.midnight()),
+            Err(_) => bail!(
+                "`{}` is not a time such as `2024-05-01`, `2024-05-01 12:30`, or `2024-05-01T12:30:00`",
+                time
+            ),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_window_bounds_are_inclusive() {
+            let range = TimeRange::new(None, None)
+                .unwrap()
+                .since("2024-05-01T10:00:00")
+                .unwrap()
+                .until("2024-05-01 11:00")
+                .unwrap();
+
+            assert!(!range.keep(1, b"2024-05-01 09:59:59 early"));
+            assert!(range.keep(2, b"2024-05-01T10:00:00 start"));
+            assert!(range.keep(3, b"[2024-05-01 11:00:00] end"));
+            assert!(!range.keep(4, b"2024-05-01 11:00:01 late"));
+            assert!(!range.keep(5, b"no timestamp"));
+        }
+
+        #[test]
+        fn test_custom_pattern_and_format() {
+            let range = TimeRange::new(
+                Some(r"\[(\d+/\w+/\d+:\d+:\d+:\d+)"),
+                Some("[day]/[month repr:short]/[year]:[hour]:[minute]:[second]"),
+            )
+            .unwrap()
+            .since("2024-05-01")
+            .unwrap();
+
+            assert!(range.keep(1, b"1.2.3.4 - - [01/May/2024:00:00:00 +0000] \"GET /\""));
+            assert!(!range.keep(2, b"1.2.3.4 - - [30/Apr/2024:23:59:59 +0000] \"GET /\""));
+        }
+
+        #[test]
+        fn test_invalid_bounds_and_formats() {
+            let range = TimeRange::new(None, None).unwrap();
+            assert!(range.clone().since("yesterday").is_err());
+            assert!(range.until("2024-13-01").is_err());
+            assert!(TimeRange::new(None, Some("[year")).is_err());
+            assert!(TimeRange::new(Some("("), None).is_err());
+        }
+    }
+}
+
+#[cfg(feature = "normalize")]
+pub use unicode::Normalization;
+
+#[cfg(feature = "normalize")]
+mod unicode {
+    use super::LineTransform;
+    use std::borrow::Cow;
+    use unicode_normalization::{UnicodeNormalization, is_nfc, is_nfd, is_nfkc, is_nfkd};
+
+    /// A Unicode normalization form, so that text written with composed
+    /// characters such as `é` matches text written with decomposed ones
+    /// such as `e` followed by a combining acute accent.
+    ///
+    /// As a [`LineTransform`], it rewrites each line into the form; the
+    /// pattern has to be in the same form, e.g. through
+    /// [`Normalization::normalize`]. Bytes that are not valid UTF-8 are left
+    /// as they are. Available with the `normalize` cargo feature, which the
+    /// CLI enables.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::filter::Normalization;
+    /// use searcher_cli_starter::{Matcher, SearchOptions, search_reader};
+    ///
+    /// let form = Normalization::Nfc;
+    /// let matcher = Matcher::new(&form.normalize("cafe\u{301}"), false, false).unwrap();
+    /// let options = SearchOptions::default().with_transform(form);
+    /// let input = "caf\u{e9}\ncafe\u{301}\ncafe\n";
+    /// let matches = search_reader(input.as_bytes(), &matcher, &options).unwrap();
+    /// assert_eq!(matches.len(), 2);
+    /// ```
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum Normalization {
+        /// Canonical composition: `e` and a combining accent become `é`
+        Nfc,
+        /// Canonical decomposition: `é` becomes `e` and a combining accent
+        Nfd,
+        /// Compatibility composition, which also folds variants such as
+        /// `ﬁ` into `fi` and full-width `Ａ` into `A`
+        Nfkc,
+        /// Compatibility decomposition
+        Nfkd,
+    }
+
+    impl Normalization {
+        /// `text` in this form, borrowed if it already is.
+        pub fn normalize(self, text: &str) -> Cow<'_, str> {
+            let normalized = match self {
+                Normalization::Nfc => is_nfc(text),
+                Normalization::Nfd => is_nfd(text),
+                Normalization::Nfkc => is_nfkc(text),
+                Normalization::Nfkd => is_nfkd(text),
+            };
+            if text.is_ascii() || normalized {
+                return Cow::Borrowed(text);
+            }
+            Cow::Owned(match self {
+                Normalization::Nfc => text.nfc().collect(),
+                Normalization::Nfd => text.nfd().collect(),
+                Normalization::Nfkc => text.nfkc().collect(),
+                Normalization::Nfkd => text.nfkd().collect(),
+            })
+        }
+    }
+
+    impl LineTransform for Normalization {
+        fn transform<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+            if line.is_ascii() {
+                return Cow::Borrowed(line);
+            }
+            if let Ok(text) = std::str::from_utf8(line) {
+                return match self.normalize(text) {
+                    Cow::Borrowed(text) => Cow::Borrowed(text.as_bytes()),
+                    Cow::Owned(text) => Cow::Owned(text.into_bytes()),
+                };
+            }
+            let mut normalized = Vec::with_capacity(line.len());
+            for chunk in line.utf8_chunks() {
+                normalized.extend_from_slice(self.normalize(chunk.valid()).as_bytes());
+                normalized.extend_from_slice(chunk.invalid());
+            }
+            Cow::Owned(normalized)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters_must_all_keep_a_line() {
+        let mut filters = LineFilters::new();
+        assert!(filters.keep(1, b"anything"));
+
+        filters.push(|line_number: usize, _: &[u8]| line_number % 2 == 1);
+        filters.push(|_: usize, line: &[u8]| !line.is_empty());
+        assert!(filters.keep(1, b"x"));
+        assert!(!filters.keep(2, b"x"));
+        assert!(!filters.keep(3, b""));
+
+        assert_eq!(filters.clone(), filters);
+        assert_ne!(filters, LineFilters::new());
+    }
+
+    #[test]
+    fn test_match_filters_run_on_matches_only() {
+        let seen = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&seen);
+        let options = crate::SearchOptions::default()
+            .with_filter(|_: usize, line: &[u8]| !line.starts_with(b"skip"))
+            .with_match_filter(move |_: usize, line: &[u8], _: &Matcher| {
+                counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                !line.ends_with(b"!")
+            });
+        let matcher = Matcher::new("x", false, false).unwrap();
+
+        let matches =
+            crate::search_reader(&b"x\nskip x\nx!\ny\nx.\n"[..], &matcher, &options).unwrap();
+        let lines: Vec<usize> = matches.iter().map(|m| m.line_number).collect();
+        assert_eq!(lines, [1, 5]);
+        assert_eq!(seen.load(std::sync::atomic::Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_transforms_rewrite_what_the_matcher_sees() {
+        assert!(matches!(
+            NormalizeWhitespace.transform(b"a b"),
+            Cow::Borrowed(b"a b")
+        ));
+        assert_eq!(NormalizeWhitespace.transform(b"a\tb"), &b"a b"[..]);
+        assert_eq!(NormalizeWhitespace.transform(b" \t "), &b""[..]);
+
+        let options = crate::SearchOptions::default()
+            .with_transform(NormalizeWhitespace)
+            .with_filter(|_: usize, line: &[u8]| !line.starts_with(b" "));
+        let matcher = Matcher::new("^a b$", false, true).unwrap();
+        let matches =
+            crate::search_reader(&b"a  b\n a b\na\t\tb \nab\n"[..], &matcher, &options).unwrap();
+        let lines: Vec<&[u8]> = matches.iter().map(|m| &m.content[..]).collect();
+        assert_eq!(lines, [&b"a  b"[..], b"a\t\tb "]);
+    }
+
+    #[cfg(feature = "normalize")]
+    #[test]
+    fn test_normalization_forms() {
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        assert_eq!(Normalization::Nfc.normalize(decomposed), composed);
+        assert_eq!(Normalization::Nfd.normalize(composed), decomposed);
+        assert!(matches!(
+            Normalization::Nfc.normalize(composed),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(Normalization::Nfkc.normalize("\u{fb01}le"), "file");
+        assert_eq!(Normalization::Nfc.normalize("\u{fb01}le"), "\u{fb01}le");
+
+        // Invalid bytes stay, the text around them is normalized
+        let line = [&b"\xff "[..], decomposed.as_bytes()].concat();
+        let expected = [&b"\xff "[..], composed.as_bytes()].concat();
+        assert_eq!(Normalization::Nfc.transform(&line), expected);
+    }
+
+    #[test]
+    fn test_dedupe_per_input_starts_over() {
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let input = &b"x 1\nx  1\nx 2\n x 1\n"[..];
+        let lines = |options: &crate::SearchOptions| -> Vec<usize> {
+            crate::search_reader(input, &matcher, options)
+                .unwrap()
+                .iter()
+                .map(|m| m.line_number)
+                .collect()
+        };
+
+        let options = crate::SearchOptions {
+            dedupe: Some(Dedupe::per_input()),
+            ..crate::SearchOptions::default()
+        };
+        assert_eq!(lines(&options), [1, 3]);
+        assert_eq!(lines(&options), [1, 3]);
+
+        let options = crate::SearchOptions {
+            dedupe: Some(Dedupe::across_inputs()),
+            ..crate::SearchOptions::default()
+        };
+        assert_eq!(lines(&options), [1, 3]);
+        assert!(lines(&options).is_empty());
+        assert_eq!(options.clone(), options);
+    }
+}
@@ -0,0 +1,210 @@
+//! The time elapsed between consecutive matches.
+//!
+//! When a request stalls, the lines it logs are far apart in time but next
+//! to each other in the output of a search. [`Gaps`] reads the timestamp of
+//! every match, as [`TimeRange`] does, and works out how long after the
+//! previous match each one came, marking the gaps longer than a threshold.
+//! This is what `searcher find --gaps` prints.
+//!
+//! ```
+//! use searcher_cli_starter::filter::TimeRange;
+//! use searcher_cli_starter::gaps::Gaps;
+//! use searcher_cli_starter::{Matcher, SearchOptions, search_reader};
+//! use std::time::Duration;
+//!
+//! let log = "2024-05-01 10:00:00 GET /a\n\
+//!            2024-05-01 10:00:01 GET /b\n\
+//!            2024-05-01 10:02:31 GET /c\n";
+//! let matcher = Matcher::new("GET", false, false).unwrap();
+//! let mut matches = search_reader(log.as_bytes(), &matcher, &SearchOptions::default()).unwrap();
+//!
+//! let gaps = Gaps::new(TimeRange::new(None, None).unwrap()).with_threshold(Duration::from_secs(60));
+//! gaps.annotate(&mut matches);
+//!
+//! assert_eq!(matches[1].content, b"+1s\t2024-05-01 10:00:01 GET /b");
+//! assert_eq!(matches[2].content, b"+2m30s!\t2024-05-01 10:02:31 GET /c");
+//! ```
+
+use crate::filter::TimeRange;
+use crate::{MatchedLine, SearchMatchBytes};
+use anyhow::{Context, Result, bail};
+use time::Duration;
+
+/// Measures the time between consecutive matches from their timestamps.
+#[derive(Debug, Clone)]
+pub struct Gaps {
+    times: TimeRange,
+    threshold: Option<Duration>,
+}
+
+impl Gaps {
+    /// Reads timestamps as `times` does; its bounds are not used.
+    pub fn new(times: TimeRange) -> Self {
+        Gaps {
+            times,
+            threshold: None,
+        }
+    }
+
+    /// Marks the gaps longer than `threshold` when annotating.
+    pub fn with_threshold(mut self, threshold: std::time::Duration) -> Self {
+        self.threshold = Some(Duration::try_from(threshold).unwrap_or(Duration::MAX));
+        self
+    }
+
+    /// The time between each match and the last match before it that has a
+    /// timestamp. It is `None` for matches without a timestamp and for the
+    /// first one with a timestamp, and negative when the clock of the log
+    /// went backwards.
+    pub fn elapsed<M: MatchedLine>(&self, matches: &[M]) -> Vec<Option<Duration>> {
+        let mut previous = None;
+        matches
+            .iter()
+            .map(|search_match| {
+                let timestamp = self.times.timestamp(search_match.content_bytes())?;
+                let elapsed = previous.map(|previous| timestamp - previous);
+                previous = Some(timestamp);
+                elapsed
+            })
+            .collect()
+    }
+
+    /// Whether `elapsed` is longer than the threshold, if there is one.
+    pub fn exceeds(&self, elapsed: Duration) -> bool {
+        self.threshold.is_some_and(|threshold| elapsed > threshold)
+    }
+
+    /// Puts the time since the previous match and a tab before each match,
+    /// as [`format_elapsed`] writes it and followed by `!` when it
+    /// [exceeds](Self::exceeds) the threshold. The first match with a
+    /// timestamp gets `-` and matches without one `?`.
+    pub fn annotate(&self, matches: &mut [SearchMatchBytes]) {
+        let elapsed = self.elapsed(matches);
+        for (search_match, elapsed) in matches.iter_mut().zip(elapsed) {
+            let mut content = match elapsed {
+                Some(elapsed) if self.exceeds(elapsed) => format!("{}!", format_elapsed(elapsed)),
+                Some(elapsed) => format_elapsed(elapsed),
+                None if self.times.timestamp(&search_match.content).is_some() => "-".to_string(),
+                None => "?".to_string(),
+            }
+            .into_bytes();
+            content.push(b'\t');
+            content.append(&mut search_match.content);
+            search_match.content = content;
+        }
+    }
+}
+
+/// Writes `elapsed` with a sign, in hours, minutes, and seconds, leaving
+/// out the larger units that are zero, e.g. `+1h2m3s`, `+2m30s`, `+1.5s`,
+/// or `-3s`. Fractions of a second are kept down to the millisecond.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::gaps::format_elapsed;
+/// use time::Duration;
+///
+/// assert_eq!(format_elapsed(Duration::seconds(3723)), "+1h2m3s");
+/// assert_eq!(format_elapsed(Duration::milliseconds(1500)), "+1.5s");
+/// assert_eq!(format_elapsed(Duration::seconds(-3)), "-3s");
+/// ```
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let sign = if elapsed.is_negative() { '-' } else { '+' };
+    let elapsed = elapsed.abs();
+    let hours = elapsed.whole_hours();
+    let minutes = elapsed.whole_minutes() % 60;
+    let seconds = elapsed.whole_seconds() % 60;
+    let millis = elapsed.subsec_milliseconds();
+
+    let mut text = sign.to_string();
+    if hours > 0 {
+        text.push_str(&format!("{}h", hours));
+    }
+    if hours > 0 || minutes > 0 {
+        text.push_str(&format!("{}m", minutes));
+    }
+    if millis > 0 {
+        let fraction = format!("{:03}", millis);
+        text.push_str(&format!("{}.{}s", seconds, fraction.trim_end_matches('0')));
+    } else {
+        text.push_str(&format!("{}s", seconds));
+    }
+    text
+}
+
+/// Reads a gap threshold: a number of seconds, which may have a fraction,
+/// or a number followed by `ms`, `s`, `m`, `h`, or `d`, e.g. `1.5`, `500ms`,
+/// or `2m`.
+///
+/// # Errors
+///
+/// Returns an error if `text` is neither, or is negative.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::gaps::parse_threshold;
+/// use std::time::Duration;
+///
+/// assert_eq!(parse_threshold("2m").unwrap(), Duration::from_secs(120));
+/// assert_eq!(parse_threshold("1.5").unwrap(), Duration::from_millis(1500));
+/// assert!(parse_threshold("soon").is_err());
+/// ```
+pub fn parse_threshold(text: &str) -> Result<std::time::Duration> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let seconds = match unit {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 60.0 * 60.0,
+        "d" => 24.0 * 60.0 * 60.0,
+        _ => bail!("`{}` has an unknown unit (use ms, s, m, h, or d)", text),
+    };
+    let count: f64 = number
+        .parse()
+        .with_context(|| format!("`{}` is not a duration such as `30s` or `2m`", text))?;
+    std::time::Duration::try_from_secs_f64(count * seconds)
+        .with_context(|| format!("`{}` is not a valid duration", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elapsed_skips_lines_without_timestamps() {
+        let gaps = Gaps::new(TimeRange::new(None, None).unwrap())
+            .with_threshold(std::time::Duration::from_secs(10));
+        let line = |line_number: usize, content: &str| SearchMatchBytes {
+            line_number,
+            content: content.as_bytes().to_vec(),
+        };
+        let mut matches = vec![
+            line(1, "2024-05-01 10:00:00 a"),
+            line(2, "no time b"),
+            line(3, "2024-05-01 10:00:10 c"),
+            line(4, "2024-05-01 10:00:21 d"),
+            line(5, "2024-05-01 10:00:20 e"),
+        ];
+        gaps.annotate(&mut matches);
+
+        let prefixes: Vec<String> = matches
+            .iter()
+            .map(|m| {
+                String::from_utf8_lossy(&m.content)
+                    .split('\t')
+                    .next()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(prefixes, ["-", "?", "+10s", "+11s!", "-1s"]);
+        assert!(parse_threshold("-1s").is_err());
+        assert!(parse_threshold("5w").is_err());
+    }
+}
@@ -0,0 +1,358 @@
+//! Searching the history of a git repository.
+//!
+//! A [`Repository`] runs the `git` command line tool to list the commits in
+//! a revision range and the files each commit added or modified, then
+//! searches those files as they were in that commit. This is what
+//! `searcher git-log` does to find when a string was introduced or removed:
+//! the oldest commit whose version of a file matches is where it appeared,
+//! and a later commit changing the file without matching is where it went.
+//!
+//! ```no_run
+//! use searcher_cli_starter::git::Repository;
+//! use searcher_cli_starter::{Matcher, SearchOptions};
+//!
+//! let repo = Repository::new(".");
+//! let matcher = Matcher::new("TODO", false, false).unwrap();
+//! let options = SearchOptions::default();
+//! for commit in repo.commits("v1.0..HEAD", &[]).unwrap() {
+//!     for found in repo.search_commit(&commit, &[], &matcher, &options).unwrap() {
+//!         println!("{}:{}:{}", found.commit, found.path, found.line_number);
+//!     }
+//! }
+//! ```
+//!
+//! [`tracked_files`] asks git which files it tracks, for `--git-tracked`.
+
+use crate::{MatchedLine, Matcher, SearchOptions, search_source};
+use anyhow::{Context, Result, bail};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A matching line in a file as it was in some commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitMatch {
+    /// The abbreviated commit hash
+    pub commit: String,
+    /// The file's path from the root of the repository
+    pub path: String,
+    /// Line number (1-based)
+    pub line_number: usize,
+    /// The line without its terminator
+    pub content: Vec<u8>,
+}
+
+impl MatchedLine for GitMatch {
+    fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    fn content_bytes(&self) -> &[u8] {
+        &self.content
+    }
+}
+
+/// A git repository whose history can be searched.
+#[derive(Debug, Clone)]
+pub struct Repository {
+    dir: PathBuf,
+}
+
+impl Repository {
+    /// Opens the repository containing `dir`. Nothing is checked until git
+    /// is first run.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Repository { dir: dir.into() }
+    }
+
+    /// The commits in `range` (e.g. `HEAD` or `v1.0..main`), newest first,
+    /// skipping merges. With `paths`, only commits touching them are listed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if git cannot be run, `dir` is not in a repository,
+    /// or the range is invalid or starts with `-`.
+    pub fn commits(&self, range: &str, paths: &[PathBuf]) -> Result<Vec<String>> {
+        check_revision(range)?;
+        let mut args = vec![
+            OsStr::new("rev-list"),
+            OsStr::new("--no-merges"),
+            OsStr::new("--abbrev-commit"),
+            OsStr::new(range),
+            OsStr::new("--"),
+        ];
+        args.extend(paths.iter().map(|path| path.as_os_str()));
+        let output = self.git(&args)?;
+        Ok(String::from_utf8_lossy(&output)
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// The files `commit` added or modified, limited to `paths` if given.
+    /// Deleted files are left out, as the commit has no content for them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if git fails, e.g. because `commit` does not exist,
+    /// or `commit` starts with `-`.
+    pub fn changed_files(&self, commit: &str, paths: &[PathBuf]) -> Result<Vec<String>> {
+        check_revision(commit)?;
+        let mut args = vec![
+            OsStr::new("diff-tree"),
+            OsStr::new("-r"),
+            OsStr::new("-z"),
+            OsStr::new("--root"),
+            OsStr::new("--no-commit-id"),
+            OsStr::new("--name-only"),
+            OsStr::new("--no-renames"),
+            OsStr::new("--diff-filter=d"),
+            OsStr::new(commit),
+            OsStr::new("--"),
+        ];
+        args.extend(paths.iter().map(|path| path.as_os_str()));
+        let output = self.git(&args)?;
+        Ok(output
+            .split(|&byte| byte == b'\0')
+            .filter(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect())
+    }
+
+    /// The contents of `path` (relative to the repository root) in `commit`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file does not exist in that commit, or
+    /// `commit` starts with `-`.
+    pub fn read_file(&self, commit: &str, path: &str) -> Result<Vec<u8>> {
+        check_revision(commit)?;
+        let object = format!("{}:{}", commit, path);
+        self.git(&[
+            OsStr::new("cat-file"),
+            OsStr::new("blob"),
+            OsStr::new(&object),
+        ])
+    }
+
+    /// Searches the files `commit` added or modified, as they were in that
+    /// commit, with [`search_reader`](crate::search_reader), applying
+    /// `options.scope` as [`search_path`](crate::search_path) would.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if git fails or the search does, e.g. because
+    /// `options.cancel` was cancelled.
+    pub fn search_commit(
+        &self,
+        commit: &str,
+        paths: &[PathBuf],
+        matcher: &Matcher,
+        options: &SearchOptions,
+    ) -> Result<Vec<GitMatch>> {
+        let mut found = Vec::new();
+        for path in self.changed_files(commit, paths)? {
+            let contents = self.read_file(commit, &path)?;
+            let matches = search_source(Path::new(&path), contents.as_slice(), matcher, options)
+                .with_context(|| format!("Could not search `{}` in {}", path, commit))?;
+            found.extend(matches.into_iter().map(|m| GitMatch {
+                commit: commit.to_string(),
+                path: path.clone(),
+                line_number: m.line_number,
+                content: m.content,
+            }));
+        }
+        Ok(found)
+    }
+
+    /// The files git tracks below `paths` (relative to the repository
+    /// directory, or all of it when empty), sorted by name and joined onto
+    /// the directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if git fails, e.g. because the directory is not in a
+    /// repository.
+    pub fn tracked_files(&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let mut args = vec![OsStr::new("ls-files"), OsStr::new("-z"), OsStr::new("--")];
+        args.extend(paths.iter().map(|path| path.as_os_str()));
+        let output = self.git(&args)?;
+        Ok(output
+            .split(|&byte| byte == b'\0')
+            .filter(|name| !name.is_empty())
+            .map(|name| self.dir.join(String::from_utf8_lossy(name).as_ref()))
+            .collect())
+    }
+
+    /// Runs `git -C <dir> <args>` and returns its stdout. Paths are taken
+    /// literally, so names with `*` or a leading `:` are not read as
+    /// patterns or pathspec magic.
+    fn git(&self, args: &[&OsStr]) -> Result<Vec<u8>> {
+        let output = Command::new("git")
+            .arg("--literal-pathspecs")
+            .arg("-C")
+            .arg(&self.dir)
+            .args(args)
+            .output()
+            .context("Could not run `git`")?;
+        if !output.status.success() {
+            let command = args
+                .first()
+                .map(|arg| arg.to_string_lossy())
+                .unwrap_or_default();
+            bail!(
+                "`git {}` failed: {}",
+                command,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(output.stdout)
+    }
+}
+
+/// Refuses a revision or range that git would read as an option.
+fn check_revision(revision: &str) -> Result<()> {
+    if revision.starts_with('-') {
+        bail!("Invalid revision `{}`: it starts with `-`", revision);
+    }
+    Ok(())
+}
+
+/// Replaces each directory in `paths` with the files git tracks below it,
+/// and drops files git does not track, keeping build artifacts and other
+/// untracked files out of a search even when ignore files miss them.
+///
+/// Each path is looked up in its own repository. Submodules and tracked
+/// files that were deleted from the working tree are left out; paths that do
+/// not exist are kept, so that searching them reports the error.
+///
+/// # Errors
+///
+/// Returns an error if git fails for a path, e.g. because it is not inside a
+/// repository.
+pub fn tracked_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        let context = || {
+            format!(
+                "Could not list the files git tracks in `{}`",
+                path.display()
+            )
+        };
+        if path.is_dir() {
+            let tracked = Repository::new(path)
+                .tracked_files(&[])
+                .with_context(context)?;
+            files.extend(tracked.into_iter().filter(|file| file.is_file()));
+        } else if path.is_file() {
+            // Keep the path as given rather than the one git prints
+            let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+            let name = path.file_name().map(PathBuf::from).unwrap_or_default();
+            let tracked = Repository::new(dir.unwrap_or(Path::new(".")))
+                .tracked_files(&[name])
+                .with_context(context)?;
+            if !tracked.is_empty() {
+                files.push(path.clone());
+            }
+        } else {
+            // Missing: searching it reports the real error
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a repository with two commits: the first adds `a.txt` with a
+    /// TODO and `b.txt`, the second removes the TODO and deletes `b.txt`.
+    fn repository(name: &str) -> Option<(PathBuf, Repository)> {
+        let dir =
+            std::env::temp_dir().join(format!("searcher_git_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .arg("-C")
+                .arg(&dir)
+                .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+                .args(["-c", "commit.gpgsign=false"])
+                .args(args)
+                .output()
+                .is_ok_and(|output| output.status.success())
+        };
+        if !git(&["init", "-q"]) {
+            // No git available
+            return None;
+        }
+        std::fs::write(dir.join("a.txt"), "x\nTODO fix\n").unwrap();
+        std::fs::write(dir.join("b.txt"), "TODO too\n").unwrap();
+        assert!(git(&["add", "-A"]) && git(&["commit", "-qm", "one"]));
+        std::fs::write(dir.join("a.txt"), "x\nfixed\n").unwrap();
+        std::fs::remove_file(dir.join("b.txt")).unwrap();
+        assert!(git(&["add", "-A"]) && git(&["commit", "-qm", "two"]));
+        Some((dir.clone(), Repository::new(dir)))
+    }
+
+    #[test]
+    fn test_search_commits_newest_first() {
+        let Some((dir, repo)) = repository("search") else {
+            return;
+        };
+        let matcher = Matcher::new("TODO", false, false).unwrap();
+        let commits = repo.commits("HEAD", &[]).unwrap();
+        assert_eq!(commits.len(), 2);
+
+        // The newest commit changed `a.txt` and deleted `b.txt`
+        assert_eq!(repo.changed_files(&commits[0], &[]).unwrap(), ["a.txt"]);
+        let options = SearchOptions::default();
+        assert!(
+            repo.search_commit(&commits[0], &[], &matcher, &options)
+                .unwrap()
+                .is_empty()
+        );
+
+        let found = repo
+            .search_commit(&commits[1], &[], &matcher, &options)
+            .unwrap();
+        let found: Vec<(&str, usize)> = found
+            .iter()
+            .map(|m| (m.path.as_str(), m.line_number))
+            .collect();
+        assert_eq!(found, [("a.txt", 2), ("b.txt", 1)]);
+
+        let only_b = repo.commits("HEAD", &[PathBuf::from("b.txt")]).unwrap();
+        assert_eq!(only_b.len(), 2);
+        assert!(repo.commits("no-such-rev", &[]).is_err());
+        let error = repo.commits("--output=x", &[]).unwrap_err();
+        assert!(error.to_string().contains("starts with `-`"));
+        assert!(!dir.join("x").exists());
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_tracked_files_skip_untracked_files() {
+        let Some((dir, repo)) = repository("tracked") else {
+            return;
+        };
+        std::fs::write(dir.join("build.out"), "TODO generated\n").unwrap();
+        // Pathspecs are literal, so `*.txt` names no file
+        assert!(
+            repo.tracked_files(&[PathBuf::from("*.txt")])
+                .unwrap()
+                .is_empty()
+        );
+
+        assert_eq!(
+            tracked_files(std::slice::from_ref(&dir)).unwrap(),
+            [dir.join("a.txt")]
+        );
+        assert!(tracked_files(&[dir.join("build.out")]).unwrap().is_empty());
+        assert_eq!(
+            tracked_files(&[dir.join("a.txt")]).unwrap(),
+            [dir.join("a.txt")]
+        );
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
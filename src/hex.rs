@@ -0,0 +1,240 @@
+//! Searching raw bytes for a byte sequence, such as a magic number.
+//!
+//! Line-based searches split their input into lines and match text, which
+//! does not suit firmware images, core dumps, or other binary files: the
+//! bytes of interest may span what looks like a line break, and a line
+//! number says little about where they are. A [`HexPattern`] is written as
+//! hex digits, e.g. `DE AD BE EF`, and its searches read the input in blocks,
+//! reporting the byte offset of every match from the start of the input.
+//!
+//! # Examples
+//!
+//! ```
+//! use searcher_cli_starter::SearchOptions;
+//! use searcher_cli_starter::hex::HexPattern;
+//!
+//! let pattern = HexPattern::parse("DE AD BE EF").unwrap();
+//! let input = b"\x00\x01\xde\xad\xbe\xef\n\xde\xad\xbe\xef";
+//! let offsets = pattern.search_reader(&input[..], &SearchOptions::default()).unwrap();
+//! assert_eq!(offsets, [2, 7]);
+//! ```
+
+use crate::diagnostic::FileContext;
+use crate::{SearchOptions, file_error};
+use anyhow::{Context, Result, bail};
+use memchr::memmem;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+/// How much of the input is read at a time.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// A sequence of bytes to look for, written in hex.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HexPattern {
+    bytes: Vec<u8>,
+}
+
+impl HexPattern {
+    /// Parses pairs of hex digits, in either case, such as `DE AD BE EF`,
+    /// `deadbeef`, or `0xDE 0xAD`. Whitespace between bytes is ignored, and
+    /// so is a `0x` in front of each group of digits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `text` has no bytes, a character other than a
+    /// hex digit, or a group with an odd number of digits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::hex::HexPattern;
+    ///
+    /// assert_eq!(HexPattern::parse("7f 45 4c 46").unwrap().bytes(), b"\x7fELF");
+    /// assert_eq!(HexPattern::parse("0x7F454C46").unwrap().bytes(), b"\x7fELF");
+    /// assert!(HexPattern::parse("DE A").is_err());
+    /// ```
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut bytes = Vec::new();
+        for group in text.split_ascii_whitespace() {
+            let digits = group
+                .strip_prefix("0x")
+                .or_else(|| group.strip_prefix("0X"))
+                .unwrap_or(group);
+            if digits.len() % 2 != 0 {
+                bail!(
+                    "Invalid hex pattern `{}`: `{}` has an odd number of digits",
+                    text,
+                    group
+                );
+            }
+            for pair in digits.as_bytes().chunks(2) {
+                let pair = std::str::from_utf8(pair).unwrap_or_default();
+                let byte = u8::from_str_radix(pair, 16)
+                    .ok()
+                    .filter(|_| pair.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .with_context(|| {
+                        format!(
+                            "Invalid hex pattern `{}`: `{}` is not a hex byte",
+                            text, pair
+                        )
+                    })?;
+                bytes.push(byte);
+            }
+        }
+        if bytes.is_empty() {
+            bail!("Invalid hex pattern `{}`: it has no bytes", text);
+        }
+        Ok(HexPattern { bytes })
+    }
+
+    /// The bytes to look for.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The offset of every match in `reader`, from its start, in order.
+    /// Matches do not overlap: after a match, the next one is looked for
+    /// past its end.
+    ///
+    /// The input is read in blocks rather than lines, so matches are found
+    /// wherever they are, even across line breaks. Only `options.cancel` and
+    /// `options.timeout` apply.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, [`Cancelled`](crate::cancel::Cancelled)
+    /// if `options.cancel` is cancelled, or
+    /// [`TimedOut`](crate::cancel::TimedOut) once `options.timeout` has passed.
+    pub fn search_reader<R: Read>(
+        &self,
+        mut reader: R,
+        options: &SearchOptions,
+    ) -> Result<Vec<u64>> {
+        let deadline = options.deadline();
+        let finder = memmem::Finder::new(&self.bytes);
+        let len = self.bytes.len();
+        let mut offsets = Vec::new();
+        // The unsearched end of the previous block, which may hold the
+        // start of a match, followed by the block just read
+        let mut window = Vec::with_capacity(BLOCK_SIZE + len);
+        // The input offset of `window[0]`
+        let mut base = 0u64;
+        // Where the next match may start, past the end of the last one
+        let mut next = 0u64;
+        let mut block = vec![0; BLOCK_SIZE];
+        loop {
+            options.check_cancelled()?;
+            options.check_deadline(deadline)?;
+            let read = match reader.read(&mut block) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error.into()),
+            };
+            window.extend_from_slice(&block[..read]);
+            let mut position = next.saturating_sub(base) as usize;
+            while let Some(found) = window.get(position..).and_then(|rest| finder.find(rest)) {
+                let start = position + found;
+                offsets.push(base + start as u64);
+                position = start + len;
+            }
+            next = next.max(base + position as u64);
+            // Keep the bytes a match starting near the end could still need
+            let keep = (len - 1).min(window.len());
+            let consumed = window.len() - keep;
+            window.drain(..consumed);
+            base += consumed as u64;
+        }
+        Ok(offsets)
+    }
+
+    /// Opens `path` and searches it like [`HexPattern::search_reader`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming `path` if it cannot be opened or read, plus
+    /// the errors of [`HexPattern::search_reader`].
+    pub fn search_path(&self, path: &Path, options: &SearchOptions) -> Result<Vec<u64>> {
+        options.check_cancelled()?;
+        let file = File::open(path).with_context(|| FileContext::read(path))?;
+        self.search_reader(file, options)
+            .map_err(|error| file_error(path, error))
+    }
+}
+
+impl FromStr for HexPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Self::parse(text)
+    }
+}
+
+impl fmt::Display for HexPattern {
+    /// Writes the bytes as uppercase hex pairs separated by spaces.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, byte) in self.bytes.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Hands out at most `step` bytes per read, to split matches across blocks.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        step: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.step.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let pattern = HexPattern::parse("de AD\tbe 0xEF").unwrap();
+        assert_eq!(pattern.bytes(), [0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(pattern.to_string(), "DE AD BE EF");
+        for invalid in ["", "  ", "DEA", "GG", "0x", "+1"] {
+            assert!(HexPattern::parse(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_matches_across_reads_do_not_overlap() {
+        let pattern = HexPattern::parse("AA AA").unwrap();
+        let input = [0xaa; 5];
+        for step in 1..=5 {
+            let offsets = pattern
+                .search_reader(Trickle { data: &input, step }, &SearchOptions::default())
+                .unwrap();
+            assert_eq!(offsets, [0, 2], "step {}", step);
+        }
+
+        let pattern = HexPattern::parse("01 02 03").unwrap();
+        let mut input = vec![0; BLOCK_SIZE - 1];
+        input.extend_from_slice(&[1, 2, 3, 0, 1, 2, 3]);
+        let offsets = pattern
+            .search_reader(Cursor::new(&input), &SearchOptions::default())
+            .unwrap();
+        let start = (BLOCK_SIZE - 1) as u64;
+        assert_eq!(offsets, [start, start + 4]);
+    }
+}
@@ -0,0 +1,478 @@
+//! Frequency tables of values extracted from matching lines.
+//!
+//! A [`Histogram`] pulls a value out of each line with a regex capture group
+//! and counts how often each value occurs, e.g. the error code of every error
+//! in a log. This is what `searcher find --count-matches-by` prints.
+//!
+//! ```
+//! use searcher_cli_starter::histogram::Histogram;
+//! use searcher_cli_starter::{Matcher, search_str};
+//!
+//! let log = "ERROR code=E42 db\nINFO ok\nERROR code=E7 cache\nERROR code=E42 db\n";
+//! let matcher = Matcher::new("ERROR", false, false).unwrap();
+//!
+//! let mut histogram = Histogram::new(r"code=(\w+)").unwrap();
+//! histogram.add_matches(&search_str(log, &matcher));
+//!
+//! assert_eq!(histogram.counts(), [("E42", 2), ("E7", 1)]);
+//! ```
+//!
+//! [`DistinctValues`] counts every match instead of one value per line, which
+//! is what `searcher extract` prints. [`TopValues`] counts them in bounded
+//! memory, for `searcher extract --top`.
+//!
+//! [`density`] counts matching lines per block of lines instead, showing
+//! where in a file they cluster, for `searcher find --density`.
+
+use crate::{MatchedLine, Matcher};
+use anyhow::{Context, Result, bail};
+use regex::bytes::Regex;
+use std::collections::{BTreeSet, HashMap};
+use std::num::NonZeroUsize;
+
+/// Counts the values a capture group extracts from lines.
+///
+/// The value of a line is the first capture group of the first match of the
+/// regex, or the group named `key` if there is one. Lines the regex does not
+/// match, or where the group does not participate, are counted separately as
+/// [`Histogram::unmatched`].
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    key: KeyPattern,
+    counts: HashMap<String, usize>,
+    unmatched: usize,
+}
+
+/// A regex extracting one value from each line: its group named `key`, else
+/// its first capture group.
+#[derive(Debug, Clone)]
+pub(crate) struct KeyPattern {
+    regex: Regex,
+    /// The index of the capture group holding the value
+    group: usize,
+}
+
+impl KeyPattern {
+    /// Compiles `pattern`, naming what the value is for in the error when it
+    /// has no capture group, e.g. `count by`.
+    pub(crate) fn new(pattern: &str, purpose: &str) -> Result<Self> {
+        let regex = Regex::new(pattern).context("Invalid regex pattern")?;
+        if regex.captures_len() < 2 {
+            bail!(
+                "The pattern `{}` has no capture group to {}, e.g. `code=(\\w+)`",
+                pattern,
+                purpose
+            );
+        }
+        let group = regex
+            .capture_names()
+            .position(|name| name == Some("key"))
+            .unwrap_or(1);
+        Ok(KeyPattern { regex, group })
+    }
+
+    /// The value of `line`, or `None` if the regex does not match it or the
+    /// group does not participate.
+    pub(crate) fn value<'l>(&self, line: &'l [u8]) -> Option<&'l [u8]> {
+        let captures = self.regex.captures(line)?;
+        captures.get(self.group).map(|value| value.as_bytes())
+    }
+}
+
+impl Histogram {
+    /// Creates an empty histogram keyed by a capture group of `pattern`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `pattern` is not a valid regex or has no capture
+    /// group.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::histogram::Histogram;
+    ///
+    /// assert!(Histogram::new(r"user=(\w+)").is_ok());
+    /// assert!(Histogram::new(r"user=\w+").is_err());
+    /// ```
+    pub fn new(pattern: &str) -> Result<Self> {
+        Ok(Histogram {
+            key: KeyPattern::new(pattern, "count by")?,
+            counts: HashMap::new(),
+            unmatched: 0,
+        })
+    }
+
+    /// Counts the value of one line.
+    pub fn add(&mut self, line: &[u8]) {
+        match self.key.value(line) {
+            Some(value) => {
+                let value = String::from_utf8_lossy(value).into_owned();
+                *self.counts.entry(value).or_default() += 1;
+            }
+            None => self.unmatched += 1,
+        }
+    }
+
+    /// Counts the value of every line in `matches`.
+    pub fn add_matches<M: MatchedLine>(&mut self, matches: &[M]) {
+        for search_match in matches {
+            self.add(search_match.content_bytes());
+        }
+    }
+
+    /// Each value with its count, most frequent first; values that occur
+    /// equally often are sorted by value.
+    pub fn counts(&self) -> Vec<(&str, usize)> {
+        by_frequency(&self.counts)
+    }
+
+    /// The number of lines counted that had no value.
+    pub fn unmatched(&self) -> usize {
+        self.unmatched
+    }
+}
+
+/// Counts the distinct values a matcher finds in lines, e.g. every error
+/// code or API key prefix in a set of logs.
+///
+/// Every match in a line counts, not just the first. The value of a match is
+/// the text of its group named `key`, else of its first capture group, else
+/// (for patterns without groups, and literal patterns) the whole match.
+/// Matches whose group does not participate, and empty matches, are not
+/// counted.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::Matcher;
+/// use searcher_cli_starter::histogram::DistinctValues;
+///
+/// let matcher = Matcher::new(r"E(\d+)", false, true).unwrap();
+/// let mut values = DistinctValues::new();
+/// values.add(&matcher, b"E42 then E7");
+/// values.add(&matcher, b"E42 again");
+///
+/// assert_eq!(values.counts(), [("42", 2), ("7", 1)]);
+/// assert_eq!(values.sorted(), [("42", 2), ("7", 1)]);
+/// assert_eq!(values.len(), 2);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DistinctValues {
+    counts: HashMap<String, usize>,
+}
+
+impl DistinctValues {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        DistinctValues::default()
+    }
+
+    /// Counts the value of every match of `matcher` in `line`.
+    pub fn add(&mut self, matcher: &Matcher, line: &[u8]) {
+        for value in match_values(matcher, line) {
+            self.count(&value);
+        }
+    }
+
+    /// Counts the values of every line in `matches`.
+    pub fn add_matches<M: MatchedLine>(&mut self, matcher: &Matcher, matches: &[M]) {
+        for search_match in matches {
+            self.add(matcher, search_match.content_bytes());
+        }
+    }
+
+    /// Each value with its count, most frequent first; values that occur
+    /// equally often are sorted by value.
+    pub fn counts(&self) -> Vec<(&str, usize)> {
+        by_frequency(&self.counts)
+    }
+
+    /// Each value with its count, sorted by value.
+    pub fn sorted(&self) -> Vec<(&str, usize)> {
+        let mut counts: Vec<(&str, usize)> = self
+            .counts
+            .iter()
+            .map(|(value, count)| (value.as_str(), *count))
+            .collect();
+        counts.sort_unstable();
+        counts
+    }
+
+    /// The number of distinct values.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Whether no value was counted.
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    fn count(&mut self, value: &str) {
+        if value.is_empty() {
+            return;
+        }
+        match self.counts.get_mut(value) {
+            Some(count) => *count += 1,
+            None => {
+                self.counts.insert(value.to_string(), 1);
+            }
+        }
+    }
+}
+
+/// Finds the most frequent values a matcher finds in lines while keeping
+/// count of a bounded number of them, with the Space-Saving algorithm.
+///
+/// Values are taken as by [`DistinctValues`]. Once `capacity` distinct
+/// values are counted, a new value replaces the least frequent one and
+/// inherits its count, so memory stays bounded however many distinct values
+/// the input holds. Counts are then upper bounds, off by at most
+/// [`TopValue::error`], but any value occurring more often than the total
+/// divided by `capacity` is sure to be kept.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::Matcher;
+/// use searcher_cli_starter::histogram::TopValues;
+///
+/// let matcher = Matcher::new(r"user=(\w+)", false, true).unwrap();
+/// let mut values = TopValues::new(100);
+/// for line in ["user=bob", "user=amy", "user=bob", "user=eve"] {
+///     values.add(&matcher, line.as_bytes());
+/// }
+///
+/// let top = values.top(1);
+/// assert_eq!((top[0].value, top[0].count, top[0].error), ("bob", 2, 0));
+/// assert_eq!(values.total(), 4);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TopValues {
+    capacity: usize,
+    counters: HashMap<String, Counter>,
+    /// The counters ordered by count, to find the least frequent
+    by_count: BTreeSet<(usize, String)>,
+    total: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Counter {
+    count: usize,
+    error: usize,
+}
+
+/// A value [`TopValues`] kept, with its count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TopValue<'a> {
+    /// The value
+    pub value: &'a str,
+    /// How often it occurred, at most `error` too many
+    pub count: usize,
+    /// How much `count` may overstate, inherited from the value it replaced
+    pub error: usize,
+}
+
+impl TopValues {
+    /// Creates an empty count of at most `capacity` values (at least one).
+    pub fn new(capacity: usize) -> Self {
+        TopValues {
+            capacity: capacity.max(1),
+            counters: HashMap::new(),
+            by_count: BTreeSet::new(),
+            total: 0,
+        }
+    }
+
+    /// Counts the value of every match of `matcher` in `line`.
+    pub fn add(&mut self, matcher: &Matcher, line: &[u8]) {
+        for value in match_values(matcher, line) {
+            self.count(value);
+        }
+    }
+
+    /// Counts the values of every line in `matches`.
+    pub fn add_matches<M: MatchedLine>(&mut self, matcher: &Matcher, matches: &[M]) {
+        for search_match in matches {
+            self.add(matcher, search_match.content_bytes());
+        }
+    }
+
+    /// The `k` values counted most often, most frequent first; values with
+    /// equal counts are sorted by value.
+    pub fn top(&self, k: usize) -> Vec<TopValue<'_>> {
+        let mut top: Vec<TopValue<'_>> = self
+            .counters
+            .iter()
+            .map(|(value, counter)| TopValue {
+                value,
+                count: counter.count,
+                error: counter.error,
+            })
+            .collect();
+        top.sort_by(|a, b| b.count.cmp(&a.count).then(a.value.cmp(b.value)));
+        top.truncate(k);
+        top
+    }
+
+    /// The number of values counted, kept or not.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    fn count(&mut self, value: String) {
+        if value.is_empty() {
+            return;
+        }
+        self.total += 1;
+        if let Some(counter) = self.counters.get_mut(&value) {
+            self.by_count.remove(&(counter.count, value.clone()));
+            counter.count += 1;
+            self.by_count.insert((counter.count, value));
+            return;
+        }
+        let mut counter = Counter { count: 1, error: 0 };
+        if self.counters.len() == self.capacity
+            && let Some((count, evicted)) = self.by_count.pop_first()
+        {
+            self.counters.remove(&evicted);
+            counter = Counter {
+                count: count + 1,
+                error: count,
+            };
+        }
+        self.by_count.insert((counter.count, value.clone()));
+        self.counters.insert(value, counter);
+    }
+}
+
+/// Counts the matches in each block of `lines` lines, as the first line
+/// number of the block with its count, from the first block to the one
+/// holding the last match. Blocks without matches in between are counted
+/// as zero, so the result can be drawn as is.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::histogram::density;
+/// use searcher_cli_starter::{Matcher, search_str};
+/// use std::num::NonZeroUsize;
+///
+/// let log = "ok\nERROR\nERROR\nok\nok\nok\nok\nERROR\n";
+/// let matches = search_str(log, &Matcher::new("ERROR", false, false).unwrap());
+/// let blocks = density(&matches, NonZeroUsize::new(3).unwrap());
+///
+/// assert_eq!(blocks, [(1, 2), (4, 0), (7, 1)]);
+/// ```
+pub fn density<M: MatchedLine>(matches: &[M], lines: NonZeroUsize) -> Vec<(usize, usize)> {
+    let lines = lines.get();
+    let mut blocks: Vec<(usize, usize)> = Vec::new();
+    for search_match in matches {
+        let block = (search_match.line_number().max(1) - 1) / lines;
+        while blocks.len() <= block {
+            blocks.push((blocks.len() * lines + 1, 0));
+        }
+        blocks[block].1 += 1;
+    }
+    blocks
+}
+
+/// The value of every match of `matcher` in `line`, as [`DistinctValues`]
+/// takes it, leaving out the matches whose group does not participate.
+pub(crate) fn match_values(matcher: &Matcher, line: &[u8]) -> Vec<String> {
+    let captures = matcher.captures_bytes(line);
+    if captures.first().is_some_and(|groups| !groups.is_empty()) {
+        captures
+            .into_iter()
+            .filter_map(|groups| {
+                let index = groups
+                    .iter()
+                    .position(|group| group.name.as_deref() == Some("key"))
+                    .unwrap_or(0);
+                groups.into_iter().nth(index)?.value
+            })
+            .collect()
+    } else {
+        matcher
+            .match_ranges(line)
+            .into_iter()
+            .map(|range| String::from_utf8_lossy(&line[range]).into_owned())
+            .collect()
+    }
+}
+
+/// `counts` most frequent first, ties sorted by value.
+fn by_frequency(counts: &HashMap<String, usize>) -> Vec<(&str, usize)> {
+    let mut counts: Vec<(&str, usize)> = counts
+        .iter()
+        .map(|(value, count)| (value.as_str(), *count))
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named_key_group_and_unmatched_lines() {
+        let mut histogram = Histogram::new(r"(\d+)ms user=(?P<key>\w+)").unwrap();
+        for line in [
+            "12ms user=bob",
+            "3ms user=amy",
+            "9ms user=bob",
+            "user=eve",
+            "x",
+        ] {
+            histogram.add(line.as_bytes());
+        }
+
+        assert_eq!(histogram.counts(), [("bob", 2), ("amy", 1)]);
+        assert_eq!(histogram.unmatched(), 2);
+    }
+
+    #[test]
+    fn test_ties_are_sorted_by_value() {
+        let mut histogram = Histogram::new("(b|a|c)").unwrap();
+        for line in ["c", "b", "a", "c"] {
+            histogram.add(line.as_bytes());
+        }
+        assert_eq!(histogram.counts(), [("c", 2), ("a", 1), ("b", 1)]);
+    }
+
+    #[test]
+    fn test_distinct_values_of_matches_and_groups() {
+        let mut values = DistinctValues::new();
+        let literal = Matcher::new("ab", true, false).unwrap();
+        values.add(&literal, b"ab AB xab");
+        assert_eq!(values.sorted(), [("AB", 1), ("ab", 2)]);
+
+        let mut values = DistinctValues::new();
+        let keyed = Matcher::new(r"(\w+)=(?P<key>\w+)?;", false, true).unwrap();
+        values.add(&keyed, b"a=1; b=2; c=; d=1;");
+        assert_eq!(values.counts(), [("1", 2), ("2", 1)]);
+
+        // Empty matches are not values
+        let mut values = DistinctValues::new();
+        values.add(&Matcher::new("x*", false, true).unwrap(), b"axxb");
+        assert_eq!(values.counts(), [("xx", 1)]);
+    }
+
+    #[test]
+    fn test_top_values_replace_the_least_frequent() {
+        let matcher = Matcher::new(r"\w", false, true).unwrap();
+        let mut values = TopValues::new(2);
+        values.add(&matcher, b"a a a b c a d");
+
+        // `c` replaced `b`, then `d` replaced `c`, inheriting its count
+        let top: Vec<(&str, usize, usize)> = values
+            .top(5)
+            .iter()
+            .map(|value| (value.value, value.count, value.error))
+            .collect();
+        assert_eq!(top, [("a", 4, 0), ("d", 3, 2)]);
+        assert_eq!(values.total(), 7);
+    }
+}
@@ -0,0 +1,241 @@
+//! Ignore files in gitignore syntax.
+//!
+//! A `.searcherignore` file lists files and directories that searches of
+//! its directory should leave out, written like a `.gitignore`. It works in
+//! any tree, which makes it the way to keep rotated or compressed logs out
+//! of a search of a log archive that is not a git repository. Inside git
+//! repositories, `.gitignore` files are read as well.
+//!
+//! An [`IgnoreFile`] holds the rules of one file. [`Walk::ignore_files`]
+//! reads them from every directory it walks, and from the parents of the
+//! directories it starts from; [`Walk::with_global_ignore`] adds one such
+//! as the file at [`global_ignore_path`] below every directory.
+//!
+//! The syntax is that of gitignore:
+//!
+//! - Blank lines and lines starting with `#` are skipped
+//! - `*`, `?`, `[a-z]`, and `**` are globs; `*` never matches a `/`
+//! - A pattern with a `/` at its start or in its middle is relative to the
+//!   file's directory; one without matches at any depth below it
+//! - A trailing `/` only matches directories
+//! - A leading `!` brings back what an earlier pattern left out, and
+//!   `\#` / `\!` start a pattern with a literal `#` or `!`
+//!
+//! The last matching pattern decides. A directory's own files take
+//! precedence over those of its parents, and a `.searcherignore` over the
+//! `.gitignore` next to it, so a `!` exception in either can bring back
+//! files ignored further up.
+//!
+//! [`Walk::ignore_files`]: crate::walk::Walk::ignore_files
+//! [`Walk::with_global_ignore`]: crate::walk::Walk::with_global_ignore
+//!
+//! # Examples
+//!
+//! ```
+//! use searcher_cli_starter::ignore::IgnoreFile;
+//! use std::path::Path;
+//!
+//! let ignore = IgnoreFile::parse("*.gz\n/archive/\n!keep.gz\n").unwrap();
+//! assert_eq!(ignore.matched(Path::new("2024/app.log.gz"), false), Some(true));
+//! assert_eq!(ignore.matched(Path::new("keep.gz"), false), Some(false));
+//! assert_eq!(ignore.matched(Path::new("archive"), true), Some(true));
+//! assert_eq!(ignore.matched(Path::new("2024/archive"), true), None);
+//! ```
+
+use crate::vfs::{StdFs, Vfs};
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The name of the ignore files searcher reads in every directory.
+pub const IGNORE_FILE_NAME: &str = ".searcherignore";
+
+/// The rules of one ignore file.
+#[derive(Debug, Clone)]
+pub struct IgnoreFile {
+    /// Every pattern, compiled
+    globs: GlobSet,
+    /// What each pattern in `globs` does, by index
+    rules: Vec<Rule>,
+}
+
+/// One pattern's effect.
+#[derive(Debug, Clone, Copy)]
+struct Rule {
+    /// Whether it starts with `!`, bringing back what it matches
+    negated: bool,
+    /// Whether it ends with `/`, matching only directories
+    dir_only: bool,
+}
+
+impl IgnoreFile {
+    /// Parses the contents of an ignore file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a pattern is not a valid glob.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        let mut rules = Vec::new();
+        for line in text.lines() {
+            // Trailing spaces are dropped unless escaped
+            let line = line.trim_end_matches('\r');
+            let line = if line.ends_with("\\ ") {
+                line
+            } else {
+                line.trim_end()
+            };
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, pattern) = match line.strip_prefix('!') {
+                Some(pattern) => (true, pattern),
+                None => (false, line),
+            };
+            let (dir_only, pattern) = match pattern.strip_suffix('/') {
+                Some(pattern) => (true, pattern),
+                None => (false, pattern),
+            };
+            let glob = match pattern.strip_prefix('/') {
+                Some(anchored) => anchored.to_string(),
+                None if pattern.contains('/') => pattern.to_string(),
+                None => format!("**/{}", pattern),
+            };
+            let glob = GlobBuilder::new(&glob)
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("Invalid ignore pattern `{}`", line))?;
+            builder.add(glob);
+            rules.push(Rule { negated, dir_only });
+        }
+        Ok(IgnoreFile {
+            globs: builder.build().context("Invalid ignore pattern")?,
+            rules,
+        })
+    }
+
+    /// Reads and parses the ignore file at `path`, or returns `None` if
+    /// there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidData`] if the file
+    /// has an invalid pattern, and the error reading it otherwise.
+    pub fn read(path: &Path) -> io::Result<Option<Self>> {
+        Self::read_in(&StdFs, path)
+    }
+
+    /// Like [`IgnoreFile::read`], but reads `path` in the file system `fs`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidData`] if the file
+    /// is not UTF-8 or has an invalid pattern, and the error reading it
+    /// otherwise.
+    pub fn read_in(fs: &dyn Vfs, path: &Path) -> io::Result<Option<Self>> {
+        let data = match fs.read(path) {
+            Ok(data) => data,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        let text = String::from_utf8(data)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        Self::parse(&text)
+            .map(Some)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, format!("{:#}", error)))
+    }
+
+    /// Whether the file ignores `path`, given relative to the directory the
+    /// file applies to: `Some(true)` if the last pattern matching it leaves
+    /// it out, `Some(false)` if that is a `!` exception, and `None` if no
+    /// pattern matches.
+    pub fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        self.globs
+            .matches(path)
+            .into_iter()
+            .filter(|&index| is_dir || !self.rules[index].dir_only)
+            .max()
+            .map(|index| !self.rules[index].negated)
+    }
+}
+
+/// Where the global ignore file is looked for: `searcher/ignore` in the
+/// user's configuration directory, i.e. `$XDG_CONFIG_HOME` or
+/// `~/.config` on Unix and `%APPDATA%` on Windows.
+///
+/// Returns `None` if the environment names no such directory.
+pub fn global_ignore_path() -> Option<PathBuf> {
+    let config_dir = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    config_dir.map(|dir| dir.join("searcher").join("ignore"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn ignored(ignore: &IgnoreFile, path: &str) -> Option<bool> {
+        ignore.matched(Path::new(path), false)
+    }
+
+    #[test]
+    fn test_patterns_follow_gitignore_rules() {
+        let ignore = IgnoreFile::parse(
+            "# rotated logs\n*.log.[0-9]\n\nbuild/\n/top.txt\nlogs/**/debug.log\n\\#notes\n",
+        )
+        .unwrap();
+
+        assert_eq!(ignored(&ignore, "app.log.1"), Some(true));
+        assert_eq!(ignored(&ignore, "a/b/app.log.2"), Some(true));
+        assert_eq!(ignored(&ignore, "app.log"), None);
+        // `*` stops at separators
+        assert_eq!(ignored(&ignore, "app.log.1/x"), None);
+
+        assert_eq!(ignore.matched(Path::new("a/build"), true), Some(true));
+        assert_eq!(ignored(&ignore, "a/build"), None);
+
+        assert_eq!(ignored(&ignore, "top.txt"), Some(true));
+        assert_eq!(ignored(&ignore, "a/top.txt"), None);
+
+        assert_eq!(ignored(&ignore, "logs/debug.log"), Some(true));
+        assert_eq!(ignored(&ignore, "logs/x/y/debug.log"), Some(true));
+        assert_eq!(ignored(&ignore, "a/logs/debug.log"), None);
+
+        assert_eq!(ignored(&ignore, "#notes"), Some(true));
+    }
+
+    #[test]
+    fn test_last_matching_pattern_wins() {
+        let ignore = IgnoreFile::parse("*.gz\n!keep*.gz\nkeep-not.gz\n").unwrap();
+        assert_eq!(ignored(&ignore, "old.gz"), Some(true));
+        assert_eq!(ignored(&ignore, "keep.gz"), Some(false));
+        assert_eq!(ignored(&ignore, "keep-not.gz"), Some(true));
+    }
+
+    #[test]
+    fn test_invalid_patterns_are_errors() {
+        let error = IgnoreFile::parse("ok\n[z-a\n").unwrap_err();
+        assert!(format!("{:#}", error).contains("`[z-a`"));
+
+        let path = env::temp_dir().join("searcher_ignore_invalid");
+        fs::write(&path, "{unclosed\n").unwrap();
+        let error = IgnoreFile::read(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(path).ok();
+
+        assert!(
+            IgnoreFile::read(Path::new("missing/.searcherignore"))
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,511 @@
+//! A persistent trigram index for repeated searches over the same files.
+//!
+//! [`Index::build`] reads every file below a set of directories and records
+//! which three-byte sequences (trigrams) each one contains. A later search
+//! asks [`Index::candidates`] which files can possibly contain the literal
+//! parts of its pattern and only opens those, which turns repeated searches
+//! over a large, mostly unchanged corpus from "read everything" into "read
+//! a handful of files".
+//!
+//! The index never hides a match:
+//!
+//! - Trigrams are stored ASCII-lowercased, so one index serves both
+//!   case-sensitive and `-i` searches; candidates are always searched for
+//!   real afterwards.
+//! - Patterns without usable literals (alternations, classes, queries) make
+//!   every file a candidate.
+//! - Files whose modification time or size changed since the index was built,
+//!   and files that were added since, are always candidates and counted as
+//!   stale so the caller can suggest a rebuild.
+//!
+//! # File format
+//!
+//! All integers are little-endian:
+//!
+//! ```text
+//! magic      b"SRCHIDX1"
+//! roots      u32 count, then per root: u32 length + UTF-8 path
+//! files      u32 count, then per file: u32 length + UTF-8 path,
+//!            u64 mtime seconds, u32 mtime nanoseconds, u64 size
+//! trigrams   u32 count, then per trigram: 3 bytes,
+//!            u32 count + that many u32 file ids (ascending)
+//! ```
+
+use crate::diagnostic::FileContext;
+use crate::query::MatcherOptions;
+use crate::walk::{Walk, each_file};
+use anyhow::{Context, Result, bail};
+use regex_syntax::hir::{Hir, HirKind};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The first bytes of every index file.
+const MAGIC: &[u8; 8] = b"SRCHIDX1";
+
+/// A three-byte sequence, ASCII-lowercased.
+type Trigram = [u8; 3];
+
+/// A file recorded in an [`Index`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedFile {
+    /// The path the file was found at while building
+    pub path: PathBuf,
+    /// Its modification time when it was indexed
+    pub modified: SystemTime,
+    /// Its size in bytes when it was indexed
+    pub len: u64,
+}
+
+impl IndexedFile {
+    /// Whether the file on disk still looks the way it did when indexed.
+    fn is_fresh(&self, metadata: &fs::Metadata) -> bool {
+        metadata.len() == self.len
+            && metadata
+                .modified()
+                .is_ok_and(|modified| modified == self.modified)
+    }
+}
+
+/// The files a search has to open, as chosen by [`Index::candidates`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Candidates {
+    /// Files that may contain a match, in walk order
+    pub files: Vec<PathBuf>,
+    /// How many of them are there only because they changed or are new
+    pub stale: usize,
+    /// How many files were ruled out by the index
+    pub skipped: usize,
+}
+
+/// A trigram index over the files below a set of directories.
+///
+/// # Examples
+///
+/// ```no_run
+/// use searcher_cli_starter::index::{Index, required_literals};
+/// use searcher_cli_starter::query::MatcherOptions;
+/// use std::path::Path;
+///
+/// let index = Index::build(&["logs".into()], |error| eprintln!("skipped: {:#}", error));
+/// index.write_to(Path::new(".searcher-index")).unwrap();
+///
+/// let index = Index::read_from(Path::new(".searcher-index")).unwrap();
+/// let literals = required_literals("connection refused", &MatcherOptions::default());
+/// for path in index.candidates(&literals).files {
+///     println!("{}", path.display());
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Index {
+    roots: Vec<PathBuf>,
+    files: Vec<IndexedFile>,
+    trigrams: BTreeMap<Trigram, Vec<u32>>,
+}
+
+impl Index {
+    /// Indexes every file below `roots`.
+    ///
+    /// Files that cannot be read are passed to `on_error` and left out; they
+    /// are treated like new files (always candidates) when querying.
+    pub fn build(roots: &[PathBuf], on_error: impl FnMut(anyhow::Error)) -> Self {
+        let mut index = Index {
+            roots: roots.to_vec(),
+            ..Index::default()
+        };
+
+        let read = |path: &Path| -> Result<Option<(fs::Metadata, Vec<u8>)>> {
+            if path.to_str().is_none() {
+                bail!(
+                    "Could not index `{}`: the path is not valid UTF-8",
+                    path.display()
+                );
+            }
+            let context = || FileContext::read(path);
+            let metadata = fs::metadata(path).with_context(context)?;
+            let data = fs::read(path).with_context(context)?;
+            // Never index an index, e.g. one written inside the indexed tree
+            Ok((!data.starts_with(MAGIC)).then_some((metadata, data)))
+        };
+        for (path, file) in each_file(roots, read, on_error) {
+            let Some((metadata, data)) = file else {
+                continue;
+            };
+            let id = index.files.len() as u32;
+            index.files.push(IndexedFile {
+                path,
+                modified: metadata.modified().unwrap_or(UNIX_EPOCH),
+                len: metadata.len(),
+            });
+            let trigrams: BTreeSet<Trigram> = data
+                .windows(3)
+                .map(|window| lowercase([window[0], window[1], window[2]]))
+                .collect();
+            for trigram in trigrams {
+                index.trigrams.entry(trigram).or_default().push(id);
+            }
+        }
+
+        index
+    }
+
+    /// The directories the index was built from.
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// The files in the index, in walk order.
+    pub fn files(&self) -> &[IndexedFile] {
+        &self.files
+    }
+
+    /// How many distinct trigrams the indexed files contain.
+    pub fn trigram_count(&self) -> usize {
+        self.trigrams.len()
+    }
+
+    /// Walks the index roots again and returns the files that may contain
+    /// every string in `literals` (see [`required_literals`]).
+    ///
+    /// Files that changed or appeared since the index was built are always
+    /// included; files that were deleted are not.
+    pub fn candidates(&self, literals: &[Vec<u8>]) -> Candidates {
+        let ids: HashMap<&Path, usize> = self
+            .files
+            .iter()
+            .enumerate()
+            .map(|(id, file)| (file.path.as_path(), id))
+            .collect();
+        let mut candidates = Candidates::default();
+
+        // Directories that cannot be listed now simply contribute no files
+        for path in Walk::new(&self.roots).flatten() {
+            let indexed = ids.get(path.as_path()).map(|&id| (id, &self.files[id]));
+            let fresh = indexed.and_then(|(id, file)| {
+                let metadata = fs::metadata(&path).ok()?;
+                file.is_fresh(&metadata).then_some(id)
+            });
+            match fresh {
+                Some(id) if !self.may_contain(id as u32, literals) => candidates.skipped += 1,
+                Some(_) => candidates.files.push(path),
+                None if indexed.is_none() && is_index_file(&path) => {}
+                None => {
+                    candidates.stale += 1;
+                    candidates.files.push(path);
+                }
+            }
+        }
+
+        candidates
+    }
+
+    /// Whether file `id` contains every trigram of every literal.
+    fn may_contain(&self, id: u32, literals: &[Vec<u8>]) -> bool {
+        literals
+            .iter()
+            .flat_map(|literal| literal.windows(3))
+            .all(|window| {
+                self.trigrams
+                    .get(&lowercase([window[0], window[1], window[2]]))
+                    .is_some_and(|ids| ids.binary_search(&id).is_ok())
+            })
+    }
+
+    /// Writes the index to `path`, replacing any existing file.
+    pub fn write_to(&self, path: &Path) -> Result<()> {
+        let write = || -> std::io::Result<()> {
+            let mut out = BufWriter::new(fs::File::create(path)?);
+            out.write_all(MAGIC)?;
+
+            write_u32(&mut out, self.roots.len() as u32)?;
+            for root in &self.roots {
+                write_path(&mut out, root)?;
+            }
+
+            write_u32(&mut out, self.files.len() as u32)?;
+            for file in &self.files {
+                write_path(&mut out, &file.path)?;
+                let since_epoch = file.modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+                out.write_all(&since_epoch.as_secs().to_le_bytes())?;
+                write_u32(&mut out, since_epoch.subsec_nanos())?;
+                out.write_all(&file.len.to_le_bytes())?;
+            }
+
+            write_u32(&mut out, self.trigrams.len() as u32)?;
+            for (trigram, ids) in &self.trigrams {
+                out.write_all(trigram)?;
+                write_u32(&mut out, ids.len() as u32)?;
+                for id in ids {
+                    write_u32(&mut out, *id)?;
+                }
+            }
+            out.flush()
+        };
+        write().with_context(|| format!("Could not write index `{}`", path.display()))
+    }
+
+    /// Reads an index written by [`Index::write_to`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not an index.
+    pub fn read_from(path: &Path) -> Result<Self> {
+        let file = fs::File::open(path)
+            .with_context(|| format!("Could not read index `{}`", path.display()))?;
+        Self::decode(&mut BufReader::new(file))
+            .with_context(|| format!("Invalid index `{}`", path.display()))
+    }
+
+    fn decode(input: &mut impl Read) -> Result<Self> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("not a searcher index (or one from an incompatible version)");
+        }
+
+        let mut index = Index::default();
+        for _ in 0..read_u32(input)? {
+            index.roots.push(read_path(input)?);
+        }
+        for _ in 0..read_u32(input)? {
+            let path = read_path(input)?;
+            let secs = read_u64(input)?;
+            let nanos = read_u32(input)?;
+            let len = read_u64(input)?;
+            index.files.push(IndexedFile {
+                path,
+                modified: UNIX_EPOCH + Duration::new(secs, nanos),
+                len,
+            });
+        }
+        for _ in 0..read_u32(input)? {
+            let mut trigram = [0; 3];
+            input.read_exact(&mut trigram)?;
+            let count = read_u32(input)?;
+            let ids = (0..count)
+                .map(|_| read_u32(input))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            if ids.iter().any(|&id| id as usize >= index.files.len()) {
+                bail!("file id out of range");
+            }
+            index.trigrams.insert(trigram, ids);
+        }
+        Ok(index)
+    }
+}
+
+/// The strings every line matched by `pattern` must contain, for
+/// [`Index::candidates`].
+///
+/// Conservative by design: an empty result means "any file may match".
+/// Boolean queries, alternations, and character classes contribute nothing;
+/// extra `all_of` patterns contribute their own literals. With
+/// `ignore_case`, non-ASCII bytes and the letters that non-ASCII characters
+/// fold to split literals: `K` also matches the Kelvin sign, `S` the long
+/// `ſ`, and `I` the dotted `İ`, which lowercases to `i` and a combining dot,
+/// and none of them leave the ASCII trigrams of the letter in the index.
+pub fn required_literals(pattern: &str, options: &MatcherOptions) -> Vec<Vec<u8>> {
+    if options.query {
+        return Vec::new();
+    }
+
+    let mut literals = Vec::new();
+    for pattern in std::iter::once(pattern).chain(options.all_of.iter().map(String::as_str)) {
+        if options.uses_regex() {
+            // An invalid regex fails later when the matcher is built
+            if let Ok(hir) = regex_syntax::parse(pattern) {
+                hir_literals(&hir, &mut literals);
+            }
+        } else {
+            literals.push(pattern.as_bytes().to_vec());
+        }
+    }
+
+    if options.ignore_case {
+        literals = literals
+            .iter()
+            .flat_map(|literal| {
+                literal.split(|byte| {
+                    !byte.is_ascii() || matches!(byte.to_ascii_lowercase(), b'i' | b'k' | b's')
+                })
+            })
+            .map(<[u8]>::to_vec)
+            .collect();
+    }
+    literals.retain(|literal| literal.len() >= 3);
+    literals
+}
+
+/// Collects the literal runs that every match of `hir` must contain.
+pub(crate) fn hir_literals(hir: &Hir, literals: &mut Vec<Vec<u8>>) {
+    match hir.kind() {
+        HirKind::Literal(literal) => literals.push(literal.0.to_vec()),
+        HirKind::Capture(capture) => hir_literals(&capture.sub, literals),
+        HirKind::Repetition(repetition) if repetition.min > 0 => {
+            hir_literals(&repetition.sub, literals)
+        }
+        HirKind::Concat(parts) => {
+            // Adjacent literals form one longer run
+            let mut run = Vec::new();
+            for part in parts {
+                if let HirKind::Literal(literal) = part.kind() {
+                    run.extend_from_slice(&literal.0);
+                    continue;
+                }
+                literals.push(std::mem::take(&mut run));
+                hir_literals(part, literals);
+            }
+            literals.push(run);
+        }
+        _ => {}
+    }
+}
+
+/// Whether `path` is an index file, which builds leave out.
+fn is_index_file(path: &Path) -> bool {
+    let mut magic = [0; 8];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|()| &magic == MAGIC)
+}
+
+/// Lowercases the ASCII letters of a trigram.
+fn lowercase(trigram: Trigram) -> Trigram {
+    trigram.map(|byte| byte.to_ascii_lowercase())
+}
+
+fn write_u32(out: &mut impl Write, value: u32) -> std::io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+fn write_path(out: &mut impl Write, path: &Path) -> std::io::Result<()> {
+    // Build skips paths that are not valid UTF-8
+    let bytes = path.to_string_lossy();
+    write_u32(out, bytes.len() as u32)?;
+    out.write_all(bytes.as_bytes())
+}
+
+fn read_u32(input: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(input: &mut impl Read) -> std::io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_path(input: &mut impl Read) -> Result<PathBuf> {
+    let len = read_u32(input)? as usize;
+    let mut bytes = vec![0; len];
+    input.read_exact(&mut bytes)?;
+    Ok(PathBuf::from(String::from_utf8(bytes)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corpus(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(name);
+        fs::remove_dir_all(&root).ok();
+        fs::create_dir_all(root.join("logs")).unwrap();
+        fs::write(root.join("logs/a.log"), "Connection refused\nok\n").unwrap();
+        fs::write(root.join("logs/b.log"), "all good\n").unwrap();
+        fs::write(root.join("notes.txt"), "connection pool resized\n").unwrap();
+        root
+    }
+
+    fn names(root: &Path, candidates: &Candidates) -> Vec<String> {
+        candidates
+            .files
+            .iter()
+            .map(|path| path.strip_prefix(root).unwrap().display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_candidates_narrow_by_literal() {
+        let root = corpus("searcher_index_narrow");
+        let index = Index::build(std::slice::from_ref(&root), |error| panic!("{}", error));
+        let literals = required_literals("connection refused", &MatcherOptions::default());
+
+        let candidates = index.candidates(&literals);
+
+        // Case-insensitive trigrams keep `Connection refused` as a candidate
+        assert_eq!(names(&root, &candidates), ["logs/a.log"]);
+        assert_eq!((candidates.stale, candidates.skipped), (0, 2));
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_round_trip_through_file() {
+        let root = corpus("searcher_index_round_trip");
+        let index = Index::build(std::slice::from_ref(&root), |error| panic!("{}", error));
+        let file = root.join(".searcher-index");
+
+        index.write_to(&file).unwrap();
+        let read = Index::read_from(&file).unwrap();
+
+        assert_eq!(read, index);
+        // The index file itself is left out of rebuilds and queries
+        let rebuilt = Index::build(std::slice::from_ref(&root), |error| panic!("{}", error));
+        assert_eq!(rebuilt.files().len(), 3);
+        assert_eq!(rebuilt.candidates(&[]).files.len(), 3);
+        assert!(Index::read_from(&root.join("notes.txt")).is_err());
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_changed_and_new_files_are_stale_candidates() {
+        let root = corpus("searcher_index_stale");
+        let index = Index::build(std::slice::from_ref(&root), |error| panic!("{}", error));
+        fs::write(root.join("logs/b.log"), "all good, still\n").unwrap();
+        fs::write(root.join("new.txt"), "fresh\n").unwrap();
+        fs::remove_file(root.join("notes.txt")).unwrap();
+
+        let literals = required_literals("refused", &MatcherOptions::default());
+        let candidates = index.candidates(&literals);
+
+        assert_eq!(
+            names(&root, &candidates),
+            ["logs/a.log", "logs/b.log", "new.txt"]
+        );
+        assert_eq!(candidates.stale, 2);
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_required_literals() {
+        let regex = MatcherOptions {
+            regex: true,
+            ..MatcherOptions::default()
+        };
+        assert_eq!(
+            required_literals(r"conn(ection)? refused: \d+", &regex),
+            [b"conn".to_vec(), b" refused: ".to_vec()]
+        );
+        assert!(required_literals("error|warn", &regex).is_empty());
+
+        let ignore_case = MatcherOptions {
+            ignore_case: true,
+            all_of: vec!["timeout".to_string()],
+            ..MatcherOptions::default()
+        };
+        assert_eq!(
+            required_literals("disk full", &ignore_case),
+            [b" full".to_vec(), b"meout".to_vec()]
+        );
+        assert_eq!(required_literals("axi", &ignore_case), [b"meout".to_vec()]);
+
+        let query = MatcherOptions {
+            query: true,
+            ..MatcherOptions::default()
+        };
+        assert!(required_literals("error AND disk", &query).is_empty());
+    }
+}
@@ -0,0 +1,246 @@
+//! Search jobs described in TOML files.
+//!
+//! A [`JobSpec`] captures everything `searcher find` takes on the command
+//! line, so a repeatable search such as a nightly log audit can be checked in
+//! and run with `searcher run audit.toml`:
+//!
+//! ```toml
+//! name = "nightly audit"
+//! pattern = "timeout|refused"
+//! paths = ["logs"]
+//!
+//! [match]
+//! regex = true
+//! ignore_case = true
+//! none_of = ["healthcheck"]
+//!
+//! [search]
+//! line_terminator = "crlf"
+//! line_ranges = ["1-10000"]
+//!
+//! [output]
+//! line_numbers = true
+//! format = "{path}:{line}: {content}"
+//! ```
+//!
+//! Only `pattern` and `paths` are required. The `[match]` and `[search]`
+//! tables use the field names of [`MatcherOptions`] and [`SearchOptions`].
+//! Unknown keys are rejected so that a typo does not silently widen a search.
+//!
+//! Available with the `job` cargo feature, which the CLI enables.
+
+use crate::printer::Template;
+use crate::query::{MatcherOptions, build_matcher};
+use crate::{Matcher, SearchOptions};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A search described in a job file.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::job::JobSpec;
+///
+/// let job = JobSpec::from_toml(r#"
+///     pattern = "TODO"
+///     paths = ["src"]
+///
+///     [match]
+///     ignore_case = true
+/// "#).unwrap();
+///
+/// assert!(job.matcher.ignore_case);
+/// assert!(job.build_matcher().unwrap().is_match("todo: tests"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobSpec {
+    /// A name for the job, used in error messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The pattern to look for
+    pub pattern: String,
+    /// The files or directories to search
+    pub paths: Vec<PathBuf>,
+    /// How to interpret the pattern and which patterns to combine with it
+    #[serde(default, rename = "match")]
+    pub matcher: MatcherOptions,
+    /// How to split the input into lines
+    #[serde(default)]
+    pub search: SearchOptions,
+    /// How to print the matches
+    #[serde(default)]
+    pub output: JobOutput,
+}
+
+/// The `[output]` table of a job file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobOutput {
+    /// Prefix each line with its line number, like `--line-numbers`
+    pub line_numbers: bool,
+    /// Print file names as headings (`true`) or on every line (`false`);
+    /// unset picks the same default as the command line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heading: Option<bool>,
+    /// A per-match template, like `--format`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// Print regex capture groups instead of lines, like `--groups`
+    pub groups: bool,
+    /// Print control characters in matched lines as they are, like
+    /// `--no-sanitize`
+    pub no_sanitize: bool,
+}
+
+impl JobSpec {
+    /// Parses and validates a job from TOML text.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the text is not valid TOML, does not describe a
+    /// job, or fails [`JobSpec::validate`].
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let job: JobSpec = toml::from_str(text).context("Invalid job file")?;
+        job.validate()?;
+        Ok(job)
+    }
+
+    /// Reads a job file.
+    ///
+    /// Relative `paths` in the file are resolved against the directory the
+    /// file is in, so a job runs the same from any working directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the file if it cannot be read or is invalid.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Could not read job file `{}`", path.display()))?;
+        let mut job = Self::from_toml(&text)
+            .with_context(|| format!("Could not load job file `{}`", path.display()))?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        for job_path in &mut job.paths {
+            if job_path.is_relative() {
+                *job_path = base.join(&*job_path);
+            }
+        }
+        Ok(job)
+    }
+
+    /// Checks that the job can run: it has a pattern and paths, its
+    /// patterns compile, and its output settings are consistent.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error describing the first problem found.
+    pub fn validate(&self) -> Result<()> {
+        let job = match &self.name {
+            Some(name) => format!("Job `{}`", name),
+            None => String::from("Job"),
+        };
+        if self.pattern.is_empty() {
+            bail!("{} has an empty `pattern`", job);
+        }
+        if self.paths.is_empty() {
+            bail!("{} has no `paths` to search", job);
+        }
+        if self.output.groups && !self.matcher.uses_regex() {
+            bail!("{} sets `output.groups` without `match.regex`", job);
+        }
+        self.build_matcher()
+            .with_context(|| format!("{} has an invalid pattern", job))?;
+        self.template()
+            .with_context(|| format!("{} has an invalid `output.format`", job))?;
+        Ok(())
+    }
+
+    /// Builds the matcher described by `pattern` and the `[match]` table.
+    pub fn build_matcher(&self) -> Result<Matcher> {
+        build_matcher(&self.pattern, &self.matcher)
+    }
+
+    /// Parses `output.format`, if set.
+    pub fn template(&self) -> Result<Option<Template>> {
+        self.output.format.as_deref().map(str::parse).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LineRange, LineTerminator};
+
+    #[test]
+    fn test_from_toml_full_job() {
+        let job = JobSpec::from_toml(
+            r#"
+            name = "audit"
+            pattern = "timeout"
+            paths = ["logs", "/var/log/app.log"]
+
+            [match]
+            regex = true
+            none_of = ["healthcheck"]
+
+            [search]
+            line_terminator = "crlf"
+            line_ranges = ["1-100"]
+
+            [output]
+            line_numbers = true
+            heading = false
+            format = "{path}:{line}"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(job.name.as_deref(), Some("audit"));
+        assert!(job.matcher.regex);
+        assert_eq!(job.matcher.none_of, ["healthcheck"]);
+        assert_eq!(job.search.line_terminator, LineTerminator::Crlf);
+        assert_eq!(
+            job.search.line_ranges,
+            ["1-100".parse::<LineRange>().unwrap()]
+        );
+        assert_eq!(job.output.heading, Some(false));
+        assert!(job.template().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_from_toml_rejects_invalid_jobs() {
+        let invalid = [
+            "paths = [\"logs\"]",
+            "pattern = \"x\"\npaths = []",
+            "pattern = \"x\"\npaths = [\"logs\"]\nignore_case = true",
+            "pattern = \"x\"\npaths = [\"logs\"]\n[match]\nignorecase = true",
+            "pattern = \"(\"\npaths = [\"logs\"]\n[match]\nregex = true",
+            "pattern = \"x\"\npaths = [\"logs\"]\n[output]\nformat = \"{column}\"",
+            "pattern = \"x\"\npaths = [\"logs\"]\n[output]\ngroups = true",
+        ];
+        for text in invalid {
+            assert!(JobSpec::from_toml(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_load_resolves_paths_against_job_file() {
+        let dir = std::env::temp_dir().join("searcher_job_load");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("job.toml");
+        fs::write(
+            &file,
+            "pattern = \"x\"\npaths = [\"logs\", \"/tmp/abs.log\"]\n",
+        )
+        .unwrap();
+
+        let job = JobSpec::load(&file).unwrap();
+
+        assert_eq!(job.paths, [dir.join("logs"), PathBuf::from("/tmp/abs.log")]);
+        fs::remove_dir_all(dir).ok();
+    }
+}
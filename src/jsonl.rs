@@ -0,0 +1,190 @@
+//! Searching chosen fields of JSON Lines input.
+//!
+//! Structured logs put one JSON object on each line. Matching the whole
+//! line finds the pattern in keys, ids, and every other field as well, e.g.
+//! `error` in `"level":"info","error_count":0`. [`JsonFields`] parses each
+//! line and hands the matcher only the values of the named fields: as a
+//! [`LineTransform`] it rewrites each line to those values, and as a
+//! [`MatchFilter`] it drops matches on lines that have none of them, such
+//! as lines that are not JSON at all.
+//!
+//! ```
+//! use searcher_cli_starter::jsonl::JsonFields;
+//! use searcher_cli_starter::{Matcher, SearchOptions, search_reader};
+//!
+//! let fields = JsonFields::new(&["message"]).unwrap();
+//! let options = SearchOptions::default()
+//!     .with_transform(fields.clone())
+//!     .with_match_filter(fields);
+//! let input = concat!(
+//!     r#"{"level":"error","message":"disk full"}"#, "\n",
+//!     r#"{"level":"info","message":"no error"}"#, "\n",
+//!     "error: not json\n",
+//! );
+//! let matcher = Matcher::new("^no error", false, true).unwrap();
+//! let matches = search_reader(input.as_bytes(), &matcher, &options).unwrap();
+//!
+//! assert_eq!(matches.len(), 1);
+//! assert_eq!(matches[0].line_number, 2);
+//! ```
+
+use crate::filter::{LineTransform, MatchFilter};
+use crate::{Matcher, SearchMatchBytes};
+use anyhow::{Result, bail};
+use serde_json::Value;
+use std::borrow::Cow;
+
+/// The fields of JSON lines to search, each a name or a dotted path such as
+/// `http.status` or `tags.0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonFields {
+    names: Vec<String>,
+    paths: Vec<Vec<String>>,
+}
+
+impl JsonFields {
+    /// Creates the list of fields to search.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `names` is empty or a name has an empty part,
+    /// such as `a..b`.
+    pub fn new<S: AsRef<str>>(names: &[S]) -> Result<Self> {
+        if names.is_empty() {
+            bail!("no JSON fields to search");
+        }
+        let mut paths = Vec::with_capacity(names.len());
+        for name in names {
+            let name = name.as_ref();
+            let path: Vec<String> = name.split('.').map(str::to_string).collect();
+            if path.iter().any(String::is_empty) {
+                bail!("`{}` is not a valid JSON field name", name);
+            }
+            paths.push(path);
+        }
+        Ok(JsonFields {
+            names: names.iter().map(|name| name.as_ref().to_string()).collect(),
+            paths,
+        })
+    }
+
+    /// The field names, as given to [`JsonFields::new`].
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// The value of each field in `line`, in the order of [`Self::names`],
+    /// with `None` for a field it does not have. Every field is `None` when
+    /// `line` is not a JSON object.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::jsonl::JsonFields;
+    /// use serde_json::json;
+    ///
+    /// let fields = JsonFields::new(&["http.status", "user"]).unwrap();
+    /// let values = fields.values(br#"{"http":{"status":503}}"#);
+    /// assert_eq!(values, [Some(json!(503)), None]);
+    /// ```
+    pub fn values(&self, line: &[u8]) -> Vec<Option<Value>> {
+        let Ok(object @ Value::Object(_)) = serde_json::from_slice::<Value>(line) else {
+            return vec![None; self.paths.len()];
+        };
+        self.paths
+            .iter()
+            .map(|path| lookup(&object, path).cloned())
+            .collect()
+    }
+
+    /// The fields of `line` as `name=value` pairs separated by spaces, with
+    /// each value written as JSON, leaving out the fields it does not have.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::jsonl::JsonFields;
+    ///
+    /// let fields = JsonFields::new(&["message", "code", "user"]).unwrap();
+    /// let line = br#"{"message":"disk full","code":28}"#;
+    /// assert_eq!(fields.describe(line), r#"message="disk full" code=28"#);
+    /// ```
+    pub fn describe(&self, line: &[u8]) -> String {
+        let mut pairs = Vec::new();
+        for (name, value) in self.names.iter().zip(self.values(line)) {
+            if let Some(value) = value {
+                pairs.push(format!("{}={}", name, value));
+            }
+        }
+        pairs.join(" ")
+    }
+
+    /// Puts the fields of each match, as [`Self::describe`] writes them, and
+    /// a tab before its line, so results show what was searched along with
+    /// the line it came from.
+    pub fn annotate(&self, matches: &mut [SearchMatchBytes]) {
+        for search_match in matches {
+            let mut content = self.describe(&search_match.content).into_bytes();
+            content.push(b'\t');
+            content.append(&mut search_match.content);
+            search_match.content = content;
+        }
+    }
+}
+
+impl LineTransform for JsonFields {
+    /// The values of the fields `line` has, one per line: strings as they
+    /// are and other values as JSON. Empty for a line that has none.
+    fn transform<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        let mut searched = Vec::new();
+        for value in self.values(line).into_iter().flatten() {
+            if !searched.is_empty() {
+                searched.push(b'\n');
+            }
+            match value {
+                Value::String(text) => searched.extend_from_slice(text.as_bytes()),
+                value => searched.extend_from_slice(value.to_string().as_bytes()),
+            }
+        }
+        Cow::Owned(searched)
+    }
+}
+
+impl MatchFilter for JsonFields {
+    /// Keeps the matches on lines with at least one of the fields, so that a
+    /// pattern matching an empty line does not match every other line.
+    fn keep(&self, _line_number: usize, line: &[u8], _matcher: &Matcher) -> bool {
+        self.values(line).iter().any(Option::is_some)
+    }
+}
+
+/// The value at `path` inside `value`, where a number picks an array item.
+fn lookup<'v>(value: &'v Value, path: &[String]) -> Option<&'v Value> {
+    path.iter().try_fold(value, |value, key| match value {
+        Value::Object(map) => map.get(key),
+        Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transform_joins_present_fields() {
+        let fields = JsonFields::new(&["msg", "tags.1", "ctx.user", "missing"]).unwrap();
+        let line = br#"{"msg":"a\tb","tags":["x","y"],"ctx":{"user":null}}"#;
+        assert_eq!(&*fields.transform(line), b"a\tb\ny\nnull");
+        assert_eq!(
+            fields.describe(line),
+            r#"msg="a\tb" tags.1="y" ctx.user=null"#
+        );
+        assert!(fields.transform(b"[1, 2]").is_empty());
+        assert!(fields.transform(b"not json").is_empty());
+
+        assert!(JsonFields::new::<&str>(&[]).is_err());
+        assert!(JsonFields::new(&["a..b"]).is_err());
+        assert!(JsonFields::new(&["."]).is_err());
+    }
+}
@@ -4,6 +4,56 @@
 //! - Case-insensitive matching
 //! - Regular expression patterns
 //! - Line number tracking
+//! - Searching readers, in-memory strings, and byte slices
+//! - Byte-oriented search for input that is not valid UTF-8
+//! - Searching files backwards, from the last line to the first
+//! - Finding only the first or last match of a file
+//! - Showing the enclosing function or section of each match
+//! - Matching multi-line records, such as stack traces, as a whole
+//! - Searching chosen fields of JSON Lines input
+//! - Dropping matches that another pattern follows within a few lines, or
+//!   pairing them with the line that does
+//! - Grouping interleaved log lines into sessions by a request or thread id
+//! - Timing the gaps between matches from their timestamps
+//! - Finding repeated lines or matched values, with where they occur
+//! - Matching only the comments, strings, or code of source files, or the text of HTML and XML
+//! - Hex byte patterns found at byte offsets in binary files
+//! - Matches returned with the lines around them
+//! - Configurable line terminators (LF, CRLF, NUL-separated records)
+//! - Sampling lines, or reading only the head or tail of files, to probe huge inputs
+//! - Pluggable line filters and transforms, such as time windows for log lines and whitespace and Unicode normalization
+//! - Boolean combinations of patterns (`error AND NOT retry`)
+//! - Whole-word matching, with language-specific case rules and word boundaries (with the `icu` feature)
+//! - Several patterns matched in one pass, recording which ones each line matched
+//! - Search-and-replace with unified diff previews
+//! - Matches masked in results, so they can be shared safely
+//! - Frequency tables of values captured from matching lines, or only the most
+//!   frequent ones in bounded memory
+//! - Match density per block of lines, to see where matches cluster in a file
+//! - Paths and lines quoted for POSIX shells or as C string literals
+//! - Searching files, stdin, buffers, tar archive members, or custom sources
+//!   through one `InputSource` trait
+//! - Recursive directory walking that reports unreadable paths without stopping
+//! - Searching files on several threads while yielding them in the walk's order, and
+//!   splitting large files into line-aligned ranges searched at once, on a
+//!   chosen number of threads optionally pinned to CPUs
+//! - Walking in-memory trees or overlays of file systems through one `Vfs` trait
+//! - Errors taken apart into their kind and path, for tools that run searches
+//! - `.searcherignore` files, and `.gitignore` files in git repositories, honored while walking
+//! - Search events sent over a channel from a background thread, for GUIs
+//! - A persistent trigram index for repeated searches of large file sets
+//! - A persistent cache of per-file results, keyed by file content
+//! - Async search of tokio readers and directories (with the `async` feature)
+//! - JavaScript bindings for `wasm32-unknown-unknown` (with the `wasm` feature)
+//! - Python bindings built with PyO3 (with the `python` feature)
+//! - Allocations, peak heap, and peak RSS of a search (with the `profiling` feature)
+//! - Search jobs described in TOML files (with the `job` feature)
+//! - An interactive terminal search UI (with the `tui` feature)
+//! - SARIF reports for code scanning tools (with the `sarif` feature)
+//! - Named rulesets checked in one pass (with the `rules` feature)
+//! - Leaked credential detection with rules and an entropy check (with the `rules` feature)
+//! - A long-running search server for editor plugins (with the `server` feature)
+//! - A JSON-RPC backend over stdio for IDE extensions (with the `server` feature)
 //!
 //! # Examples
 //!
@@ -69,9 +119,88 @@
 //! }
 //! ```
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use memchr::memmem;
 use regex::Regex;
-use std::io::{BufRead, BufReader, Read};
+use std::borrow::Cow;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::{ControlFlow, Range};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "async")]
+pub mod async_search;
+pub mod bench;
+pub mod cache;
+pub mod cancel;
+pub mod context;
+pub mod diagnostic;
+pub mod dups;
+pub mod events;
+pub mod filter;
+#[cfg(feature = "timestamps")]
+pub mod gaps;
+pub mod git;
+pub mod hex;
+pub mod histogram;
+pub mod ignore;
+pub mod index;
+#[cfg(feature = "job")]
+pub mod job;
+#[cfg(feature = "jsonl")]
+pub mod jsonl;
+pub mod lines;
+#[cfg(feature = "icu")]
+pub mod locale;
+pub mod multi;
+pub mod preprocess;
+pub mod printer;
+#[cfg(feature = "profiling")]
+pub mod profile;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod query;
+pub mod records;
+pub mod redact;
+pub mod replace;
+#[cfg(feature = "server")]
+pub mod rpc;
+#[cfg(feature = "rules")]
+pub mod rules;
+#[cfg(feature = "sarif")]
+pub mod sarif;
+pub mod scope;
+pub mod searcher;
+#[cfg(feature = "rules")]
+pub mod secrets;
+pub mod sequence;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod sessions;
+pub mod source;
+pub mod split;
+pub mod threads;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod vfs;
+pub mod walk;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watch;
+
+use cancel::{CancellationToken, Cancelled, TimedOut};
+use diagnostic::FileContext;
+use filter::{
+    Dedupe, LineFilter, LineFilters, LineTransform, LineTransforms, MatchFilter, MatchFilters,
+};
+use lines::{LineReader, ReverseLineReader, count_lines};
+use preprocess::Preprocessor;
+use query::CompositeMatcher;
+use scope::{Language, Lexer, Scope};
+use source::{FileSource, search_input, search_input_with};
 
 /// Represents a single line that matched the search pattern.
 ///
@@ -93,6 +222,7 @@ use std::io::{BufRead, BufReader, Read};
 /// assert_eq!(search_match.content, "error in function");
 /// ```
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SearchMatch {
     /// The line number where the match was found (1-based indexing)
     pub line_number: usize,
@@ -100,6 +230,455 @@ pub struct SearchMatch {
     pub content: String,
 }
 
+/// Represents a single matching line as raw bytes.
+///
+/// This is the byte-oriented counterpart of [`SearchMatch`], produced by
+/// [`search_lines_bytes`]. The content is kept exactly as it appeared in the
+/// input (minus the line terminator), so it may contain invalid UTF-8.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::SearchMatchBytes;
+///
+/// let search_match = SearchMatchBytes {
+///     line_number: 7,
+///     content: b"bad \xFF byte".to_vec(),
+/// };
+///
+/// assert_eq!(search_match.content_lossy(), "bad \u{FFFD} byte");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchMatchBytes {
+    /// The line number where the match was found (1-based indexing)
+    pub line_number: usize,
+    /// The raw bytes of the matching line, without the line terminator
+    pub content: Vec<u8>,
+}
+
+impl SearchMatchBytes {
+    /// Returns the content as text, replacing invalid UTF-8 with `U+FFFD`.
+    pub fn content_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.content)
+    }
+}
+
+/// A matching line whose content is borrowed, produced by
+/// [`search_lines_ref`].
+///
+/// The content lives only as long as the line buffer of the search, i.e.
+/// until the callback returns. Copy what you need, or the whole match with
+/// [`SearchMatchRef::to_owned`].
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::{SearchMatch, SearchMatchRef};
+///
+/// let line = String::from("error in function");
+/// let search_match = SearchMatchRef { line_number: 42, content: &line };
+///
+/// assert_eq!(
+///     search_match.to_owned(),
+///     SearchMatch { line_number: 42, content: line.clone() }
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchMatchRef<'a> {
+    /// The line number where the match was found (1-based indexing)
+    pub line_number: usize,
+    /// The content of the matching line, without its line ending
+    pub content: &'a str,
+}
+
+impl SearchMatchRef<'_> {
+    /// Copies the match into an owned [`SearchMatch`].
+    pub fn to_owned(&self) -> SearchMatch {
+        SearchMatch {
+            line_number: self.line_number,
+            content: self.content.to_string(),
+        }
+    }
+}
+
+impl From<SearchMatchRef<'_>> for SearchMatch {
+    fn from(search_match: SearchMatchRef<'_>) -> Self {
+        search_match.to_owned()
+    }
+}
+
+/// How the input is split into lines (or records).
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::LineTerminator;
+///
+/// assert_eq!(LineTerminator::default(), LineTerminator::Lf);
+/// assert_eq!(LineTerminator::Nul.byte(), b'\0');
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum LineTerminator {
+    /// Lines end with `\n`; any `\r` before it is kept in the content
+    #[default]
+    Lf,
+    /// Lines end with `\n` or `\r\n`; a trailing `\r` is stripped
+    Crlf,
+    /// Records are separated by NUL bytes, as produced by `find -print0`
+    Nul,
+}
+
+impl LineTerminator {
+    /// The byte that ends a line or record.
+    pub fn byte(self) -> u8 {
+        match self {
+            LineTerminator::Lf | LineTerminator::Crlf => b'\n',
+            LineTerminator::Nul => b'\0',
+        }
+    }
+
+    /// `line` without this terminator, nor the `\r` before it for
+    /// [`LineTerminator::Crlf`].
+    pub(crate) fn strip(self, line: &[u8]) -> &[u8] {
+        let line = line.strip_suffix(&[self.byte()]).unwrap_or(line);
+        if self == LineTerminator::Crlf {
+            line.strip_suffix(b"\r").unwrap_or(line)
+        } else {
+            line
+        }
+    }
+}
+
+/// Options controlling how [`search_reader`] reads its input.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::{LineTerminator, SearchOptions};
+///
+/// let options = SearchOptions {
+///     line_terminator: LineTerminator::Crlf,
+///     ..SearchOptions::default()
+/// };
+/// assert_ne!(options, SearchOptions::default());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct SearchOptions {
+    /// How the input is split into lines
+    pub line_terminator: LineTerminator,
+    /// Only search lines inside these ranges (all lines when empty)
+    pub line_ranges: Vec<LineRange>,
+    /// Added to every line number, e.g. `1000` when the input is a chunk
+    /// that starts at line 1001 of a larger file. `line_ranges` and filters
+    /// see the shifted numbers too
+    pub line_offset: usize,
+    /// Stop with a [`Cancelled`] error once this token is cancelled
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub cancel: Option<CancellationToken>,
+    /// Give up on an input with a [`TimedOut`] error once searching it has
+    /// taken this long (checked between lines)
+    #[cfg_attr(feature = "serde", serde(with = "duration_secs"))]
+    pub timeout: Option<Duration>,
+    /// Only search lines every one of these filters keeps (see [`filter`])
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub filters: LineFilters,
+    /// Only report matches every one of these filters keeps
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub match_filters: MatchFilters,
+    /// Rewrite each line with these before the matcher sees it, e.g.
+    /// [`NormalizeWhitespace`](filter::NormalizeWhitespace); filters and the
+    /// reported match still get the line as read
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub transforms: LineTransforms,
+    /// Search the output of this command instead of the files it applies to
+    /// (only used by [`search_path`])
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub preprocessor: Option<Preprocessor>,
+    /// Only match the comments, strings, or code of each file, or the text
+    /// of HTML and XML files, skipping files in languages [`scope`] does not
+    /// know (only used by [`search_path`])
+    pub scope: Option<Scope>,
+    /// Drop matching lines that repeat one already reported
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub dedupe: Option<Dedupe>,
+    /// Only read the first or last bytes of each file (only used by
+    /// [`search_path`])
+    pub byte_window: Option<ByteWindow>,
+    /// Leave line numbers at 0 where finding them would mean reading input
+    /// the search itself does not (only used by
+    /// [`search_path_reverse_with`])
+    pub skip_line_numbers: bool,
+}
+
+impl SearchOptions {
+    /// Adds a [`LineFilter`], run on each line before matching.
+    pub fn with_filter(mut self, filter: impl LineFilter + 'static) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    /// Adds a [`LineTransform`], run on each line before matching.
+    pub fn with_transform(mut self, transform: impl LineTransform + 'static) -> Self {
+        self.transforms.push(transform);
+        self
+    }
+
+    /// Adds a [`MatchFilter`], run on each matching line.
+    pub fn with_match_filter(mut self, filter: impl MatchFilter + 'static) -> Self {
+        self.match_filters.push(filter);
+        self
+    }
+
+    /// Returns a [`Cancelled`] error if the search should stop.
+    pub(crate) fn check_cancelled(&self) -> Result<()> {
+        match &self.cancel {
+            Some(token) if token.is_cancelled() => Err(Cancelled.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// When a search starting now must give up, if there is a time limit.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.timeout.map(|timeout| Instant::now() + timeout)
+    }
+
+    /// Returns a [`TimedOut`] error if `deadline` has passed.
+    pub(crate) fn check_deadline(&self, deadline: Option<Instant>) -> Result<()> {
+        match (deadline, self.timeout) {
+            (Some(deadline), Some(limit)) if Instant::now() >= deadline => {
+                Err(TimedOut { limit }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Serializes an optional [`Duration`] as a number of seconds, e.g. `2.5`.
+#[cfg(feature = "serde")]
+mod duration_secs {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value
+            .map(|duration| duration.as_secs_f64())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<f64>::deserialize(deserializer)?
+            .map(|secs| Duration::try_from_secs_f64(secs).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+/// An inclusive range of 1-based line numbers.
+///
+/// Parsed from `N` (a single line), `N-M`, or `N-` (from line N to the end
+/// of the input).
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::LineRange;
+///
+/// let range: LineRange = "100-500".parse().unwrap();
+/// assert!(range.contains(100));
+/// assert!(range.contains(500));
+/// assert!(!range.contains(501));
+///
+/// let open: LineRange = "10-".parse().unwrap();
+/// assert_eq!(open.end, None);
+///
+/// assert!("5-2".parse::<LineRange>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "String", into = "String"))]
+pub struct LineRange {
+    /// The first line in the range
+    pub start: usize,
+    /// The last line in the range, or `None` for "until the end"
+    pub end: Option<usize>,
+}
+
+impl LineRange {
+    /// Returns true if `line_number` falls inside the range.
+    pub fn contains(&self, line_number: usize) -> bool {
+        line_number >= self.start && self.end.is_none_or(|end| line_number <= end)
+    }
+}
+
+impl FromStr for LineRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse_line = |part: &str| -> Result<usize> {
+            let line: usize = part
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid line range `{}`", s))?;
+            if line == 0 {
+                bail!("Invalid line range `{}`: line numbers start at 1", s);
+            }
+            Ok(line)
+        };
+
+        let range = match s.split_once('-') {
+            None => {
+                let line = parse_line(s)?;
+                LineRange {
+                    start: line,
+                    end: Some(line),
+                }
+            }
+            Some((start, end)) if end.trim().is_empty() => LineRange {
+                start: parse_line(start)?,
+                end: None,
+            },
+            Some((start, end)) => LineRange {
+                start: parse_line(start)?,
+                end: Some(parse_line(end)?),
+            },
+        };
+
+        if range.end.is_some_and(|end| end < range.start) {
+            bail!("Invalid line range `{}`: end is before start", s);
+        }
+        Ok(range)
+    }
+}
+
+impl fmt::Display for LineRange {
+    /// Formats the range in the syntax accepted by [`FromStr`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.end {
+            Some(end) if end == self.start => write!(f, "{}", self.start),
+            Some(end) => write!(f, "{}-{}", self.start, end),
+            None => write!(f, "{}-", self.start),
+        }
+    }
+}
+
+impl TryFrom<String> for LineRange {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<LineRange> for String {
+    fn from(range: LineRange) -> Self {
+        range.to_string()
+    }
+}
+
+/// The part of each file to read, to probe huge files before a full scan.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::{ByteWindow, Matcher, SearchOptions, search_path};
+///
+/// let path = std::env::temp_dir().join("byte_window_example.log");
+/// std::fs::write(&path, "start ok\nmiddle error\nend error\n").unwrap();
+///
+/// let matcher = Matcher::new("error", false, false).unwrap();
+/// let options = SearchOptions {
+///     byte_window: Some(ByteWindow::Tail(12)),
+///     ..SearchOptions::default()
+/// };
+/// let matches = search_path(&path, &matcher, &options).unwrap();
+/// assert_eq!(matches.len(), 1);
+/// assert_eq!(matches[0].content, b"end error");
+/// assert_eq!(matches[0].line_number, 1);
+/// # std::fs::remove_file(path).ok();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ByteWindow {
+    /// The first this many bytes; the last line read may be cut short
+    Head(u64),
+    /// The last this many bytes, from the first line that starts within
+    /// them. Line numbers count from there, as the lines before are not read
+    Tail(u64),
+}
+
+impl ByteWindow {
+    /// `file`, positioned at the start of the window and ending with it.
+    /// `terminator` is what ends the line a tail starts in the middle of.
+    fn open(self, mut file: File, terminator: u8) -> io::Result<Box<dyn Read>> {
+        match self {
+            ByteWindow::Head(len) => Ok(Box::new(file.take(len))),
+            ByteWindow::Tail(len) => {
+                let size = file.metadata()?.len();
+                if size <= len {
+                    return Ok(Box::new(file));
+                }
+                // Start on the byte before the tail, so that a line starting
+                // right at it is kept
+                file.seek(SeekFrom::Start(size - len - 1))?;
+                let mut reader = BufReader::new(file);
+                reader.skip_until(terminator)?;
+                Ok(Box::new(reader))
+            }
+        }
+    }
+}
+
+/// Common view over the text and byte flavors of a match.
+///
+/// Output code such as the [`printer`] only needs the line number and the
+/// raw bytes of a line, so it accepts anything implementing this trait.
+pub trait MatchedLine {
+    /// The 1-based line number of the match
+    fn line_number(&self) -> usize;
+    /// The content of the matching line as bytes
+    fn content_bytes(&self) -> &[u8];
+}
+
+impl MatchedLine for SearchMatch {
+    fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    fn content_bytes(&self) -> &[u8] {
+        self.content.as_bytes()
+    }
+}
+
+impl MatchedLine for SearchMatchBytes {
+    fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    fn content_bytes(&self) -> &[u8] {
+        &self.content
+    }
+}
+
+impl MatchedLine for SearchMatchRef<'_> {
+    fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    fn content_bytes(&self) -> &[u8] {
+        self.content.as_bytes()
+    }
+}
+
 /// Pattern matching strategy.
 ///
 /// Supports both literal string matching and regular expression patterns.
@@ -125,6 +704,30 @@ pub struct SearchMatch {
 /// assert!(matcher.is_match("hello"));
 /// assert!(matcher.is_match("hero"));
 /// ```
+///
+/// # Thread safety
+///
+/// A `Matcher` is `Send + Sync`, and matching only needs `&self`, so one
+/// compiled matcher can serve any number of threads, either borrowed in a
+/// scope or shared as an `Arc<Matcher>`. Use [`Matcher::clone_compiled`]
+/// when a thread needs a matcher of its own.
+///
+/// ```
+/// use searcher_cli_starter::Matcher;
+/// use std::thread;
+///
+/// let matcher = Matcher::new(r"error \d+", false, true).unwrap();
+/// let files = ["ok\nerror 1\n", "error 2\nerror 3\n"];
+///
+/// let counts: Vec<usize> = thread::scope(|scope| {
+///     let workers: Vec<_> = files
+///         .iter()
+///         .map(|file| scope.spawn(|| file.lines().filter(|l| matcher.is_match(l)).count()))
+///         .collect();
+///     workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+/// });
+/// assert_eq!(counts, [1, 2]);
+/// ```
 pub enum Matcher {
     /// Literal string matching with optional case-insensitive comparison
     Literal {
@@ -137,7 +740,20 @@ pub enum Matcher {
     Regex {
         /// The compiled regular expression
         regex: Regex,
+        /// The same expression compiled for matching raw bytes
+        bytes_regex: regex::bytes::Regex,
+        /// Finds a literal every match must contain, to skip lines without
+        /// it before running the regex
+        prefilter: Option<Box<memmem::Finder<'static>>>,
     },
+    /// Several patterns combined with AND / OR / NOT
+    Composite(Box<CompositeMatcher>),
+}
+
+impl From<CompositeMatcher> for Matcher {
+    fn from(composite: CompositeMatcher) -> Self {
+        Matcher::Composite(Box::new(composite))
+    }
 }
 
 impl Matcher {
@@ -183,7 +799,13 @@ impl Matcher {
                 pattern.to_string()
             };
             let regex = Regex::new(&regex_pattern).context("Invalid regex pattern")?;
-            Ok(Matcher::Regex { regex })
+            let bytes_regex =
+                regex::bytes::Regex::new(&regex_pattern).context("Invalid regex pattern")?;
+            Ok(Matcher::Regex {
+                regex,
+                bytes_regex,
+                prefilter: prefilter(&regex_pattern),
+            })
         } else {
             Ok(Matcher::Literal {
                 pattern: if ignore_case {
@@ -196,6 +818,96 @@ impl Matcher {
         }
     }
 
+    /// Returns a copy of the matcher without compiling its pattern again.
+    ///
+    /// Compiled regexes are reference counted, so the copy shares them with
+    /// the original; each copy still keeps its own scratch space for
+    /// matching, so copies never contend with each other.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::Matcher;
+    ///
+    /// let matcher = Matcher::new(r"\bfn \w+", false, true).unwrap();
+    /// let copy = matcher.clone_compiled();
+    /// assert!(copy.is_match("pub fn main()"));
+    /// ```
+    pub fn clone_compiled(&self) -> Self {
+        match self {
+            Matcher::Literal {
+                pattern,
+                ignore_case,
+            } => Matcher::Literal {
+                pattern: pattern.clone(),
+                ignore_case: *ignore_case,
+            },
+            Matcher::Regex {
+                regex,
+                bytes_regex,
+                prefilter,
+            } => Matcher::Regex {
+                regex: regex.clone(),
+                bytes_regex: bytes_regex.clone(),
+                prefilter: prefilter.clone(),
+            },
+            Matcher::Composite(composite) => {
+                Matcher::Composite(Box::new(composite.clone_compiled()))
+            }
+        }
+    }
+
+    /// A stable 64-bit hash of what the matcher matches: its pattern or
+    /// patterns and their options, such as case-insensitivity and whether
+    /// they are regexes.
+    ///
+    /// Matchers built from the same arguments have the same fingerprint, in
+    /// every process, on every platform, and across versions of this crate,
+    /// so it can key caches of search results such as
+    /// [`ResultCache`](cache::ResultCache). Matchers that always match the
+    /// same lines may share a fingerprint even if built differently, e.g. an
+    /// `-i` literal written in upper or lower case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::Matcher;
+    ///
+    /// let matcher = Matcher::new("timeout", true, false).unwrap();
+    /// assert_eq!(matcher.fingerprint(), Matcher::new("TIMEOUT", true, false).unwrap().fingerprint());
+    /// assert_ne!(matcher.fingerprint(), Matcher::new("timeout", false, false).unwrap().fingerprint());
+    /// assert_ne!(matcher.fingerprint(), Matcher::new("timeout", true, true).unwrap().fingerprint());
+    /// ```
+    pub fn fingerprint(&self) -> u64 {
+        let mut description = Vec::new();
+        self.describe(&mut description);
+        cache::hash_bytes(&description)
+    }
+
+    /// Appends an unambiguous encoding of the matcher to `out`, for
+    /// [`Matcher::fingerprint`]. Each kind starts with its own tag byte and
+    /// every pattern is prefixed with its length.
+    pub(crate) fn describe(&self, out: &mut Vec<u8>) {
+        let mut pattern = |tag: u8, pattern: &str| {
+            out.push(tag);
+            out.extend_from_slice(&(pattern.len() as u64).to_le_bytes());
+            out.extend_from_slice(pattern.as_bytes());
+        };
+        match self {
+            Matcher::Literal {
+                pattern: literal,
+                ignore_case: false,
+            } => pattern(b'L', literal),
+            Matcher::Literal {
+                pattern: literal,
+                ignore_case: true,
+            } => pattern(b'l', literal),
+            // An `ignore_case` regex carries `(?i)` in its pattern
+            Matcher::Regex { regex, .. } => pattern(b'R', regex.as_str()),
+            Matcher::Composite(composite) => composite.describe(out),
+        }
+    }
+
     /// Checks if the given line matches the pattern.
     ///
     /// # Arguments
@@ -236,119 +948,1276 @@ impl Matcher {
                     line.contains(pattern)
                 }
             }
-            Matcher::Regex { regex } => regex.is_match(line),
+            Matcher::Regex {
+                regex, prefilter, ..
+            } => passes(prefilter, line.as_bytes()) && regex.is_match(line),
+            Matcher::Composite(composite) => composite.is_match(line),
+        }
+    }
+
+    /// Checks if the given raw line matches the pattern.
+    ///
+    /// This is the byte-oriented counterpart of [`Matcher::is_match`]. Regex
+    /// patterns are evaluated with `regex::bytes::Regex`, so lines containing
+    /// invalid UTF-8 can still match. Case-insensitive literals fall back to a
+    /// lossy UTF-8 conversion of the line before comparing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::Matcher;
+    ///
+    /// let matcher = Matcher::new("error", false, false).unwrap();
+    /// assert!(matcher.is_match_bytes(b"\xFF\xFE error in blob"));
+    ///
+    /// let matcher = Matcher::new(r"code=\d+", false, true).unwrap();
+    /// assert!(matcher.is_match_bytes(b"\x00code=42\x00"));
+    /// ```
+    pub fn is_match_bytes(&self, line: &[u8]) -> bool {
+        match self {
+            Matcher::Literal {
+                pattern,
+                ignore_case,
+            } => {
+                if *ignore_case {
+                    String::from_utf8_lossy(line)
+                        .to_lowercase()
+                        .contains(pattern.as_str())
+                } else {
+                    memmem::find(line, pattern.as_bytes()).is_some()
+                }
+            }
+            Matcher::Regex {
+                bytes_regex,
+                prefilter,
+                ..
+            } => passes(prefilter, line) && bytes_regex.is_match(line),
+            Matcher::Composite(composite) => composite.is_match_bytes(line),
+        }
+    }
+
+    /// Finds the byte ranges of every non-overlapping match in `line`.
+    ///
+    /// Used to highlight matches or report their columns. Composite matchers
+    /// have no single match span and always return an empty Vec, even for
+    /// lines they match.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::Matcher;
+    ///
+    /// let matcher = Matcher::new("rust", true, false).unwrap();
+    /// assert_eq!(matcher.match_ranges(b"Rust and rust"), [0..4, 9..13]);
+    ///
+    /// let matcher = Matcher::new(r"\d+", false, true).unwrap();
+    /// assert_eq!(matcher.match_ranges(b"a1 b22"), [1..2, 4..6]);
+    /// ```
+    pub fn match_ranges(&self, line: &[u8]) -> Vec<Range<usize>> {
+        let regex_ranges =
+            |regex: &regex::bytes::Regex| regex.find_iter(line).map(|m| m.range()).collect();
+        match self {
+            Matcher::Literal {
+                pattern,
+                ignore_case: false,
+            } => memmem::find_iter(line, pattern.as_bytes())
+                .map(|start| start..start + pattern.len())
+                .collect(),
+            // Lowercasing ASCII keeps byte offsets; anything else needs a regex
+            Matcher::Literal { pattern, .. } if line.is_ascii() => {
+                memmem::find_iter(&line.to_ascii_lowercase(), pattern.as_bytes())
+                    .map(|start| start..start + pattern.len())
+                    .collect()
+            }
+            Matcher::Literal { pattern, .. } => {
+                match regex::bytes::Regex::new(&format!("(?i){}", regex::escape(pattern))) {
+                    Ok(regex) => regex_ranges(&regex),
+                    Err(_) => Vec::new(),
+                }
+            }
+            Matcher::Regex { bytes_regex, .. } => regex_ranges(bytes_regex),
+            Matcher::Composite(_) => Vec::new(),
         }
     }
+
+    /// Extracts the capture groups of every match of a regex pattern in `line`.
+    ///
+    /// Each element of the returned Vec describes one match of the pattern and
+    /// lists its groups in order, starting at group 1 (the whole-match group 0
+    /// is omitted). Groups that did not participate in the match have a value
+    /// of `None`. Literal and composite matchers have no groups, so they always
+    /// return an empty Vec.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::Matcher;
+    ///
+    /// let matcher = Matcher::new(r"id=(?P<id>\d+) took (\d+)ms", false, true).unwrap();
+    /// let captures = matcher.captures("id=7 took 12ms; id=9 took 3ms");
+    ///
+    /// assert_eq!(captures.len(), 2);
+    /// assert_eq!(captures[0][0].name.as_deref(), Some("id"));
+    /// assert_eq!(captures[0][0].value.as_deref(), Some("7"));
+    /// assert_eq!(captures[1][1].index, 2);
+    /// assert_eq!(captures[1][1].value.as_deref(), Some("3"));
+    /// ```
+    pub fn captures(&self, line: &str) -> Vec<Vec<CaptureGroup>> {
+        self.captures_bytes(line.as_bytes())
+    }
+
+    /// Byte-oriented counterpart of [`Matcher::captures`].
+    ///
+    /// The line may contain invalid UTF-8; group values are converted to
+    /// text lossily, replacing any invalid sequences with `U+FFFD`.
+    pub fn captures_bytes(&self, line: &[u8]) -> Vec<Vec<CaptureGroup>> {
+        let Matcher::Regex { bytes_regex, .. } = self else {
+            return Vec::new();
+        };
+        let names: Vec<Option<&str>> = bytes_regex.capture_names().collect();
+
+        bytes_regex
+            .captures_iter(line)
+            .map(|captures| {
+                (1..captures.len())
+                    .map(|index| CaptureGroup {
+                        index,
+                        name: names[index].map(str::to_string),
+                        value: captures
+                            .get(index)
+                            .map(|m| String::from_utf8_lossy(m.as_bytes()).into_owned()),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
 }
 
-/// Searches through a reader line-by-line for lines matching the pattern.
+/// Builds a finder for the longest literal every match of `pattern` must
+/// contain, or `None` if there is no such literal (e.g. `\d+` or `a|b`).
+///
+/// Literals shorter than three bytes are ignored: they occur on too many
+/// lines for skipping ahead to pay off.
+fn prefilter(pattern: &str) -> Option<Box<memmem::Finder<'static>>> {
+    let hir = regex_syntax::parse(pattern).ok()?;
+    let mut literals = Vec::new();
+    index::hir_literals(&hir, &mut literals);
+    let literal = literals.into_iter().max_by_key(Vec::len)?;
+    if literal.len() < 3 {
+        return None;
+    }
+    Some(Box::new(memmem::Finder::new(&literal).into_owned()))
+}
+
+/// Whether `line` can match according to a [`prefilter`]; lines always pass
+/// when there is none.
+fn passes(prefilter: &Option<Box<memmem::Finder<'static>>>, line: &[u8]) -> bool {
+    prefilter
+        .as_ref()
+        .is_none_or(|finder| finder.find(line).is_some())
+}
+
+/// The value of a single regex capture group within one match.
+///
+/// Produced by [`Matcher::captures`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureGroup {
+    /// The group number (1-based, in order of opening parenthesis)
+    pub index: usize,
+    /// The group name for `(?P<name>...)` groups
+    pub name: Option<String>,
+    /// The captured text, or `None` if the group did not participate
+    pub value: Option<String>,
+}
+
+impl CaptureGroup {
+    /// Returns the group name, or its number for unnamed groups.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::CaptureGroup;
+    ///
+    /// let group = CaptureGroup { index: 2, name: None, value: None };
+    /// assert_eq!(group.label(), "2");
+    /// ```
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => self.index.to_string(),
+        }
+    }
+}
+
+/// Turns search results into one line per regex match listing its groups.
+///
+/// Each output line keeps the line number of the line it came from and
+/// holds the groups as space-separated `label=value` pairs (see
+/// [`CaptureGroup::label`]). Groups that did not participate have an empty
+/// value. Literal and composite matchers have no groups and produce nothing.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::{Matcher, capture_lines, search_lines_bytes};
+/// use std::io::Cursor;
+///
+/// let matcher = Matcher::new(r"(?P<key>\w+)=(\d+)", false, true).unwrap();
+/// let matches = search_lines_bytes(Cursor::new("a=1 b=2\nnone"), &matcher).unwrap();
+/// let lines = capture_lines(&matcher, &matches);
+///
+/// assert_eq!(lines.len(), 2);
+/// assert_eq!(lines[0].content, b"key=a 2=1");
+/// assert_eq!(lines[1].content, b"key=b 2=2");
+/// assert_eq!(lines[1].line_number, 1);
+/// ```
+pub fn capture_lines(matcher: &Matcher, matches: &[SearchMatchBytes]) -> Vec<SearchMatchBytes> {
+    let mut lines = Vec::new();
+    for search_match in matches {
+        for groups in matcher.captures_bytes(&search_match.content) {
+            let fields: Vec<String> = groups
+                .iter()
+                .map(|group| format!("{}={}", group.label(), group.value.as_deref().unwrap_or("")))
+                .collect();
+            lines.push(SearchMatchBytes {
+                line_number: search_match.line_number,
+                content: fields.join(" ").into_bytes(),
+            });
+        }
+    }
+    lines
+}
+
+/// Searches through a reader line-by-line for lines matching the pattern.
+///
+/// This function processes input line-by-line using buffered I/O for efficiency.
+/// It works with any type implementing the `Read` trait, including files, strings,
+/// and standard input.
+///
+/// # Arguments
+///
+/// * `reader` - Any type implementing Read (files, strings, stdin, etc.)
+/// * `matcher` - The Matcher to use for pattern matching
+///
+/// # Returns
+///
+/// Returns a Result containing a Vec of SearchMatch structs for all matching lines,
+/// or an error if reading fails.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Reading from the input source fails
+/// - A line contains invalid UTF-8
+///
+/// # Performance
+///
+/// - Time complexity: O(n × m) where n = number of lines, m = average line length
+/// - Space complexity: O(k × m) where k = number of matches
+/// - Streams input line-by-line without loading entire file into memory
+///
+/// # Examples
+///
+/// ## Searching in-memory strings
+///
+/// ```
+/// use searcher_cli_starter::{Matcher, search_lines};
+/// use std::io::Cursor;
+///
+/// let input = "hello world\nrust is great\nhello rust";
+/// let cursor = Cursor::new(input);
+///
+/// let matcher = Matcher::new("hello", false, false).unwrap();
+/// let results = search_lines(cursor, &matcher).unwrap();
+///
+/// assert_eq!(results.len(), 2);
+/// assert_eq!(results[0].line_number, 1);
+/// assert_eq!(results[0].content, "hello world");
+/// assert_eq!(results[1].line_number, 3);
+/// assert_eq!(results[1].content, "hello rust");
+/// ```
+///
+/// ## Searching files
+///
+/// ```no_run
+/// use searcher_cli_starter::{Matcher, search_lines};
+/// use std::fs::File;
+///
+/// let file = File::open("data.txt").unwrap();
+/// let matcher = Matcher::new("error", true, false).unwrap();
+/// let results = search_lines(file, &matcher).unwrap();
+///
+/// for result in results {
+///     println!("Line {}: {}", result.line_number, result.content);
+/// }
+/// ```
+///
+/// ## Case-insensitive search
+///
+/// ```
+/// use searcher_cli_starter::{Matcher, search_lines};
+/// use std::io::Cursor;
+///
+/// let input = "Rust\nRUST\nrust";
+/// let cursor = Cursor::new(input);
+///
+/// let matcher = Matcher::new("rust", true, false).unwrap();
+/// let results = search_lines(cursor, &matcher).unwrap();
+///
+/// assert_eq!(results.len(), 3);
+/// ```
+///
+/// ## Regex search
+///
+/// ```
+/// use searcher_cli_starter::{Matcher, search_lines};
+/// use std::io::Cursor;
+///
+/// let input = "rust\nrest\nrat";
+/// let cursor = Cursor::new(input);
+///
+/// let matcher = Matcher::new("r.st", false, true).unwrap();
+/// let results = search_lines(cursor, &matcher).unwrap();
+///
+/// assert_eq!(results.len(), 2);
+/// ```
+pub fn search_lines<R: Read>(reader: R, matcher: &Matcher) -> Result<Vec<SearchMatch>> {
+    let mut matches = Vec::new();
+    search_lines_with(reader, matcher, |search_match| {
+        matches.push(search_match);
+        ControlFlow::Continue(())
+    })?;
+    Ok(matches)
+}
+
+/// Searches through a reader like [`search_lines`], handing each match to
+/// `visit` as soon as it is found instead of collecting them.
+///
+/// Returning [`ControlFlow::Break`] from `visit` stops the search without
+/// reading the rest of the input, e.g. once enough matches have been seen.
+///
+/// # Errors
+///
+/// Returns an error if reading from the input source fails or a line is not
+/// valid UTF-8. Matches visited before the error are not undone.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::{Matcher, search_lines_with};
+/// use std::io::Cursor;
+/// use std::ops::ControlFlow;
+///
+/// let matcher = Matcher::new("error", false, false).unwrap();
+/// let mut first = None;
+/// search_lines_with(Cursor::new("ok\nerror 1\nerror 2\n"), &matcher, |search_match| {
+///     first = Some(search_match);
+///     ControlFlow::Break(())
+/// })
+/// .unwrap();
+///
+/// assert_eq!(first.unwrap().line_number, 2);
+/// ```
+pub fn search_lines_with<R, F>(reader: R, matcher: &Matcher, mut visit: F) -> Result<()>
+where
+    R: Read,
+    F: FnMut(SearchMatch) -> ControlFlow<()>,
+{
+    search_lines_ref(reader, matcher, |search_match| visit(search_match.into()))
+}
+
+/// Searches through a reader like [`search_lines_with`], but hands `visit`
+/// matches that borrow their content from the line buffer.
+///
+/// Lines are read in blocks by a [`LineReader`], so nothing is allocated
+/// per line or per match; `visit` decides what to copy, e.g. with
+/// [`SearchMatchRef::to_owned`]. Line endings are handled like
+/// [`search_lines`]: both `\n` and `\r\n` are stripped.
+///
+/// # Errors
+///
+/// Returns an error if reading from the input source fails or a line is not
+/// valid UTF-8.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::{Matcher, search_lines_ref};
+/// use std::io::Cursor;
+/// use std::ops::ControlFlow;
+///
+/// let matcher = Matcher::new("user=", false, false).unwrap();
+/// let mut users = Vec::new();
+/// search_lines_ref(Cursor::new("login user=amy\nidle\nlogout user=bob\n"), &matcher, |search_match| {
+///     // Copy only the part that is needed
+///     if let Some((_, user)) = search_match.content.split_once("user=") {
+///         users.push(user.to_string());
+///     }
+///     ControlFlow::Continue(())
+/// })
+/// .unwrap();
+///
+/// assert_eq!(users, ["amy", "bob"]);
+/// ```
+pub fn search_lines_ref<R, F>(reader: R, matcher: &Matcher, visit: F) -> Result<()>
+where
+    R: Read,
+    F: FnMut(SearchMatchRef<'_>) -> ControlFlow<()>,
+{
+    search_lines_in(&mut LineReader::new(reader, b'\n'), matcher, visit)
+}
+
+/// The loop behind [`search_lines_ref`], over the lines of `lines`.
+pub(crate) fn search_lines_in<R, F>(
+    lines: &mut LineReader<R>,
+    matcher: &Matcher,
+    mut visit: F,
+) -> Result<()>
+where
+    R: Read,
+    F: FnMut(SearchMatchRef<'_>) -> ControlFlow<()>,
+{
+    for_each_line(lines, |line_number, content| {
+        if matcher.is_match(content) {
+            visit(SearchMatchRef {
+                line_number,
+                content,
+            })
+        } else {
+            ControlFlow::Continue(())
+        }
+    })
+}
+
+/// Hands each line of `lines` to `visit` with its number, counted from 1,
+/// until `visit` returns [`ControlFlow::Break`]. Lines are split as
+/// [`search_lines`] splits them: both `\n` and `\r\n` are stripped.
+///
+/// # Errors
+///
+/// Returns an error if reading fails or a line is not valid UTF-8.
+pub(crate) fn for_each_line<R, F>(lines: &mut LineReader<R>, mut visit: F) -> Result<()>
+where
+    R: Read,
+    F: FnMut(usize, &str) -> ControlFlow<()>,
+{
+    let mut line_number = 0;
+
+    while let Some(line) = lines.next_line()? {
+        line_number += 1; // 1-based indexing
+        let line = LineTerminator::Crlf.strip(line);
+        let Ok(content) = std::str::from_utf8(line) else {
+            bail!("Line {} is not valid UTF-8", line_number);
+        };
+        if visit(line_number, content).is_break() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Searches through a reader line-by-line without requiring valid UTF-8.
+///
+/// Lines are read as raw bytes and matched with [`Matcher::is_match_bytes`],
+/// which makes this suitable for binary-ish logs and files in legacy
+/// encodings. Lines end as [`search_lines`] ends them: both `\n` and `\r\n`
+/// are removed, and any other bytes are kept as-is in the result. Use
+/// [`search_reader`] to choose a different line terminator.
+///
+/// # Arguments
+///
+/// * `reader` - Any type implementing Read (files, strings, stdin, etc.)
+/// * `matcher` - The Matcher to use for pattern matching
+///
+/// # Errors
+///
+/// Returns an error if reading from the input source fails. Invalid UTF-8 is
+/// never an error.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::{Matcher, search_lines_bytes};
+/// use std::io::Cursor;
+///
+/// let input: &[u8] = b"ok\n\xFF\xFE error\nfine";
+/// let matcher = Matcher::new("error", false, false).unwrap();
+/// let results = search_lines_bytes(Cursor::new(input), &matcher).unwrap();
+///
+/// assert_eq!(results.len(), 1);
+/// assert_eq!(results[0].line_number, 2);
+/// assert_eq!(results[0].content, b"\xFF\xFE error");
+/// ```
+pub fn search_lines_bytes<R: Read>(reader: R, matcher: &Matcher) -> Result<Vec<SearchMatchBytes>> {
+    let options = SearchOptions {
+        line_terminator: LineTerminator::Crlf,
+        ..SearchOptions::default()
+    };
+    search_reader(reader, matcher, &options)
+}
+
+/// Searches through a reader using the given [`SearchOptions`].
+///
+/// This is the general form of [`search_lines_bytes`]: the input is split on
+/// `options.line_terminator`, the terminator is removed from each line, and
+/// with [`LineTerminator::Crlf`] a trailing `\r` is stripped as well.
+///
+/// When `options.line_ranges` is non-empty, only lines inside those ranges
+/// are matched, and reading stops as soon as the last bounded range has been
+/// passed.
+///
+/// # Errors
+///
+/// Returns an error if reading from the input source fails.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::{LineTerminator, Matcher, SearchOptions, search_reader};
+/// use std::io::Cursor;
+///
+/// // Windows line endings
+/// let options = SearchOptions { line_terminator: LineTerminator::Crlf, ..Default::default() };
+/// let matcher = Matcher::new("done$", false, true).unwrap();
+/// let results = search_reader(Cursor::new("work\r\ndone\r\n"), &matcher, &options).unwrap();
+/// assert_eq!(results[0].content, b"done");
+///
+/// // NUL-separated records such as `find -print0` output
+/// let options = SearchOptions { line_terminator: LineTerminator::Nul, ..Default::default() };
+/// let matcher = Matcher::new(".rs", false, false).unwrap();
+/// let results = search_reader(Cursor::new("a.rs\0b.txt\0c.rs\0"), &matcher, &options).unwrap();
+/// assert_eq!(results.len(), 2);
+/// assert_eq!(results[1].line_number, 3);
+/// ```
+pub fn search_reader<R: Read>(
+    reader: R,
+    matcher: &Matcher,
+    options: &SearchOptions,
+) -> Result<Vec<SearchMatchBytes>> {
+    let mut matches = Vec::new();
+    search_reader_with(reader, matcher, options, |search_match| {
+        matches.push(search_match);
+        ControlFlow::Continue(())
+    })?;
+    Ok(matches)
+}
+
+/// Searches through a reader like [`search_reader`], handing each match to
+/// `visit` as soon as it is found instead of collecting them.
+///
+/// Returning [`ControlFlow::Break`] from `visit` stops the search without
+/// reading the rest of the input.
+///
+/// # Errors
+///
+/// Returns the errors of [`search_reader`]. Matches visited before the error
+/// are not undone.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::{Matcher, SearchOptions, search_reader_with};
+/// use std::ops::ControlFlow;
+///
+/// let matcher = Matcher::new("error", false, false).unwrap();
+/// let mut count = 0;
+/// search_reader_with(&b"error\nerror\nerror\n"[..], &matcher, &SearchOptions::default(), |_| {
+///     count += 1;
+///     if count == 2 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+/// })
+/// .unwrap();
+///
+/// assert_eq!(count, 2);
+/// ```
+pub fn search_reader_with<R, F>(
+    reader: R,
+    matcher: &Matcher,
+    options: &SearchOptions,
+    visit: F,
+) -> Result<()>
+where
+    R: Read,
+    F: FnMut(SearchMatchBytes) -> ControlFlow<()>,
+{
+    let mut lines = LineReader::new(reader, options.line_terminator.byte());
+    search_lexed(
+        &mut lines,
+        matcher,
+        options,
+        None,
+        options.deadline(),
+        visit,
+    )
+}
+
+/// Searches `reader` like [`search_reader`], but giving up at `deadline`
+/// instead of `options.timeout` after starting, for a search of one input
+/// in parts that must share the time limit.
+pub(crate) fn search_reader_until<R: Read>(
+    reader: R,
+    matcher: &Matcher,
+    options: &SearchOptions,
+    deadline: Option<Instant>,
+) -> Result<Vec<SearchMatchBytes>> {
+    let mut matches = Vec::new();
+    let mut lines = LineReader::new(reader, options.line_terminator.byte());
+    search_lexed(
+        &mut lines,
+        matcher,
+        options,
+        None,
+        deadline,
+        |search_match| {
+            matches.push(search_match);
+            ControlFlow::Continue(())
+        },
+    )?;
+    Ok(matches)
+}
+
+/// Searches `reader`, the contents of the file at `path`, with
+/// `options.scope` applied to it: only the given scope of each line is
+/// matched, and files in a language the lexer does not know are not searched
+/// at all.
+pub(crate) fn search_source<R: Read>(
+    path: &Path,
+    reader: R,
+    matcher: &Matcher,
+    options: &SearchOptions,
+) -> Result<Vec<SearchMatchBytes>> {
+    let mut matches = Vec::new();
+    let buffer = &mut Vec::new();
+    search_source_with(path, reader, matcher, options, buffer, |search_match| {
+        matches.push(search_match);
+        ControlFlow::Continue(())
+    })?;
+    Ok(matches)
+}
+
+/// Like [`search_source`], handing each match to `visit` as
+/// [`search_reader_with`] does. Lines are read into `buffer`, which is
+/// handed back for the next input afterwards.
+pub(crate) fn search_source_with<R, F>(
+    path: &Path,
+    reader: R,
+    matcher: &Matcher,
+    options: &SearchOptions,
+    buffer: &mut Vec<u8>,
+    visit: F,
+) -> Result<()>
+where
+    R: Read,
+    F: FnMut(SearchMatchBytes) -> ControlFlow<()>,
+{
+    let lexer = match options.scope {
+        None => None,
+        Some(scope) => match Language::from_path(path) {
+            Some(language) if scope != Scope::Text || language.is_markup() => {
+                Some(Lexer::new(language))
+            }
+            _ => return Ok(()),
+        },
+    };
+    let terminator = options.line_terminator.byte();
+    let mut lines = LineReader::with_buffer(reader, terminator, std::mem::take(buffer));
+    let result = search_lexed(
+        &mut lines,
+        matcher,
+        options,
+        lexer,
+        options.deadline(),
+        visit,
+    );
+    *buffer = lines.into_buffer();
+    result
+}
+
+/// The loop behind [`search_reader_with`], running each line of `lines`
+/// through `lexer` if there is one and giving up at `deadline`.
+fn search_lexed<R, F>(
+    lines: &mut LineReader<R>,
+    matcher: &Matcher,
+    options: &SearchOptions,
+    mut lexer: Option<Lexer>,
+    deadline: Option<Instant>,
+    mut visit: F,
+) -> Result<()>
+where
+    R: Read,
+    F: FnMut(SearchMatchBytes) -> ControlFlow<()>,
+{
+    let last_line = last_requested_line(&options.line_ranges);
+    let mut line_number = options.line_offset;
+    let dedupe = options.dedupe.as_ref().map(Dedupe::for_input);
+
+    loop {
+        if last_line.is_some_and(|last| line_number >= last) {
+            break;
+        }
+        options.check_cancelled()?;
+        options.check_deadline(deadline)?;
+        let Some(line) = lines.next_line()? else {
+            break;
+        };
+        line_number += 1;
+        if let Some(content) = check_line(line, line_number, matcher, options, lexer.as_mut())
+            && dedupe.as_ref().is_none_or(|dedupe| dedupe.insert(content))
+        {
+            let search_match = SearchMatchBytes {
+                line_number,
+                content: content.to_vec(),
+            };
+            if visit(search_match).is_break() {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Decides whether a line read by [`search_reader`] (or its async
+/// counterpart) is a match.
+///
+/// Lines outside `options.line_ranges` are rejected untouched. Otherwise the
+/// terminator (and `\r` for CRLF) is stripped from `line` before it goes
+/// through `options.filters`, the matcher, and `options.match_filters`; a
+/// matching line is returned without them as the match content.
+///
+/// The matcher sees the line as rewritten by `options.transforms`. With a
+/// `lexer` and `options.scope`, it only sees the line as masked by
+/// [`Lexer::mask`] (before the transforms). The lexer is given every line, including those
+/// outside the ranges, so that it knows where each one starts.
+pub(crate) fn check_line<'a>(
+    line: &'a [u8],
+    line_number: usize,
+    matcher: &Matcher,
+    options: &SearchOptions,
+    lexer: Option<&mut Lexer>,
+) -> Option<&'a [u8]> {
+    let line = line_content(line, options);
+    let haystack = match (lexer, options.scope) {
+        (Some(lexer), Some(scope)) => lexer.mask(line, scope),
+        _ => line,
+    };
+    if !options.line_ranges.is_empty()
+        && !options.line_ranges.iter().any(|r| r.contains(line_number))
+    {
+        return None;
+    }
+    let matched = options.filters.keep(line_number, line)
+        && matcher.is_match_bytes(&options.transforms.apply(haystack))
+        && options.match_filters.keep(line_number, line, matcher);
+    matched.then_some(line)
+}
+
+/// Strips the terminator (and `\r` for CRLF) from a line read with
+/// `options.line_terminator`.
+pub(crate) fn line_content<'a>(line: &'a [u8], options: &SearchOptions) -> &'a [u8] {
+    options.line_terminator.strip(line)
+}
+
+/// Reads `reader` like [`search_reader_with`], but hands every line to
+/// `visit`, with whether it matched, instead of only the matches. This is
+/// what `--passthru` prints, marking the matches within the whole input.
+///
+/// Lines are given without their terminator and with 1-based line numbers,
+/// shifted by `options.line_offset`.
+/// Lines outside `options.line_ranges` or dropped by a filter are visited
+/// as not matching, and reading does not stop after the last range.
+/// `options.timeout`, `scope`, and `dedupe` are ignored, since this is meant
+/// for streams that are never done.
+///
+/// # Errors
+///
+/// Returns an error if reading from `reader` fails, or [`Cancelled`] if
+/// `options.cancel` is cancelled. Lines visited before the error are not
+/// undone.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::{Matcher, SearchOptions, search_reader_passthru};
+/// use std::ops::ControlFlow;
+///
+/// let matcher = Matcher::new("ERROR", false, false).unwrap();
+/// let mut marked = String::new();
+/// search_reader_passthru(&b"ok\nERROR x\nok\n"[..], &matcher, &SearchOptions::default(),
+///     |_, line, matched| {
+///         marked.push(if matched { '*' } else { ' ' });
+///         marked.push_str(&String::from_utf8_lossy(line));
+///         marked.push('\n');
+///         ControlFlow::Continue(())
+///     },
+/// )
+/// .unwrap();
+///
+/// assert_eq!(marked, " ok\n*ERROR x\n ok\n");
+/// ```
+pub fn search_reader_passthru<R, F>(
+    reader: R,
+    matcher: &Matcher,
+    options: &SearchOptions,
+    mut visit: F,
+) -> Result<()>
+where
+    R: Read,
+    F: FnMut(usize, &[u8], bool) -> ControlFlow<()>,
+{
+    let mut lines = LineReader::new(reader, options.line_terminator.byte());
+    let mut line_number = options.line_offset;
+
+    loop {
+        options.check_cancelled()?;
+        let Some(line) = lines.next_line()? else {
+            break;
+        };
+        line_number += 1;
+        let matched = check_line(line, line_number, matcher, options, None).is_some();
+        if visit(line_number, line_content(line, options), matched).is_break() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Opens the file at `path` and searches it with [`search_reader`].
+///
+/// If `options.preprocessor` applies to `path`, its output is searched
+/// instead; see [`Preprocessor::search`]. With `options.scope`, only that
+/// scope of each line is matched, and files in languages [`scope`] does not
+/// know are skipped. With `options.byte_window`, only that part of the file
+/// is read.
+///
+/// # Errors
+///
+/// Returns an error naming the file if it cannot be opened or read, if it
+/// takes longer than `options.timeout` ([`TimedOut`]), if the preprocessor
+/// fails, or [`Cancelled`] if `options.cancel` is cancelled before or during
+/// the search.
+///
+/// # Examples
+///
+/// ```no_run
+/// use searcher_cli_starter::{Matcher, SearchOptions, search_path};
+///
+/// let matcher = Matcher::new("error", true, false).unwrap();
+/// let results = search_path("app.log".as_ref(), &matcher, &SearchOptions::default()).unwrap();
+/// println!("{} matching lines", results.len());
+/// ```
+pub fn search_path(
+    path: &Path,
+    matcher: &Matcher,
+    options: &SearchOptions,
+) -> Result<Vec<SearchMatchBytes>> {
+    search_input(&FileSource::new(path), matcher, options)
+}
+
+/// Whether the file at `path` has a matching line, searched as
+/// [`search_path`] would but stopping at the first match instead of reading
+/// the rest of the file.
+///
+/// A file that goes through `options.preprocessor` is searched to the end,
+/// so that the command does not fail with a broken pipe.
+///
+/// # Errors
+///
+/// Returns the errors of [`search_path`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use searcher_cli_starter::{Matcher, SearchOptions, path_has_match};
+///
+/// let matcher = Matcher::new("old_api(", false, false).unwrap();
+/// if path_has_match("src/main.rs".as_ref(), &matcher, &SearchOptions::default()).unwrap() {
+///     println!("src/main.rs still uses the old API");
+/// }
+/// ```
+pub fn path_has_match(path: &Path, matcher: &Matcher, options: &SearchOptions) -> Result<bool> {
+    let mut found = false;
+    search_path_with(path, matcher, options, |_| {
+        found = true;
+        ControlFlow::Break(())
+    })?;
+    Ok(found)
+}
+
+/// The first matching line of the file at `path`, searched as
+/// [`search_path`] would but stopping there instead of reading the rest of
+/// the file.
+///
+/// # Errors
+///
+/// Returns the errors of [`search_path`].
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::{Matcher, SearchOptions, first_match};
+///
+/// let path = std::env::temp_dir().join("first_match_example.log");
+/// std::fs::write(&path, "ok\nerror: disk\nerror: network\n").unwrap();
+///
+/// let matcher = Matcher::new("error", false, false).unwrap();
+/// let first = first_match(&path, &matcher, &SearchOptions::default()).unwrap().unwrap();
+/// assert_eq!(first.line_number, 2);
+/// assert_eq!(first.content, b"error: disk");
+/// # std::fs::remove_file(path).ok();
+/// ```
+pub fn first_match(
+    path: &Path,
+    matcher: &Matcher,
+    options: &SearchOptions,
+) -> Result<Option<SearchMatchBytes>> {
+    let mut first = None;
+    search_path_with(path, matcher, options, |search_match| {
+        first = Some(search_match);
+        ControlFlow::Break(())
+    })?;
+    Ok(first)
+}
+
+/// Searches the file at `path` like [`search_path`], handing each match to
+/// `visit` as soon as it is found as [`search_reader_with`] does.
+///
+/// Returning [`ControlFlow::Break`] from `visit` stops reading the file. A
+/// file that goes through `options.preprocessor` is searched to the end
+/// first, so that the command does not fail with a broken pipe.
+///
+/// # Errors
+///
+/// Returns the errors of [`search_path`]. Matches visited before the error
+/// are not undone.
+pub fn search_path_with<F>(
+    path: &Path,
+    matcher: &Matcher,
+    options: &SearchOptions,
+    visit: F,
+) -> Result<()>
+where
+    F: FnMut(SearchMatchBytes) -> ControlFlow<()>,
+{
+    search_input_with(&FileSource::new(path), matcher, options, visit)
+}
+
+/// Searches the file at `path` like [`search_path`], but from its last line
+/// to its first, returning the matches in that order.
+///
+/// See [`search_path_reverse_with`].
+///
+/// # Errors
+///
+/// Returns the errors of [`search_path_reverse_with`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use searcher_cli_starter::{Matcher, SearchOptions, search_path_reverse};
+///
+/// let matcher = Matcher::new("error", true, false).unwrap();
+/// let results = search_path_reverse("app.log".as_ref(), &matcher, &SearchOptions::default()).unwrap();
+/// if let Some(latest) = results.first() {
+///     println!("last error on line {}", latest.line_number);
+/// }
+/// ```
+pub fn search_path_reverse(
+    path: &Path,
+    matcher: &Matcher,
+    options: &SearchOptions,
+) -> Result<Vec<SearchMatchBytes>> {
+    let mut matches = Vec::new();
+    search_path_reverse_with(path, matcher, options, |search_match| {
+        matches.push(search_match);
+        ControlFlow::Continue(())
+    })?;
+    Ok(matches)
+}
+
+/// The last matching line of the file at `path`, found by reading it
+/// backwards as [`search_path_reverse_with`] does and stopping at the first
+/// match. With `options.skip_line_numbers`, and no line ranges or filters,
+/// only the end of a large log is read for its latest entry; otherwise the
+/// lines before the match are counted for its line number.
+///
+/// # Errors
+///
+/// Returns the errors of [`search_path_reverse_with`].
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::{Matcher, SearchOptions, last_match};
+///
+/// let path = std::env::temp_dir().join("last_match_example.log");
+/// std::fs::write(&path, "error: disk\nerror: network\nok\n").unwrap();
+///
+/// let matcher = Matcher::new("error", false, false).unwrap();
+/// let last = last_match(&path, &matcher, &SearchOptions::default()).unwrap().unwrap();
+/// assert_eq!(last.line_number, 2);
+/// assert_eq!(last.content, b"error: network");
+/// # std::fs::remove_file(path).ok();
+/// ```
+pub fn last_match(
+    path: &Path,
+    matcher: &Matcher,
+    options: &SearchOptions,
+) -> Result<Option<SearchMatchBytes>> {
+    let mut last = None;
+    search_path_reverse_with(path, matcher, options, |search_match| {
+        last = Some(search_match);
+        ControlFlow::Break(())
+    })?;
+    Ok(last)
+}
+
+/// Searches the file at `path` from its last line to its first, handing
+/// each match to `visit` as soon as it is found, e.g. to find the most
+/// recent entries of a log.
+///
+/// The file is read backwards a block at a time, so only the lines around
+/// the matches are ever in memory, and returning [`ControlFlow::Break`] from
+/// `visit` stops reading before the start of the file. For matches to have
+/// their usual line numbers, the lines before the first match found, the
+/// last in the file, are counted without being kept; with
+/// `options.line_ranges` or filters, which judge lines by their number, all
+/// of them are counted before the search starts. With
+/// `options.skip_line_numbers` and neither of those, nothing is counted and
+/// matches are numbered 0. Lines appended to the file while it is searched
+/// are left out.
+///
+/// Lines go through `options` as with [`search_path`], except that
+/// `options.preprocessor`, `scope`, and `byte_window` are not used: the file
+/// is always read as it is, in full. With `options.dedupe`, the last of a
+/// set of repeated lines is the one reported.
+///
+/// # Errors
+///
+/// Returns the errors of [`search_path`].
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::{Matcher, SearchOptions, search_path_reverse_with};
+/// use std::ops::ControlFlow;
+///
+/// let path = std::env::temp_dir().join("reverse_example.log");
+/// std::fs::write(&path, "error: disk\nok\nerror: network\nok\n").unwrap();
+///
+/// let matcher = Matcher::new("error", false, false).unwrap();
+/// let mut last = None;
+/// search_path_reverse_with(&path, &matcher, &SearchOptions::default(), |search_match| {
+///     last = Some(search_match);
+///     ControlFlow::Break(())
+/// })
+/// .unwrap();
+///
+/// let last = last.unwrap();
+/// assert_eq!(last.line_number, 3);
+/// assert_eq!(last.content, b"error: network");
+/// # std::fs::remove_file(path).ok();
+/// ```
+pub fn search_path_reverse_with<F>(
+    path: &Path,
+    matcher: &Matcher,
+    options: &SearchOptions,
+    visit: F,
+) -> Result<()>
+where
+    F: FnMut(SearchMatchBytes) -> ControlFlow<()>,
+{
+    options.check_cancelled()?;
+    let file = File::open(path).with_context(|| FileContext::read(path))?;
+    search_file_reverse(file, matcher, options, visit).map_err(|error| file_error(path, error))
+}
+
+/// The loop behind [`search_path_reverse_with`].
+fn search_file_reverse<R, F>(
+    mut input: R,
+    matcher: &Matcher,
+    options: &SearchOptions,
+    mut visit: F,
+) -> Result<()>
+where
+    R: Read + Seek,
+    F: FnMut(SearchMatchBytes) -> ControlFlow<()>,
+{
+    let terminator = options.line_terminator.byte();
+    let deadline = options.deadline();
+    let dedupe = options.dedupe.as_ref().map(Dedupe::for_input);
+    // Taken once, so the lines counted are the lines searched
+    let len = input.seek(SeekFrom::End(0))?;
+    let mut lines = ReverseLineReader::ending_at(input, terminator, len);
+    // The number of the line read last, once known: line ranges and filters
+    // need it for every line, else it is only counted at the first match
+    let numbered = !options.line_ranges.is_empty()
+        || !options.filters.is_empty()
+        || !options.match_filters.is_empty();
+    let mut line_number = None;
+    if numbered {
+        let lines_before = count_lines_before(lines.get_mut(), len, terminator)?;
+        line_number = Some(options.line_offset + lines_before + 1);
+    }
+
+    loop {
+        options.check_cancelled()?;
+        options.check_deadline(deadline)?;
+        let Some(line) = lines.prev_line()? else {
+            break;
+        };
+        if let Some(number) = &mut line_number {
+            *number -= 1;
+        }
+        let Some(content) = check_line(line, line_number.unwrap_or(0), matcher, options, None)
+            .filter(|content| dedupe.as_ref().is_none_or(|dedupe| dedupe.insert(content)))
+            .map(<[u8]>::to_vec)
+        else {
+            continue;
+        };
+        let line_number = match line_number {
+            Some(number) => number,
+            None => {
+                if options.skip_line_numbers {
+                    0
+                } else {
+                    let offset = lines.offset();
+                    let number = options.line_offset
+                        + count_lines_before(lines.get_mut(), offset, terminator)?
+                        + 1;
+                    *line_number.insert(number)
+                }
+            }
+        };
+        if visit(SearchMatchBytes {
+            line_number,
+            content,
+        })
+        .is_break()
+        {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Counts the lines of `input` before `offset`, where a line starts.
+fn count_lines_before<R: Read + Seek>(
+    input: &mut R,
+    offset: u64,
+    terminator: u8,
+) -> io::Result<usize> {
+    input.seek(SeekFrom::Start(0))?;
+    count_lines(input.take(offset), terminator)
+}
+
+/// Opens the file at `path` for searching, at the start of
+/// `options.byte_window` if there is one.
+pub(crate) fn open_window(path: &Path, options: &SearchOptions) -> Result<Box<dyn Read>> {
+    let file = File::open(path).with_context(|| FileContext::read(path))?;
+    match options.byte_window {
+        None => Ok(Box::new(file)),
+        Some(window) => window
+            .open(file, options.line_terminator.byte())
+            .with_context(|| FileContext::read(path)),
+    }
+}
+
+/// Names the file at `path` in an error from searching it.
+pub(crate) fn file_error(path: &Path, error: anyhow::Error) -> anyhow::Error {
+    if error.is::<TimedOut>() {
+        error.context(FileContext::timed_out(path))
+    } else {
+        error.context(FileContext::read(path))
+    }
+}
+
+/// Returns the last line any range can match, or `None` if reading must
+/// continue to the end of the input.
+pub(crate) fn last_requested_line(ranges: &[LineRange]) -> Option<usize> {
+    if ranges.is_empty() {
+        return None;
+    }
+    ranges
+        .iter()
+        .map(|range| range.end)
+        .try_fold(0, |last, end| end.map(|end| last.max(end)))
+}
+
+/// Searches an in-memory string for lines matching the pattern.
 ///
-/// This function processes input line-by-line using buffered I/O for efficiency.
-/// It works with any type implementing the `Read` trait, including files, strings,
-/// and standard input.
+/// This is a convenience wrapper for text that is already in memory. It skips
+/// the `Cursor` + `Read` setup and the buffered I/O layer entirely, splitting
+/// the haystack directly with [`str::lines`]. Line endings are handled the same
+/// way as [`search_lines`]: both `\n` and `\r\n` are stripped.
 ///
 /// # Arguments
 ///
-/// * `reader` - Any type implementing Read (files, strings, stdin, etc.)
+/// * `haystack` - The text to search
 /// * `matcher` - The Matcher to use for pattern matching
 ///
 /// # Returns
 ///
-/// Returns a Result containing a Vec of SearchMatch structs for all matching lines,
-/// or an error if reading fails.
-///
-/// # Errors
-///
-/// Returns an error if:
-/// - Reading from the input source fails
-/// - A line contains invalid UTF-8
-///
-/// # Performance
-///
-/// - Time complexity: O(n × m) where n = number of lines, m = average line length
-/// - Space complexity: O(k × m) where k = number of matches
-/// - Streams input line-by-line without loading entire file into memory
+/// Returns a Vec of SearchMatch structs for all matching lines. Searching a
+/// string cannot fail, so no `Result` is involved.
 ///
 /// # Examples
 ///
-/// ## Searching in-memory strings
-///
 /// ```
-/// use searcher_cli_starter::{Matcher, search_lines};
-/// use std::io::Cursor;
-///
-/// let input = "hello world\nrust is great\nhello rust";
-/// let cursor = Cursor::new(input);
+/// use searcher_cli_starter::{Matcher, search_str};
 ///
 /// let matcher = Matcher::new("hello", false, false).unwrap();
-/// let results = search_lines(cursor, &matcher).unwrap();
+/// let results = search_str("hello world\nrust is great\nhello rust", &matcher);
 ///
 /// assert_eq!(results.len(), 2);
-/// assert_eq!(results[0].line_number, 1);
-/// assert_eq!(results[0].content, "hello world");
 /// assert_eq!(results[1].line_number, 3);
 /// assert_eq!(results[1].content, "hello rust");
 /// ```
+pub fn search_str(haystack: &str, matcher: &Matcher) -> Vec<SearchMatch> {
+    haystack
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| matcher.is_match(line))
+        .map(|(line_number, line)| SearchMatch {
+            line_number: line_number + 1, // 1-based indexing
+            content: line.to_string(),
+        })
+        .collect()
+}
+
+/// Searches an in-memory byte slice for lines matching the pattern.
 ///
-/// ## Searching files
-///
-/// ```no_run
-/// use searcher_cli_starter::{Matcher, search_lines};
-/// use std::fs::File;
-///
-/// let file = File::open("data.txt").unwrap();
-/// let matcher = Matcher::new("error", true, false).unwrap();
-/// let results = search_lines(file, &matcher).unwrap();
-///
-/// for result in results {
-///     println!("Line {}: {}", result.line_number, result.content);
-/// }
-/// ```
-///
-/// ## Case-insensitive search
+/// Like [`search_str`], this avoids the `Read` machinery for data that is
+/// already in memory (for example a memory-mapped file or a network buffer).
 ///
-/// ```
-/// use searcher_cli_starter::{Matcher, search_lines};
-/// use std::io::Cursor;
+/// # Arguments
 ///
-/// let input = "Rust\nRUST\nrust";
-/// let cursor = Cursor::new(input);
+/// * `haystack` - The bytes to search
+/// * `matcher` - The Matcher to use for pattern matching
 ///
-/// let matcher = Matcher::new("rust", true, false).unwrap();
-/// let results = search_lines(cursor, &matcher).unwrap();
+/// # Errors
 ///
-/// assert_eq!(results.len(), 3);
-/// ```
+/// Returns an error if `haystack` is not valid UTF-8, matching the behavior
+/// of [`search_lines`].
 ///
-/// ## Regex search
+/// # Examples
 ///
 /// ```
-/// use searcher_cli_starter::{Matcher, search_lines};
-/// use std::io::Cursor;
+/// use searcher_cli_starter::{Matcher, search_bytes};
 ///
-/// let input = "rust\nrest\nrat";
-/// let cursor = Cursor::new(input);
+/// let matcher = Matcher::new("rust", false, false).unwrap();
+/// let results = search_bytes(b"hello world\nrust is great", &matcher).unwrap();
 ///
-/// let matcher = Matcher::new("r.st", false, true).unwrap();
-/// let results = search_lines(cursor, &matcher).unwrap();
+/// assert_eq!(results.len(), 1);
+/// assert_eq!(results[0].line_number, 2);
 ///
-/// assert_eq!(results.len(), 2);
+/// assert!(search_bytes(b"invalid \xFF utf-8", &matcher).is_err());
 /// ```
-pub fn search_lines<R: Read>(reader: R, matcher: &Matcher) -> Result<Vec<SearchMatch>> {
-    let buf_reader = BufReader::new(reader);
-    let mut matches = Vec::new();
-
-    for (line_number, line) in buf_reader.lines().enumerate() {
-        let content = line?;
-        if matcher.is_match(&content) {
-            matches.push(SearchMatch {
-                line_number: line_number + 1, // 1-based indexing
-                content,
-            });
-        }
-    }
-
-    Ok(matches)
+pub fn search_bytes(haystack: &[u8], matcher: &Matcher) -> Result<Vec<SearchMatch>> {
+    let text = std::str::from_utf8(haystack).context("Input is not valid UTF-8")?;
+    Ok(search_str(text, matcher))
 }
 
 #[cfg(test)]
@@ -382,6 +2251,39 @@ mod tests {
         assert_eq!(results.len(), 0);
     }
 
+    #[test]
+    fn test_search_lines_with_stops_reading_on_break() {
+        // The invalid UTF-8 on line 3 would fail the search if it were read
+        let input: &[u8] = b"skip\nhit\n\xFF\nhit\n";
+        let matcher = Matcher::new("hit", false, false).unwrap();
+
+        let mut visited = Vec::new();
+        search_lines_with(input, &matcher, |search_match| {
+            visited.push(search_match.line_number);
+            ControlFlow::Break(())
+        })
+        .unwrap();
+        assert_eq!(visited, [2]);
+
+        let result = search_lines_with(input, &matcher, |_| ControlFlow::Continue(()));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_search_lines_ref_borrows_each_line() {
+        let matcher = Matcher::new("b", false, false).unwrap();
+        let mut copied = Vec::new();
+        search_lines_ref(&b"ab\r\nc\nb"[..], &matcher, |search_match| {
+            copied.push(search_match.to_owned());
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+
+        assert_eq!(copied, search_lines(&b"ab\r\nc\nb"[..], &matcher).unwrap());
+        assert_eq!(copied[0].content, "ab");
+        assert_eq!(copied[1].line_number, 3);
+    }
+
     #[test]
     fn test_search_case_sensitive() {
         let input = "Hello World\nhello world";
@@ -582,6 +2484,61 @@ mod tests {
         assert_eq!(results.len(), 3);
     }
 
+    #[test]
+    fn test_regex_prefilter_picks_longest_required_literal() {
+        let needle = |pattern: &str| prefilter(pattern).map(|finder| finder.needle().to_vec());
+
+        assert_eq!(
+            needle(r"user=\w+ timeout after \d+ms").unwrap(),
+            b" timeout after "
+        );
+        assert_eq!(needle(r"(?:abc)+d").unwrap(), b"abc");
+        assert!(needle(r"\d+").is_none());
+        assert!(needle(r"\d{4}ms").is_none());
+        assert!(needle("error|warn").is_none());
+        assert!(needle("(?i)error").is_none());
+    }
+
+    #[test]
+    fn test_regex_prefilter_does_not_change_results() {
+        let lines = [
+            "timeout 5ms",
+            "TIMEOUT 5ms",
+            "timeout",
+            "slow 5ms",
+            "x timeout 12ms y",
+        ];
+        for pattern in [r"timeout \d+ms", r"(?i)timeout \d+ms", r"^\w+ \d", "o{0}ut"] {
+            let matcher = Matcher::new(pattern, false, true).unwrap();
+            let regex = Regex::new(pattern).unwrap();
+            for line in lines {
+                assert_eq!(
+                    matcher.is_match(line),
+                    regex.is_match(line),
+                    "{pattern} {line}"
+                );
+                assert_eq!(
+                    matcher.is_match_bytes(line.as_bytes()),
+                    regex.is_match(line)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_matcher_is_send_sync_and_clones_without_recompiling() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Matcher>();
+
+        let matcher: Matcher = query::CompositeMatcher::parse("err.r AND NOT retry", true, true)
+            .unwrap()
+            .into();
+        let copy = matcher.clone_compiled();
+        for line in ["ERROR", "error retry", "retry", "errxr"] {
+            assert_eq!(copy.is_match(line), matcher.is_match(line), "{line}");
+        }
+    }
+
     #[test]
     fn test_invalid_regex_returns_error() {
         let result = Matcher::new("[unclosed", false, true);
@@ -601,4 +2558,561 @@ mod tests {
         assert_eq!(results[1].line_number, 2);
         assert_eq!(results[2].line_number, 3);
     }
+
+    // In-memory search tests
+    #[test]
+    fn test_search_str_matches_search_lines() {
+        let input = "alpha\nbeta\r\nalphabet\n\nalpha";
+        let matcher = Matcher::new("alpha", false, false).unwrap();
+
+        let from_str = search_str(input, &matcher);
+        let from_reader = search_lines(Cursor::new(input), &matcher).unwrap();
+
+        assert_eq!(from_str, from_reader);
+        assert_eq!(from_str.len(), 3);
+        assert_eq!(from_str[2].line_number, 5);
+    }
+
+    #[test]
+    fn test_search_str_strips_crlf() {
+        let matcher = Matcher::new("end$", false, true).unwrap();
+        let results = search_str("line end\r\nother", &matcher);
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].content, "line end");
+    }
+
+    #[test]
+    fn test_search_bytes_valid_utf8() {
+        let matcher = Matcher::new("café", true, false).unwrap();
+        let results = search_bytes("menu\nCAFÉ au lait".as_bytes(), &matcher).unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line_number, 2);
+    }
+
+    #[test]
+    fn test_search_bytes_invalid_utf8_returns_error() {
+        let matcher = Matcher::new("x", false, false).unwrap();
+        assert!(search_bytes(b"\xC3\x28", &matcher).is_err());
+    }
+
+    // Byte-oriented search tests
+    #[test]
+    fn test_search_lines_bytes_invalid_utf8() {
+        let input: &[u8] = b"first\nbad \xFF match\nmatch again\n";
+        let matcher = Matcher::new("match", false, false).unwrap();
+        let results = search_lines_bytes(Cursor::new(input), &matcher).unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].line_number, 2);
+        assert_eq!(results[0].content, b"bad \xFF match");
+        assert_eq!(results[1].line_number, 3);
+        assert_eq!(results[1].content, b"match again");
+    }
+
+    #[test]
+    fn test_search_lines_bytes_regex() {
+        let input: &[u8] = b"\x00id=17\x00\nno id\nid=x";
+        let matcher = Matcher::new(r"id=\d+", false, true).unwrap();
+        let results = search_lines_bytes(Cursor::new(input), &matcher).unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line_number, 1);
+    }
+
+    #[test]
+    fn test_search_lines_bytes_ignore_case() {
+        let input: &[u8] = b"\xFFERROR\nerror\nok";
+        let matcher = Matcher::new("Error", true, false).unwrap();
+        let results = search_lines_bytes(Cursor::new(input), &matcher).unwrap();
+
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn test_search_lines_bytes_agrees_with_search_lines() {
+        let input = "rust\r\nRust\nrusty\r\ncrust";
+        for pattern in ["^rust", "t$"] {
+            let matcher = Matcher::new(pattern, true, true).unwrap();
+
+            let text = search_lines(Cursor::new(input), &matcher).unwrap();
+            let bytes = search_lines_bytes(Cursor::new(input), &matcher).unwrap();
+
+            assert_eq!(text.len(), bytes.len());
+            for (t, b) in text.iter().zip(&bytes) {
+                assert_eq!(t.line_number, b.line_number);
+                assert_eq!(t.content.as_bytes(), b.content.as_slice());
+            }
+        }
+    }
+
+    // Line terminator tests
+    #[test]
+    fn test_search_reader_lf_keeps_carriage_return() {
+        let matcher = Matcher::new("line", false, false).unwrap();
+        let results =
+            search_reader(Cursor::new("line\r\n"), &matcher, &SearchOptions::default()).unwrap();
+
+        assert_eq!(results[0].content, b"line\r");
+    }
+
+    #[test]
+    fn test_search_reader_crlf_strips_carriage_return() {
+        let options = SearchOptions {
+            line_terminator: LineTerminator::Crlf,
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("line", false, false).unwrap();
+        let results =
+            search_reader(Cursor::new("line one\r\nline two\n"), &matcher, &options).unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].content, b"line one");
+        assert_eq!(results[1].content, b"line two");
+    }
+
+    #[test]
+    fn test_search_reader_nul_records() {
+        let options = SearchOptions {
+            line_terminator: LineTerminator::Nul,
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("two", false, false).unwrap();
+        let results =
+            search_reader(Cursor::new("one\0two\nlines\0three"), &matcher, &options).unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line_number, 2);
+        assert_eq!(results[0].content, b"two\nlines");
+    }
+
+    // Composite matcher tests
+    #[test]
+    fn test_search_with_composite_matcher() {
+        let input = "error: disk full\nerror: retrying\ninfo: ok";
+        let composite = CompositeMatcher::parse("error AND NOT retry", false, false).unwrap();
+        let matcher = Matcher::from(composite);
+        let results = search_lines(Cursor::new(input), &matcher).unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line_number, 1);
+    }
+
+    // Capture group tests
+    #[test]
+    fn test_captures_numbered_and_named() {
+        let matcher = Matcher::new(r"(?P<level>[A-Z]+) (\d+)", false, true).unwrap();
+        let captures = matcher.captures("ERROR 500");
+
+        assert_eq!(
+            captures,
+            vec![vec![
+                CaptureGroup {
+                    index: 1,
+                    name: Some("level".to_string()),
+                    value: Some("ERROR".to_string()),
+                },
+                CaptureGroup {
+                    index: 2,
+                    name: None,
+                    value: Some("500".to_string()),
+                },
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_captures_optional_group() {
+        let matcher = Matcher::new(r"a(b)?", false, true).unwrap();
+        let captures = matcher.captures("a");
+
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0][0].value, None);
+    }
+
+    #[test]
+    fn test_captures_literal_is_empty() {
+        let matcher = Matcher::new("(x)", false, false).unwrap();
+        assert!(matcher.captures("(x)").is_empty());
+    }
+
+    #[test]
+    fn test_match_ranges() {
+        let matcher = Matcher::new("aa", false, false).unwrap();
+        assert_eq!(matcher.match_ranges(b"aaa aa"), [0..2, 4..6]);
+
+        // Non-ASCII lines keep the offsets of the original bytes
+        let matcher = Matcher::new("ÉTÉ", true, false).unwrap();
+        let ranges = matcher.match_ranges("à été".as_bytes());
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0], 3..8);
+
+        let matcher = Matcher::from(CompositeMatcher::parse("a AND b", false, false).unwrap());
+        assert!(matcher.match_ranges(b"a b").is_empty());
+    }
+
+    #[test]
+    fn test_captures_bytes_invalid_utf8() {
+        let matcher = Matcher::new(r"key=(\w+)", false, true).unwrap();
+        let captures = matcher.captures_bytes(b"\xFF key=ab \xFE key=cd");
+
+        assert_eq!(captures.len(), 2);
+        assert_eq!(captures[1][0].value.as_deref(), Some("cd"));
+    }
+
+    // Line range tests
+    #[test]
+    fn test_line_range_parsing() {
+        assert_eq!(
+            "7".parse::<LineRange>().unwrap(),
+            LineRange {
+                start: 7,
+                end: Some(7)
+            }
+        );
+        assert_eq!(
+            "2-4".parse::<LineRange>().unwrap(),
+            LineRange {
+                start: 2,
+                end: Some(4)
+            }
+        );
+        assert_eq!(
+            "3-".parse::<LineRange>().unwrap(),
+            LineRange {
+                start: 3,
+                end: None
+            }
+        );
+        for invalid in ["", "0-3", "a-b", "4-2", "-5"] {
+            assert!(invalid.parse::<LineRange>().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_line_range_display_round_trips() {
+        for text in ["7", "2-4", "3-"] {
+            assert_eq!(text.parse::<LineRange>().unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn test_search_reader_line_ranges() {
+        let input = "hit 1\nhit 2\nhit 3\nhit 4\nhit 5\nhit 6";
+        let options = SearchOptions {
+            line_ranges: vec!["2-3".parse().unwrap(), "5".parse().unwrap()],
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("hit", false, false).unwrap();
+        let results = search_reader(Cursor::new(input), &matcher, &options).unwrap();
+
+        let lines: Vec<usize> = results.iter().map(|m| m.line_number).collect();
+        assert_eq!(lines, vec![2, 3, 5]);
+    }
+
+    #[test]
+    fn test_search_reader_stops_after_last_range() {
+        // The reader fails if anything past line 2 is requested
+        struct FailAfter<'a>(&'a [u8]);
+        impl Read for FailAfter<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.0.is_empty() {
+                    return Err(std::io::Error::other("read past requested range"));
+                }
+                let n = self.0.len().min(buf.len()).min(1);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+
+        let options = SearchOptions {
+            line_ranges: vec!["1-2".parse().unwrap()],
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let results = search_reader(FailAfter(b"x\nx\n"), &matcher, &options).unwrap();
+
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn test_search_reader_open_range_reads_to_end() {
+        let options = SearchOptions {
+            line_ranges: vec!["2-".parse().unwrap()],
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let results = search_reader(Cursor::new("x\nx\nx\nx"), &matcher, &options).unwrap();
+
+        assert_eq!(results.len(), 3);
+    }
+
+    #[test]
+    fn test_search_reader_line_offset() {
+        let options = SearchOptions {
+            line_offset: 1000,
+            line_ranges: vec!["1002-".parse().unwrap()],
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let results = search_reader(Cursor::new("x\ny\nx\nx"), &matcher, &options).unwrap();
+
+        let lines: Vec<usize> = results.iter().map(|m| m.line_number).collect();
+        assert_eq!(lines, vec![1003, 1004]);
+    }
+
+    #[test]
+    fn test_search_source_scope_lexes_lines_outside_ranges() {
+        let input = "/* x\nx */ x\nx // x\n";
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let options = SearchOptions {
+            line_ranges: vec!["2-".parse().unwrap()],
+            scope: Some(Scope::Code),
+            ..SearchOptions::default()
+        };
+        // Line 1 is skipped, but still opens the comment line 2 starts in
+        let results = search_source(Path::new("a.rs"), input.as_bytes(), &matcher, &options);
+        let lines: Vec<usize> = results.unwrap().iter().map(|m| m.line_number).collect();
+        assert_eq!(lines, [2, 3]);
+
+        let options = SearchOptions {
+            scope: Some(Scope::Comments),
+            ..options
+        };
+        let results = search_source(Path::new("a.rs"), input.as_bytes(), &matcher, &options);
+        assert_eq!(results.unwrap().len(), 2);
+        // No syntax is known for `.txt`
+        let results = search_source(Path::new("a.txt"), input.as_bytes(), &matcher, &options);
+        assert!(results.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_path_has_match() {
+        let path = Path::new("tests/fixtures/sample.txt");
+        let options = SearchOptions::default();
+        let matcher = Matcher::new("Rust", false, false).unwrap();
+        assert!(path_has_match(path, &matcher, &options).unwrap());
+
+        let matcher = Matcher::new("Python", false, false).unwrap();
+        assert!(!path_has_match(path, &matcher, &options).unwrap());
+
+        let error = path_has_match(Path::new("missing.txt"), &matcher, &options).unwrap_err();
+        assert_eq!(error.to_string(), "Could not read file `missing.txt`");
+    }
+
+    #[test]
+    fn test_search_path_reverse_agrees_with_search_path() {
+        let path = Path::new("tests/fixtures/sample.txt");
+        let matcher = Matcher::new("e", false, false).unwrap();
+        let options = SearchOptions {
+            line_ranges: vec!["2-5".parse().unwrap()],
+            line_offset: 1,
+            ..SearchOptions::default()
+        };
+        let mut forward = search_path(path, &matcher, &options).unwrap();
+        forward.reverse();
+        assert!(forward.len() > 1);
+        assert_eq!(
+            search_path_reverse(path, &matcher, &options).unwrap(),
+            forward
+        );
+
+        // Without line ranges, lines are only counted from the first match
+        let options = SearchOptions {
+            line_offset: 1,
+            ..SearchOptions::default()
+        };
+        let mut forward = search_path(path, &matcher, &options).unwrap();
+        forward.reverse();
+        assert_eq!(
+            search_path_reverse(path, &matcher, &options).unwrap(),
+            forward
+        );
+
+        let error = search_path_reverse(Path::new("missing.txt"), &matcher, &options).unwrap_err();
+        assert_eq!(error.to_string(), "Could not read file `missing.txt`");
+    }
+
+    /// Fails any read that starts before `from`.
+    struct ReadsFrom {
+        input: Cursor<Vec<u8>>,
+        from: u64,
+    }
+
+    impl Read for ReadsFrom {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            if self.input.position() < self.from {
+                return Err(io::Error::other("read before the end"));
+            }
+            self.input.read(buffer)
+        }
+    }
+
+    impl Seek for ReadsFrom {
+        fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+            self.input.seek(position)
+        }
+    }
+
+    #[test]
+    fn test_last_match_without_line_numbers_reads_only_the_end() {
+        let mut log = "ok\n".repeat(500_000);
+        log.push_str("error: disk\nok\n");
+        let input = || ReadsFrom {
+            from: log.len() as u64 - 200_000,
+            input: Cursor::new(log.clone().into_bytes()),
+        };
+        let matcher = Matcher::new("error", false, false).unwrap();
+        let last = |options: &SearchOptions| {
+            let mut last = None;
+            search_file_reverse(input(), &matcher, options, |search_match| {
+                last = Some(search_match);
+                ControlFlow::Break(())
+            })
+            .map(|()| last)
+        };
+
+        let options = SearchOptions {
+            skip_line_numbers: true,
+            ..SearchOptions::default()
+        };
+        let found = last(&options).unwrap().unwrap();
+        assert_eq!(found.content, b"error: disk");
+        assert_eq!(found.line_number, 0);
+
+        // Numbering the match means reading every line before it
+        assert!(last(&SearchOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_search_path_byte_window() {
+        let path = std::env::temp_dir().join("searcher_lib_byte_window.txt");
+        std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        let matcher = Matcher::new("", false, false).unwrap();
+        let lines = |window| {
+            let options = SearchOptions {
+                byte_window: Some(window),
+                ..SearchOptions::default()
+            };
+            search_path(&path, &matcher, &options)
+                .unwrap()
+                .into_iter()
+                .map(|m| String::from_utf8(m.content).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(lines(ByteWindow::Head(6)), ["one", "tw"]);
+        assert_eq!(lines(ByteWindow::Head(0)), Vec::<String>::new());
+        // The tail starts right at `three`, so it is kept whole
+        assert_eq!(lines(ByteWindow::Tail(6)), ["three"]);
+        assert_eq!(lines(ByteWindow::Tail(8)), ["three"]);
+        assert_eq!(lines(ByteWindow::Tail(10)), ["two", "three"]);
+        assert_eq!(lines(ByteWindow::Tail(100)), ["one", "two", "three"]);
+        std::fs::remove_file(path).ok();
+    }
+
+    // Serde tests
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_search_options_from_json() {
+        let options: SearchOptions =
+            serde_json::from_str(r#"{"line_terminator": "crlf", "line_ranges": ["10-20", "30-"]}"#)
+                .unwrap();
+
+        assert_eq!(options.line_terminator, LineTerminator::Crlf);
+        assert_eq!(
+            options.line_ranges,
+            ["10-20".parse().unwrap(), "30-".parse().unwrap()]
+        );
+        assert_eq!(
+            serde_json::from_str::<SearchOptions>("{}").unwrap(),
+            SearchOptions::default()
+        );
+        assert!(serde_json::from_str::<SearchOptions>(r#"{"line_ranges": ["5-2"]}"#).is_err());
+
+        let options: SearchOptions = serde_json::from_str(r#"{"timeout": 2.5}"#).unwrap();
+        assert_eq!(options.timeout, Some(Duration::from_millis(2500)));
+        assert_eq!(serde_json::to_value(&options).unwrap()["timeout"], 2.5);
+        assert!(serde_json::from_str::<SearchOptions>(r#"{"timeout": -1}"#).is_err());
+
+        let options: SearchOptions =
+            serde_json::from_str(r#"{"byte_window": {"tail": 1024}}"#).unwrap();
+        assert_eq!(options.byte_window, Some(ByteWindow::Tail(1024)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_search_match_round_trip() {
+        let search_match = SearchMatch {
+            line_number: 3,
+            content: "error here".to_string(),
+        };
+        let json = serde_json::to_string(&search_match).unwrap();
+
+        assert_eq!(json, r#"{"line_number":3,"content":"error here"}"#);
+        assert_eq!(
+            serde_json::from_str::<SearchMatch>(&json).unwrap(),
+            search_match
+        );
+    }
+
+    // Cancellation tests
+    #[test]
+    fn test_search_path_times_out() {
+        let options = SearchOptions {
+            timeout: Some(Duration::ZERO),
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("Rust", false, false).unwrap();
+        let error =
+            search_path(Path::new("tests/fixtures/sample.txt"), &matcher, &options).unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&TimedOut {
+                limit: Duration::ZERO
+            })
+        );
+        assert_eq!(
+            error.to_string(),
+            "Timed out searching `tests/fixtures/sample.txt`"
+        );
+    }
+
+    #[test]
+    fn test_search_reader_stops_when_cancelled() {
+        use cancel::CancellationToken;
+
+        /// Cancels the token once the first line has been read.
+        struct CancelAfterFirstLine<'a>(&'a [u8], CancellationToken);
+
+        impl Read for CancelAfterFirstLine<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.0.first() == Some(&b'\n') {
+                    self.1.cancel();
+                }
+                let n = self.0.len().min(buf.len()).min(1);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+
+        let token = CancellationToken::new();
+        let options = SearchOptions {
+            cancel: Some(token.clone()),
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let error = search_reader(
+            CancelAfterFirstLine(b"x\nx\nx\n", token),
+            &matcher,
+            &options,
+        )
+        .unwrap_err();
+
+        assert!(error.is::<Cancelled>());
+    }
 }
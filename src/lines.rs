@@ -0,0 +1,374 @@
+//! Splitting input into lines a block at a time.
+//!
+//! A [`LineReader`] reads its input in large blocks and finds line
+//! terminators with `memchr`, handing out each line as a slice of its buffer
+//! instead of copying it into a separate `String` or `Vec`. A line that
+//! crosses the end of a block is moved to the front of the buffer before the
+//! next block is read, so lines are always contiguous and line numbers stay
+//! correct however the input is split up.
+//!
+//! ```
+//! use searcher_cli_starter::lines::LineReader;
+//!
+//! let mut lines = LineReader::new(&b"one\ntwo\r\nthree"[..], b'\n');
+//! assert_eq!(lines.next_line().unwrap(), Some(&b"one\n"[..]));
+//! assert_eq!(lines.next_line().unwrap(), Some(&b"two\r\n"[..]));
+//! assert_eq!(lines.next_line().unwrap(), Some(&b"three"[..]));
+//! assert_eq!(lines.next_line().unwrap(), None);
+//! ```
+//!
+//! A [`ReverseLineReader`] reads a seekable input backwards instead, from
+//! its last line to its first, so that the end of a large log can be
+//! searched without reading the rest. [`count_lines`] counts the lines of an
+//! input without keeping them.
+
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+
+/// Size of the blocks read from the input. Lines longer than this grow the
+/// buffer.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Reads lines out of large blocks of input.
+pub struct LineReader<R> {
+    reader: R,
+    terminator: u8,
+    buffer: Vec<u8>,
+    /// Where the next line starts in `buffer`
+    start: usize,
+    /// How far past `start` is known to contain no terminator
+    searched: usize,
+    /// The end of the bytes read into `buffer`
+    end: usize,
+    eof: bool,
+}
+
+impl<R: Read> LineReader<R> {
+    /// Creates a reader splitting `reader` into lines ending with
+    /// `terminator`.
+    pub fn new(reader: R, terminator: u8) -> Self {
+        Self::with_buffer(reader, terminator, Vec::new())
+    }
+
+    /// Creates a reader that reads into `buffer`, e.g. one taken back with
+    /// [`LineReader::into_buffer`] from a previous reader, so that searching
+    /// many inputs does not allocate a new buffer for each.
+    pub fn with_buffer(reader: R, terminator: u8, mut buffer: Vec<u8>) -> Self {
+        if buffer.len() < BLOCK_SIZE {
+            buffer.resize(BLOCK_SIZE, 0);
+        }
+        LineReader {
+            reader,
+            terminator,
+            buffer,
+            start: 0,
+            searched: 0,
+            end: 0,
+            eof: false,
+        }
+    }
+
+    /// Returns the next line including its terminator, or `None` at the end
+    /// of the input. The last line has no terminator if the input does not
+    /// end with one.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from the underlying reader other than
+    /// [`ErrorKind::Interrupted`], which is retried.
+    pub fn next_line(&mut self) -> io::Result<Option<&[u8]>> {
+        loop {
+            let unsearched = &self.buffer[self.start + self.searched..self.end];
+            if let Some(position) = memchr::memchr(self.terminator, unsearched) {
+                let line_start = self.start;
+                self.start += self.searched + position + 1;
+                self.searched = 0;
+                return Ok(Some(&self.buffer[line_start..self.start]));
+            }
+            self.searched = self.end - self.start;
+
+            if self.eof {
+                if self.start == self.end {
+                    return Ok(None);
+                }
+                let line_start = self.start;
+                self.start = self.end;
+                self.searched = 0;
+                return Ok(Some(&self.buffer[line_start..self.end]));
+            }
+            self.fill()?;
+        }
+    }
+
+    /// Gives back the buffer for reuse with [`LineReader::with_buffer`].
+    pub fn into_buffer(self) -> Vec<u8> {
+        self.buffer
+    }
+
+    /// Reads the next block after the unfinished line at `start`.
+    fn fill(&mut self) -> io::Result<()> {
+        if self.start > 0 {
+            self.buffer.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+        }
+        if self.end == self.buffer.len() {
+            // The line is longer than the buffer
+            self.buffer.resize(self.buffer.len() * 2, 0);
+        }
+        loop {
+            match self.reader.read(&mut self.buffer[self.end..]) {
+                Ok(0) => self.eof = true,
+                Ok(read) => self.end += read,
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            }
+            return Ok(());
+        }
+    }
+}
+
+/// Reads lines out of large blocks of input, from the last line to the
+/// first.
+///
+/// ```
+/// use searcher_cli_starter::lines::ReverseLineReader;
+/// use std::io::Cursor;
+///
+/// let mut lines = ReverseLineReader::new(Cursor::new("one\ntwo\nthree"), b'\n').unwrap();
+/// assert_eq!(lines.prev_line().unwrap(), Some(&b"three"[..]));
+/// assert_eq!(lines.prev_line().unwrap(), Some(&b"two\n"[..]));
+/// assert_eq!(lines.prev_line().unwrap(), Some(&b"one\n"[..]));
+/// assert_eq!(lines.prev_line().unwrap(), None);
+/// ```
+pub struct ReverseLineReader<R> {
+    reader: R,
+    terminator: u8,
+    buffer: Vec<u8>,
+    /// Where the unread part of `buffer` starts
+    start: usize,
+    /// Where the unread part of `buffer` ends, which is where the last line
+    /// handed out started
+    end: usize,
+    /// The offset in the input of `buffer[start]`
+    position: u64,
+}
+
+impl<R: Read + Seek> ReverseLineReader<R> {
+    /// Creates a reader splitting `reader` into lines ending with
+    /// `terminator`, starting from the end of the input.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if seeking to the end of `reader` fails.
+    pub fn new(mut reader: R, terminator: u8) -> io::Result<Self> {
+        let end = reader.seek(SeekFrom::End(0))?;
+        Ok(Self::ending_at(reader, terminator, end))
+    }
+
+    /// Creates a reader splitting the first `end` bytes of `reader` into
+    /// lines, starting from the last of them, e.g. to leave out what was
+    /// appended to a log after its length was taken.
+    pub fn ending_at(reader: R, terminator: u8, end: u64) -> Self {
+        let buffer = vec![0; BLOCK_SIZE];
+        ReverseLineReader {
+            reader,
+            terminator,
+            start: buffer.len(),
+            end: buffer.len(),
+            buffer,
+            position: end,
+        }
+    }
+
+    /// The offset in the input where the line returned last starts.
+    pub fn offset(&self) -> u64 {
+        self.position + (self.end - self.start) as u64
+    }
+
+    /// The underlying reader. It may be read and moved freely between
+    /// lines, as each block is read after seeking to it.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Returns the line before the one returned last, including its
+    /// terminator, or `None` once the first line has been returned. The
+    /// last line of the input has no terminator if the input does not end
+    /// with one.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from the underlying reader other than
+    /// [`ErrorKind::Interrupted`], which is retried.
+    pub fn prev_line(&mut self) -> io::Result<Option<&[u8]>> {
+        loop {
+            // The terminator ending the line itself is not where it starts
+            let before_end = self.end.saturating_sub(1).max(self.start);
+            if let Some(position) =
+                memchr::memrchr(self.terminator, &self.buffer[self.start..before_end])
+            {
+                let line_end = self.end;
+                self.end = self.start + position + 1;
+                return Ok(Some(&self.buffer[self.end..line_end]));
+            }
+            if self.position == 0 {
+                if self.start == self.end {
+                    return Ok(None);
+                }
+                let line_end = self.end;
+                self.end = self.start;
+                return Ok(Some(&self.buffer[self.start..line_end]));
+            }
+            self.fill()?;
+        }
+    }
+
+    /// Reads the block before the unfinished line at `start`.
+    fn fill(&mut self) -> io::Result<()> {
+        let len = (BLOCK_SIZE as u64).min(self.position) as usize;
+        if self.start < len {
+            // Make room in front of the unfinished line
+            let unread = self.end - self.start;
+            let size = self.buffer.len().max(unread + len);
+            let mut buffer = vec![0; size];
+            buffer[size - unread..].copy_from_slice(&self.buffer[self.start..self.end]);
+            self.buffer = buffer;
+            self.start = size - unread;
+            self.end = size;
+        }
+        self.position -= len as u64;
+        self.reader.seek(SeekFrom::Start(self.position))?;
+        self.start -= len;
+        read_full(
+            &mut self.reader,
+            &mut self.buffer[self.start..self.start + len],
+        )
+    }
+}
+
+/// Fills `buf` from `reader`, retrying reads that are interrupted or cut
+/// short.
+fn read_full<R: Read>(reader: &mut R, mut buf: &mut [u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        match reader.read(buf) {
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(read) => buf = &mut buf[read..],
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(())
+}
+
+/// Counts the lines of `reader` ending with `terminator`, reading it a block
+/// at a time. A last line without a terminator counts too.
+///
+/// ```
+/// use searcher_cli_starter::lines::count_lines;
+///
+/// assert_eq!(count_lines(&b"a\nb\n"[..], b'\n').unwrap(), 2);
+/// assert_eq!(count_lines(&b"a\nb"[..], b'\n').unwrap(), 2);
+/// assert_eq!(count_lines(&b""[..], b'\n').unwrap(), 0);
+/// ```
+///
+/// # Errors
+///
+/// Returns any error from `reader` other than [`ErrorKind::Interrupted`],
+/// which is retried.
+pub fn count_lines<R: Read>(mut reader: R, terminator: u8) -> io::Result<usize> {
+    let mut block = vec![0; BLOCK_SIZE];
+    let mut lines = 0;
+    let mut last = None;
+    loop {
+        match reader.read(&mut block) {
+            Ok(0) => break,
+            Ok(read) => {
+                lines += memchr::memchr_iter(terminator, &block[..read]).count();
+                last = Some(block[read - 1]);
+            }
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(lines + usize::from(last.is_some_and(|byte| byte != terminator)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Hands out its input a few bytes at a time.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    fn collect<R: Read>(mut lines: LineReader<R>) -> Vec<Vec<u8>> {
+        let mut collected = Vec::new();
+        while let Some(line) = lines.next_line().unwrap() {
+            collected.push(line.to_vec());
+        }
+        collected
+    }
+
+    #[test]
+    fn test_lines_across_block_boundaries() {
+        let long = "x".repeat(BLOCK_SIZE * 3 + 5);
+        let input = format!("a\n\n{long}\nb\r\nc");
+        let expected: Vec<Vec<u8>> = ["a\n", "\n", &format!("{long}\n"), "b\r\n", "c"]
+            .iter()
+            .map(|line| line.as_bytes().to_vec())
+            .collect();
+
+        assert_eq!(collect(LineReader::new(input.as_bytes(), b'\n')), expected);
+        assert_eq!(
+            collect(LineReader::new(Trickle(input.as_bytes()), b'\n')),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_other_terminators_and_empty_input() {
+        let lines = LineReader::new(&b"a\nb\0c\0"[..], b'\0');
+        assert_eq!(collect(lines), [&b"a\nb\0"[..], b"c\0"]);
+        assert!(collect(LineReader::new(&b""[..], b'\n')).is_empty());
+    }
+
+    #[test]
+    fn test_reverse_lines_across_block_boundaries() {
+        let long = "x".repeat(BLOCK_SIZE * 3 + 5);
+        let input = format!("a\n\n{long}\nb\r\n{long}c\n\n");
+        let mut expected = collect(LineReader::new(input.as_bytes(), b'\n'));
+        expected.reverse();
+
+        let mut lines = ReverseLineReader::new(Cursor::new(input.as_bytes()), b'\n').unwrap();
+        let mut collected = Vec::new();
+        let mut offset = input.len();
+        while let Some(line) = lines.prev_line().unwrap() {
+            offset -= line.len();
+            collected.push(line.to_vec());
+            assert_eq!(lines.offset(), offset as u64);
+        }
+        assert_eq!(collected, expected);
+
+        // Bytes past `end` are left out
+        let mut lines = ReverseLineReader::ending_at(Cursor::new(b"a\nb\nappended\n"), b'\n', 4);
+        assert_eq!(lines.prev_line().unwrap(), Some(&b"b\n"[..]));
+        assert_eq!(lines.prev_line().unwrap(), Some(&b"a\n"[..]));
+        assert_eq!(lines.prev_line().unwrap(), None);
+        assert_eq!(
+            count_lines(input.as_bytes(), b'\n').unwrap(),
+            expected.len()
+        );
+
+        let mut lines = ReverseLineReader::new(Cursor::new(b""), b'\n').unwrap();
+        assert_eq!(lines.prev_line().unwrap(), None);
+    }
+}
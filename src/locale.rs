@@ -0,0 +1,217 @@
+//! Language-specific case rules and word boundaries, built on ICU.
+//!
+//! Plain `-i` folds case the same way for every language, and `-w` treats
+//! any run of letters, digits, and `_` as a word. Both are wrong for some
+//! languages:
+//!
+//! - Turkish and Azerbaijani pair `i` with `İ` and `ı` with `I`, so `istanbul`
+//!   should match `İSTANBUL` but not `ISTANBUL`
+//! - Greek drops accents in uppercase, so `οδός` should match `ΟΔΟΣ`
+//! - Thai, Lao, Khmer, Burmese, Chinese, and Japanese write words without
+//!   spaces between them, and `can't` is one word, not two
+//!
+//! A [`Locale`] folds case by the rules of its language and finds word
+//! boundaries with the Unicode word segmentation algorithm, which uses
+//! dictionaries for the languages written without spaces. Available with
+//! the `icu` cargo feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use searcher_cli_starter::locale::Locale;
+//! use searcher_cli_starter::{Matcher, SearchOptions, search_reader};
+//!
+//! let turkish = Locale::new("tr").unwrap();
+//! let matcher = Matcher::new(&turkish.fold("istanbul"), false, false).unwrap();
+//! let options = SearchOptions::default().with_transform(turkish);
+//! let input = "İSTANBUL\nISTANBUL\n";
+//! let matches = search_reader(input.as_bytes(), &matcher, &options).unwrap();
+//! assert_eq!(matches.len(), 1);
+//! assert_eq!(matches[0].line_number, 1);
+//! ```
+
+use crate::Matcher;
+use crate::filter::LineTransform;
+use anyhow::{Context, Result};
+use icu_casemap::CaseMapper;
+use icu_locid::LanguageIdentifier;
+use icu_segmenter::WordSegmenter;
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+
+/// The case rules and word boundaries of a language.
+///
+/// As a [`LineTransform`], it folds the case of every line like
+/// [`Locale::fold`], so a pattern folded the same way matches regardless of
+/// case.
+#[derive(Clone)]
+pub struct Locale {
+    /// The language, as parsed from its tag
+    language: LanguageIdentifier,
+    /// Whether the language pairs `i` with `İ` and `ı` with `I`
+    turkic: bool,
+    case_mapper: CaseMapper,
+    words: Arc<WordSegmenter>,
+}
+
+impl Locale {
+    /// The locale for a BCP 47 language tag, such as `tr`, `el-GR`, or `th`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `tag` is not a valid language tag.
+    pub fn new(tag: &str) -> Result<Self> {
+        let language: LanguageIdentifier = tag
+            .parse()
+            .map_err(|error| anyhow::anyhow!("{:?}", error))
+            .with_context(|| format!("Invalid locale `{}`", tag))?;
+        let turkic = matches!(language.language.as_str(), "tr" | "az");
+        Ok(Locale {
+            language,
+            turkic,
+            case_mapper: CaseMapper::new(),
+            words: Arc::new(WordSegmenter::new_auto()),
+        })
+    }
+
+    /// `text` with its case folded by the language's rules, for comparing
+    /// it without regard to case.
+    ///
+    /// The text is uppercased by the language's rules first, which is what
+    /// makes Greek words match with or without their accents, then folded,
+    /// with the Turkic `i` mappings for Turkish and Azerbaijani.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::locale::Locale;
+    ///
+    /// let turkish = Locale::new("tr").unwrap();
+    /// assert_eq!(turkish.fold("İSTANBUL"), "istanbul");
+    /// assert_eq!(turkish.fold("ISPARTA"), "ısparta");
+    ///
+    /// let greek = Locale::new("el").unwrap();
+    /// assert_eq!(greek.fold("οδός"), greek.fold("ΟΔΟΣ"));
+    /// ```
+    pub fn fold<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let upper = self.case_mapper.uppercase_to_string(text, &self.language);
+        let folded = if self.turkic {
+            self.case_mapper.fold_turkic_string(&upper)
+        } else {
+            self.case_mapper.fold_string(&upper)
+        };
+        if folded == text {
+            Cow::Borrowed(text)
+        } else {
+            Cow::Owned(folded)
+        }
+    }
+
+    /// The byte offsets in `text` where a word starts or ends, including 0
+    /// and the length of `text`. Invalid UTF-8 counts as
+    /// [`char::REPLACEMENT_CHARACTER`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::locale::Locale;
+    ///
+    /// let english = Locale::new("en").unwrap();
+    /// assert_eq!(english.word_boundaries(b"can't stop"), [0, 5, 6, 10]);
+    /// ```
+    pub fn word_boundaries(&self, text: &[u8]) -> Vec<usize> {
+        self.words.segment_utf8(text).collect()
+    }
+
+    /// Whether one of `matcher`'s matches in `line` is a whole word: it
+    /// starts and ends on word boundaries. Composite matchers have no match
+    /// spans, so they never match whole words.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::Matcher;
+    /// use searcher_cli_starter::locale::Locale;
+    ///
+    /// let english = Locale::new("en").unwrap();
+    /// let matcher = Matcher::new("can", false, false).unwrap();
+    /// assert!(english.has_word_match(b"you can go", &matcher));
+    /// assert!(!english.has_word_match(b"you can't go", &matcher));
+    /// ```
+    pub fn has_word_match(&self, line: &[u8], matcher: &Matcher) -> bool {
+        let ranges = matcher.match_ranges(line);
+        if ranges.is_empty() {
+            return false;
+        }
+        let boundaries = self.word_boundaries(line);
+        ranges.iter().any(|range| {
+            boundaries.binary_search(&range.start).is_ok()
+                && boundaries.binary_search(&range.end).is_ok()
+        })
+    }
+}
+
+impl fmt::Debug for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Locale")
+            .field(&self.language.to_string())
+            .finish()
+    }
+}
+
+impl LineTransform for Locale {
+    fn transform<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        if let Ok(text) = std::str::from_utf8(line) {
+            return match self.fold(text) {
+                Cow::Borrowed(text) => Cow::Borrowed(text.as_bytes()),
+                Cow::Owned(text) => Cow::Owned(text.into_bytes()),
+            };
+        }
+        let mut folded = Vec::with_capacity(line.len());
+        for chunk in line.utf8_chunks() {
+            folded.extend_from_slice(self.fold(chunk.valid()).as_bytes());
+            folded.extend_from_slice(chunk.invalid());
+        }
+        Cow::Owned(folded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_follows_the_language() {
+        let turkish = Locale::new("tr").unwrap();
+        let english = Locale::new("en").unwrap();
+        assert_eq!(turkish.fold("Iİ"), "ıi");
+        assert_eq!(english.fold("STRASSE"), english.fold("Straße"));
+        assert!(matches!(english.fold("plain"), Cow::Borrowed(_)));
+
+        let greek = Locale::new("el-GR").unwrap();
+        assert_eq!(greek.fold("Οδός"), "οδοσ");
+        assert_ne!(english.fold("οδός"), english.fold("ΟΔΟΣ"));
+
+        assert_eq!(turkish.transform(b"\xff I"), &b"\xff \xc4\xb1"[..]);
+        assert!(Locale::new("not a tag").is_err());
+    }
+
+    #[test]
+    fn test_word_matches_use_word_boundaries() {
+        let english = Locale::new("en").unwrap();
+        let matcher = Matcher::new("cat", false, false).unwrap();
+        assert!(english.has_word_match(b"concat, cat", &matcher));
+        assert!(!english.has_word_match(b"concatenate", &matcher));
+
+        let composite = crate::query::build_matcher(
+            "cat",
+            &crate::query::MatcherOptions {
+                query: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(!english.has_word_match(b"cat", &composite));
+    }
+}
@@ -2,52 +2,3370 @@
 //!
 //! This is the binary executable that provides a CLI wrapper around
 //! the searcher library functionality.
+//!
+//! The work is split into subcommands (`find`, `replace`, `count`, `files`,
+//! `run`, `lint`, `index`, `cache`, `watch`, `tui`, `serve`, and `bench`). Running `searcher PATTERN PATH...` without a subcommand is
+//! shorthand for `searcher find`, so existing scripts keep working.
+
+use anyhow::{Context, Result, bail};
+use clap::{ArgGroup, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use notify_debouncer_mini::new_debouncer;
+use notify_debouncer_mini::notify::RecursiveMode;
+use searcher_cli_starter::bench;
+use searcher_cli_starter::cache::{ResultCache, hash_bytes};
+use searcher_cli_starter::cancel::{CancellationToken, Cancelled};
+use searcher_cli_starter::diagnostic::{Diagnostic, FileContext};
+use searcher_cli_starter::dups::Duplicates;
+use searcher_cli_starter::filter::{
+    Dedupe, LineFilters, LineTransform, LineTransforms, MatchFilters, Normalization,
+    NormalizeWhitespace, Sample, TimeRange,
+};
+use searcher_cli_starter::gaps::{Gaps, parse_threshold};
+use searcher_cli_starter::git::{self, Repository};
+use searcher_cli_starter::hex::HexPattern;
+use searcher_cli_starter::histogram::{DistinctValues, Histogram, TopValues, density};
+use searcher_cli_starter::ignore::{self, IgnoreFile};
+use searcher_cli_starter::index::{Index, required_literals};
+use searcher_cli_starter::job::JobSpec;
+use searcher_cli_starter::jsonl::JsonFields;
+#[cfg(feature = "icu")]
+use searcher_cli_starter::locale::Locale;
+use searcher_cli_starter::multi::{PatternSet, PatternStats};
+use searcher_cli_starter::preprocess::Preprocessor;
+use searcher_cli_starter::printer::{
+    Printer, PrinterOptions, Quote, Template, display_path, sanitize,
+};
+#[cfg(feature = "profiling")]
+use searcher_cli_starter::profile::Profiler;
+use searcher_cli_starter::query::{self, MatcherOptions};
+use searcher_cli_starter::records::search_path_records;
+use searcher_cli_starter::redact::Redactor;
+use searcher_cli_starter::replace::{Replacer, unified_diff, write_replaced};
+use searcher_cli_starter::rpc;
+use searcher_cli_starter::rules::{Rule, RuleSet, Severity};
+use searcher_cli_starter::sarif::{Level, SarifLog, SarifRule};
+use searcher_cli_starter::scope::Scope;
+use searcher_cli_starter::searcher::Searcher;
+use searcher_cli_starter::secrets::{DEFAULT_ENTROPY_THRESHOLD, SecretScanner, builtin_rules};
+use searcher_cli_starter::sequence::{Pair, search_path_not_within, search_path_pairs};
+use searcher_cli_starter::server::SearchServer;
+use searcher_cli_starter::sessions::Sessions;
+use searcher_cli_starter::split::{Split, search_path_split, share_threads};
+use searcher_cli_starter::threads::{Threads, parse_cpu_list};
+use searcher_cli_starter::tui;
+use searcher_cli_starter::walk::{
+    FileError, Walk, each_file, each_file_in, each_file_parallel, parse_file_time,
+    spans_multiple_files,
+};
+use searcher_cli_starter::watch::MatchTracker;
+use searcher_cli_starter::{
+    ByteWindow, LineRange, LineTerminator, Matcher, SearchMatchBytes, SearchOptions, capture_lines,
+    first_match, last_match, path_has_match, search_path, search_path_reverse, search_path_with,
+};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::num::NonZeroUsize;
+use std::ops::{ControlFlow, Range};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Mutex, OnceLock, PoisonError, mpsc};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
-use anyhow::{Context, Result};
-use clap::Parser;
-use searcher_cli_starter::{search_lines, Matcher};
-use std::fs::File;
-use std::path::PathBuf;
+#[cfg(feature = "bench-alloc")]
+#[global_allocator]
+static ALLOCATOR: bench::CountingAllocator = bench::CountingAllocator;
 
-/// Search for a pattern in a file and display the lines that contain it.
+/// Search for a pattern in files and display the lines that contain it.
+///
+/// `searcher PATTERN PATH...` is shorthand for `searcher find PATTERN PATH...`.
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(
+    name = "searcher",
+    version,
+    about,
+    long_about = None,
+    after_help = "Run `searcher PATTERN PATH...` without a subcommand as a shortcut for `searcher find`."
+)]
 struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the lines that match a pattern (the default without a subcommand)
+    Find(Box<FindArgs>),
+    /// Replace matches, printing the result, a diff, or rewriting the files
+    Replace(ReplaceArgs),
+    /// Print the number of matching lines in each file
+    Count(SelectArgs),
+    /// Print the names of the files that contain a match
+    Files(SelectArgs),
+    /// Print every distinct match (or capture group) with how often it occurs
+    Extract(ExtractArgs),
+    /// Print the lines (or matched values) that occur more than once, with where
+    Dups(DupsArgs),
+    /// Run the search described in a TOML job file
+    Run(RunArgs),
+    /// Check files against a ruleset of named patterns
+    Lint(LintArgs),
+    /// Look for leaked credentials: API keys, tokens, private keys, and random-looking strings
+    Secrets(SecretsArgs),
+    /// Search the files changed by each commit in a revision range
+    GitLog(GitLogArgs),
+    /// Build a trigram index of a directory, or search using one
+    #[command(subcommand)]
+    Index(IndexCommand),
+    /// Manage the result cache written by `--cache-dir`
+    #[command(subcommand)]
+    Cache(CacheCommand),
+    /// Search, then keep printing new matches as files change
+    Watch(WatchArgs),
+    /// Search interactively, with results updating as you type
+    Tui(TuiArgs),
+    /// Answer JSON search requests on a Unix socket, over HTTP, or as JSON-RPC on stdio
+    Serve(ServeArgs),
+    /// Measure search throughput, lines/sec, and allocations on your own data
+    Bench(BenchArgs),
+    /// Print the man page in roff format, or write one page per subcommand
+    Man(ManArgs),
+}
+
+/// Arguments for `searcher man`.
+#[derive(Args)]
+struct ManArgs {
+    /// Write `searcher.1` and a page per subcommand into this directory
+    /// instead of printing the main page
+    #[arg(long = "dir", value_name = "DIR")]
+    dir: Option<PathBuf>,
+}
+
+/// Arguments for `searcher run`.
+#[derive(Args)]
+struct RunArgs {
+    /// The job file describing the pattern, paths, and output
+    job: PathBuf,
+
+    /// Don't print warnings about files that could not be read
+    #[arg(short = 's', long = "no-messages")]
+    no_messages: bool,
+}
+
+/// Arguments for `searcher lint`.
+#[derive(Args)]
+struct LintArgs {
+    /// The ruleset file, in TOML or (for `.yaml` / `.yml` files) YAML
+    #[arg(long = "rules", value_name = "FILE")]
+    rules: PathBuf,
+
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Print findings as text or as a SARIF 2.1.0 report
+    #[arg(long = "output", value_name = "FORMAT", default_value = "text")]
+    output_format: LintFormat,
+
+    /// Write paths with SEP between their components, e.g. `/` on Windows
+    #[arg(long = "path-separator", value_name = "SEP", value_parser = parse_path_separator)]
+    path_separator: Option<char>,
+}
+
+/// Arguments for `searcher secrets`.
+#[derive(Args)]
+struct SecretsArgs {
+    /// A ruleset file of more credential patterns, checked along with the
+    /// built-in ones
+    #[arg(long = "rules", value_name = "FILE")]
+    rules: Option<PathBuf>,
+
+    /// Report tokens from this entropy on, in bits per character
+    #[arg(long = "entropy-threshold", value_name = "BITS", default_value_t = DEFAULT_ENTROPY_THRESHOLD)]
+    entropy_threshold: f64,
+
+    /// Only report what the rules find, not random-looking tokens
+    #[arg(long = "no-entropy", conflicts_with = "entropy_threshold")]
+    no_entropy: bool,
+
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Print findings as text or as a SARIF 2.1.0 report
+    #[arg(long = "output", value_name = "FORMAT", default_value = "text")]
+    output_format: LintFormat,
+
+    /// Write paths with SEP between their components, e.g. `/` on Windows
+    #[arg(long = "path-separator", value_name = "SEP", value_parser = parse_path_separator)]
+    path_separator: Option<char>,
+}
+
+/// Arguments for `searcher git-log`.
+#[derive(Args)]
+struct GitLogArgs {
+    #[command(flatten)]
+    matching: MatchArgs,
+
+    /// The commits to search, e.g. `v1.0..HEAD`
+    #[arg(default_value = "HEAD")]
+    range: String,
+
+    /// Only search these paths, given after `--`
+    #[arg(last = true)]
+    paths: Vec<PathBuf>,
+
+    /// The repository to search
+    #[arg(long = "repo", value_name = "DIR", default_value = ".")]
+    repo: PathBuf,
+}
+
+/// Arguments for `searcher extract`.
+#[derive(Args)]
+struct ExtractArgs {
+    #[command(flatten)]
+    matching: MatchArgs,
+
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Order values by how often they occur or by the values themselves
+    #[arg(long = "sort", value_name = "ORDER", default_value = "count")]
+    sort: ExtractOrder,
+
+    /// Print only the K most frequent values, with their share of all the
+    /// values, counting a bounded number of values at a time
+    #[arg(
+        long = "top",
+        value_name = "K",
+        conflicts_with = "sort",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    top: Option<u32>,
+}
+
+/// Arguments for `searcher dups`.
+#[derive(Args)]
+struct DupsArgs {
+    /// Count the matches of this regex (or its capture group) instead of
+    /// whole lines
+    #[arg(short = 'e', long = "pattern", value_name = "REGEX")]
+    pattern: Option<String>,
+
+    /// Match `--pattern` case-insensitively
+    #[arg(short = 'i', long = "ignore-case", requires = "pattern")]
+    ignore_case: bool,
+
+    /// Only print the values that occur at least N times
+    #[arg(
+        long = "min-count",
+        value_name = "N",
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u64).range(2..)
+    )]
+    min_count: u64,
+
+    /// Print at most N locations of each value
+    #[arg(long = "locations", value_name = "N", default_value_t = 5)]
+    locations: usize,
+
+    #[command(flatten)]
+    input: InputArgs,
+}
+
+/// How many values `searcher extract --top` keeps count of at least.
+const TOP_VALUES_KEPT: usize = 1000;
+
+/// The values of `searcher extract --sort`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ExtractOrder {
+    /// Most frequent first
+    Count,
+    /// Sorted by value
+    Value,
+}
+
+/// The values of `searcher lint --output` and `searcher secrets --output`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LintFormat {
+    /// `path:line:column: severity[rule]: message`, then a summary per rule
+    Text,
+    /// A SARIF 2.1.0 report with a rule per ruleset rule
+    Sarif,
+}
+
+/// The `searcher index` subcommands.
+#[derive(Subcommand)]
+enum IndexCommand {
+    /// Index the files below the given directories
+    Build(IndexBuildArgs),
+    /// Search the indexed files that can contain the pattern
+    Query(IndexQueryArgs),
+}
+
+/// The `searcher cache` subcommands.
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// Delete a result cache and every entry in it
+    Clear(CacheClearArgs),
+}
+
+/// Arguments for `searcher cache clear`.
+#[derive(Args)]
+struct CacheClearArgs {
+    /// The directory given to `--cache-dir`
+    dir: PathBuf,
+}
+
+/// Arguments for `searcher index build`.
+#[derive(Args)]
+struct IndexBuildArgs {
+    /// The directories (or files) to index
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Where to write the index
+    #[arg(long = "index", value_name = "FILE", default_value = DEFAULT_INDEX)]
+    index: PathBuf,
+
+    /// Don't print warnings about files that could not be read
+    #[arg(short = 's', long = "no-messages")]
+    no_messages: bool,
+}
+
+/// Arguments for `searcher index query`.
+#[derive(Args)]
+struct IndexQueryArgs {
+    #[command(flatten)]
+    matching: MatchArgs,
+
+    /// The index to use
+    #[arg(long = "index", value_name = "FILE", default_value = DEFAULT_INDEX)]
+    index: PathBuf,
+
+    #[command(flatten)]
+    output: OutputArgs,
+
+    /// Don't print warnings about files that could not be read
+    #[arg(short = 's', long = "no-messages")]
+    no_messages: bool,
+}
+
+/// Where `searcher index` keeps its index unless `--index` says otherwise.
+const DEFAULT_INDEX: &str = ".searcher-index";
+
+/// Arguments for `searcher serve`.
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+struct ServeArgs {
+    /// The Unix socket to listen on; one JSON request per line, answered
+    /// with one JSON object per line
+    #[arg(long = "socket", value_name = "PATH")]
+    socket: Option<PathBuf>,
+
+    /// Answer `POST /search` requests over HTTP on this address, e.g.
+    /// `127.0.0.1:7700`, streaming JSON lines back (needs the `serve-http`
+    /// feature)
+    #[arg(long = "http", value_name = "ADDR")]
+    http: Option<String>,
+
+    /// Speak JSON-RPC on stdin and stdout, like a language server, so an IDE
+    /// extension can run searcher as its backend
+    #[arg(long = "stdio")]
+    stdio: bool,
+}
+
+/// Arguments for `searcher bench`.
+#[derive(Args)]
+struct BenchArgs {
     /// The pattern to look for
     pattern: String,
 
-    /// The path to the file to read
+    /// The file to search
     path: PathBuf,
 
     /// Perform case-insensitive matching
     #[arg(short = 'i', long = "ignore-case")]
     ignore_case: bool,
 
+    /// Interpret pattern as a regular expression
+    #[arg(short = 'r', long = "regex")]
+    regex: bool,
+
+    /// How many times to search the file
+    #[arg(long = "iterations", default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    iterations: u32,
+}
+
+/// The pattern and how to interpret it, shared by every search subcommand.
+#[derive(Args)]
+struct MatchArgs {
+    /// The pattern to look for
+    pattern: String,
+
+    /// Perform case-insensitive matching
+    #[arg(short = 'i', long = "ignore-case")]
+    ignore_case: bool,
+
+    /// Interpret pattern as a regular expression
+    #[arg(short = 'r', long = "regex", overrides_with = "fixed_strings")]
+    regex: bool,
+
+    /// Match patterns as plain text, even characters like `.`, `*`, or `(`
+    /// (the default, undoing an earlier `-r`)
+    #[arg(short = 'F', long = "fixed-strings", overrides_with = "regex")]
+    fixed_strings: bool,
+
+    /// Interpret pattern as a boolean query, e.g. `error AND NOT retry`
+    #[arg(long = "query")]
+    query: bool,
+
+    /// Only match whole words, not parts of longer ones
+    #[arg(short = 'w', long = "word-regexp")]
+    word_regexp: bool,
+
+    /// Fold case for `-i` and find word boundaries for `-w` by the rules of
+    /// this language, e.g. `tr` for Turkish or `el` for Greek
+    #[cfg(feature = "icu")]
+    #[arg(long = "locale", value_name = "LANG")]
+    locale: Option<String>,
+
+    /// Additional pattern every matching line must contain (repeatable)
+    #[arg(long = "all-of", value_name = "PATTERN")]
+    all_of: Vec<String>,
+
+    /// Require at least one of these patterns to match as well (repeatable)
+    #[arg(long = "any-of", value_name = "PATTERN")]
+    any_of: Vec<String>,
+
+    /// Reject lines containing any of these patterns (repeatable)
+    #[arg(long = "none-of", value_name = "PATTERN")]
+    none_of: Vec<String>,
+}
+
+/// How to split the input into lines, and which of them to search.
+#[derive(Args)]
+struct LineArgs {
+    /// Treat CRLF as a line terminator and strip `\r` from matched lines (the default)
+    #[arg(
+        long = "crlf",
+        conflicts_with = "null_data",
+        overrides_with = "no_crlf"
+    )]
+    crlf: bool,
+
+    /// Keep the `\r` of CRLF line endings in matched lines
+    #[arg(long = "no-crlf", overrides_with = "crlf")]
+    no_crlf: bool,
+
+    /// Split input on NUL bytes instead of newlines (e.g. `find -print0` output)
+    #[arg(short = 'z', long = "null-data")]
+    null_data: bool,
+
+    /// Only search lines in this range, e.g. `100-500`, `42`, or `10-` (repeatable)
+    #[arg(long = "lines", value_name = "RANGE")]
+    lines: Vec<LineRange>,
+
+    /// Add N to every line number, e.g. for a chunk split out of a larger file
+    #[arg(long = "line-offset", value_name = "N", default_value_t = 0)]
+    line_offset: usize,
+}
+
+/// The files to search and how to split them into lines.
+#[derive(Args)]
+struct InputArgs {
+    /// The files or directories to search
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    #[command(flatten)]
+    splitting: LineArgs,
+
+    /// Only search one line in every N, to probe huge files quickly; the
+    /// results are partial
+    #[arg(long = "sample", value_name = "N")]
+    sample: Option<NonZeroUsize>,
+
+    /// Only read the first SIZE bytes of each file, e.g. `10M`; the results
+    /// are partial
+    #[arg(
+        long = "head-bytes",
+        value_name = "SIZE",
+        value_parser = parse_filesize,
+        conflicts_with = "tail_bytes"
+    )]
+    head_bytes: Option<u64>,
+
+    /// Only read the last SIZE bytes of each file, from the first line
+    /// starting in them, numbering lines from there; the results are partial
+    #[arg(long = "tail-bytes", value_name = "SIZE", value_parser = parse_filesize)]
+    tail_bytes: Option<u64>,
+
+    /// Give up on a file after searching it for this many seconds, e.g. `2.5`
+    #[arg(long = "timeout", value_name = "SECS", value_parser = parse_timeout)]
+    timeout: Option<Duration>,
+
+    /// Only search lines timestamped at or after this time, e.g. `2024-05-01 12:00`
+    #[arg(long = "since", value_name = "TIME")]
+    since: Option<String>,
+
+    /// Only search lines timestamped at or before this time, e.g. `2024-05-01`
+    #[arg(long = "until", value_name = "TIME")]
+    until: Option<String>,
+
+    /// Regex finding the timestamp of a line for `--since` / `--until` and
+    /// `--gaps` (its first capture group, if it has one)
+    #[arg(long = "time-pattern", value_name = "REGEX")]
+    time_pattern: Option<String>,
+
+    /// How timestamps are written, e.g. `[day]/[month repr:short]/[year]:[hour]:[minute]:[second]`
+    #[arg(long = "time-format", value_name = "FORMAT")]
+    time_format: Option<String>,
+
+    /// Search the output of COMMAND instead of each file; it gets the path as
+    /// its argument and the contents on stdin, e.g. `--pre zcat`
+    #[arg(long = "pre", value_name = "COMMAND")]
+    pre: Option<PathBuf>,
+
+    /// Only run `--pre` on files matching this glob, e.g. `*.gz` (repeatable)
+    #[arg(long = "pre-glob", value_name = "GLOB", requires = "pre")]
+    pre_glob: Vec<String>,
+
+    /// Only search files tracked by git, as listed by `git ls-files`
+    #[arg(long = "git-tracked")]
+    git_tracked: bool,
+
+    /// Search files that `.searcherignore` and `.gitignore` files, and the global ignore file, leave out
+    #[arg(long = "no-ignore")]
+    no_ignore: bool,
+
+    /// Also leave out what FILE lists, in `.searcherignore` syntax (repeatable)
+    #[arg(
+        long = "ignore-file",
+        value_name = "FILE",
+        conflicts_with = "no_ignore"
+    )]
+    ignore_file: Vec<PathBuf>,
+
+    /// Skip files larger than this, e.g. `50M` (suffixes K, M, and G)
+    #[arg(long = "max-filesize", value_name = "SIZE", value_parser = parse_filesize)]
+    max_filesize: Option<u64>,
+
+    /// Only search files modified since this age or UTC time, e.g. `2d` or `2024-01-01`
+    #[arg(long = "newer-than", value_name = "WHEN", value_parser = parse_file_time_now)]
+    newer_than: Option<SystemTime>,
+
+    /// Only search files last modified before this age or UTC time, written like `--newer-than`
+    #[arg(long = "older-than", value_name = "WHEN", value_parser = parse_file_time_now)]
+    older_than: Option<SystemTime>,
+
+    /// Don't descend into directories on other file systems, such as network mounts
+    #[arg(long = "one-file-system")]
+    one_file_system: bool,
+
+    /// Only match inside comments, string literals, or the remaining code,
+    /// or the text of HTML and XML files; files in languages without a
+    /// known syntax are skipped
+    #[arg(long = "only", value_name = "SCOPE")]
+    only: Option<OnlyScope>,
+
+    /// Trim each line and collapse runs of whitespace to one space before
+    /// matching, so `connection refused` matches however the words are spaced
+    #[arg(long = "normalize-ws")]
+    normalize_ws: bool,
+
+    /// Bring the pattern and every line into this Unicode normalization
+    /// form before matching, so `café` matches however its `é` is encoded
+    #[arg(long = "normalize", value_name = "FORM")]
+    normalize: Option<NormalizeForm>,
+
+    /// Parse each line as JSON and match only the `--field` values, e.g.
+    /// `--jsonl --field message` for structured logs; `find` prints the
+    /// fields and a tab before each matching line
+    #[arg(long = "jsonl", requires = "fields")]
+    jsonl: bool,
+
+    /// With `--jsonl`, a field to search, such as `message` or `http.status`;
+    /// repeat it to search several
+    #[arg(long = "field", value_name = "NAME", requires = "jsonl")]
+    fields: Vec<String>,
+
+    /// Don't report a matching line again if the same line (ignoring
+    /// whitespace differences) was already reported from its file
+    #[arg(long = "dedupe")]
+    dedupe: bool,
+
+    /// Like `--dedupe`, but across all files, e.g. for rotated logs
+    #[arg(long = "dedupe-across-files")]
+    dedupe_across_files: bool,
+
+    /// Remember each file's matches in DIR, keyed by its content, so that
+    /// running the same search again only searches the files that changed
+    #[arg(
+        long = "cache-dir",
+        value_name = "DIR",
+        conflicts_with_all = ["pre", "dedupe_across_files"]
+    )]
+    cache_dir: Option<PathBuf>,
+
+    /// Skip files and directories we lack permission to read, only counting
+    /// them in a notice (default)
+    #[arg(long = "skip-permission-errors", overrides_with = "strict")]
+    skip_permission_errors: bool,
+
+    /// Report files and directories we lack permission to read as errors,
+    /// making the exit status 2
+    #[arg(long = "strict", overrides_with = "skip_permission_errors")]
+    strict: bool,
+
+    /// Don't print warnings about files that could not be read
+    #[arg(short = 's', long = "no-messages")]
+    no_messages: bool,
+
+    /// Exit with status 1 if no line matches
+    #[arg(long = "fail-if-no-match", conflicts_with = "fail_if_match")]
+    fail_if_no_match: bool,
+
+    /// Exit with status 1 if any line matches, e.g. to fail a build on a
+    /// pattern that should not occur
+    #[arg(long = "fail-if-match")]
+    fail_if_match: bool,
+
+    /// Don't let files that could not be searched make the exit status 2;
+    /// they are still reported
+    #[arg(long = "ignore-errors")]
+    ignore_errors: bool,
+
+    /// Print a warning about each file that could not be read as text, or
+    /// as a JSON object per line for tools
+    #[arg(long = "error-format", value_name = "FORMAT", default_value = "text")]
+    error_format: ErrorFormat,
+}
+
+/// The values of `--error-format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ErrorFormat {
+    /// `searcher: ` followed by the message
+    Text,
+    /// `{"type":"error","kind":...,"path":...,"message":...}`
+    Json,
+}
+
+/// How matching lines are printed.
+#[derive(Args)]
+struct OutputArgs {
     /// Show line numbers with output lines
     #[arg(short = 'n', long = "line-numbers")]
     line_numbers: bool,
 
-    /// Interpret pattern as a regular expression
-    #[arg(short = 'r', long = "regex")]
+    /// Print the file name once above its matches (default when writing to a terminal)
+    #[arg(long = "heading", overrides_with = "no_heading")]
+    heading: bool,
+
+    /// Print the file name on every matching line instead of as a heading
+    #[arg(long = "no-heading", overrides_with = "heading")]
+    no_heading: bool,
+
+    /// Format each match with a template, e.g. `{path}:{line}: {content}`
+    #[arg(long = "format", value_name = "TEMPLATE")]
+    format: Option<Template>,
+
+    /// Print control characters and ANSI escape sequences in matched lines as they are
+    #[arg(long = "no-sanitize")]
+    no_sanitize: bool,
+
+    /// Write paths with SEP between their components, e.g. `/` on Windows
+    #[arg(long = "path-separator", value_name = "SEP", value_parser = parse_path_separator)]
+    path_separator: Option<char>,
+
+    /// Quote paths and matched lines so scripts can pass them on as they
+    /// are: for a POSIX shell, or as C string literals
+    #[arg(long = "quote", value_name = "STYLE", default_value = "none")]
+    quote: QuoteStyle,
+}
+
+/// Arguments for `searcher find` and the bare `searcher PATTERN PATH...` form.
+#[derive(Args)]
+struct FindArgs {
+    #[command(flatten)]
+    matching: MatchArgs,
+
+    #[command(flatten)]
+    input: InputArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+
+    /// Also match lines containing this pattern (repeatable); `--output`
+    /// reports which patterns each line matched
+    #[arg(
+        short = 'e',
+        long = "pattern",
+        value_name = "PATTERN",
+        conflicts_with_all = ["query", "all_of", "any_of", "none_of", "groups"]
+    )]
+    patterns: Vec<String>,
+
+    /// Print how many lines each pattern matched, and in which files, to stderr
+    #[arg(
+        long = "stats-per-pattern",
+        conflicts_with_all = ["query", "all_of", "any_of", "none_of", "groups"]
+    )]
+    stats_per_pattern: bool,
+
+    /// Print how many lines and files matched and how long the search took
+    /// to stderr, with the memory it used if built with `profiling`
+    #[arg(
+        long = "stats",
+        conflicts_with_all = ["passthru", "context_delimiter", "record_separator", "then", "count_files"]
+    )]
+    stats: bool,
+
+    /// Print the regex capture groups of each match as `name=value` pairs
+    #[arg(long = "groups", requires = "regex")]
+    groups: bool,
+
+    /// Print `path:line:column:content` once per match, for Vim's quickfix list
+    #[arg(long = "vimgrep", conflicts_with_all = ["groups", "format", "heading"])]
+    vimgrep: bool,
+
+    /// Print matches for another tool instead of as text
+    #[arg(
+        long = "output",
+        value_name = "FORMAT",
+        default_value = "text",
+        conflicts_with_all = ["groups", "format", "heading", "vimgrep"]
+    )]
+    output_format: OutputFormat,
+
+    /// Print how often each value of a regex capture group occurs in the
+    /// matching lines, e.g. `code=(\w+)`, instead of the lines
+    #[arg(
+        long = "count-matches-by",
+        value_name = "REGEX",
+        conflicts_with_all = ["groups", "format", "heading", "vimgrep", "output_format"]
+    )]
+    count_matches_by: Option<String>,
+
+    /// Group the matching lines by the value of a regex capture group, e.g.
+    /// `req=(\w+)`, printing each value followed by its lines
+    #[arg(
+        long = "correlate-by",
+        value_name = "REGEX",
+        conflicts_with_all = [
+            "groups", "format", "heading", "vimgrep", "output_format", "count_matches_by",
+            "passthru", "context_delimiter", "record_separator", "then", "count_files", "hex",
+        ]
+    )]
+    correlate_by: Option<String>,
+
+    /// Instead of the matching lines, print how many there are in each
+    /// block of LINES lines (1000 without a value) of each file, with a bar
+    /// chart showing where they cluster
+    #[arg(
+        long = "density",
+        value_name = "LINES",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "1000",
+        conflicts_with_all = [
+            "groups", "format", "vimgrep", "output_format", "count_matches_by", "correlate_by",
+            "passthru", "context_delimiter", "record_separator", "then", "count_files", "gaps",
+            "hex",
+        ]
+    )]
+    density: Option<NonZeroUsize>,
+
+    /// Print before each matching line the time since the previous match of
+    /// its file, read from the line's timestamp as for `--since`
+    #[arg(
+        long = "gaps",
+        conflicts_with_all = [
+            "passthru", "context_delimiter", "record_separator", "then", "count_files",
+            "count_matches_by", "correlate_by", "reverse",
+        ]
+    )]
+    gaps: bool,
+
+    /// With `--gaps`, mark the gaps longer than this with `!`, e.g. `30s`,
+    /// `500ms`, or `2m`
+    #[arg(
+        long = "gap-threshold",
+        value_name = "DURATION",
+        requires = "gaps",
+        value_parser = parse_threshold
+    )]
+    gap_threshold: Option<Duration>,
+
+    /// Fail (exit 1) when the pattern matches, printing a summary of where
+    #[arg(long = "forbid")]
+    forbid: bool,
+
+    /// With `--forbid`, only fail when more than N lines match
+    #[arg(long = "allow-count", value_name = "N", requires = "forbid")]
+    allow_count: Option<usize>,
+
+    /// Print every line as it is read, marking and highlighting the matches;
+    /// a path of `-` reads stdin, e.g. `tail -f app.log | searcher --passthru ERROR -`
+    #[arg(
+        long = "passthru",
+        conflicts_with_all = [
+            "groups", "format", "heading", "vimgrep", "output_format",
+            "count_matches_by", "forbid", "stats_per_pattern",
+        ]
+    )]
+    passthru: bool,
+
+    /// Print each matching line after the lines of its section: those from
+    /// the last line matching REGEX on, e.g. `^fn |^def ` for the enclosing
+    /// function or `^\[.*\]$` for the INI section
+    #[arg(
+        long = "context-delimiter",
+        value_name = "REGEX",
+        conflicts_with_all = [
+            "groups", "format", "heading", "vimgrep", "output_format",
+            "count_matches_by", "forbid", "stats_per_pattern", "passthru",
+        ]
+    )]
+    context_delimiter: Option<String>,
+
+    /// Split each file into records at the lines matching REGEX, e.g. `^$`
+    /// for blank lines between stack traces, and print every record the
+    /// pattern matches as a whole
+    #[arg(
+        long = "record-separator",
+        value_name = "REGEX",
+        conflicts_with_all = [
+            "groups", "format", "heading", "vimgrep", "output_format",
+            "count_matches_by", "forbid", "stats_per_pattern", "passthru",
+            "context_delimiter", "redact",
+        ]
+    )]
+    record_separator: Option<String>,
+
+    /// Drop each match followed within N lines by a line matching PATTERN,
+    /// e.g. `--not-within 5 'retry succeeded' ERROR` for unhandled errors
+    #[arg(
+        long = "not-within",
+        num_args = 2,
+        value_names = ["N", "PATTERN"],
+        conflicts_with_all = [
+            "passthru", "context_delimiter", "record_separator", "count_files", "reverse",
+            "first", "last", "only", "pre", "dedupe", "dedupe_across_files", "cache_dir",
+        ]
+    )]
+    not_within: Option<Vec<String>>,
+
+    /// Print each match followed within `--within` lines by a line matching
+    /// PATTERN, together with that line, e.g. `--then unlock --within 20 lock`
+    #[arg(
+        long = "then",
+        value_name = "PATTERN",
+        requires = "within",
+        conflicts_with_all = [
+            "groups", "format", "heading", "vimgrep", "output_format",
+            "count_matches_by", "forbid", "stats_per_pattern", "passthru",
+            "context_delimiter", "record_separator", "not_within", "count_files", "reverse",
+            "first", "last", "redact", "only", "pre", "dedupe", "dedupe_across_files",
+        ]
+    )]
+    then: Option<String>,
+
+    /// With `--then`, how many lines after a match to look for PATTERN
+    #[arg(long = "within", value_name = "N", requires = "then")]
+    within: Option<usize>,
+
+    /// Print only the number of files with a match, searching several files
+    /// at once and each only up to its first match
+    #[arg(
+        long = "count-files",
+        conflicts_with_all = [
+            "groups", "format", "heading", "vimgrep", "output_format",
+            "count_matches_by", "forbid", "stats_per_pattern", "passthru",
+            "context_delimiter", "record_separator",
+        ]
+    )]
+    count_files: bool,
+
+    /// Search one file per CPU at a time, still printing each file's
+    /// matches in the order the files are found, so output is the same
+    /// from run to run
+    #[arg(
+        long = "parallel",
+        conflicts_with_all = [
+            "passthru", "context_delimiter", "record_separator", "then", "count_files",
+        ]
+    )]
+    parallel: bool,
+
+    /// Search files larger than this in ranges of about this size at once,
+    /// e.g. `256M` (default 64M); implies `--parallel`
+    #[arg(
+        long = "split-size",
+        value_name = "SIZE",
+        value_parser = parse_filesize,
+        conflicts_with_all = [
+            "passthru", "context_delimiter", "record_separator", "then", "count_files",
+        ]
+    )]
+    split_size: Option<u64>,
+
+    /// Search on N threads (default: one per CPU); implies `--parallel`
+    /// unless with `--count-files`
+    #[arg(
+        long = "threads",
+        value_name = "N",
+        conflicts_with_all = ["passthru", "context_delimiter", "record_separator", "then"]
+    )]
+    threads: Option<NonZeroUsize>,
+
+    /// Pin the search threads to these CPUs, one each in turn, e.g. `0-7`
+    /// to keep the search on one NUMA node (Linux only); implies `--parallel`
+    /// unless with `--count-files`
+    #[arg(
+        long = "thread-affinity",
+        value_name = "CPUS",
+        conflicts_with_all = ["passthru", "context_delimiter", "record_separator", "then"]
+    )]
+    thread_affinity: Option<String>,
+
+    /// Print each file's matches from its last line to its first, reading
+    /// it backwards in blocks, e.g. to see the latest log entries first
+    #[arg(
+        long = "reverse",
+        conflicts_with_all = [
+            "passthru", "context_delimiter", "record_separator", "count_files", "only", "pre",
+            "head_bytes", "tail_bytes", "cache_dir",
+        ]
+    )]
+    reverse: bool,
+
+    /// Print only the first matching line of each file, stopping there
+    #[arg(
+        long = "first",
+        conflicts_with_all = [
+            "last", "passthru", "context_delimiter", "record_separator", "count_files",
+            "reverse", "cache_dir",
+        ]
+    )]
+    first: bool,
+
+    /// Print only the last matching line of each file, reading it backwards
+    /// from its end to find it
+    #[arg(
+        long = "last",
+        conflicts_with_all = [
+            "passthru", "context_delimiter", "record_separator", "count_files", "reverse",
+            "only", "pre", "head_bytes", "tail_bytes", "cache_dir",
+        ]
+    )]
+    last: bool,
+
+    /// Print matching lines with every match replaced by `****`, so the
+    /// output can be shared without the secrets or personal data it matched
+    #[arg(
+        long = "redact",
+        conflicts_with_all = [
+            "query", "all_of", "any_of", "none_of", "groups", "vimgrep",
+            "count_matches_by",
+        ]
+    )]
+    redact: bool,
+
+    /// Read PATTERN as bytes written in hex, e.g. `DE AD BE EF`, and print
+    /// the byte offset of every match in the raw contents of each file
+    #[arg(
+        long = "hex",
+        conflicts_with_all = [
+            "ignore_case", "regex", "fixed_strings", "query", "word_regexp",
+            "all_of", "any_of", "none_of", "patterns", "groups", "format",
+            "vimgrep", "output_format", "count_matches_by", "forbid",
+            "stats_per_pattern", "passthru", "context_delimiter", "record_separator",
+            "not_within", "then", "gaps", "count_files", "reverse", "first", "last", "redact", "sample", "head_bytes", "tail_bytes",
+        ]
+    )]
+    hex: bool,
+}
+
+/// The values of `--output`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Matching lines, laid out by the other output flags
+    Text,
+    /// GitHub Actions `::warning` annotations
+    Github,
+    /// A SARIF 2.1.0 report, for code scanning tools
+    Sarif,
+}
+
+/// The values of `--quote`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum QuoteStyle {
+    /// Write paths and lines as they are
+    None,
+    /// Single quotes where a shell would need them, e.g. `'my notes.txt'`
+    Shell,
+    /// Double quotes with C escapes, e.g. `"tab\there"`
+    C,
+}
+
+/// The values of `--only`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OnlyScope {
+    /// Line and block comments
+    Comments,
+    /// String literals
+    Strings,
+    /// Everything outside comments and strings
+    Code,
+    /// The text between the tags of HTML and XML files
+    Text,
+}
+
+/// The values of `--normalize`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum NormalizeForm {
+    /// Canonical composition
+    Nfc,
+    /// Canonical decomposition
+    Nfd,
+    /// Compatibility composition, which also folds ligatures and full-width forms
+    Nfkc,
+    /// Compatibility decomposition
+    Nfkd,
+}
+
+/// What `print_matches` prints for each matching line.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Layout<'a> {
+    /// The line itself, laid out by the printer options
+    Lines,
+    /// Its regex capture groups (`--groups`)
+    Groups,
+    /// One `path:line:column:content` entry per match (`--vimgrep`)
+    Vimgrep,
+    /// A GitHub Actions annotation titled with the pattern (`--output github`)
+    Github(&'a str),
+    /// A result in a SARIF report with the pattern as its rule (`--output sarif`)
+    Sarif(&'a str),
+    /// Nothing, but the line counts towards a frequency table of the values
+    /// captured by this regex (`--count-matches-by`)
+    CountBy(&'a str),
+    /// Nothing, but the line is added to the session of the value captured
+    /// by this regex, printed once every file is searched (`--correlate-by`)
+    CorrelateBy(&'a str),
+    /// Nothing, but the line counts towards the matches per block of this
+    /// many lines of its file (`--density`)
+    Density(NonZeroUsize),
+}
+
+impl Layout<'_> {
+    /// Lines, or capture groups with `--groups`, which only a regex matcher
+    /// has: the matchers of `--query` and `--all-of` / `--any-of` have none.
+    fn new(groups: bool, matcher: &Matcher) -> Result<Self> {
+        if !groups {
+            return Ok(Layout::Lines);
+        }
+        if !matches!(matcher, Matcher::Regex { .. }) {
+            bail!("`--groups` needs a single regex pattern");
+        }
+        Ok(Layout::Groups)
+    }
+}
+
+/// Arguments for `searcher watch`.
+#[derive(Args)]
+struct WatchArgs {
+    #[command(flatten)]
+    matching: MatchArgs,
+
+    #[command(flatten)]
+    input: InputArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+/// Arguments for `searcher tui`.
+#[derive(Args)]
+struct TuiArgs {
+    /// The files or directories to search
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Perform case-insensitive matching
+    #[arg(short = 'i', long = "ignore-case")]
+    ignore_case: bool,
+
+    /// Interpret the pattern as a regular expression
+    #[arg(short = 'r', long = "regex", conflicts_with = "query")]
     regex: bool,
+
+    /// Interpret the pattern as a boolean query, e.g. `error AND NOT retry`
+    #[arg(long = "query")]
+    query: bool,
 }
 
-fn main() -> Result<()> {
-    let args = Cli::parse();
+/// Arguments for `searcher replace`.
+#[derive(Args)]
+#[command(group(ArgGroup::new("writes").args(["write", "interactive"]).multiple(true)))]
+struct ReplaceArgs {
+    #[command(flatten)]
+    matching: MatchArgs,
 
-    let file = File::open(&args.path)
-        .with_context(|| format!("Could not read file `{}`", args.path.display()))?;
+    /// Text to replace every match with (`$1` / `${name}` expand regex groups)
+    replacement: String,
 
-    let matcher = Matcher::new(&args.pattern, args.ignore_case, args.regex)?;
-    let matches = search_lines(file, &matcher)?;
+    /// The files or directories to rewrite
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    #[command(flatten)]
+    splitting: LineArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+
+    /// Write replacements back to the files instead of printing them
+    #[arg(long = "write", conflicts_with_all = ["diff", "lines", "null_data"])]
+    write: bool,
+
+    /// Show a unified diff of the proposed replacements without writing
+    #[arg(long = "diff", conflicts_with_all = ["lines", "null_data"])]
+    diff: bool,
+
+    /// Keep a copy of each rewritten file with this suffix, e.g. `.bak`;
+    /// needs `--write` or `--interactive`
+    #[arg(
+        long = "backup-suffix",
+        value_name = "SUFFIX",
+        requires = "writes",
+        conflicts_with = "diff"
+    )]
+    backup_suffix: Option<String>,
+
+    /// Confirm each file's replacements before writing it
+    #[arg(long = "interactive", conflicts_with_all = ["diff", "lines", "null_data"])]
+    interactive: bool,
+
+    /// Don't print warnings about files that could not be read or written
+    #[arg(short = 's', long = "no-messages")]
+    no_messages: bool,
+}
+
+/// Arguments for `searcher count` and `searcher files`.
+#[derive(Args)]
+struct SelectArgs {
+    #[command(flatten)]
+    matching: MatchArgs,
+
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Write paths with SEP between their components, e.g. `/` on Windows
+    #[arg(long = "path-separator", value_name = "SEP", value_parser = parse_path_separator)]
+    path_separator: Option<char>,
+
+    /// Quote paths for a POSIX shell, or as C string literals
+    #[arg(long = "quote", value_name = "STYLE", default_value = "none")]
+    quote: QuoteStyle,
+
+    /// Search one file per CPU at a time, still printing the files in the
+    /// order they are found
+    #[arg(long = "parallel")]
+    parallel: bool,
+
+    /// Search files larger than this in ranges of about this size at once,
+    /// e.g. `256M` (default 64M); implies `--parallel`
+    #[arg(long = "split-size", value_name = "SIZE", value_parser = parse_filesize)]
+    split_size: Option<u64>,
+
+    /// Search on N threads (default: one per CPU); implies `--parallel`
+    #[arg(long = "threads", value_name = "N")]
+    threads: Option<NonZeroUsize>,
+
+    /// Pin the search threads to these CPUs, one each in turn, e.g. `0-7`
+    /// (Linux only); implies `--parallel`
+    #[arg(long = "thread-affinity", value_name = "CPUS")]
+    thread_affinity: Option<String>,
+}
+
+impl SelectArgs {
+    /// The threads to search on, if searching in parallel.
+    fn parallel(&self) -> Result<Option<Threads>> {
+        let parallel = self.parallel
+            || self.split_size.is_some()
+            || self.threads.is_some()
+            || self.thread_affinity.is_some();
+        parallel
+            .then(|| threads(self.threads, self.thread_affinity.as_deref()))
+            .transpose()
+    }
+
+    /// `path` as `--path-separator` and `--quote` ask for it.
+    fn shown_path(&self, path: &Path) -> String {
+        let path = display_path(path, self.path_separator);
+        String::from_utf8_lossy(&self.quote.quote().apply(path.as_bytes())).into_owned()
+    }
+}
+
+impl MatchArgs {
+    fn matcher_options(&self) -> MatcherOptions {
+        MatcherOptions {
+            ignore_case: self.ignore_case,
+            regex: self.regex,
+            fixed_strings: self.fixed_strings,
+            query: self.query,
+            word_regexp: self.word_regexp,
+            all_of: self.all_of.clone(),
+            any_of: self.any_of.clone(),
+            none_of: self.none_of.clone(),
+        }
+    }
+
+    fn build_matcher(&self) -> Result<Matcher> {
+        query::build_matcher(&self.pattern, &self.matcher_options())
+    }
+
+    /// A matcher for another pattern of the command line, such as the one
+    /// of `--not-within`, taking `-i`, `-r`, `-F`, and `-w` from the main
+    /// pattern. It is matched against lines as they are read.
+    fn build_other_matcher(&self, pattern: &str) -> Result<Matcher> {
+        let options = MatcherOptions {
+            query: false,
+            all_of: Vec::new(),
+            any_of: Vec::new(),
+            none_of: Vec::new(),
+            ..self.matcher_options()
+        };
+        query::build_matcher(pattern, &options)
+    }
+
+    /// Like `build_matcher`, with every pattern brought into the form the
+    /// lines will be in: the `--normalize` form, and folded by `--locale`
+    /// for `-i`.
+    fn build_matcher_for(&self, input: &InputArgs) -> Result<Matcher> {
+        let prepare_all = |patterns: &[String]| -> Result<Vec<String>> {
+            patterns
+                .iter()
+                .map(|pattern| self.prepare_pattern(input, pattern))
+                .collect()
+        };
+        let options = MatcherOptions {
+            all_of: prepare_all(&self.all_of)?,
+            any_of: prepare_all(&self.any_of)?,
+            none_of: prepare_all(&self.none_of)?,
+            ..self.matcher_options_for_locale()?
+        };
+        query::build_matcher(&self.prepare_pattern(input, &self.pattern)?, &options)
+    }
+
+    /// `matcher_options`, less what `--locale` does instead: with it,
+    /// `search_options` folds the lines for `-i` and keeps only whole words
+    /// for `-w`. Regexes keep `-i`, as folding them could change what they
+    /// mean, e.g. `\S` into `\s`.
+    fn matcher_options_for_locale(&self) -> Result<MatcherOptions> {
+        #[allow(unused_mut)]
+        let mut options = self.matcher_options();
+        #[cfg(feature = "icu")]
+        if self.locale.is_some() {
+            if self.query {
+                bail!("`--locale` does not support `--query`");
+            }
+            let combined =
+                !self.all_of.is_empty() || !self.any_of.is_empty() || !self.none_of.is_empty();
+            if self.word_regexp && combined {
+                bail!(
+                    "`--locale` with `-w` needs a single pattern, without `--all-of`, `--any-of`, or `--none-of`"
+                );
+            }
+            options.word_regexp = false;
+            options.ignore_case &= options.uses_regex();
+        }
+        Ok(options)
+    }
+
+    /// `pattern` in the form the lines will be in: spaced as `--normalize-ws`
+    /// spaces them, in the `--normalize` form, and folded by `--locale` for
+    /// `-i`.
+    fn prepare_pattern(&self, input: &InputArgs, pattern: &str) -> Result<String> {
+        let pattern = input.normalize_pattern(pattern, !self.matcher_options().uses_regex());
+        #[cfg(feature = "icu")]
+        if let Some(locale) = self.locale()?
+            && self.ignore_case
+            && !self.matcher_options().uses_regex()
+        {
+            return Ok(locale.fold(&pattern).into_owned());
+        }
+        Ok(pattern)
+    }
+
+    /// `input.search_options()`, plus the stages `--locale` adds: folding
+    /// each line for `-i`, and keeping only whole-word matches for `-w`.
+    fn search_options(&self, input: &InputArgs) -> Result<SearchOptions> {
+        #[allow(unused_mut)]
+        let mut options = input.search_options()?;
+        #[cfg(feature = "icu")]
+        if let Some(locale) = self.locale()? {
+            if self.ignore_case {
+                options.transforms.push(locale.clone());
+            }
+            if self.word_regexp {
+                let transforms = options.transforms.clone();
+                options
+                    .match_filters
+                    .push(move |_: usize, line: &[u8], matcher: &Matcher| {
+                        locale.has_word_match(&transforms.apply(line), matcher)
+                    });
+            }
+        }
+        Ok(options)
+    }
 
-    for search_match in matches {
-        if args.line_numbers {
-            println!("{}:{}", search_match.line_number, search_match.content);
+    /// The `--locale`, if any.
+    #[cfg(feature = "icu")]
+    fn locale(&self) -> Result<Option<Locale>> {
+        self.locale.as_deref().map(Locale::new).transpose()
+    }
+
+    /// The `--locale` tag, for keys of the result cache.
+    fn locale_tag(&self) -> Option<&str> {
+        #[cfg(feature = "icu")]
+        return self.locale.as_deref();
+        #[cfg(not(feature = "icu"))]
+        None
+    }
+}
+
+impl LineArgs {
+    /// NUL with `-z`, otherwise CRLF unless `--no-crlf` keeps the `\r`, so
+    /// files with Windows line endings print the same everywhere.
+    fn line_terminator(&self) -> LineTerminator {
+        if self.null_data {
+            LineTerminator::Nul
+        } else if self.no_crlf {
+            LineTerminator::Lf
         } else {
-            println!("{}", search_match.content);
+            LineTerminator::Crlf
         }
     }
 
-    Ok(())
+    /// Options splitting lines as asked, and stopping at Ctrl-C.
+    fn search_options(&self) -> SearchOptions {
+        SearchOptions {
+            line_terminator: self.line_terminator(),
+            line_ranges: self.lines.clone(),
+            line_offset: self.line_offset,
+            cancel: Some(interrupt_token().clone()),
+            ..SearchOptions::default()
+        }
+    }
+}
+
+impl InputArgs {
+    /// The paths to search: as given, or only the files git tracks below
+    /// them with `--git-tracked`.
+    fn files(&self) -> Result<Vec<PathBuf>> {
+        if self.git_tracked {
+            git::tracked_files(&self.paths)
+        } else {
+            Ok(self.paths.clone())
+        }
+    }
+
+    /// The Unicode normalization `--normalize` asks for.
+    fn normalization(&self) -> Option<Normalization> {
+        self.normalize.map(|form| match form {
+            NormalizeForm::Nfc => Normalization::Nfc,
+            NormalizeForm::Nfd => Normalization::Nfd,
+            NormalizeForm::Nfkc => Normalization::Nfkc,
+            NormalizeForm::Nfkd => Normalization::Nfkd,
+        })
+    }
+
+    /// `pattern` with its whitespace collapsed for `--normalize-ws` if it is
+    /// `literal` (a regex's spaces may be escaped or quantified), then in the
+    /// `--normalize` form, if any.
+    fn normalize_pattern(&self, pattern: &str, literal: bool) -> String {
+        let mut pattern = pattern.to_string();
+        if self.normalize_ws && literal {
+            let collapsed = NormalizeWhitespace.transform(pattern.as_bytes());
+            pattern = String::from_utf8_lossy(&collapsed).into_owned();
+        }
+        match self.normalization() {
+            Some(form) => form.normalize(&pattern).into_owned(),
+            None => pattern,
+        }
+    }
+
+    /// Reports skipped files as `--no-messages` and `--strict` ask.
+    fn messages(&self) -> Messages {
+        let mut messages = Messages::new(self.no_messages);
+        messages.strict = self.strict;
+        messages.json = self.error_format == ErrorFormat::Json;
+        messages.fail_if_match = self.fail_if_match;
+        messages.fail_if_no_match = self.fail_if_no_match;
+        messages.ignore_errors = self.ignore_errors;
+        messages.partial = self.partial_note();
+        messages
+    }
+
+    /// What `--sample`, `--head-bytes`, and `--tail-bytes` leave out, for
+    /// the notice that the results are partial.
+    fn partial_note(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(every) = self.sample {
+            parts.push(format!("one line in every {}", every));
+        }
+        if let Some(bytes) = self.head_bytes {
+            parts.push(format!("the first {} bytes of each file", bytes));
+        }
+        if let Some(bytes) = self.tail_bytes {
+            parts.push(format!(
+                "the last {} bytes of each file, with line numbers counted from there",
+                bytes
+            ));
+        }
+        if parts.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "results are partial: searched only {}",
+            parts.join(" of ")
+        ))
+    }
+
+    /// The part of each file `--head-bytes` or `--tail-bytes` reads.
+    fn byte_window(&self) -> Option<ByteWindow> {
+        self.head_bytes
+            .map(ByteWindow::Head)
+            .or(self.tail_bytes.map(ByteWindow::Tail))
+    }
+
+    /// Walks `paths`, skipping files larger than `--max-filesize` or outside
+    /// `--newer-than` / `--older-than`, other file systems with
+    /// `--one-file-system`, the `--cache-dir` directory, and what ignore
+    /// files list unless `--no-ignore` is given.
+    fn walk<P: AsRef<Path>>(&self, paths: impl IntoIterator<Item = P>) -> Result<Walk> {
+        let mut walk = Walk::new(paths);
+        if !self.no_ignore {
+            walk = walk.ignore_files();
+            let global = ignore::global_ignore_path().filter(|path| path.is_file());
+            for path in global.iter().chain(&self.ignore_file) {
+                let file = fs::read_to_string(path)
+                    .with_context(|| format!("Could not read ignore file `{}`", path.display()))?;
+                let ignore = IgnoreFile::parse(&file)
+                    .with_context(|| format!("Invalid ignore file `{}`", path.display()))?;
+                walk = walk.with_global_ignore(ignore);
+            }
+        }
+        if let Some(limit) = self.max_filesize {
+            walk = walk.with_max_filesize(limit);
+        }
+        if let Some(time) = self.newer_than {
+            walk = walk.newer_than(time);
+        }
+        if let Some(time) = self.older_than {
+            walk = walk.older_than(time);
+        }
+        if self.one_file_system {
+            walk = walk.one_file_system();
+        }
+        if let Some(dir) = &self.cache_dir {
+            walk = walk.skip_dir(dir);
+        }
+        Ok(walk)
+    }
+
+    /// Opens the `--cache-dir` cache for searching with `matcher`, if one was
+    /// asked for.
+    ///
+    /// Its key covers the matcher, every option that changes which lines
+    /// match, and the version, so that no other search ever reads its
+    /// entries.
+    fn result_cache(&self, matching: &MatchArgs, matcher: &Matcher) -> Result<Option<ResultCache>> {
+        let Some(dir) = &self.cache_dir else {
+            return Ok(None);
+        };
+        let key = format!(
+            "{} {:016x} {:?} {:?} {:?} {:?} {:?} {:?}",
+            env!("CARGO_PKG_VERSION"),
+            matcher.fingerprint(),
+            (
+                self.splitting.line_terminator(),
+                &self.splitting.lines,
+                self.splitting.line_offset
+            ),
+            (
+                &self.since,
+                &self.until,
+                &self.time_pattern,
+                &self.time_format
+            ),
+            (self.only, self.dedupe, self.normalize_ws, self.normalize),
+            (self.sample, self.byte_window()),
+            (self.jsonl, &self.fields),
+            (
+                matching.locale_tag(),
+                matching.ignore_case,
+                matching.word_regexp
+            ),
+        );
+        ResultCache::open(dir, hash_bytes(key.as_bytes())).map(Some)
+    }
+
+    fn search_options(&self) -> Result<SearchOptions> {
+        let mut filters = LineFilters::new();
+        if self.since.is_some() || self.until.is_some() {
+            let mut range =
+                TimeRange::new(self.time_pattern.as_deref(), self.time_format.as_deref())?;
+            if let Some(since) = &self.since {
+                range = range.since(since).context("Invalid `--since`")?;
+            }
+            if let Some(until) = &self.until {
+                range = range.until(until).context("Invalid `--until`")?;
+            }
+            filters.push(range);
+        }
+        if let Some(every) = self.sample {
+            filters.push(Sample::new(every));
+        }
+        let preprocessor = match &self.pre {
+            Some(command) => Some(Preprocessor::new(command).with_globs(&self.pre_glob)?),
+            None => None,
+        };
+        let mut transforms = LineTransforms::new();
+        let mut match_filters = MatchFilters::new();
+        // Fields are taken out first, so the other stages see their values
+        if let Some(fields) = self.json_fields()? {
+            transforms.push(fields.clone());
+            match_filters.push(fields);
+        }
+        if self.normalize_ws {
+            transforms.push(NormalizeWhitespace);
+        }
+        if let Some(form) = self.normalization() {
+            transforms.push(form);
+        }
+        Ok(SearchOptions {
+            timeout: self.timeout,
+            filters,
+            preprocessor,
+            scope: self.only.map(|only| match only {
+                OnlyScope::Comments => Scope::Comments,
+                OnlyScope::Strings => Scope::Strings,
+                OnlyScope::Code => Scope::Code,
+                OnlyScope::Text => Scope::Text,
+            }),
+            dedupe: if self.dedupe_across_files {
+                Some(Dedupe::across_inputs())
+            } else if self.dedupe {
+                Some(Dedupe::per_input())
+            } else {
+                None
+            },
+            transforms,
+            match_filters,
+            byte_window: self.byte_window(),
+            skip_line_numbers: false,
+            ..self.splitting.search_options()
+        })
+    }
+
+    /// The `--field`s to search with `--jsonl`, if it is set.
+    fn json_fields(&self) -> Result<Option<JsonFields>> {
+        if !self.jsonl {
+            return Ok(None);
+        }
+        JsonFields::new(&self.fields)
+            .map(Some)
+            .context("Invalid `--field`")
+    }
+
+    /// Reads the timestamps of matches for `--gaps`, as `--since` would.
+    fn gaps(&self, threshold: Option<Duration>) -> Result<Gaps> {
+        let times = TimeRange::new(self.time_pattern.as_deref(), self.time_format.as_deref())?;
+        Ok(match threshold {
+            Some(threshold) => Gaps::new(times).with_threshold(threshold),
+            None => Gaps::new(times),
+        })
+    }
+}
+
+/// Parses `--timeout`, a positive number of seconds.
+fn parse_timeout(value: &str) -> Result<Duration> {
+    let secs: f64 = value
+        .parse()
+        .with_context(|| format!("`{}` is not a number of seconds", value))?;
+    if secs <= 0.0 {
+        bail!("the timeout must be greater than zero");
+    }
+    Duration::try_from_secs_f64(secs).with_context(|| format!("`{}` is not a valid timeout", value))
+}
+
+/// Parses `--path-separator`, which must be a single character.
+fn parse_path_separator(value: &str) -> Result<char> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(separator), None) => Ok(separator),
+        _ => bail!("expected a single character, got `{}`", value),
+    }
+}
+
+/// Parses `--max-filesize`, a number of bytes with an optional `K`, `M`, or
+/// `G` suffix.
+fn parse_filesize(value: &str) -> Result<u64> {
+    let (digits, unit) = match value.char_indices().last() {
+        Some((i, suffix)) if suffix.is_ascii_alphabetic() => {
+            let unit = match suffix.to_ascii_uppercase() {
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                _ => bail!("`{}` has an unknown suffix (use K, M, or G)", value),
+            };
+            (&value[..i], unit)
+        }
+        _ => (value, 1),
+    };
+    let size: u64 = digits
+        .parse()
+        .with_context(|| format!("`{}` is not a size such as `50M`", value))?;
+    size.checked_mul(unit)
+        .with_context(|| format!("`{}` is too large", value))
+}
+
+/// Parses `--newer-than` / `--older-than`, taking ages back from now.
+fn parse_file_time_now(value: &str) -> Result<SystemTime> {
+    parse_file_time(value, SystemTime::now())
+}
+
+impl OutputArgs {
+    /// The printer options asked for, ending printed lines with NUL if the
+    /// input was split on it (`-z`).
+    fn printer_options(&self, multiple_files: bool, null_data: bool) -> PrinterOptions {
+        let heading = if self.heading {
+            Some(true)
+        } else if self.no_heading {
+            Some(false)
+        } else {
+            None
+        };
+        PrinterOptions {
+            line_numbers: self.line_numbers,
+            with_filename: multiple_files,
+            heading: use_heading(heading, multiple_files),
+            template: self.format.clone(),
+            color: io::stdout().is_terminal(),
+            sanitize: !self.no_sanitize,
+            path_separator: self.path_separator,
+            quote: self.quote.quote(),
+            null_data,
+        }
+    }
+}
+
+impl QuoteStyle {
+    fn quote(self) -> Quote {
+        match self {
+            QuoteStyle::None => Quote::None,
+            QuoteStyle::Shell => Quote::Shell,
+            QuoteStyle::C => Quote::C,
+        }
+    }
+}
+
+/// Whether to print file names as headings: as requested, or by default
+/// when several files are written to a terminal.
+fn use_heading(requested: Option<bool>, multiple_files: bool) -> bool {
+    requested.unwrap_or_else(|| multiple_files && io::stdout().is_terminal())
+}
+
+/// Runs the command, exiting with 2 if it fails as a whole, e.g. because
+/// its pattern is invalid, like clap does for bad arguments.
+fn main() -> ExitCode {
+    let cli = Cli::parse_from(with_default_subcommand(env::args_os()));
+    run(cli.command).unwrap_or_else(|error| {
+        eprintln!("Error: {:?}", error);
+        ExitCode::from(2)
+    })
+}
+
+fn run(command: Command) -> Result<ExitCode> {
+    match command {
+        Command::Find(args) => run_find(&args),
+        Command::Replace(args) => run_replace(&args),
+        Command::Count(args) => run_count(&args),
+        Command::Files(args) => run_files(&args),
+        Command::Extract(args) => run_extract(&args),
+        Command::Dups(args) => run_dups(&args),
+        Command::Run(args) => run_job(&args),
+        Command::Lint(args) => run_lint(&args),
+        Command::Secrets(args) => run_secrets(&args),
+        Command::GitLog(args) => run_git_log(&args),
+        Command::Index(IndexCommand::Build(args)) => run_index_build(&args),
+        Command::Index(IndexCommand::Query(args)) => run_index_query(&args),
+        Command::Cache(CacheCommand::Clear(args)) => run_cache_clear(&args),
+        Command::Watch(args) => run_watch(&args),
+        Command::Tui(args) => run_tui(&args),
+        Command::Serve(args) => run_serve(&args),
+        Command::Bench(args) => run_bench(&args),
+        Command::Man(args) => run_man(&args),
+    }
+}
+
+/// Inserts `find` after the program name unless the first argument already
+/// names a subcommand or asks for help or the version.
+///
+/// This keeps `searcher PATTERN PATH...` working. A pattern that happens to
+/// equal a subcommand name needs the explicit form: `searcher find count FILE`.
+fn with_default_subcommand(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut args: Vec<OsString> = args.into_iter().collect();
+    let Some(first) = args.get(1) else {
+        return args;
+    };
+
+    let first = first.to_string_lossy();
+    let is_subcommand = first == "help"
+        || Cli::command()
+            .get_subcommands()
+            .any(|subcommand| subcommand.get_name() == first);
+    let is_top_level_flag = matches!(first.as_ref(), "-h" | "--help" | "-V" | "--version");
+    if !is_subcommand && !is_top_level_flag {
+        args.insert(1, OsString::from("find"));
+    }
+    args
+}
+
+/// The token cancelled by Ctrl-C, installing the handler on first use.
+///
+/// The first Ctrl-C lets the current line finish and stops the search; a
+/// second one exits immediately.
+fn interrupt_token() -> &'static CancellationToken {
+    static TOKEN: OnceLock<CancellationToken> = OnceLock::new();
+    TOKEN.get_or_init(|| {
+        use signal_hook::consts::SIGINT;
+        use signal_hook::flag;
+
+        let token = CancellationToken::new();
+        // Registered first so it only sees the flag set by an earlier Ctrl-C
+        flag::register_conditional_shutdown(SIGINT, 130, token.flag()).ok();
+        flag::register(SIGINT, token.flag()).ok();
+        token
+    })
+}
+
+/// Reports files that could not be searched without stopping the search,
+/// and what was searched before an interrupt.
+struct Messages {
+    quiet: bool,
+    /// Whether permission errors are failures rather than only counted
+    strict: bool,
+    /// Whether every file skipped, including those only counted, is
+    /// reported as a JSON `error` object, and notices as `notice` objects
+    json: bool,
+    /// Whether the run fails with status 1 if any line matched
+    fail_if_match: bool,
+    /// Whether the run fails with status 1 if no line matched
+    fail_if_no_match: bool,
+    /// Whether files that could not be searched leave the exit status alone
+    ignore_errors: bool,
+    failures: Cell<usize>,
+    too_large: Cell<usize>,
+    denied: Cell<usize>,
+    searched: Cell<usize>,
+    matched: Cell<usize>,
+    /// Why the results are partial, if they are
+    partial: Option<String>,
+}
+
+impl Messages {
+    fn new(quiet: bool) -> Self {
+        Messages {
+            quiet,
+            strict: false,
+            json: false,
+            fail_if_match: false,
+            fail_if_no_match: false,
+            ignore_errors: false,
+            failures: Cell::new(0),
+            too_large: Cell::new(0),
+            denied: Cell::new(0),
+            searched: Cell::new(0),
+            matched: Cell::new(0),
+            partial: None,
+        }
+    }
+
+    /// Records a file that was searched, for the summary printed on Ctrl-C.
+    fn searched(&self, matches: usize) {
+        self.searched.set(self.searched.get() + 1);
+        self.matched.set(self.matched.get() + matches);
+    }
+
+    /// Records a file that was skipped, printing why unless `--no-messages` is set.
+    ///
+    /// Files over `--max-filesize`, and unless `--strict` is set, files and
+    /// directories we lack permission to read, are only counted, for the
+    /// notices printed by [`Messages::finish`], and do not make the run fail.
+    fn warn(&self, error: &anyhow::Error) {
+        if self.json && !self.quiet {
+            eprintln!("{}", Diagnostic::new(error).to_json());
+        }
+        if has_io_error(error, io::ErrorKind::FileTooLarge) {
+            self.too_large.set(self.too_large.get() + 1);
+            return;
+        }
+        if !self.strict && has_io_error(error, io::ErrorKind::PermissionDenied) {
+            self.denied.set(self.denied.get() + 1);
+            return;
+        }
+        self.failures.set(self.failures.get() + 1);
+        if !self.json && !self.quiet {
+            eprintln!("searcher: {:#}", error);
+        }
+    }
+
+    /// Prints a notice about the run as a whole, as a JSON `notice` object
+    /// with `--error-format json`.
+    fn notice(&self, message: &str) {
+        if self.json {
+            let notice = serde_json::json!({ "type": "notice", "message": message });
+            eprintln!("{}", notice);
+        } else {
+            eprintln!("searcher: {}", message);
+        }
+    }
+
+    /// Prints how many files were skipped, and whether the results are
+    /// partial, and returns the exit code for the run: 2 if a file could not
+    /// be searched (unless `--ignore-errors` is set), else 1 if
+    /// `--fail-if-match` or `--fail-if-no-match` applies, else 0.
+    ///
+    /// After Ctrl-C, prints what was searched before it instead and exits
+    /// with 130, as shells do for processes killed by SIGINT.
+    fn finish(&self) -> ExitCode {
+        if interrupt_token().is_cancelled() {
+            let files = self.searched.get();
+            let lines = self.matched.get();
+            self.notice(&format!(
+                "interrupted after searching {} {} ({} matching {})",
+                files,
+                if files == 1 { "file" } else { "files" },
+                lines,
+                if lines == 1 { "line" } else { "lines" },
+            ));
+            return ExitCode::from(130);
+        }
+        let too_large = self.too_large.get();
+        if too_large > 0 && !self.quiet {
+            self.notice(&format!(
+                "skipped {} {} larger than `--max-filesize`",
+                too_large,
+                if too_large == 1 { "file" } else { "files" },
+            ));
+        }
+        let denied = self.denied.get();
+        if denied > 0 && !self.quiet {
+            self.notice(&format!(
+                "skipped {} {} without permission to read {} (use `--strict` to report them)",
+                denied,
+                if denied == 1 { "path" } else { "paths" },
+                if denied == 1 { "it" } else { "them" },
+            ));
+        }
+        if let Some(partial) = &self.partial {
+            self.notice(partial);
+        }
+        let failures = self.failures.get();
+        if failures > 0 && !self.quiet {
+            let noun = if failures == 1 { "file" } else { "files" };
+            self.notice(&format!("{} {} could not be searched", failures, noun));
+        }
+        let matched = self.matched.get() > 0;
+        if failures > 0 && !self.ignore_errors {
+            ExitCode::from(2)
+        } else if (self.fail_if_match && matched) || (self.fail_if_no_match && !matched) {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+/// Whether `error` was caused by an I/O error of the given kind, from
+/// walking a directory or from opening or reading a file.
+fn has_io_error(error: &anyhow::Error, kind: io::ErrorKind) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<FileError>()
+            .is_some_and(|error| error.error.kind() == kind)
+            || cause
+                .downcast_ref::<io::Error>()
+                .is_some_and(|error| error.kind() == kind)
+    })
+}
+
+/// Runs `searcher cache clear`.
+fn run_cache_clear(args: &CacheClearArgs) -> Result<ExitCode> {
+    let entries = ResultCache::clear(&args.dir)?;
+    println!(
+        "Removed {} cached result{} from `{}`",
+        entries,
+        if entries == 1 { "" } else { "s" },
+        args.dir.display()
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Runs `searcher serve` until Ctrl-C, or with `--stdio` until the client
+/// shuts it down.
+fn run_serve(args: &ServeArgs) -> Result<ExitCode> {
+    let server = SearchServer::new().with_cancel(interrupt_token().clone());
+    match (&args.socket, &args.http) {
+        (Some(socket), _) => serve_socket(&server, socket)?,
+        (None, Some(address)) => serve_http(&server, address)?,
+        (None, None) if args.stdio => rpc::serve(&server, io::stdin().lock(), io::stdout())?,
+        (None, None) => unreachable!("clap requires `--socket`, `--http`, or `--stdio`"),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Serves on the Unix socket at `path`, removing it when done.
+#[cfg(unix)]
+fn serve_socket(server: &SearchServer, path: &Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    // A socket left behind by a server that did not exit cleanly
+    let is_socket =
+        fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket());
+    if is_socket {
+        if UnixStream::connect(path).is_ok() {
+            bail!("`{}` is already in use", path.display());
+        }
+        fs::remove_file(path).ok();
+    }
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Could not listen on `{}`", path.display()))?;
+    let served = server.serve(&listener);
+    fs::remove_file(path).ok();
+    served
+}
+
+#[cfg(not(unix))]
+fn serve_socket(_server: &SearchServer, _path: &Path) -> Result<()> {
+    bail!("`searcher serve --socket` needs Unix domain sockets");
+}
+
+/// Serves `POST /search` over HTTP on `address`.
+#[cfg(feature = "serve-http")]
+fn serve_http(server: &SearchServer, address: &str) -> Result<()> {
+    let http = tiny_http::Server::http(address)
+        .map_err(|error| anyhow::anyhow!(error))
+        .with_context(|| format!("Could not listen on `{}`", address))?;
+    server.serve_http(&http)
+}
+
+#[cfg(not(feature = "serve-http"))]
+fn serve_http(_server: &SearchServer, _address: &str) -> Result<()> {
+    bail!("`searcher serve --http` needs searcher built with the `serve-http` feature");
+}
+
+/// Runs `searcher bench` and prints its report.
+fn run_bench(args: &BenchArgs) -> Result<ExitCode> {
+    let data = fs::read(&args.path).with_context(|| FileContext::read(&args.path))?;
+    let matcher = Matcher::new(&args.pattern, args.ignore_case, args.regex)?;
+    let report = bench::run(&data, &matcher, &SearchOptions::default(), args.iterations)?;
+
+    println!(
+        "Benchmark:    `{}` in {} ({})",
+        args.pattern,
+        args.path.display(),
+        if args.regex { "regex" } else { "literal" }
+    );
+    println!("{}", report);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Runs `searcher man`, rendering the man page from the CLI definition.
+fn run_man(args: &ManArgs) -> Result<ExitCode> {
+    match &args.dir {
+        Some(dir) => clap_mangen::generate_to(Cli::command(), dir)
+            .with_context(|| format!("Could not write man pages to `{}`", dir.display()))?,
+        None => clap_mangen::Man::new(Cli::command()).render(&mut io::stdout().lock())?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Searches the given files and prints the matches.
+fn run_find(args: &FindArgs) -> Result<ExitCode> {
+    if args.hex {
+        return run_hex(args);
+    }
+    let redactor = args.redact.then(Redactor::default);
+    // Matches are found in the transformed line, so their spans would not
+    // cover what is printed
+    if redactor.is_some()
+        && !args
+            .matching
+            .search_options(&args.input)?
+            .transforms
+            .is_empty()
+    {
+        bail!(
+            "`--redact` does not support `--jsonl`, `--normalize-ws`, `--normalize`, or `--locale` with `-i`"
+        );
+    }
+    let patterns = if args.patterns.is_empty() && !args.stats_per_pattern {
+        None
+    } else {
+        let options = args.matching.matcher_options_for_locale()?;
+        let all = std::iter::once(&args.matching.pattern)
+            .chain(&args.patterns)
+            .map(|pattern| args.matching.prepare_pattern(&args.input, pattern))
+            .collect::<Result<Vec<_>>>()?;
+        let build = if options.word_regexp {
+            PatternSet::whole_words
+        } else {
+            PatternSet::new
+        };
+        Some(build(&all, options.ignore_case, options.uses_regex())?)
+    };
+    let built;
+    let matcher = match &patterns {
+        Some(patterns) => patterns.matcher(),
+        None => {
+            built = args.matching.build_matcher_for(&args.input)?;
+            &built
+        }
+    };
+    let options = args.output.printer_options(
+        spans_multiple_files(&args.input.paths),
+        args.input.splitting.null_data,
+    );
+    let messages = args.input.messages();
+    if args.passthru || args.context_delimiter.is_some() {
+        run_passthru(args, matcher, redactor.as_ref(), options, &messages)?;
+        return Ok(messages.finish());
+    }
+    let threads = threads(args.threads, args.thread_affinity.as_deref())?;
+    if args.count_files {
+        let count = count_matching_files(
+            args.input.walk(args.input.files()?)?,
+            &threads,
+            matcher,
+            &args.matching.search_options(&args.input)?,
+            &messages,
+        );
+        println!("{}", count);
+        return Ok(messages.finish());
+    }
+    if let Some(separator) = &args.record_separator {
+        run_records(args, matcher, separator, options, &messages)?;
+        return Ok(messages.finish());
+    }
+    if let Some(then) = &args.then {
+        run_pairs(args, matcher, then, options, &messages)?;
+        return Ok(messages.finish());
+    }
+
+    let layout = match args.output_format {
+        OutputFormat::Github => Layout::Github(&args.matching.pattern),
+        OutputFormat::Sarif => Layout::Sarif(&args.matching.pattern),
+        OutputFormat::Text if args.vimgrep => Layout::Vimgrep,
+        OutputFormat::Text if args.count_matches_by.is_some() => {
+            Layout::CountBy(args.count_matches_by.as_deref().unwrap_or_default())
+        }
+        OutputFormat::Text if args.correlate_by.is_some() => {
+            Layout::CorrelateBy(args.correlate_by.as_deref().unwrap_or_default())
+        }
+        OutputFormat::Text if let Some(lines) = args.density => Layout::Density(lines),
+        OutputFormat::Text => Layout::new(args.groups, matcher)?,
+    };
+    let mut stats = patterns.as_ref().map(PatternStats::new);
+    let mut search_options = args.matching.search_options(&args.input)?;
+    let searchers = Searchers::new(matcher);
+    // `--last` then only reads the end of a file when no line number is shown
+    search_options.skip_line_numbers = matches!(layout, Layout::Lines)
+        && !args.output.line_numbers
+        && args.output.format.is_none()
+        && !args.gaps;
+    let cache = args.input.result_cache(&args.matching, matcher)?;
+    let parallel = args.parallel
+        || args.split_size.is_some()
+        || args.threads.is_some()
+        || args.thread_affinity.is_some();
+    let files = args.input.files()?;
+    let (threads, split) = split(parallel.then_some(&threads), args.split_size, &files);
+    let json_fields = args.input.json_fields()?;
+    let gaps = args
+        .gaps
+        .then(|| args.input.gaps(args.gap_threshold))
+        .transpose()?;
+    let not_within = match args.not_within.as_deref() {
+        Some([lines, pattern]) => {
+            let lines: usize = lines.parse().with_context(|| {
+                format!(
+                    "Invalid `--not-within`: `{}` is not a number of lines",
+                    lines
+                )
+            })?;
+            Some((lines, args.matching.build_other_matcher(pattern)?))
+        }
+        _ => None,
+    };
+    #[cfg(feature = "profiling")]
+    let profiler = args.stats.then(Profiler::start);
+    let started = Instant::now();
+    let matched = print_matches(
+        args.input.walk(files)?,
+        threads,
+        matcher,
+        patterns.as_ref().zip(stats.as_mut()),
+        |path| {
+            let mut matches = if args.first {
+                Vec::from_iter(first_match(path, matcher, &search_options)?)
+            } else if args.last {
+                Vec::from_iter(last_match(path, matcher, &search_options)?)
+            } else if args.reverse {
+                search_path_reverse(path, matcher, &search_options)?
+            } else if let Some((within, other)) = &not_within {
+                search_path_not_within(path, matcher, other, *within, &search_options)?
+            } else {
+                search_file(
+                    path,
+                    &searchers,
+                    &search_options,
+                    cache.as_ref(),
+                    split.as_ref(),
+                )?
+            };
+            if let Some(fields) = &json_fields {
+                fields.annotate(&mut matches);
+            }
+            if let Some(gaps) = &gaps {
+                gaps.annotate(&mut matches);
+            }
+            Ok(matches)
+        },
+        redactor.as_ref(),
+        options,
+        layout,
+        &messages,
+    )?;
+    let status = messages.finish();
+    if let Some(stats) = stats.filter(|_| args.stats_per_pattern) {
+        report_pattern_stats(&stats);
+    }
+    if args.stats {
+        report_stats(&matched, started.elapsed(), &messages);
+        #[cfg(feature = "profiling")]
+        if let Some(profiler) = profiler {
+            eprintln!("{}", profiler.finish());
+        }
+    }
+    if args.forbid && !interrupt_token().is_cancelled() {
+        let allowed = args.allow_count.unwrap_or(0);
+        let mut pattern = args.matching.pattern.clone();
+        for extra in &args.patterns {
+            pattern = format!("{}` or `{}", pattern, extra);
+        }
+        if report_forbidden(&pattern, &matched, allowed) {
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+    Ok(status)
+}
+
+/// Runs `searcher find --hex`: prints `path:offset` for every match of the
+/// byte sequence in each file, or only the offset when searching one file.
+fn run_hex(args: &FindArgs) -> Result<ExitCode> {
+    let pattern = HexPattern::parse(&args.matching.pattern)?;
+    let search_options = SearchOptions {
+        cancel: Some(interrupt_token().clone()),
+        timeout: args.input.timeout,
+        ..SearchOptions::default()
+    };
+    let with_filename = spans_multiple_files(&args.input.paths);
+    let mut stdout = io::stdout().lock();
+    let messages = args.input.messages();
+
+    for (path, offsets) in each_file_in(
+        args.input.walk(args.input.files()?)?,
+        |path| pattern.search_path(path, &search_options),
+        |error| messages.warn(&error),
+    ) {
+        messages.searched(offsets.len());
+        for offset in offsets {
+            if with_filename {
+                let path = display_path(&path, args.output.path_separator);
+                writeln!(stdout, "{}:{}", path, offset)?;
+            } else {
+                writeln!(stdout, "{}", offset)?;
+            }
+        }
+    }
+
+    Ok(messages.finish())
+}
+
+/// Runs `searcher find --record-separator`: prints every record of every
+/// file that `matcher` matches.
+fn run_records(
+    args: &FindArgs,
+    matcher: &Matcher,
+    separator: &str,
+    options: PrinterOptions,
+    messages: &Messages,
+) -> Result<()> {
+    let separator = Matcher::new(separator, false, true).context("Invalid `--record-separator`")?;
+    let search_options = args.matching.search_options(&args.input)?;
+    if !search_options.line_ranges.is_empty()
+        || !search_options.filters.is_empty()
+        || !search_options.match_filters.is_empty()
+        || !search_options.transforms.is_empty()
+        || search_options.preprocessor.is_some()
+        || search_options.scope.is_some()
+        || search_options.dedupe.is_some()
+        || search_options.byte_window.is_some()
+    {
+        bail!(
+            "`--record-separator` matches whole records, so it does not support options that work on single lines, such as `--lines`, `--only`, or `--pre`"
+        );
+    }
+    let mut printer = Printer::new(io::stdout().lock(), options);
+
+    for (path, records) in each_file_in(
+        args.input.walk(args.input.files()?)?,
+        |path| search_path_records(path, matcher, &separator, &search_options),
+        |error| messages.warn(&error),
+    ) {
+        messages.searched(records.len());
+        for record in &records {
+            printer.print_record(&path, record)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs `searcher find --then`: prints every match that a line matching
+/// `then` follows closely enough, and that line after it, with `--` between
+/// the pairs.
+fn run_pairs(
+    args: &FindArgs,
+    matcher: &Matcher,
+    then: &str,
+    options: PrinterOptions,
+    messages: &Messages,
+) -> Result<()> {
+    let then = args.matching.build_other_matcher(then)?;
+    let within = args.within.unwrap_or_default();
+    let search_options = args.matching.search_options(&args.input)?;
+    let mut printer = Printer::new(io::stdout().lock(), options);
+
+    for (path, pairs) in each_file_in(
+        args.input.walk(args.input.files()?)?,
+        |path| search_path_pairs(path, matcher, &then, within, &search_options),
+        |error| messages.warn(&error),
+    ) {
+        messages.searched(pairs.len());
+        for Pair { first, then: next } in &pairs {
+            printer.print_group_separator()?;
+            printer.print_passthru(&path, first.line_number, &first.content, true, matcher)?;
+            printer.print_passthru(&path, next.line_number, &next.content, true, &then)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs `searcher find --passthru`: prints every line of every file, or of
+/// stdin for `-`, as it is read, marking the matching ones. With
+/// `--context-delimiter`, only the matching lines and their sections are
+/// printed.
+fn run_passthru(
+    args: &FindArgs,
+    matcher: &Matcher,
+    redactor: Option<&Redactor>,
+    options: PrinterOptions,
+    messages: &Messages,
+) -> Result<()> {
+    let search_options = args.matching.search_options(&args.input)?;
+    let delimiter = args
+        .context_delimiter
+        .as_deref()
+        .map(|regex| Matcher::new(regex, false, true).context("Invalid `--context-delimiter`"))
+        .transpose()?;
+    let mut printer = Printer::new(io::stdout().lock(), options);
+
+    for path in args.input.files()? {
+        if path == Path::new("-") {
+            let stdin = io::stdin().lock();
+            match printer.print_passthru_reader(
+                Path::new("<stdin>"),
+                stdin,
+                matcher,
+                redactor,
+                delimiter.as_ref(),
+                &search_options,
+            )? {
+                Ok(matched) => messages.searched(matched),
+                Err(error) if error.is::<Cancelled>() => return Ok(()),
+                Err(error) => messages.warn(&error.context("Could not read stdin")),
+            }
+            continue;
+        }
+        for entry in args.input.walk([&path])? {
+            let read = entry.map_err(anyhow::Error::from).and_then(|path| {
+                let file = File::open(&path).with_context(|| FileContext::read(&path))?;
+                Ok((path, file))
+            });
+            let searched = match read {
+                Ok((path, file)) => printer
+                    .print_passthru_reader(
+                        &path,
+                        file,
+                        matcher,
+                        redactor,
+                        delimiter.as_ref(),
+                        &search_options,
+                    )?
+                    .with_context(|| FileContext::read(&path)),
+                Err(error) => Err(error),
+            };
+            match searched {
+                Ok(matched) => messages.searched(matched),
+                Err(error) if error.is::<Cancelled>() => return Ok(()),
+                Err(error) => messages.warn(&error),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prints the `--stats-per-pattern` table to stderr: the lines each pattern
+/// matched, followed by its count in each file.
+fn report_pattern_stats(stats: &PatternStats) {
+    eprintln!("{:>9}  PATTERN / FILE", "LINES");
+    for count in stats.patterns() {
+        eprintln!("{:>9}  {}", count.lines, count.pattern);
+        for (path, lines) in &count.files {
+            eprintln!("{:>9}    {}", lines, display_path(path, None));
+        }
+    }
+}
+
+/// Prints the `--stats` summary to stderr: the lines and files that matched,
+/// how long the search took, and how many files `messages` counted as
+/// skipped without failing the run.
+fn report_stats(matched: &[(PathBuf, usize)], elapsed: Duration, messages: &Messages) {
+    let lines: usize = matched.iter().map(|(_, count)| count).sum();
+    eprintln!(
+        "Matches:      {} {} in {} {}",
+        lines,
+        if lines == 1 { "line" } else { "lines" },
+        matched.len(),
+        if matched.len() == 1 { "file" } else { "files" }
+    );
+    eprintln!("Elapsed:      {:.3} ms", elapsed.as_secs_f64() * 1000.0);
+    eprintln!(
+        "Skipped:      {} too large, {} without permission",
+        messages.too_large.get(),
+        messages.denied.get()
+    );
+}
+
+/// Prints the `--forbid` summary to stderr: the verdict, then a table of
+/// the files that matched. Returns whether more than `allowed` lines matched.
+fn report_forbidden(pattern: &str, matched: &[(PathBuf, usize)], allowed: usize) -> bool {
+    let total: usize = matched.iter().map(|(_, count)| count).sum();
+    let lines = if total == 1 { "line" } else { "lines" };
+    let violated = total > allowed;
+    if total == 0 {
+        eprintln!("searcher: forbidden pattern `{}` not found", pattern);
+    } else if violated {
+        eprintln!(
+            "searcher: forbidden pattern `{}` matched {} {} (allowed: {})",
+            pattern, total, lines, allowed
+        );
+    } else {
+        eprintln!(
+            "searcher: forbidden pattern `{}` matched {} {}, within the allowed {}",
+            pattern, total, lines, allowed
+        );
+    }
+    if !matched.is_empty() {
+        eprintln!("{:>9}  FILE", "MATCHES");
+        for (path, count) in matched {
+            eprintln!("{:>9}  {}", count, display_path(path, None));
+        }
+    }
+    violated
+}
+
+/// Runs `searcher run`: loads a job file and searches like `find` would.
+fn run_job(args: &RunArgs) -> Result<ExitCode> {
+    let job = JobSpec::load(&args.job)?;
+    let matcher = job.build_matcher()?;
+    let search_options = SearchOptions {
+        cancel: Some(interrupt_token().clone()),
+        ..job.search.clone()
+    };
+    let multiple_files = spans_multiple_files(&job.paths);
+    let options = PrinterOptions {
+        line_numbers: job.output.line_numbers,
+        with_filename: multiple_files,
+        heading: use_heading(job.output.heading, multiple_files),
+        template: job.template()?,
+        sanitize: !job.output.no_sanitize,
+        null_data: search_options.line_terminator == LineTerminator::Nul,
+        ..PrinterOptions::default()
+    };
+    let messages = Messages::new(args.no_messages);
+
+    print_matches(
+        Walk::new(&job.paths),
+        None,
+        &matcher,
+        None,
+        |path| search_path(path, &matcher, &search_options),
+        None,
+        options,
+        Layout::new(job.output.groups, &matcher)?,
+        &messages,
+    )?;
+    Ok(messages.finish())
+}
+
+/// Runs `searcher lint`: checks every file against all rules in one pass.
+///
+/// Exits with 1 when a rule with severity `warning` or `error` was broken.
+fn run_lint(args: &LintArgs) -> Result<ExitCode> {
+    if args.input.normalize.is_some() {
+        bail!("`searcher lint` does not support `--normalize`");
+    }
+    let linter = RuleSet::load(&args.rules)?.compile()?;
+    report_findings(
+        linter.rules(),
+        &args.input,
+        args.output_format,
+        args.path_separator,
+        |path, options| {
+            let findings = linter.check_path(path, options)?;
+            Ok(findings
+                .into_iter()
+                .map(|finding| Report {
+                    severity: linter.rules()[finding.rule].severity,
+                    rule: finding.rule,
+                    line_number: finding.line_number,
+                    range: finding.range,
+                    content: finding.content,
+                })
+                .collect())
+        },
+    )
+}
+
+/// Runs `searcher secrets`: checks every file against the credential rules
+/// and, unless `--no-entropy` is given, for random-looking tokens.
+///
+/// The secrets themselves are masked in the output. Exits with 1 when a
+/// secret with severity `warning` or `error` was found.
+fn run_secrets(args: &SecretsArgs) -> Result<ExitCode> {
+    if args.input.normalize.is_some() {
+        bail!("`searcher secrets` does not support `--normalize`");
+    }
+    let mut rules = builtin_rules();
+    if let Some(path) = &args.rules {
+        rules.rules.extend(RuleSet::load(path)?.rules);
+    }
+    let threshold = (!args.no_entropy).then_some(args.entropy_threshold);
+    let scanner = SecretScanner::new(rules, threshold)?;
+    report_findings(
+        scanner.rules(),
+        &args.input,
+        args.output_format,
+        args.path_separator,
+        |path, options| {
+            let secrets = scanner.scan_path(path, options)?;
+            Ok(secrets
+                .into_iter()
+                .map(|mut secret| {
+                    secret.mask();
+                    Report {
+                        rule: secret.rule,
+                        severity: secret.severity,
+                        line_number: secret.line_number,
+                        range: secret.range,
+                        content: secret.content,
+                    }
+                })
+                .collect())
+        },
+    )
+}
+
+/// A rule broken on a line, as `searcher lint` and `searcher secrets`
+/// report it.
+struct Report {
+    /// The index of the rule
+    rule: usize,
+    severity: Severity,
+    line_number: usize,
+    range: Range<usize>,
+    content: Vec<u8>,
+}
+
+/// Checks every file of `input` with `check`, printing what it reports
+/// about `rules` as text or SARIF, then a summary per rule on stderr.
+///
+/// Returns 1 when a report has severity `warning` or `error`.
+fn report_findings(
+    rules: &[Rule],
+    input: &InputArgs,
+    output_format: LintFormat,
+    path_separator: Option<char>,
+    check: impl Fn(&Path, &SearchOptions) -> Result<Vec<Report>>,
+) -> Result<ExitCode> {
+    let search_options = input.search_options()?;
+    let messages = input.messages();
+    let mut stdout = io::stdout().lock();
+    let level = |severity| match severity {
+        Severity::Note => Level::Note,
+        Severity::Warning => Level::Warning,
+        Severity::Error => Level::Error,
+    };
+    let mut sarif = (output_format == LintFormat::Sarif).then(|| {
+        SarifLog::new(
+            rules
+                .iter()
+                .map(|rule| SarifRule {
+                    id: rule.id.clone(),
+                    message: rule.message(),
+                    level: level(rule.severity),
+                })
+                .collect(),
+        )
+    });
+    let mut counts = vec![0; rules.len()];
+    let mut failed = false;
+
+    for (path, reports) in each_file_in(
+        input.walk(input.files()?)?,
+        |path| check(path, &search_options),
+        |error| messages.warn(&error),
+    ) {
+        messages.searched(reports.len());
+        for report in reports {
+            counts[report.rule] += 1;
+            failed |= report.severity >= Severity::Warning;
+            let rule = &rules[report.rule];
+            match &mut sarif {
+                Some(sarif) => sarif.add_result_with_level(
+                    report.rule,
+                    level(report.severity),
+                    &path,
+                    report.line_number,
+                    &report.content,
+                    Some(report.range),
+                ),
+                None => writeln!(
+                    stdout,
+                    "{}:{}:{}: {}[{}]: {}",
+                    display_path(&path, path_separator),
+                    report.line_number,
+                    report.range.start + 1,
+                    report.severity,
+                    rule.id,
+                    rule.message()
+                )?,
+            }
+        }
+    }
+    if let Some(sarif) = sarif {
+        sarif.write_to(&mut stdout)?;
+    }
+
+    let status = messages.finish();
+    if interrupt_token().is_cancelled() {
+        return Ok(status);
+    }
+    let total: usize = counts.iter().sum();
+    eprintln!(
+        "searcher: {} {}",
+        total,
+        if total == 1 { "finding" } else { "findings" }
+    );
+    eprintln!("{:>9}  {:<8}  RULE", "FINDINGS", "SEVERITY");
+    for (rule, count) in rules.iter().zip(&counts) {
+        eprintln!("{:>9}  {:<8}  {}", count, rule.severity, rule.id);
+    }
+
+    Ok(if failed { ExitCode::FAILURE } else { status })
+}
+
+/// Runs `searcher index build`, writing the index and a one-line summary.
+fn run_index_build(args: &IndexBuildArgs) -> Result<ExitCode> {
+    let messages = Messages::new(args.no_messages);
+    let index = Index::build(&args.paths, |error| messages.warn(&error));
+    index.write_to(&args.index)?;
+
+    println!(
+        "Indexed {} files ({} trigrams) into {}",
+        index.files().len(),
+        index.trigram_count(),
+        args.index.display()
+    );
+    Ok(messages.finish())
+}
+
+/// Runs `searcher index query`: searches only the files the index allows.
+fn run_index_query(args: &IndexQueryArgs) -> Result<ExitCode> {
+    let index = Index::read_from(&args.index)?;
+    let matcher = args.matching.build_matcher()?;
+    let literals = required_literals(&args.matching.pattern, &args.matching.matcher_options());
+    let candidates = index.candidates(&literals);
+    if candidates.stale > 0 && !args.no_messages {
+        eprintln!(
+            "searcher: {} {} changed since the index was built; run `searcher index build` to refresh it",
+            candidates.stale,
+            if candidates.stale == 1 {
+                "file has"
+            } else {
+                "files have"
+            },
+        );
+    }
+
+    let search_options = SearchOptions {
+        cancel: Some(interrupt_token().clone()),
+        ..SearchOptions::default()
+    };
+    let messages = Messages::new(args.no_messages);
+    print_matches(
+        Walk::new(&candidates.files),
+        None,
+        &matcher,
+        None,
+        |path| search_path(path, &matcher, &search_options),
+        None,
+        args.output.printer_options(true, false),
+        Layout::Lines,
+        &messages,
+    )?;
+    Ok(messages.finish())
+}
+
+/// Runs `searcher watch`: prints the current matches, then the new matches
+/// of every file that changes, until interrupted.
+fn run_watch(args: &WatchArgs) -> Result<ExitCode> {
+    if args.input.git_tracked {
+        bail!("`searcher watch` does not support `--git-tracked`");
+    }
+    let matcher = args.matching.build_matcher_for(&args.input)?;
+    let search_options = args.matching.search_options(&args.input)?;
+    let cache = args.input.result_cache(&args.matching, &matcher)?;
+    let mut printer = Printer::new(
+        io::stdout().lock(),
+        args.output
+            .printer_options(true, args.input.splitting.null_data),
+    );
+    let messages = args.input.messages();
+    let mut tracker = MatchTracker::new();
+    let searchers = Searchers::new(&matcher);
+
+    // Start watching first so that changes made during the first search count
+    let (sender, events) = mpsc::channel();
+    let mut debouncer = new_debouncer(Duration::from_millis(200), sender)
+        .context("Could not watch for file changes")?;
+    for path in &args.input.paths {
+        let absolute = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+        debouncer
+            .watcher()
+            .watch(&absolute, RecursiveMode::Recursive)
+            .with_context(|| format!("Could not watch `{}`", path.display()))?;
+    }
+
+    let mut print_new = |paths: &[PathBuf], tracker: &mut MatchTracker| -> Result<()> {
+        for (path, matches) in each_file(
+            paths,
+            |path| search_file(path, &searchers, &search_options, cache.as_ref(), None),
+            |error| messages.warn(&error),
+        ) {
+            let key = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+            printer.print_file(&path, &tracker.update(&key, matches))?;
+        }
+        Ok(())
+    };
+    print_new(&args.input.paths, &mut tracker)?;
+
+    let current_dir = env::current_dir().and_then(fs::canonicalize).ok();
+    while !interrupt_token().is_cancelled() {
+        let changed = match events.recv_timeout(Duration::from_millis(100)) {
+            Ok(Ok(changed)) => changed,
+            Ok(Err(error)) => {
+                messages
+                    .warn(&anyhow::Error::from(error).context("Could not watch for file changes"));
+                continue;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        let mut paths: Vec<PathBuf> = changed.into_iter().map(|event| event.path).collect();
+        paths.sort();
+        paths.dedup();
+
+        for path in paths {
+            if !path.is_file() {
+                // Deleted, or a directory whose files get events of their own
+                tracker.forget(&path);
+                continue;
+            }
+            // Show paths relative to where searcher was started, like the first search
+            let shown = current_dir
+                .as_deref()
+                .and_then(|dir| path.strip_prefix(dir).ok())
+                .map_or_else(|| path.clone(), Path::to_path_buf);
+            print_new(&[shown], &mut tracker)?;
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Runs `searcher tui`, the interactive search UI.
+fn run_tui(args: &TuiArgs) -> Result<ExitCode> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        bail!("`searcher tui` needs an interactive terminal");
+    }
+    let matcher_options = MatcherOptions {
+        ignore_case: args.ignore_case,
+        regex: args.regex,
+        query: args.query,
+        ..MatcherOptions::default()
+    };
+    let mut app = tui::App::new(&args.paths, matcher_options, SearchOptions::default());
+    tui::run(&mut app)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the matching lines (or capture groups) of every file of `walk`, as
+/// found by `search`.
+///
+/// With the `-e` patterns of `find`, also counts the lines each pattern
+/// matched. With a `redactor`, the matches are masked once the lines have
+/// been counted. With `threads`, files are searched on them at once, and
+/// printed in the same order. Returns each file that matched with its
+/// number of matching lines.
+#[allow(clippy::too_many_arguments)]
+fn print_matches(
+    walk: Walk,
+    threads: Option<&Threads>,
+    matcher: &Matcher,
+    mut patterns: Option<(&PatternSet, &mut PatternStats)>,
+    search: impl Fn(&Path) -> Result<Vec<SearchMatchBytes>> + Send + Sync,
+    redactor: Option<&Redactor>,
+    options: PrinterOptions,
+    layout: Layout<'_>,
+    messages: &Messages,
+) -> Result<Vec<(PathBuf, usize)>> {
+    let sanitize_values = options.sanitize;
+    let mut printer = Printer::new(io::stdout().lock(), options);
+    let mut sarif = match layout {
+        Layout::Sarif(pattern) => {
+            let ids = match &patterns {
+                Some((set, _)) => set.patterns().to_vec(),
+                None => vec![pattern.to_string()],
+            };
+            Some(SarifLog::new(
+                ids.into_iter()
+                    .map(|id| SarifRule {
+                        message: format!("Line matches `{}`", id),
+                        id,
+                        level: Level::Warning,
+                    })
+                    .collect(),
+            ))
+        }
+        _ => None,
+    };
+    let mut histogram = match layout {
+        Layout::CountBy(pattern) => Some(Histogram::new(pattern)?),
+        _ => None,
+    };
+    let mut sessions = match layout {
+        Layout::CorrelateBy(pattern) => Some(Sessions::new(pattern)?),
+        _ => None,
+    };
+    let mut matched = Vec::new();
+    thread::scope(|scope| -> Result<()> {
+        let files = each_file_on(scope, walk, threads, search, |error| messages.warn(&error));
+        for (path, mut matches) in files {
+            messages.searched(matches.len());
+            if !matches.is_empty() {
+                matched.push((path.clone(), matches.len()));
+            }
+            if let Some((set, stats)) = &mut patterns {
+                let mut matches = set.annotate(matches);
+                stats.add(&path, &matches);
+                if let Some(redactor) = redactor {
+                    for line in &mut matches {
+                        let spans = matcher.match_ranges(&line.content);
+                        if let Cow::Owned(redacted) = redactor.redact(&line.content, &spans) {
+                            line.content = redacted;
+                        }
+                    }
+                }
+                match layout {
+                    // Annotations and SARIF results name the pattern they are
+                    // for, once for every pattern a line matched
+                    Layout::Github(_) | Layout::Sarif(_) => {
+                        for line in &matches {
+                            for &index in &line.pattern_indices {
+                                let line = std::slice::from_ref(line);
+                                let pattern_matcher = set.pattern_matcher(index);
+                                match &mut sarif {
+                                    Some(sarif) => {
+                                        sarif.add_matches(index, &path, line, pattern_matcher)
+                                    }
+                                    None => printer.print_github(
+                                        &path,
+                                        line,
+                                        pattern_matcher,
+                                        &set.patterns()[index],
+                                    )?,
+                                }
+                            }
+                        }
+                    }
+                    Layout::Vimgrep => printer.print_vimgrep(&path, &matches, matcher)?,
+                    Layout::Lines | Layout::Groups => printer.print_file(&path, &matches)?,
+                    Layout::CountBy(_) => {
+                        if let Some(histogram) = &mut histogram {
+                            histogram.add_matches(&matches);
+                        }
+                    }
+                    Layout::CorrelateBy(_) => {
+                        if let Some(sessions) = &mut sessions {
+                            sessions.add_matches(&path, &matches);
+                        }
+                    }
+                    Layout::Density(lines) => {
+                        printer.print_density(&path, &density(&matches, lines), lines.get())?
+                    }
+                }
+                continue;
+            }
+            if let Some(redactor) = redactor {
+                redactor.redact_matches(matcher, &mut matches);
+            }
+            match layout {
+                Layout::Lines => printer.print_file(&path, &matches)?,
+                Layout::Groups => printer.print_file(&path, &capture_lines(matcher, &matches))?,
+                Layout::Vimgrep => printer.print_vimgrep(&path, &matches, matcher)?,
+                Layout::Github(title) => printer.print_github(&path, &matches, matcher, title)?,
+                Layout::Sarif(_) => {
+                    if let Some(sarif) = &mut sarif {
+                        sarif.add_matches(0, &path, &matches, matcher);
+                    }
+                }
+                Layout::CountBy(_) => {
+                    if let Some(histogram) = &mut histogram {
+                        histogram.add_matches(&matches);
+                    }
+                }
+                Layout::CorrelateBy(_) => {
+                    if let Some(sessions) = &mut sessions {
+                        sessions.add_matches(&path, &matches);
+                    }
+                }
+                Layout::Density(lines) => {
+                    printer.print_density(&path, &density(&matches, lines), lines.get())?
+                }
+            }
+        }
+        Ok(())
+    })?;
+    // Reports cover every file, so they are written once all are searched
+    if let Some(sessions) = sessions {
+        for session in sessions.sessions() {
+            printer.print_session(session)?;
+        }
+    }
+    let mut stdout = printer.into_inner();
+    if let Some(sarif) = sarif {
+        sarif.write_to(&mut stdout)?;
+    }
+    if let Some(histogram) = histogram {
+        for (value, count) in histogram.counts() {
+            write!(stdout, "{:>9}  ", count)?;
+            if sanitize_values {
+                stdout.write_all(&sanitize(value.as_bytes()))?;
+            } else {
+                stdout.write_all(value.as_bytes())?;
+            }
+            writeln!(stdout)?;
+        }
+    }
+    Ok(matched)
+}
+
+/// Searches the file at `path`, through the `--cache-dir` cache if there is
+/// one, or else in ranges on several threads with a `split`, or else with
+/// one of `searchers`.
+fn search_file(
+    path: &Path,
+    searchers: &Searchers<'_>,
+    search_options: &SearchOptions,
+    cache: Option<&ResultCache>,
+    split: Option<&Split>,
+) -> Result<Vec<SearchMatchBytes>> {
+    let matcher = searchers.matcher;
+    match (cache, split) {
+        (Some(cache), _) => cache.search_path(path, matcher, search_options),
+        (None, Some(split)) => search_path_split(path, matcher, search_options, split),
+        (None, None) => searchers.search_path(path, search_options),
+    }
+}
+
+/// The [`Searcher`]s of the threads searching files at once, each kept with
+/// its buffer for the next file one of them searches.
+struct Searchers<'m> {
+    matcher: &'m Matcher,
+    idle: Mutex<Vec<Searcher<&'m Matcher>>>,
+}
+
+impl<'m> Searchers<'m> {
+    fn new(matcher: &'m Matcher) -> Self {
+        Searchers {
+            matcher,
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Searches the file at `path` with an idle searcher, or a new one if
+    /// all of them are busy.
+    fn search_path(&self, path: &Path, options: &SearchOptions) -> Result<Vec<SearchMatchBytes>> {
+        let idle = || self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut searcher = idle().pop().unwrap_or_else(|| Searcher::new(self.matcher));
+        let mut matches = Vec::new();
+        let result = searcher.search_path(path, options, &mut matches);
+        idle().push(searcher);
+        result.map(|()| matches)
+    }
+}
+
+/// The threads to search the files of `paths` on, and how to split a large
+/// one with `--split-size`, sharing `threads` between the two.
+fn split<'a>(
+    threads: Option<&'a Threads>,
+    split_size: Option<u64>,
+    paths: &[PathBuf],
+) -> (Option<&'a Threads>, Option<Split>) {
+    match threads {
+        Some(threads) => share_threads(
+            threads,
+            split_size.unwrap_or(Split::DEFAULT_RANGE_SIZE),
+            paths,
+        ),
+        None => (None, None),
+    }
+}
+
+/// The threads of `--parallel` and `--count-files`: `--threads` of them,
+/// else one per CPU of `--thread-affinity`, else one per available CPU,
+/// pinned to the CPUs of `--thread-affinity`.
+fn threads(count: Option<NonZeroUsize>, affinity: Option<&str>) -> Result<Threads> {
+    let Some(affinity) = affinity else {
+        return Ok(count.map_or_else(Threads::available, Threads::new));
+    };
+    let cpus = parse_cpu_list(affinity).context("Invalid `--thread-affinity`")?;
+    let count = count.unwrap_or(NonZeroUsize::new(cpus.len()).unwrap_or(NonZeroUsize::MIN));
+    Threads::new(count)
+        .pinned_to(cpus)
+        .context("Invalid `--thread-affinity`")
+}
+
+/// The files of `walk` with what `read` returns for each, in the order the
+/// walk found them. With `threads`, they are read on those threads of
+/// `scope` at once.
+fn each_file_on<'scope, 'a, T: Send + 'scope>(
+    scope: &'scope thread::Scope<'scope, '_>,
+    walk: Walk,
+    threads: Option<&Threads>,
+    read: impl Fn(&Path) -> Result<T> + Send + Sync + 'scope,
+    on_error: impl FnMut(anyhow::Error) + 'a,
+) -> Box<dyn Iterator<Item = (PathBuf, T)> + 'a>
+where
+    'scope: 'a,
+{
+    match threads {
+        Some(threads) => Box::new(each_file_parallel(scope, walk, threads, read, on_error)),
+        None => Box::new(each_file_in(walk, read, on_error)),
+    }
+}
+
+/// Counts the files below the walk with at least one matching line for
+/// `--count-files`, searching one file per thread at a time and each only
+/// up to its first match.
+fn count_matching_files(
+    walk: Walk,
+    threads: &Threads,
+    matcher: &Matcher,
+    search_options: &SearchOptions,
+    messages: &Messages,
+) -> usize {
+    thread::scope(|scope| {
+        each_file_parallel(
+            scope,
+            walk,
+            threads,
+            |path| path_has_match(path, matcher, search_options),
+            |error| messages.warn(&error),
+        )
+        .filter(|&(_, found)| {
+            messages.searched(usize::from(found));
+            found
+        })
+        .count()
+    })
+}
+
+/// Prints how many lines match in each file, `path:count` when there are several.
+fn run_count(args: &SelectArgs) -> Result<ExitCode> {
+    let matcher = args.matching.build_matcher_for(&args.input)?;
+    let search_options = args.matching.search_options(&args.input)?;
+    let cache = args.input.result_cache(&args.matching, &matcher)?;
+    let with_filename = spans_multiple_files(&args.input.paths);
+    let mut stdout = io::stdout().lock();
+    let messages = args.input.messages();
+    let threads = args.parallel()?;
+    let searchers = Searchers::new(&matcher);
+    let files = args.input.files()?;
+    let (threads, split) = split(threads.as_ref(), args.split_size, &files);
+
+    let walk = args.input.walk(files)?;
+    thread::scope(|scope| -> Result<()> {
+        for (path, matches) in each_file_on(
+            scope,
+            walk,
+            threads,
+            |path| {
+                search_file(
+                    path,
+                    &searchers,
+                    &search_options,
+                    cache.as_ref(),
+                    split.as_ref(),
+                )
+            },
+            |error| messages.warn(&error),
+        ) {
+            messages.searched(matches.len());
+            if with_filename {
+                writeln!(stdout, "{}:{}", args.shown_path(&path), matches.len())?;
+            } else {
+                writeln!(stdout, "{}", matches.len())?;
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok(messages.finish())
+}
+
+/// Prints the path of every file with at least one matching line.
+fn run_files(args: &SelectArgs) -> Result<ExitCode> {
+    let matcher = args.matching.build_matcher_for(&args.input)?;
+    let search_options = args.matching.search_options(&args.input)?;
+    let cache = args.input.result_cache(&args.matching, &matcher)?;
+    let mut stdout = io::stdout().lock();
+    let messages = args.input.messages();
+    let threads = args.parallel()?;
+    let searchers = Searchers::new(&matcher);
+    let files = args.input.files()?;
+    let (threads, split) = split(threads.as_ref(), args.split_size, &files);
+
+    let walk = args.input.walk(files)?;
+    thread::scope(|scope| -> Result<()> {
+        for (path, matches) in each_file_on(
+            scope,
+            walk,
+            threads,
+            |path| {
+                search_file(
+                    path,
+                    &searchers,
+                    &search_options,
+                    cache.as_ref(),
+                    split.as_ref(),
+                )
+            },
+            |error| messages.warn(&error),
+        ) {
+            messages.searched(matches.len());
+            if !matches.is_empty() {
+                writeln!(stdout, "{}", args.shown_path(&path))?;
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok(messages.finish())
+}
+
+/// Runs `searcher extract`: prints every distinct value the pattern matches
+/// across all files, with its count.
+fn run_extract(args: &ExtractArgs) -> Result<ExitCode> {
+    let matcher = args.matching.build_matcher_for(&args.input)?;
+    if matches!(matcher, Matcher::Composite(_)) {
+        bail!("`searcher extract` needs a single pattern, not a query");
+    }
+    if let Some(k) = args.top {
+        return run_extract_top(args, &matcher, k as usize);
+    }
+    let search_options = args.matching.search_options(&args.input)?;
+    let cache = args.input.result_cache(&args.matching, &matcher)?;
+    let messages = args.input.messages();
+    let mut values = DistinctValues::new();
+    let searchers = Searchers::new(&matcher);
+
+    for (_, matches) in each_file_in(
+        args.input.walk(args.input.files()?)?,
+        |path| search_file(path, &searchers, &search_options, cache.as_ref(), None),
+        |error| messages.warn(&error),
+    ) {
+        messages.searched(matches.len());
+        values.add_matches(&matcher, &matches);
+    }
+
+    let mut stdout = io::stdout().lock();
+    let counts = match args.sort {
+        ExtractOrder::Count => values.counts(),
+        ExtractOrder::Value => values.sorted(),
+    };
+    for (value, count) in counts {
+        write!(stdout, "{:>9}  ", count)?;
+        stdout.write_all(&sanitize(value.as_bytes()))?;
+        writeln!(stdout)?;
+    }
+    Ok(messages.finish())
+}
+
+/// Runs `searcher extract --top`: prints the `k` most frequent values with
+/// their count and their share of all values, marking the counts that may
+/// be too high with `~`.
+fn run_extract_top(args: &ExtractArgs, matcher: &Matcher, k: usize) -> Result<ExitCode> {
+    let search_options = args.matching.search_options(&args.input)?;
+    let cache = args.input.result_cache(&args.matching, matcher)?;
+    let messages = args.input.messages();
+    let mut values = TopValues::new((k * 10).max(TOP_VALUES_KEPT));
+    let searchers = Searchers::new(matcher);
+
+    for (_, matches) in each_file_in(
+        args.input.walk(args.input.files()?)?,
+        |path| search_file(path, &searchers, &search_options, cache.as_ref(), None),
+        |error| messages.warn(&error),
+    ) {
+        messages.searched(matches.len());
+        values.add_matches(matcher, &matches);
+    }
+
+    let mut stdout = io::stdout().lock();
+    for value in values.top(k) {
+        let count = if value.error > 0 {
+            format!("~{}", value.count)
+        } else {
+            value.count.to_string()
+        };
+        let share = 100.0 * value.count as f64 / values.total() as f64;
+        write!(stdout, "{:>9}  {:>5.1}%  ", count, share)?;
+        stdout.write_all(&sanitize(value.value.as_bytes()))?;
+        writeln!(stdout)?;
+    }
+    Ok(messages.finish())
+}
+
+/// Runs `searcher dups`: prints every line, or value matched by
+/// `--pattern`, occurring at least `--min-count` times, most frequent first,
+/// each followed by the `path:line` of its first occurrences.
+fn run_dups(args: &DupsArgs) -> Result<ExitCode> {
+    // Every line is a match when counting whole lines
+    let matcher = match &args.pattern {
+        Some(pattern) => Matcher::new(pattern, args.ignore_case, true)?,
+        None => Matcher::new("", false, false)?,
+    };
+    let search_options = args.input.search_options()?;
+    let messages = args.input.messages();
+    let mut duplicates = Duplicates::new().with_max_locations(args.locations);
+
+    for (_, lines) in each_file_in(
+        args.input.walk(args.input.files()?)?,
+        |path| {
+            let mut lines = 0;
+            search_path_with(path, &matcher, &search_options, |line| {
+                lines += 1;
+                match &args.pattern {
+                    Some(_) => {
+                        duplicates.add_matches(path, line.line_number, &matcher, &line.content)
+                    }
+                    None => duplicates.add(path, line.line_number, &line.content),
+                }
+                ControlFlow::Continue(())
+            })?;
+            Ok(lines)
+        },
+        |error| messages.warn(&error),
+    ) {
+        messages.searched(lines);
+    }
+
+    let mut stdout = io::stdout().lock();
+    let min_count = usize::try_from(args.min_count).unwrap_or(usize::MAX);
+    for duplicate in duplicates.repeated(min_count) {
+        write!(stdout, "{:>9}  ", duplicate.count)?;
+        stdout.write_all(&sanitize(duplicate.value))?;
+        writeln!(stdout)?;
+        for (path, line_number) in &duplicate.locations {
+            let path = display_path(path, None);
+            writeln!(stdout, "{:>11}{}:{}", "", path, line_number)?;
+        }
+        let more = duplicate.count - duplicate.locations.len();
+        if more > 0 {
+            writeln!(stdout, "{:>11}and {} more", "", more)?;
+        }
+    }
+    Ok(messages.finish())
+}
+
+/// Runs `searcher git-log`: prints `commit:path:line:content` for the
+/// matching lines of every file each commit added or modified, newest first.
+fn run_git_log(args: &GitLogArgs) -> Result<ExitCode> {
+    let matcher = args.matching.build_matcher()?;
+    let repo = Repository::new(&args.repo);
+    let search_options = SearchOptions {
+        cancel: Some(interrupt_token().clone()),
+        ..SearchOptions::default()
+    };
+    let mut stdout = io::stdout().lock();
+
+    for commit in repo.commits(&args.range, &args.paths)? {
+        let found = match repo.search_commit(&commit, &args.paths, &matcher, &search_options) {
+            Err(_) if interrupt_token().is_cancelled() => break,
+            found => found?,
+        };
+        for found in found {
+            write!(
+                stdout,
+                "{}:{}:{}:",
+                found.commit, found.path, found.line_number
+            )?;
+            stdout.write_all(&sanitize(&found.content))?;
+            writeln!(stdout)?;
+        }
+    }
+
+    if interrupt_token().is_cancelled() {
+        return Ok(ExitCode::from(130));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Runs `searcher replace`: prints replaced lines, or previews, confirms, or
+/// writes whole files.
+fn run_replace(args: &ReplaceArgs) -> Result<ExitCode> {
+    let matcher = args.matching.build_matcher()?;
+    let replacer = Replacer::new(&matcher, &args.replacement)?;
+    let messages = Messages::new(args.no_messages);
+    if args.write || args.diff || args.interactive {
+        rewrite_files(args, &replacer, &messages)?;
+        return Ok(messages.finish());
+    }
+
+    let options = args
+        .output
+        .printer_options(spans_multiple_files(&args.paths), args.splitting.null_data);
+    let search_options = args.splitting.search_options();
+    let mut printer = Printer::new(io::stdout().lock(), options);
+    for (path, mut matches) in each_file(
+        &args.paths,
+        |path| search_path(path, &matcher, &search_options),
+        |error| messages.warn(&error),
+    ) {
+        messages.searched(matches.len());
+        replacer.replace_matches(&mut matches);
+        printer.print_file(&path, &matches)?;
+    }
+
+    Ok(messages.finish())
+}
+
+/// Applies a replacement to whole files, previewing, confirming, or writing them.
+fn rewrite_files(args: &ReplaceArgs, replacer: &Replacer, messages: &Messages) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    let mut answers = io::stdin().lock().lines();
+
+    for (path, original) in each_file(
+        &args.paths,
+        |path| fs::read(path).with_context(|| FileContext::read(path)),
+        |error| messages.warn(&error),
+    ) {
+        // Only between files, so a file is never left half written
+        if interrupt_token().is_cancelled() {
+            break;
+        }
+        let replaced = replacer.replace_text(&original);
+        messages.searched(replaced.changes.len());
+        if replaced.changes.is_empty() {
+            continue;
+        }
+
+        if args.diff || args.interactive {
+            let diff = unified_diff(&path.display().to_string(), &original, &replaced.changes, 3);
+            stdout.write_all(diff.as_bytes())?;
+            stdout.flush()?;
+        }
+        if args.diff {
+            continue;
+        }
+        if args.interactive {
+            match confirm(&path, &mut answers)? {
+                Answer::Yes => {}
+                Answer::No => continue,
+                Answer::Quit => break,
+            }
+        }
+
+        if let Err(error) = write_replaced(&path, &replaced.output, args.backup_suffix.as_deref()) {
+            messages.warn(&error);
+        }
+    }
+
+    Ok(())
+}
+
+/// A response to an interactive replacement prompt.
+enum Answer {
+    Yes,
+    No,
+    Quit,
+}
+
+/// Asks on stderr whether to apply the changes shown for `path`.
+fn confirm(path: &Path, answers: &mut impl Iterator<Item = io::Result<String>>) -> Result<Answer> {
+    loop {
+        eprint!("Apply changes to `{}`? [y/n/q] ", path.display());
+        io::stderr().flush()?;
+        let Some(answer) = answers.next() else {
+            // End of input: treat like quitting without touching more files
+            return Ok(Answer::Quit);
+        };
+        match answer?.trim().to_lowercase().as_str() {
+            "y" | "yes" => return Ok(Answer::Yes),
+            "n" | "no" => return Ok(Answer::No),
+            "q" | "quit" => return Ok(Answer::Quit),
+            _ => eprintln!("Please answer y, n, or q."),
+        }
+    }
 }
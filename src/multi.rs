@@ -0,0 +1,445 @@
+//! Searching for several patterns at once.
+//!
+//! A [`PatternSet`] compiles a list of patterns into a single combined
+//! matcher plus a `regex::bytes::RegexSet`. Every line is scanned once by the
+//! combined matcher, however many patterns there are; only the lines that
+//! match go through the set again, to find out which patterns they matched.
+//! Each resulting [`PatternMatch`] records those patterns by index.
+//!
+//! ```
+//! use searcher_cli_starter::SearchOptions;
+//! use searcher_cli_starter::multi::PatternSet;
+//! use std::io::Cursor;
+//!
+//! let set = PatternSet::new(&["timeout", "refused"], false, false).unwrap();
+//! let input = "ok\nconnection refused\nread timeout, connection refused\n";
+//! let matches = set.search_reader(Cursor::new(input), &SearchOptions::default()).unwrap();
+//!
+//! assert_eq!(matches.len(), 2);
+//! assert_eq!(matches[0].pattern_id(&set), "refused");
+//! assert_eq!(matches[1].pattern_indices, [0, 1]);
+//! ```
+//!
+//! [`PatternStats`] adds matches up per pattern and per file, e.g. for a log
+//! triage summary of which errors occur where.
+
+use crate::query::word_pattern;
+use crate::{MatchedLine, Matcher, SearchMatchBytes, SearchOptions, search_path, search_reader};
+use anyhow::{Context, Result, bail};
+use regex::bytes::{RegexSet, RegexSetBuilder};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Several patterns compiled to be matched in a single pass.
+///
+/// All patterns share the same case-insensitivity and regex settings, like
+/// the terms of a [`CompositeMatcher`](crate::query::CompositeMatcher).
+pub struct PatternSet {
+    patterns: Vec<String>,
+    combined: Combined,
+}
+
+/// The matchers behind a [`PatternSet`], also used by
+/// [`Linter`](crate::rules::Linter) for its rules.
+pub(crate) struct Combined {
+    /// Matches a line that any pattern matches
+    any: Matcher,
+    /// Tells which patterns a line matches
+    set: RegexSet,
+    /// One matcher per pattern, to locate its matches
+    matchers: Vec<Matcher>,
+}
+
+impl Combined {
+    /// Combines patterns written as regexes in `sources`, matched
+    /// case-insensitively with `ignore_case`, with `matchers` for each of
+    /// them on its own.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the combined patterns exceed the regex size limit.
+    pub(crate) fn new(
+        sources: &[String],
+        ignore_case: bool,
+        matchers: Vec<Matcher>,
+    ) -> Result<Self> {
+        let alternation: Vec<String> = sources.iter().map(|s| format!("(?:{})", s)).collect();
+        let any = Matcher::new(&alternation.join("|"), ignore_case, true)?;
+        let set = RegexSetBuilder::new(sources)
+            .case_insensitive(ignore_case)
+            .build()?;
+        Ok(Combined { any, set, matchers })
+    }
+
+    /// A matcher for lines that match at least one of the patterns.
+    pub(crate) fn matcher(&self) -> &Matcher {
+        &self.any
+    }
+
+    /// A matcher for the pattern at `index` alone.
+    pub(crate) fn pattern_matcher(&self, index: usize) -> &Matcher {
+        &self.matchers[index]
+    }
+
+    /// The indices of every pattern that matches `line`, in ascending order.
+    pub(crate) fn matching_patterns(&self, line: &[u8]) -> Vec<usize> {
+        self.set.matches(line).into_iter().collect()
+    }
+}
+
+impl PatternSet {
+    /// Compiles `patterns`, which are identified by their index in the slice.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `patterns` is empty, if a pattern is empty or is
+    /// not a valid regex, or if the combined patterns exceed the regex size
+    /// limit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::multi::PatternSet;
+    ///
+    /// let set = PatternSet::new(&["warn", r"err(or)?"], true, true).unwrap();
+    /// assert_eq!(set.matching_patterns(b"WARN: err"), [0, 1]);
+    ///
+    /// assert!(PatternSet::new(&["ok", "("], false, true).is_err());
+    /// ```
+    pub fn new<S: AsRef<str>>(patterns: &[S], ignore_case: bool, use_regex: bool) -> Result<Self> {
+        Self::build(patterns, ignore_case, use_regex, false)
+    }
+
+    /// Like [`PatternSet::new`], but every pattern only matches whole words,
+    /// as with [`word_pattern`]. [`PatternSet::patterns`] still lists the
+    /// patterns as given.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`PatternSet::new`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::multi::PatternSet;
+    ///
+    /// let set = PatternSet::whole_words(&["cat", "dog"], false, false).unwrap();
+    /// assert_eq!(set.matching_patterns(b"concatenate a dog"), [1]);
+    /// assert_eq!(set.patterns(), ["cat", "dog"]);
+    /// ```
+    pub fn whole_words<S: AsRef<str>>(
+        patterns: &[S],
+        ignore_case: bool,
+        use_regex: bool,
+    ) -> Result<Self> {
+        Self::build(patterns, ignore_case, use_regex, true)
+    }
+
+    fn build<S: AsRef<str>>(
+        patterns: &[S],
+        ignore_case: bool,
+        use_regex: bool,
+        word: bool,
+    ) -> Result<Self> {
+        if patterns.is_empty() {
+            bail!("A pattern set needs at least one pattern");
+        }
+        let patterns: Vec<String> = patterns.iter().map(|p| p.as_ref().to_string()).collect();
+        let mut sources = Vec::with_capacity(patterns.len());
+        let mut matchers = Vec::with_capacity(patterns.len());
+        for pattern in &patterns {
+            if pattern.is_empty() {
+                bail!("Patterns in a pattern set cannot be empty");
+            }
+            let source = if word {
+                word_pattern(pattern, use_regex)
+            } else if use_regex {
+                pattern.clone()
+            } else {
+                regex::escape(pattern)
+            };
+            matchers.push(
+                if word {
+                    Matcher::new(&source, ignore_case, true)
+                } else {
+                    Matcher::new(pattern, ignore_case, use_regex)
+                }
+                .with_context(|| format!("Invalid pattern `{}`", pattern))?,
+            );
+            sources.push(source);
+        }
+
+        let combined = Combined::new(&sources, ignore_case, matchers)
+            .context("Could not combine the patterns into one")?;
+        Ok(PatternSet { patterns, combined })
+    }
+
+    /// The patterns, in the order given.
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    /// The number of patterns.
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    /// Always false: a set has at least one pattern.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// A matcher for lines that match at least one of the patterns.
+    ///
+    /// It can be used anywhere a single [`Matcher`] is expected, e.g. with
+    /// [`search_reader`], and its [`Matcher::match_ranges`] cover the
+    /// matches of every pattern.
+    pub fn matcher(&self) -> &Matcher {
+        self.combined.matcher()
+    }
+
+    /// A matcher for the pattern at `index` alone, e.g. to locate its
+    /// matches with [`Matcher::match_ranges`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn pattern_matcher(&self, index: usize) -> &Matcher {
+        self.combined.pattern_matcher(index)
+    }
+
+    /// The indices of every pattern that matches `line`, in ascending order.
+    pub fn matching_patterns(&self, line: &[u8]) -> Vec<usize> {
+        self.combined.matching_patterns(line)
+    }
+
+    /// Records which patterns matched each line found with
+    /// [`PatternSet::matcher`].
+    pub fn annotate(&self, lines: Vec<SearchMatchBytes>) -> Vec<PatternMatch> {
+        lines
+            .into_iter()
+            .map(|line| PatternMatch {
+                pattern_indices: self.matching_patterns(&line.content),
+                line_number: line.line_number,
+                content: line.content,
+            })
+            .collect()
+    }
+
+    /// Searches `reader` for lines matching any of the patterns, like
+    /// [`search_reader`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`search_reader`].
+    pub fn search_reader<R: Read>(
+        &self,
+        reader: R,
+        options: &SearchOptions,
+    ) -> Result<Vec<PatternMatch>> {
+        Ok(self.annotate(search_reader(reader, self.matcher(), options)?))
+    }
+
+    /// Searches the file at `path`, like [`search_path`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`search_path`].
+    pub fn search_path(&self, path: &Path, options: &SearchOptions) -> Result<Vec<PatternMatch>> {
+        Ok(self.annotate(search_path(path, self.matcher(), options)?))
+    }
+}
+
+/// A line matched by a [`PatternSet`], with the patterns it matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PatternMatch {
+    /// The line number where the match was found (1-based indexing)
+    pub line_number: usize,
+    /// The raw bytes of the matching line, without the line terminator
+    pub content: Vec<u8>,
+    /// The index of every pattern the line matched, in ascending order
+    pub pattern_indices: Vec<usize>,
+}
+
+impl PatternMatch {
+    /// The index of the first pattern the line matched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern_indices` is empty, which never happens for matches
+    /// produced by a [`PatternSet`].
+    pub fn pattern_index(&self) -> usize {
+        self.pattern_indices[0]
+    }
+
+    /// The first pattern the line matched, looked up in the `set` that
+    /// produced the match.
+    pub fn pattern_id<'a>(&self, set: &'a PatternSet) -> &'a str {
+        &set.patterns[self.pattern_index()]
+    }
+}
+
+impl MatchedLine for PatternMatch {
+    fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    fn content_bytes(&self) -> &[u8] {
+        &self.content
+    }
+}
+
+/// How many lines each pattern of a [`PatternSet`] matched, overall and per file.
+///
+/// A line that matches several patterns counts once for each of them.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::SearchOptions;
+/// use searcher_cli_starter::multi::{PatternSet, PatternStats};
+/// use std::io::Cursor;
+/// use std::path::Path;
+///
+/// let set = PatternSet::new(&["timeout", "refused"], false, false).unwrap();
+/// let mut stats = PatternStats::new(&set);
+/// for (path, text) in [("a.log", "timeout\nrefused\n"), ("b.log", "timeout\n")] {
+///     let matches = set.search_reader(Cursor::new(text), &SearchOptions::default()).unwrap();
+///     stats.add(Path::new(path), &matches);
+/// }
+///
+/// let timeout = &stats.patterns()[0];
+/// assert_eq!(timeout.pattern, "timeout");
+/// assert_eq!(timeout.lines, 2);
+/// assert_eq!(timeout.files.len(), 2);
+/// assert_eq!(stats.patterns()[1].files[Path::new("a.log")], 1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PatternStats {
+    patterns: Vec<PatternCount>,
+}
+
+/// The matches of a single pattern, part of [`PatternStats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PatternCount {
+    /// The pattern
+    pub pattern: String,
+    /// How many lines it matched in all files
+    pub lines: usize,
+    /// How many lines it matched in each file it matched at all
+    pub files: BTreeMap<PathBuf, usize>,
+}
+
+impl PatternStats {
+    /// Creates empty statistics for the patterns of `set`.
+    pub fn new(set: &PatternSet) -> Self {
+        PatternStats {
+            patterns: set
+                .patterns()
+                .iter()
+                .map(|pattern| PatternCount {
+                    pattern: pattern.clone(),
+                    ..PatternCount::default()
+                })
+                .collect(),
+        }
+    }
+
+    /// Counts the `matches` found in `path` by the same [`PatternSet`].
+    ///
+    /// Adding matches from the same path again adds to its counts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a match refers to a pattern the set did not have.
+    pub fn add(&mut self, path: &Path, matches: &[PatternMatch]) {
+        for search_match in matches {
+            for &index in &search_match.pattern_indices {
+                let count = &mut self.patterns[index];
+                count.lines += 1;
+                *count.files.entry(path.to_path_buf()).or_default() += 1;
+            }
+        }
+    }
+
+    /// The counts of each pattern, in the order of the set.
+    pub fn patterns(&self) -> &[PatternCount] {
+        &self.patterns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_literal_patterns_are_not_regexes() {
+        let set = PatternSet::new(&["a.b", "(x)"], false, false).unwrap();
+        let matches = set
+            .search_reader(
+                Cursor::new("axb\na.b\n(x) and a.b\nx\n"),
+                &SearchOptions::default(),
+            )
+            .unwrap();
+
+        let found: Vec<(usize, Vec<usize>)> = matches
+            .iter()
+            .map(|m| (m.line_number, m.pattern_indices.clone()))
+            .collect();
+        assert_eq!(found, [(2, vec![0]), (3, vec![0, 1])]);
+        assert_eq!(matches[1].pattern_id(&set), "a.b");
+    }
+
+    #[test]
+    fn test_ignore_case_applies_to_every_pattern() {
+        let set = PatternSet::new(&["error", "WARN"], true, false).unwrap();
+        assert_eq!(set.matching_patterns(b"Error, warning"), [0, 1]);
+        assert!(set.matcher().is_match("a warning"));
+        assert!(!set.matcher().is_match("fine"));
+        let ranges = set.pattern_matcher(1).match_ranges(b"a Warning");
+        assert_eq!(ranges.first(), Some(&(2..6)));
+    }
+
+    #[test]
+    fn test_invalid_sets() {
+        let none: [&str; 0] = [];
+        assert!(PatternSet::new(&none, false, false).is_err());
+        assert!(PatternSet::new(&["ok", ""], false, false).is_err());
+        let error = PatternSet::new(&["ok", "[z-a]"], false, true)
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("Invalid pattern `[z-a]`"));
+    }
+
+    #[test]
+    fn test_stats_count_each_matched_pattern() {
+        let set = PatternSet::new(&["a", "b", "c"], false, false).unwrap();
+        let mut stats = PatternStats::new(&set);
+        let options = SearchOptions::default();
+        stats.add(
+            Path::new("one"),
+            &set.search_reader(Cursor::new("ab\nb\n"), &options).unwrap(),
+        );
+        stats.add(
+            Path::new("two"),
+            &set.search_reader(Cursor::new("a\n"), &options).unwrap(),
+        );
+        stats.add(
+            Path::new("one"),
+            &set.search_reader(Cursor::new("a\n"), &options).unwrap(),
+        );
+
+        let counts: Vec<(usize, usize)> = stats
+            .patterns()
+            .iter()
+            .map(|count| (count.lines, count.files.len()))
+            .collect();
+        assert_eq!(counts, [(3, 2), (2, 1), (0, 0)]);
+        assert_eq!(stats.patterns()[0].files[Path::new("one")], 2);
+    }
+}
@@ -0,0 +1,194 @@
+//! Searching the output of an external command instead of the file itself.
+//!
+//! A [`Preprocessor`] runs a command for each file and searches what it
+//! writes to stdout, which is what `--pre` does. The command gets the file's
+//! path as its only argument and the file's contents on stdin, so anything
+//! from `gzip -dc` to a PDF-to-text script can be used without the searcher
+//! knowing about the format. `--pre-glob` limits the command to some files;
+//! the others are searched as usual.
+//!
+//! ```no_run
+//! use searcher_cli_starter::preprocess::Preprocessor;
+//! use searcher_cli_starter::{Matcher, SearchOptions, search_path};
+//!
+//! let options = SearchOptions {
+//!     preprocessor: Some(Preprocessor::new("zcat").with_globs(&["*.gz"]).unwrap()),
+//!     ..SearchOptions::default()
+//! };
+//! let matcher = Matcher::new("error", false, false).unwrap();
+//! let matches = search_path("app.log.gz".as_ref(), &matcher, &options).unwrap();
+//! ```
+
+use crate::diagnostic::FileContext;
+use crate::{Matcher, SearchMatchBytes, SearchOptions, search_source};
+use anyhow::{Context, Result, bail};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// A command whose output is searched in place of a file's contents.
+#[derive(Debug, Clone)]
+pub struct Preprocessor {
+    command: PathBuf,
+    globs: Vec<String>,
+    /// `globs` compiled, or `None` to preprocess every file
+    glob_set: Option<GlobSet>,
+}
+
+impl Preprocessor {
+    /// Creates a preprocessor running `command` on every file.
+    pub fn new(command: impl Into<PathBuf>) -> Self {
+        Preprocessor {
+            command: command.into(),
+            globs: Vec::new(),
+            glob_set: None,
+        }
+    }
+
+    /// Only runs the command on files whose path or file name matches one of
+    /// `globs`, e.g. `*.gz`. An empty list keeps every file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a glob is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::preprocess::Preprocessor;
+    ///
+    /// let pre = Preprocessor::new("zcat").with_globs(&["*.gz", "*.bz2"]).unwrap();
+    /// assert!(pre.applies_to("logs/app.log.gz".as_ref()));
+    /// assert!(!pre.applies_to("logs/app.log".as_ref()));
+    /// assert!(Preprocessor::new("zcat").with_globs(&["{*.gz"]).is_err());
+    /// ```
+    pub fn with_globs<S: AsRef<str>>(mut self, globs: &[S]) -> Result<Self> {
+        if globs.is_empty() {
+            self.globs.clear();
+            self.glob_set = None;
+            return Ok(self);
+        }
+        let mut builder = GlobSetBuilder::new();
+        for glob in globs {
+            let glob = glob.as_ref();
+            builder.add(Glob::new(glob).with_context(|| format!("Invalid glob `{}`", glob))?);
+        }
+        self.glob_set = Some(builder.build().context("Invalid glob")?);
+        self.globs = globs.iter().map(|glob| glob.as_ref().to_string()).collect();
+        Ok(self)
+    }
+
+    /// The command that is run.
+    pub fn command(&self) -> &Path {
+        &self.command
+    }
+
+    /// The globs limiting which files are preprocessed.
+    pub fn globs(&self) -> &[String] {
+        &self.globs
+    }
+
+    /// Whether `path` goes through the command.
+    pub fn applies_to(&self, path: &Path) -> bool {
+        match &self.glob_set {
+            None => true,
+            Some(glob_set) => {
+                glob_set.is_match(path)
+                    || path.file_name().is_some_and(|name| glob_set.is_match(name))
+            }
+        }
+    }
+
+    /// Runs the command on `path` and searches its output with
+    /// [`search_reader`](crate::search_reader), applying `options.scope` as
+    /// [`search_path`](crate::search_path) would to the file. The output is searched as it is produced, so it is
+    /// never held in memory as a whole.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened, the command cannot be
+    /// started or exits unsuccessfully, or searching its output fails.
+    pub fn search(
+        &self,
+        path: &Path,
+        matcher: &Matcher,
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchMatchBytes>> {
+        let file = File::open(path).with_context(|| FileContext::read(path))?;
+        let mut child = Command::new(&self.command)
+            .arg(path)
+            .stdin(file)
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Could not run `{}`", self.command.display()))?;
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+
+        // Read everything the command writes even if the search stops early,
+        // so that it does not fail with a broken pipe
+        let searched = search_source(path, &mut stdout, matcher, options).and_then(|matches| {
+            io::copy(&mut stdout, &mut io::sink())?;
+            Ok(matches)
+        });
+        let matches = match searched {
+            Ok(matches) => matches,
+            Err(error) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(error);
+            }
+        };
+        drop(stdout);
+
+        let status = child
+            .wait()
+            .with_context(|| format!("Could not run `{}`", self.command.display()))?;
+        if !status.success() {
+            bail!(
+                "`{}` failed on `{}` ({})",
+                self.command.display(),
+                path.display(),
+                status
+            );
+        }
+        Ok(matches)
+    }
+}
+
+impl PartialEq for Preprocessor {
+    fn eq(&self, other: &Self) -> bool {
+        self.command == other.command && self.globs == other.globs
+    }
+}
+
+impl Eq for Preprocessor {}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_searches_command_output() {
+        let path = std::env::temp_dir().join(format!("searcher_preprocess_{}", std::process::id()));
+        std::fs::write(&path, "ok\nerror one\n").unwrap();
+        let matcher = Matcher::new("error", false, false).unwrap();
+
+        // Sorting moves the error to the first line
+        let matches = Preprocessor::new("sort")
+            .search(&path, &matcher, &SearchOptions::default())
+            .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line_number, 1);
+
+        let error = Preprocessor::new("false")
+            .search(&path, &matcher, &SearchOptions::default())
+            .unwrap_err();
+        assert!(
+            error.to_string().starts_with("`false` failed on"),
+            "{error}"
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,1396 @@
+//! Output formatting for search results.
+//!
+//! The [`Printer`] turns the matches found in a file into the text the CLI
+//! writes to stdout. Two layouts are supported:
+//!
+//! - **No heading** (classic): every line stands on its own, optionally
+//!   prefixed with the file name, e.g. `src/main.rs:12:fn main() {`
+//! - **Heading**: the file name is printed once, followed by the matching
+//!   lines beneath it, with a blank line separating files
+//!
+//! A [`Template`] can replace both layouts with a user-defined format such as
+//! `+{line} {path}` for editor integrations.
+//!
+//! [`Printer::print_passthru`] prints every line of the input instead, with
+//! the matches marked, and [`Printer::print_passthru_reader`] does so for a
+//! whole reader, optionally keeping only the matches and their sections.
+//!
+//! Matched lines are written as they are found, so a log containing ANSI
+//! escape sequences could move the cursor or recolor the terminal it is
+//! printed to. With [`PrinterOptions::sanitize`], which the CLI sets unless
+//! `--no-sanitize` is given, such bytes are escaped by [`sanitize`] first.
+//!
+//! Paths are written with [`display_path`], so that on Windows a path never
+//! mixes `/` and `\` even when a directory given as `src/` is walked, and
+//! [`PrinterOptions::path_separator`] can make output the same on every
+//! platform.
+//!
+//! # Examples
+//!
+//! ```
+//! use searcher_cli_starter::printer::{Printer, PrinterOptions};
+//! use searcher_cli_starter::SearchMatch;
+//! use std::path::Path;
+//!
+//! let options = PrinterOptions {
+//!     line_numbers: true,
+//!     with_filename: true,
+//!     heading: false,
+//!     ..PrinterOptions::default()
+//! };
+//! let mut printer = Printer::new(Vec::new(), options);
+//!
+//! let matches = vec![SearchMatch {
+//!     line_number: 3,
+//!     content: String::from("hello rust"),
+//! }];
+//! printer.print_file(Path::new("notes.txt"), &matches).unwrap();
+//!
+//! let output = String::from_utf8(printer.into_inner()).unwrap();
+//! assert_eq!(output, "notes.txt:3:hello rust\n");
+//! ```
+
+use crate::context::Section;
+use crate::records::Record;
+use crate::redact::Redactor;
+use crate::sessions::Session;
+use crate::{MatchedLine, Matcher, SearchOptions, search_reader_passthru};
+use anyhow::{Result, bail};
+use std::borrow::Cow;
+use std::io::{self, Read, Write};
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Controls how matches are laid out by a [`Printer`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrinterOptions {
+    /// Prefix each line with its 1-based line number
+    pub line_numbers: bool,
+    /// Prefix each line with the file name (ignored in heading mode)
+    pub with_filename: bool,
+    /// Print the file name once as a heading above its matches
+    pub heading: bool,
+    /// Format every match with this template instead of the built-in layouts
+    pub template: Option<Template>,
+    /// Highlight matches with ANSI colors in [`Printer::print_passthru`]
+    pub color: bool,
+    /// Escape control characters in matched lines with [`sanitize`] before
+    /// writing them
+    pub sanitize: bool,
+    /// Write paths with this character between their components instead of
+    /// the platform's own (see [`display_path`])
+    pub path_separator: Option<char>,
+    /// Quote every path and matched line as this asks, so scripts can use
+    /// them as they are
+    pub quote: Quote,
+    /// End each line of the input that is printed with NUL instead of
+    /// `\n`, for input split into NUL-terminated records (`-z`)
+    pub null_data: bool,
+}
+
+/// How paths and matched lines are quoted for the programs reading them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Quote {
+    /// Written as they are
+    #[default]
+    None,
+    /// In single quotes for POSIX shells, unless made only of characters
+    /// that need none, such as `src/main.rs`
+    Shell,
+    /// As a C string literal in double quotes, with escapes for `"`, `\`,
+    /// and every byte that is not printable ASCII
+    C,
+}
+
+impl Quote {
+    /// `text` quoted in this style. Text that needs no quotes is borrowed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::printer::Quote;
+    ///
+    /// assert_eq!(Quote::Shell.apply(b"src/main.rs"), &b"src/main.rs"[..]);
+    /// assert_eq!(Quote::Shell.apply(b"it's here"), &br"'it'\''s here'"[..]);
+    /// assert_eq!(Quote::C.apply("say \"hi\"\t\u{e9}".as_bytes()), &br#""say \"hi\"\t\303\251""#[..]);
+    /// ```
+    pub fn apply(self, text: &[u8]) -> Cow<'_, [u8]> {
+        match self {
+            Quote::None => Cow::Borrowed(text),
+            Quote::Shell => {
+                let plain =
+                    |byte: &u8| byte.is_ascii_alphanumeric() || b"@%+=:,./_-".contains(byte);
+                if !text.is_empty() && text.iter().all(plain) {
+                    return Cow::Borrowed(text);
+                }
+                let mut quoted = Vec::with_capacity(text.len() + 2);
+                quoted.push(b'\'');
+                for &byte in text {
+                    match byte {
+                        b'\'' => quoted.extend_from_slice(br"'\''"),
+                        byte => quoted.push(byte),
+                    }
+                }
+                quoted.push(b'\'');
+                Cow::Owned(quoted)
+            }
+            Quote::C => {
+                let mut quoted = Vec::with_capacity(text.len() + 2);
+                quoted.push(b'"');
+                for &byte in text {
+                    match byte {
+                        b'"' => quoted.extend_from_slice(br#"\""#),
+                        b'\\' => quoted.extend_from_slice(br"\\"),
+                        b'\n' => quoted.extend_from_slice(br"\n"),
+                        b'\r' => quoted.extend_from_slice(br"\r"),
+                        b'\t' => quoted.extend_from_slice(br"\t"),
+                        b' '..=b'~' => quoted.push(byte),
+                        // Octal, since a hex escape would swallow the hex
+                        // digits after it
+                        byte => quoted.extend_from_slice(format!("\\{:03o}", byte).as_bytes()),
+                    }
+                }
+                quoted.push(b'"');
+                Cow::Owned(quoted)
+            }
+        }
+    }
+}
+
+/// Starts a highlighted match: bold red.
+const HIGHLIGHT_START: &[u8] = b"\x1b[1;31m";
+/// Ends a highlighted match.
+const HIGHLIGHT_END: &[u8] = b"\x1b[0m";
+/// The length of the bar of the busiest block in [`Printer::print_density`].
+const DENSITY_BAR_WIDTH: usize = 40;
+
+/// A per-match output template such as `{path}:{line}: {content}`.
+///
+/// Supported placeholders:
+///
+/// - `{path}` - the file the match was found in
+/// - `{line}` - the 1-based line number
+/// - `{content}` - the matching line
+///
+/// Use `{{` and `}}` for literal braces. A newline is appended after every
+/// rendered match.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::printer::Template;
+///
+/// let template: Template = "+{line} {path}".parse().unwrap();
+/// assert_eq!(template.render("src/lib.rs", 42, b"fn main() {}"), b"+42 src/lib.rs");
+///
+/// assert!("{column}".parse::<Template>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Path,
+    Line,
+    Content,
+}
+
+impl Template {
+    /// Renders the template for a single match.
+    pub fn render(&self, path: &str, line_number: usize, content: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => output.extend_from_slice(text.as_bytes()),
+                Segment::Path => output.extend_from_slice(path.as_bytes()),
+                Segment::Line => output.extend_from_slice(line_number.to_string().as_bytes()),
+                Segment::Content => output.extend_from_slice(content),
+            }
+        }
+        output
+    }
+}
+
+impl FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => bail!("Invalid format `{}`: unclosed `{{`", s),
+                        }
+                    }
+                    let segment = match name.as_str() {
+                        "path" => Segment::Path,
+                        "line" => Segment::Line,
+                        "content" => Segment::Content,
+                        _ => bail!("Invalid format `{}`: unknown placeholder `{{{}}}`", s, name),
+                    };
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(segment);
+                }
+                '}' => bail!("Invalid format `{}`: unmatched `}}` (use `}}}}`)", s),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Template { segments })
+    }
+}
+
+/// Writes search results to an output stream.
+///
+/// The printer remembers whether it has already written a file group so
+/// that heading mode can separate consecutive files with a blank line.
+pub struct Printer<W: Write> {
+    writer: W,
+    options: PrinterOptions,
+    printed_any: bool,
+}
+
+impl<W: Write> Printer<W> {
+    /// Creates a new printer writing to `writer`.
+    pub fn new(writer: W, options: PrinterOptions) -> Self {
+        Printer {
+            writer,
+            options,
+            printed_any: false,
+        }
+    }
+
+    /// Prints all matches found in a single file.
+    ///
+    /// Nothing is written when `matches` is empty, so files without
+    /// matches never produce a heading. Both [`crate::SearchMatch`] and
+    /// [`crate::SearchMatchBytes`] can be printed; byte content is written
+    /// unchanged unless [`PrinterOptions::sanitize`] is set. When a [`Template`] is set, each match is rendered with it
+    /// and no headings are printed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the underlying stream fails.
+    pub fn print_file<M: MatchedLine>(&mut self, path: &Path, matches: &[M]) -> io::Result<()> {
+        if matches.is_empty() {
+            return Ok(());
+        }
+
+        let path = &*self.shown_path(path);
+        if let Some(template) = &self.options.template {
+            let terminator = self.terminator();
+            for search_match in matches {
+                let rendered = template.render(
+                    path,
+                    search_match.line_number(),
+                    &self.shown(search_match.content_bytes()),
+                );
+                self.writer.write_all(&rendered)?;
+                self.writer.write_all(terminator)?;
+            }
+            self.printed_any = true;
+            return Ok(());
+        }
+
+        if self.options.heading {
+            if self.printed_any {
+                writeln!(self.writer)?;
+            }
+            writeln!(self.writer, "{}", path)?;
+        }
+
+        for search_match in matches {
+            if self.options.with_filename && !self.options.heading {
+                write!(self.writer, "{}:", path)?;
+            }
+            if self.options.line_numbers {
+                write!(self.writer, "{}:", search_match.line_number())?;
+            }
+            self.write_content(search_match.content_bytes())?;
+            self.end_line()?;
+        }
+
+        self.printed_any = true;
+        Ok(())
+    }
+
+    /// Prints one `path:line:column:content` entry per match, the format
+    /// Vim's `:grep` and quickfix list read without a custom `errorformat`.
+    ///
+    /// A line with several matches is printed once for each. Columns are
+    /// 1-based byte offsets found with [`Matcher::match_ranges`]; composite
+    /// matchers have no match positions, so their lines get column 1. The
+    /// layout options are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::printer::{Printer, PrinterOptions};
+    /// use searcher_cli_starter::{Matcher, search_str};
+    /// use std::path::Path;
+    ///
+    /// let matcher = Matcher::new("x", false, false).unwrap();
+    /// let matches = search_str("a x x", &matcher);
+    /// let mut printer = Printer::new(Vec::new(), PrinterOptions::default());
+    /// printer.print_vimgrep(Path::new("f.txt"), &matches, &matcher).unwrap();
+    ///
+    /// let output = String::from_utf8(printer.into_inner()).unwrap();
+    /// assert_eq!(output, "f.txt:1:3:a x x\nf.txt:1:5:a x x\n");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the underlying stream fails.
+    pub fn print_vimgrep<M: MatchedLine>(
+        &mut self,
+        path: &Path,
+        matches: &[M],
+        matcher: &Matcher,
+    ) -> io::Result<()> {
+        let path = display_path(path, self.options.path_separator);
+        for search_match in matches {
+            let content = search_match.content_bytes();
+            let ranges = matcher.match_ranges(content);
+            let columns: Vec<usize> = if ranges.is_empty() {
+                vec![1]
+            } else {
+                ranges.iter().map(|range| range.start + 1).collect()
+            };
+            for column in columns {
+                write!(
+                    self.writer,
+                    "{}:{}:{}:",
+                    path,
+                    search_match.line_number(),
+                    column
+                )?;
+                self.write_content(content)?;
+                self.end_line()?;
+            }
+            self.printed_any = true;
+        }
+        Ok(())
+    }
+
+    /// Prints a GitHub Actions workflow command for each matching line, e.g.
+    /// `::warning file=src/a.rs,line=3,col=5,title=TODO::// TODO: tests`.
+    ///
+    /// GitHub shows these as annotations on the lines of a pull request.
+    /// The column is that of the first match (see [`Matcher::match_ranges`])
+    /// and is left out for composite matchers. The message is the line with
+    /// surrounding whitespace trimmed. The layout options are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::printer::{Printer, PrinterOptions};
+    /// use searcher_cli_starter::{Matcher, search_str};
+    /// use std::path::Path;
+    ///
+    /// let matcher = Matcher::new("TODO", false, false).unwrap();
+    /// let matches = search_str("fn a() {}\n    // TODO: tests\n", &matcher);
+    /// let mut printer = Printer::new(Vec::new(), PrinterOptions::default());
+    /// printer
+    ///     .print_github(Path::new("src/a.rs"), &matches, &matcher, "TODO")
+    ///     .unwrap();
+    ///
+    /// let output = String::from_utf8(printer.into_inner()).unwrap();
+    /// assert_eq!(output, "::warning file=src/a.rs,line=2,col=8,title=TODO::// TODO: tests\n");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the underlying stream fails.
+    pub fn print_github<M: MatchedLine>(
+        &mut self,
+        path: &Path,
+        matches: &[M],
+        matcher: &Matcher,
+        title: &str,
+    ) -> io::Result<()> {
+        let file = escape_github_property(&display_path(path, self.options.path_separator));
+        let title = escape_github_property(title);
+        for search_match in matches {
+            let content = search_match.content_bytes();
+            write!(
+                self.writer,
+                "::warning file={},line={}",
+                file,
+                search_match.line_number()
+            )?;
+            if let Some(range) = matcher.match_ranges(content).first() {
+                write!(self.writer, ",col={}", range.start + 1)?;
+            }
+            let message = String::from_utf8_lossy(content);
+            writeln!(
+                self.writer,
+                ",title={}::{}",
+                title,
+                escape_github_data(message.trim())
+            )?;
+            self.printed_any = true;
+        }
+        Ok(())
+    }
+
+    /// Prints one line of the whole input for `--passthru`, marking whether
+    /// it matched.
+    ///
+    /// The file name and line number (as enabled by the options) are
+    /// followed by `:` on matching lines and `-` on the others, like context
+    /// lines in `grep`. With [`PrinterOptions::color`], the matches are
+    /// highlighted, or the whole line for composite matchers, which have no
+    /// match positions. Headings and templates are not used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::Matcher;
+    /// use searcher_cli_starter::printer::{Printer, PrinterOptions};
+    /// use std::path::Path;
+    ///
+    /// let matcher = Matcher::new("err", false, false).unwrap();
+    /// let options = PrinterOptions { line_numbers: true, ..PrinterOptions::default() };
+    /// let mut printer = Printer::new(Vec::new(), options);
+    /// printer.print_passthru(Path::new("app.log"), 1, b"ok", false, &matcher).unwrap();
+    /// printer.print_passthru(Path::new("app.log"), 2, b"an err", true, &matcher).unwrap();
+    ///
+    /// let output = String::from_utf8(printer.into_inner()).unwrap();
+    /// assert_eq!(output, "1-ok\n2:an err\n");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the underlying stream fails.
+    pub fn print_passthru(
+        &mut self,
+        path: &Path,
+        line_number: usize,
+        line: &[u8],
+        matched: bool,
+        matcher: &Matcher,
+    ) -> io::Result<()> {
+        let separator = if matched { ':' } else { '-' };
+        if self.options.with_filename {
+            let path = self.shown_path(path);
+            write!(self.writer, "{}{}", path, separator)?;
+        }
+        if self.options.line_numbers {
+            write!(self.writer, "{}{}", line_number, separator)?;
+        }
+        // Quoting pieces of the line would quote each of them separately
+        if matched && self.options.color && self.options.quote == Quote::None {
+            let mut ranges = matcher.match_ranges(line);
+            if ranges.is_empty() {
+                ranges.push(0..line.len());
+            }
+            let mut written = 0;
+            for range in ranges {
+                self.write_content(&line[written..range.start])?;
+                self.writer.write_all(HIGHLIGHT_START)?;
+                self.write_content(&line[range.clone()])?;
+                self.writer.write_all(HIGHLIGHT_END)?;
+                written = range.end;
+            }
+            self.write_content(&line[written..])?;
+        } else {
+            self.write_content(line)?;
+        }
+        self.end_line()?;
+        self.printed_any = true;
+        Ok(())
+    }
+
+    /// Prints `--` to separate a group of lines from the ones printed
+    /// before it, as `grep` does between non-adjacent context. Nothing is
+    /// written before the first group.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the underlying stream fails.
+    pub fn print_group_separator(&mut self) -> io::Result<()> {
+        if self.printed_any {
+            writeln!(self.writer, "--")?;
+        }
+        Ok(())
+    }
+
+    /// Prints every line of `reader` with [`Printer::print_passthru`] as it
+    /// is read, and returns how many matched. The matches are masked with
+    /// `redactor` if there is one. With a `delimiter`, only the matching
+    /// lines are printed, each after the lines of its [`Section`] not
+    /// printed yet, and groups that do not follow on from each other are
+    /// separated by `--`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::printer::{Printer, PrinterOptions};
+    /// use searcher_cli_starter::{Matcher, SearchOptions};
+    /// use std::path::Path;
+    ///
+    /// let matcher = Matcher::new("err", false, false).unwrap();
+    /// let delimiter = Matcher::new("^fn ", false, true).unwrap();
+    /// let input = "fn a() {\n  ok\n  err\n}\nfn b() {\n  err\n}\n";
+    /// let mut printer = Printer::new(Vec::new(), PrinterOptions::default());
+    /// let matched = printer
+    ///     .print_passthru_reader(Path::new("a.rs"), input.as_bytes(), &matcher, None,
+    ///         Some(&delimiter), &SearchOptions::default())
+    ///     .unwrap()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(matched, 2);
+    /// let output = String::from_utf8(printer.into_inner()).unwrap();
+    /// assert_eq!(output, "fn a() {\n  ok\n  err\n--\nfn b() {\n  err\n");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Failing to write is the outer error, which should end the run;
+    /// failing to read, or [`Cancelled`](crate::cancel::Cancelled), is the
+    /// inner one, which only ends this input.
+    pub fn print_passthru_reader<R: Read>(
+        &mut self,
+        path: &Path,
+        reader: R,
+        matcher: &Matcher,
+        redactor: Option<&Redactor>,
+        delimiter: Option<&Matcher>,
+        options: &SearchOptions,
+    ) -> io::Result<Result<usize>> {
+        let mut matched = 0;
+        let mut section = delimiter.map(Section::new);
+        // The last line printed from a section, so none is printed twice
+        let mut printed_through = 0;
+        let mut written = Ok(());
+        let read =
+            search_reader_passthru(reader, matcher, options, |line_number, line, is_match| {
+                matched += usize::from(is_match);
+                let shown = match redactor {
+                    Some(redactor) if is_match => {
+                        redactor.redact(line, &matcher.match_ranges(line))
+                    }
+                    _ => Cow::Borrowed(line),
+                };
+                written = match &mut section {
+                    None => self.print_passthru(path, line_number, &shown, is_match, matcher),
+                    Some(section) => {
+                        section.push(line_number, line);
+                        if is_match {
+                            let printed = std::mem::replace(&mut printed_through, line_number);
+                            self.print_in_section(
+                                path,
+                                section,
+                                printed,
+                                line_number,
+                                &shown,
+                                matcher,
+                            )
+                        } else {
+                            Ok(())
+                        }
+                    }
+                };
+                if written.is_ok() {
+                    ControlFlow::Continue(())
+                } else {
+                    ControlFlow::Break(())
+                }
+            });
+        written?;
+        Ok(read.map(|()| matched))
+    }
+
+    /// Prints a matching line after the lines of `section` since line
+    /// `printed_through`, separating them with `--` from the lines printed
+    /// before unless they follow on from them.
+    fn print_in_section(
+        &mut self,
+        path: &Path,
+        section: &Section,
+        printed_through: usize,
+        line_number: usize,
+        line: &[u8],
+        matcher: &Matcher,
+    ) -> io::Result<()> {
+        let context: Vec<(usize, &[u8])> = section
+            .lines_before(line_number)
+            .filter(|(number, _)| *number > printed_through)
+            .collect();
+        let first = context.first().map_or(line_number, |(number, _)| *number);
+        if printed_through == 0 || first > printed_through + 1 {
+            self.print_group_separator()?;
+        }
+        for (number, line) in context {
+            self.print_passthru(path, number, line, false, matcher)?;
+        }
+        self.print_passthru(path, line_number, line, true, matcher)
+    }
+
+    /// Prints a [`Record`]: a `record N, lines A-B` heading, after the path
+    /// when [`PrinterOptions::with_filename`] is set, followed by its lines.
+    /// Records are separated by `--`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::printer::{Printer, PrinterOptions};
+    /// use searcher_cli_starter::records::Record;
+    /// use std::path::Path;
+    ///
+    /// let record = Record {
+    ///     number: 2,
+    ///     line_number: 4,
+    ///     line_count: 2,
+    ///     content: b"Error: refused\n  at connect".to_vec(),
+    /// };
+    /// let mut printer = Printer::new(Vec::new(), PrinterOptions::default());
+    /// printer.print_record(Path::new("app.log"), &record).unwrap();
+    ///
+    /// let output = String::from_utf8(printer.into_inner()).unwrap();
+    /// assert_eq!(output, "record 2, lines 4-5\nError: refused\n  at connect\n");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the underlying stream fails.
+    pub fn print_record(&mut self, path: &Path, record: &Record) -> io::Result<()> {
+        self.print_group_separator()?;
+        if self.options.with_filename {
+            let path = self.shown_path(path);
+            write!(self.writer, "{}:", path)?;
+        }
+        if record.line_count == 1 {
+            writeln!(
+                self.writer,
+                "record {}, line {}",
+                record.number, record.line_number
+            )?;
+        } else {
+            let last = record.last_line_number();
+            let first = record.line_number;
+            writeln!(
+                self.writer,
+                "record {}, lines {}-{}",
+                record.number, first, last
+            )?;
+        }
+        for (line_number, line) in (record.line_number..).zip(record.lines()) {
+            if self.options.line_numbers {
+                write!(self.writer, "{}:", line_number)?;
+            }
+            self.write_content(line)?;
+            self.end_line()?;
+        }
+        self.printed_any = true;
+        Ok(())
+    }
+
+    /// Prints a [`Session`]: its key as a heading, followed by its lines,
+    /// each after its path when [`PrinterOptions::with_filename`] is set.
+    /// Sessions are separated by a blank line, as files are in heading mode.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::printer::{Printer, PrinterOptions};
+    /// use searcher_cli_starter::sessions::Sessions;
+    /// use std::path::Path;
+    ///
+    /// let mut sessions = Sessions::new(r"req=(\w+)").unwrap();
+    /// sessions.add(Path::new("app.log"), 1, b"req=a start");
+    /// sessions.add(Path::new("app.log"), 2, b"req=b start");
+    /// sessions.add(Path::new("app.log"), 3, b"req=a done");
+    /// let options = PrinterOptions { line_numbers: true, ..PrinterOptions::default() };
+    /// let mut printer = Printer::new(Vec::new(), options);
+    /// for session in sessions.sessions() {
+    ///     printer.print_session(session).unwrap();
+    /// }
+    ///
+    /// let output = String::from_utf8(printer.into_inner()).unwrap();
+    /// assert_eq!(output, "a\n1:req=a start\n3:req=a done\n\nb\n2:req=b start\n");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the underlying stream fails.
+    pub fn print_session(&mut self, session: &Session) -> io::Result<()> {
+        if self.printed_any {
+            writeln!(self.writer)?;
+        }
+        self.write_content(session.key.as_bytes())?;
+        writeln!(self.writer)?;
+        for (path, line) in &session.lines {
+            if self.options.with_filename {
+                let path = self.shown_path(path);
+                write!(self.writer, "{}:", path)?;
+            }
+            if self.options.line_numbers {
+                write!(self.writer, "{}:", line.line_number)?;
+            }
+            self.write_content(&line.content)?;
+            self.end_line()?;
+        }
+        self.printed_any = true;
+        Ok(())
+    }
+
+    /// Prints the blocks of a file found by [`density`]: a line per block of
+    /// `lines` lines with its range, its number of matches, and a bar of up
+    /// to 40 `#` scaled to the busiest block. The path comes first, on a
+    /// line of its own, when [`PrinterOptions::with_filename`] or
+    /// [`PrinterOptions::heading`] is set, and files are separated by a
+    /// blank line. Files without blocks print nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::printer::{Printer, PrinterOptions};
+    /// use std::path::Path;
+    ///
+    /// let mut printer = Printer::new(Vec::new(), PrinterOptions::default());
+    /// printer.print_density(Path::new("app.log"), &[(1, 4), (11, 0), (21, 1)], 10).unwrap();
+    ///
+    /// let output = String::from_utf8(printer.into_inner()).unwrap();
+    /// assert_eq!(
+    ///     output,
+    ///     "           1-10        4  ########################################\n\
+    ///      \x20         11-20        0\n\
+    ///      \x20         21-30        1  ##########\n"
+    /// );
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the underlying stream fails.
+    ///
+    /// [`density`]: crate::histogram::density
+    pub fn print_density(
+        &mut self,
+        path: &Path,
+        blocks: &[(usize, usize)],
+        lines: usize,
+    ) -> io::Result<()> {
+        if blocks.is_empty() {
+            return Ok(());
+        }
+        if self.printed_any {
+            writeln!(self.writer)?;
+        }
+        if self.options.with_filename || self.options.heading {
+            writeln!(self.writer, "{}", self.shown_path(path))?;
+        }
+        let busiest = blocks.iter().map(|&(_, count)| count).max().unwrap_or(0);
+        for &(first, count) in blocks {
+            let range = format!("{}-{}", first, first + lines - 1);
+            let bar = "#".repeat((count * DENSITY_BAR_WIDTH).div_ceil(busiest.max(1)));
+            let row = format!("{:>15}  {:>7}  {}", range, count, bar);
+            writeln!(self.writer, "{}", row.trim_end())?;
+        }
+        self.printed_any = true;
+        Ok(())
+    }
+
+    /// Consumes the printer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// `content`, escaped and quoted if the options ask for it. C strings
+    /// escape control characters themselves, so they are not sanitized.
+    fn shown<'a>(&self, content: &'a [u8]) -> Cow<'a, [u8]> {
+        if !self.options.sanitize || self.options.quote == Quote::C {
+            return self.options.quote.apply(content);
+        }
+        match sanitize(content) {
+            Cow::Borrowed(content) => self.options.quote.apply(content),
+            Cow::Owned(content) => Cow::Owned(self.options.quote.apply(&content).into_owned()),
+        }
+    }
+
+    /// `path` as [`display_path`] writes it, quoted if the options ask for
+    /// it.
+    fn shown_path<'p>(&self, path: &'p Path) -> Cow<'p, str> {
+        let path = display_path(path, self.options.path_separator);
+        match self.options.quote.apply(path.as_bytes()) {
+            // Quoting only adds ASCII around and inside valid UTF-8
+            Cow::Owned(quoted) => Cow::Owned(String::from_utf8_lossy(&quoted).into_owned()),
+            Cow::Borrowed(_) => path,
+        }
+    }
+
+    /// Writes a matched line (or part of one), escaped and quoted if the
+    /// options ask for it.
+    fn write_content(&mut self, content: &[u8]) -> io::Result<()> {
+        let content = self.shown(content);
+        self.writer.write_all(&content)
+    }
+
+    /// Ends a line of the input with its terminator.
+    fn end_line(&mut self) -> io::Result<()> {
+        self.writer.write_all(self.terminator())
+    }
+
+    /// NUL for [`PrinterOptions::null_data`], otherwise `\n`.
+    fn terminator(&self) -> &'static [u8] {
+        if self.options.null_data { b"\0" } else { b"\n" }
+    }
+}
+
+/// Writes `path` for output, with `separator` between its components, or
+/// the platform's own separator when `separator` is `None`.
+///
+/// Every character the platform treats as a separator is replaced: `/` on
+/// Unix, where a `\` is part of a file name, and both `/` and `\` on
+/// Windows, so a path never mixes the two. Passing `Some('/')` gives the
+/// same output everywhere, for tools that parse it.
+///
+/// Control characters in file names are escaped as [`sanitize`] escapes
+/// them in content, so a file named to hold an ANSI sequence cannot change
+/// the terminal's state when listed; this holds even with `--no-sanitize`,
+/// which is only about matched lines.
+///
+/// File names need not be valid UTF-8. The bytes that are not are written
+/// as `\xff`-style escapes, like [`sanitize`] writes control characters,
+/// so different bytes print differently instead of all as a lossy
+/// `\u{fffd}`. A backslash is left as it is, though, so a name spelling out
+/// `\xff` in text prints the same as one holding the byte: the output is
+/// for reading, not for getting the name back.
+/// On Windows, the `\\?\` of a long path given as `\\?\C:\logs` is left
+/// out, as it only tells the system to take the path as it is.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::printer::display_path;
+/// use std::path::Path;
+///
+/// let path = Path::new("src").join("printer.rs");
+/// assert_eq!(display_path(&path, Some('/')), "src/printer.rs");
+/// assert_eq!(display_path(Path::new("\x1b[2Ja.log"), None), r"\x1b[2Ja.log");
+/// #[cfg(windows)]
+/// assert_eq!(display_path(Path::new("src/a\\b.rs"), None), "src\\a\\b.rs");
+/// #[cfg(unix)]
+/// {
+///     use std::os::unix::ffi::OsStrExt;
+///
+///     let path = Path::new(std::ffi::OsStr::from_bytes(b"caf\xe9.txt"));
+///     assert_eq!(display_path(path, None), "caf\\xe9.txt");
+/// }
+/// ```
+pub fn display_path(path: &Path, separator: Option<char>) -> Cow<'_, str> {
+    let separator = separator.unwrap_or(std::path::MAIN_SEPARATOR);
+    let replace = |c: char| {
+        if std::path::is_separator(c) {
+            separator
+        } else {
+            c
+        }
+    };
+    let safe = |c: char| c == '\t' || !c.is_control();
+    let (prefix, bytes) = strip_verbatim(path.as_os_str().as_encoded_bytes());
+    if prefix.is_empty()
+        && let Ok(text) = std::str::from_utf8(bytes)
+        && text.chars().all(|c| replace(c) == c && safe(c))
+    {
+        return Cow::Borrowed(text);
+    }
+
+    let mut displayed = String::with_capacity(bytes.len() + 8);
+    displayed.extend(prefix.chars().map(replace));
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if safe(c) {
+                displayed.push(replace(c));
+            } else if c.is_ascii() {
+                displayed.push_str(&format!("\\x{:02x}", c as u32));
+            } else {
+                displayed.push_str(&format!("\\u{{{:x}}}", c as u32));
+            }
+        }
+        for byte in chunk.invalid() {
+            displayed.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    Cow::Owned(displayed)
+}
+
+/// Splits the `\\?\` off a Windows verbatim path to a drive or share,
+/// returning what to write in its place and the rest of the path:
+/// `\\?\C:\x` is written as `C:\x`, and `\\?\UNC\server\x` as
+/// `\\server\x`.
+#[cfg(windows)]
+fn strip_verbatim(path: &[u8]) -> (&'static str, &[u8]) {
+    if let Some(share) = path.strip_prefix(br"\\?\UNC\") {
+        (r"\\", share)
+    } else if let Some(drive) = path.strip_prefix(br"\\?\")
+        && drive.get(1) == Some(&b':')
+    {
+        ("", drive)
+    } else {
+        ("", path)
+    }
+}
+
+#[cfg(not(windows))]
+fn strip_verbatim(path: &[u8]) -> (&'static str, &[u8]) {
+    ("", path)
+}
+
+/// Escapes whatever in `content` a terminal could interpret rather than
+/// display, so that printing a hostile or binary log cannot change the
+/// terminal's state.
+///
+/// Control characters other than tab are written as `\x1b`-style escapes
+/// (`\u{9b}` for the C1 controls beyond ASCII), which covers the `ESC`
+/// starting every ANSI sequence and NUL bytes. Bytes that are not valid
+/// UTF-8 are kept as they are, except `0x80` to `0x9f`, which 8-bit
+/// terminals read as C1 controls and are written as `\x9b`-style escapes.
+/// Anything else, including backslashes, is left alone, and content needing
+/// no escapes is borrowed.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::printer::sanitize;
+///
+/// assert_eq!(sanitize(b"\x1b[2Jerror\0"), &br"\x1b[2Jerror\x00"[..]);
+/// assert_eq!(sanitize("\tcaf\u{e9} \u{9b}".as_bytes()), "\tcaf\u{e9} \\u{9b}".as_bytes());
+/// assert_eq!(sanitize(b"\xff\x9b"), &b"\xff\\x9b"[..]);
+/// ```
+pub fn sanitize(content: &[u8]) -> Cow<'_, [u8]> {
+    let safe = |c: char| c == '\t' || !c.is_control();
+    let safe_byte = |byte: &u8| !(0x80..=0x9f).contains(byte);
+    if content
+        .utf8_chunks()
+        .all(|chunk| chunk.valid().chars().all(safe) && chunk.invalid().iter().all(safe_byte))
+    {
+        return Cow::Borrowed(content);
+    }
+
+    let mut escaped = Vec::with_capacity(content.len() + 16);
+    for chunk in content.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if safe(c) {
+                escaped.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            } else if c.is_ascii() {
+                escaped.extend_from_slice(format!("\\x{:02x}", c as u32).as_bytes());
+            } else {
+                escaped.extend_from_slice(format!("\\u{{{:x}}}", c as u32).as_bytes());
+            }
+        }
+        for byte in chunk.invalid() {
+            if safe_byte(byte) {
+                escaped.push(*byte);
+            } else {
+                escaped.extend_from_slice(format!("\\x{:02x}", byte).as_bytes());
+            }
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Escapes the message of a GitHub Actions workflow command.
+fn escape_github_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes a property value (`file=...`) of a GitHub Actions workflow command.
+fn escape_github_property(text: &str) -> String {
+    escape_github_data(text)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::CompositeMatcher;
+    use crate::{SearchMatch, SearchMatchBytes};
+
+    fn sample_matches() -> Vec<SearchMatch> {
+        vec![
+            SearchMatch {
+                line_number: 1,
+                content: String::from("first"),
+            },
+            SearchMatch {
+                line_number: 4,
+                content: String::from("second"),
+            },
+        ]
+    }
+
+    fn render(options: PrinterOptions, files: &[(&str, Vec<SearchMatch>)]) -> String {
+        let mut printer = Printer::new(Vec::new(), options);
+        for (path, matches) in files {
+            printer.print_file(Path::new(path), matches).unwrap();
+        }
+        String::from_utf8(printer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_plain_output() {
+        let output = render(PrinterOptions::default(), &[("a.txt", sample_matches())]);
+        assert_eq!(output, "first\nsecond\n");
+    }
+
+    #[test]
+    fn test_no_heading_with_filename_and_line_numbers() {
+        let options = PrinterOptions {
+            line_numbers: true,
+            with_filename: true,
+            heading: false,
+            ..PrinterOptions::default()
+        };
+        let output = render(options, &[("a.txt", sample_matches())]);
+        assert_eq!(output, "a.txt:1:first\na.txt:4:second\n");
+    }
+
+    #[test]
+    fn test_null_data_ends_lines_with_nul() {
+        let options = PrinterOptions {
+            heading: true,
+            null_data: true,
+            ..PrinterOptions::default()
+        };
+        let output = render(options.clone(), &[("a.txt", sample_matches())]);
+        assert_eq!(output, "a.txt\nfirst\0second\0");
+
+        let options = PrinterOptions {
+            template: Some("{line}:{content}".parse().unwrap()),
+            ..options
+        };
+        let output = render(options, &[("a.txt", sample_matches())]);
+        assert_eq!(output, "1:first\x004:second\0");
+    }
+
+    #[test]
+    fn test_heading_groups_files() {
+        let options = PrinterOptions {
+            line_numbers: true,
+            with_filename: true,
+            heading: true,
+            ..PrinterOptions::default()
+        };
+        let output = render(
+            options,
+            &[("a.txt", sample_matches()), ("b.txt", sample_matches())],
+        );
+        assert_eq!(
+            output,
+            "a.txt\n1:first\n4:second\n\nb.txt\n1:first\n4:second\n"
+        );
+    }
+
+    #[test]
+    fn test_path_separator_replaces_native_separators() {
+        let options = PrinterOptions {
+            with_filename: true,
+            path_separator: Some('+'),
+            ..PrinterOptions::default()
+        };
+        let output = render(options, &[("logs/app/a.txt", sample_matches())]);
+        assert_eq!(output, "logs+app+a.txt:first\nlogs+app+a.txt:second\n");
+
+        let unchanged = display_path(Path::new("logs/a.txt"), Some('/'));
+        assert!(matches!(unchanged, Cow::Borrowed("logs/a.txt")));
+    }
+
+    #[test]
+    fn test_quote_applies_to_paths_and_lines() {
+        let options = PrinterOptions {
+            with_filename: true,
+            line_numbers: true,
+            quote: Quote::Shell,
+            ..PrinterOptions::default()
+        };
+        let matches = vec![SearchMatch {
+            line_number: 2,
+            content: String::from("echo $HOME"),
+        }];
+        let output = render(options.clone(), &[("my notes.txt", matches.clone())]);
+        assert_eq!(output, "'my notes.txt':2:'echo $HOME'\n");
+
+        let options = PrinterOptions {
+            quote: Quote::C,
+            ..options
+        };
+        let matches = vec![SearchMatch {
+            line_number: 2,
+            content: String::from("a\x1b[31m\\"),
+        }];
+        let output = render(options, &[("notes.txt", matches)]);
+        assert_eq!(output, "\"notes.txt\":2:\"a\\033[31m\\\\\"\n");
+        assert_eq!(Quote::Shell.apply(b""), &b"''"[..]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_verbatim_prefix_is_left_out() {
+        assert_eq!(
+            display_path(Path::new(r"\\?\C:\logs\a.txt"), None),
+            r"C:\logs\a.txt"
+        );
+        assert_eq!(
+            display_path(Path::new(r"\\?\UNC\server\share\a.txt"), Some('/')),
+            "//server/share/a.txt"
+        );
+    }
+
+    #[test]
+    fn test_control_characters_in_paths_are_escaped() {
+        let path = Path::new("logs/\x1b]0;owned\x07\u{9b}1m.log");
+        assert_eq!(
+            display_path(path, Some('/')),
+            "logs/\\x1b]0;owned\\x07\\u{9b}1m.log"
+        );
+        assert_eq!(display_path(Path::new("a\tb.log"), None), "a\tb.log");
+
+        // Also without sanitizing, which is only about matched lines
+        let output = render(
+            PrinterOptions {
+                with_filename: true,
+                sanitize: false,
+                ..PrinterOptions::default()
+            },
+            &[("\x1b[2Ja.txt", sample_matches())],
+        );
+        assert_eq!(output, "\\x1b[2Ja.txt:first\n\\x1b[2Ja.txt:second\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_paths_are_escaped() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"logs/\xff\xfe/a\x80.txt"));
+        assert_eq!(display_path(path, Some('+')), "logs+\\xff\\xfe+a\\x80.txt");
+        // Backslashes are not escaped, so this name prints as the one above
+        let spelled = Path::new(r"logs/\xff\xfe/a\x80.txt");
+        assert_eq!(
+            display_path(spelled, Some('+')),
+            display_path(path, Some('+'))
+        );
+
+        let mut printer = Printer::new(
+            Vec::new(),
+            PrinterOptions {
+                heading: true,
+                ..PrinterOptions::default()
+            },
+        );
+        printer.print_file(path, &sample_matches()).unwrap();
+        let output = String::from_utf8(printer.into_inner()).unwrap();
+        assert!(output.starts_with("logs/\\xff\\xfe/a\\x80.txt\n"));
+    }
+
+    #[test]
+    fn test_heading_skips_files_without_matches() {
+        let options = PrinterOptions {
+            heading: true,
+            ..PrinterOptions::default()
+        };
+        let output = render(
+            options,
+            &[("a.txt", Vec::new()), ("b.txt", sample_matches())],
+        );
+        assert_eq!(output, "b.txt\nfirst\nsecond\n");
+    }
+
+    #[test]
+    fn test_byte_content_written_unchanged() {
+        let matches = vec![SearchMatchBytes {
+            line_number: 2,
+            content: b"raw \xFF bytes".to_vec(),
+        }];
+        let mut printer = Printer::new(Vec::new(), PrinterOptions::default());
+        printer.print_file(Path::new("a.bin"), &matches).unwrap();
+
+        assert_eq!(printer.into_inner(), b"raw \xFF bytes\n");
+    }
+
+    #[test]
+    fn test_template_output() {
+        let options = PrinterOptions {
+            heading: true,
+            template: Some("{path}:{line}: {content}".parse().unwrap()),
+            ..PrinterOptions::default()
+        };
+        let output = render(
+            options,
+            &[("a.txt", sample_matches()), ("b.txt", sample_matches())],
+        );
+        assert_eq!(
+            output,
+            "a.txt:1: first\na.txt:4: second\nb.txt:1: first\nb.txt:4: second\n"
+        );
+    }
+
+    #[test]
+    fn test_vimgrep_one_entry_per_match() {
+        let matcher = Matcher::new(r"\d+", false, true).unwrap();
+        let matches = vec![SearchMatchBytes {
+            line_number: 7,
+            content: b"a1 b22 \xFF".to_vec(),
+        }];
+        let mut printer = Printer::new(Vec::new(), PrinterOptions::default());
+        printer
+            .print_vimgrep(Path::new("x.log"), &matches, &matcher)
+            .unwrap();
+
+        assert_eq!(
+            printer.into_inner(),
+            b"x.log:7:2:a1 b22 \xFF\nx.log:7:5:a1 b22 \xFF\n"
+        );
+    }
+
+    #[test]
+    fn test_github_escapes_commands() {
+        let matcher = Matcher::new("done", false, false).unwrap();
+        let matches = vec![SearchMatch {
+            line_number: 2,
+            content: String::from("  100% done\r"),
+        }];
+        let mut printer = Printer::new(Vec::new(), PrinterOptions::default());
+        printer
+            .print_github(Path::new("a,b.txt"), &matches, &matcher, "x: y")
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(printer.into_inner()).unwrap(),
+            "::warning file=a%2Cb.txt,line=2,col=8,title=x%3A y::100%25 done\n"
+        );
+    }
+
+    #[test]
+    fn test_template_escaped_braces() {
+        let template: Template = "{{{line}}} {content}".parse().unwrap();
+        assert_eq!(template.render("a", 3, b"x"), b"{3} x");
+    }
+
+    #[test]
+    fn test_template_errors() {
+        for invalid in ["{line", "{nope}", "a } b"] {
+            assert!(invalid.parse::<Template>().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_passthru_highlights_matches() {
+        let options = PrinterOptions {
+            with_filename: true,
+            color: true,
+            ..PrinterOptions::default()
+        };
+        let mut printer = Printer::new(Vec::new(), options);
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let path = Path::new("a");
+        printer
+            .print_passthru(path, 1, b"axbx", true, &matcher)
+            .unwrap();
+        printer
+            .print_passthru(path, 2, b"y", false, &matcher)
+            .unwrap();
+        // Composite matchers have no positions, so the whole line is marked
+        let query = Matcher::from(CompositeMatcher::parse("x z", false, false).unwrap());
+        printer
+            .print_passthru(path, 3, b"xz", true, &query)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(printer.into_inner()).unwrap(),
+            "a:a\x1b[1;31mx\x1b[0mb\x1b[1;31mx\x1b[0m\na-y\na:\x1b[1;31mxz\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_passthru_reader_masks_and_keeps_sections() {
+        let options = PrinterOptions {
+            line_numbers: true,
+            ..PrinterOptions::default()
+        };
+        let matcher = Matcher::new("key=\\d+", false, true).unwrap();
+        let delimiter = Matcher::new(r"^\[", false, true).unwrap();
+        let redactor = Redactor::default();
+        let input = "top key=0\n[a]\nx\nkey=1\nkey=2\n[b]\ny\n[c]\nkey=3\n";
+        let print = |delimiter| {
+            let mut printer = Printer::new(Vec::new(), options.clone());
+            let path = Path::new("a.ini");
+            let read = printer.print_passthru_reader(
+                path,
+                input.as_bytes(),
+                &matcher,
+                Some(&redactor),
+                delimiter,
+                &SearchOptions::default(),
+            );
+            assert_eq!(read.unwrap().unwrap(), 4);
+            String::from_utf8(printer.into_inner()).unwrap()
+        };
+
+        assert_eq!(
+            print(None),
+            "1:top ****\n2-[a]\n3-x\n4:****\n5:****\n6-[b]\n7-y\n8-[c]\n9:****\n"
+        );
+        // Lines before the first delimiter have no section, and the section
+        // of line 4 follows on from line 1 so needs no `--`
+        assert_eq!(
+            print(Some(&delimiter)),
+            "1:top ****\n2-[a]\n3-x\n4:****\n5:****\n--\n8-[c]\n9:****\n"
+        );
+    }
+
+    #[test]
+    fn test_sanitize_escapes_content_but_not_highlights() {
+        let options = PrinterOptions {
+            line_numbers: true,
+            color: true,
+            sanitize: true,
+            ..PrinterOptions::default()
+        };
+        let mut printer = Printer::new(Vec::new(), options);
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let matches = vec![SearchMatchBytes {
+            line_number: 1,
+            content: b"\x1b]0;owned\x07x\r".to_vec(),
+        }];
+        printer.print_file(Path::new("a"), &matches).unwrap();
+        printer
+            .print_passthru(Path::new("a"), 2, b"\x1bx", true, &matcher)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(printer.into_inner()).unwrap(),
+            "1:\\x1b]0;owned\\x07x\\x0d\n2:\\x1b\x1b[1;31mx\x1b[0m\n"
+        );
+        assert!(matches!(sanitize(b"plain \xc3\xa9"), Cow::Borrowed(_)));
+    }
+}
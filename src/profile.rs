@@ -0,0 +1,127 @@
+//! Memory use of a search, for `--stats` (`profiling` feature).
+//!
+//! A [`Profiler`] started before a search and finished after it gives a
+//! [`Profile`] of the allocations made in between, the most heap memory in
+//! use at once, and the most memory the process has had resident. Searching
+//! a file should allocate about the same whatever its size: many
+//! allocations per line mean a fast path was missed.
+//!
+//! Allocations are counted by [`CountingAllocator`], which the binary must
+//! install as its global allocator; without it they read as zero. Peak RSS
+//! is read from `/proc/self/status`, so it is only known on Linux.
+//!
+//! ```
+//! use searcher_cli_starter::profile::Profiler;
+//! use searcher_cli_starter::{Matcher, search_str};
+//!
+//! let profiler = Profiler::start();
+//! let matcher = Matcher::new("error", false, false).unwrap();
+//! let matches = search_str("ok\nerror\n", &matcher);
+//! let profile = profiler.finish();
+//!
+//! assert_eq!(matches.len(), 1);
+//! println!("{}", profile);
+//! ```
+//!
+//! [`CountingAllocator`]: crate::bench::CountingAllocator
+
+use crate::bench::{allocation_counts, heap_bytes, reset_peak_heap};
+use std::fmt;
+use std::fs;
+
+/// The memory a search used, as measured by a [`Profiler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Profile {
+    /// Allocations made during the search
+    pub allocations: u64,
+    /// Bytes allocated during the search, including those freed again
+    pub allocated_bytes: u64,
+    /// The most heap memory in use at once during the search, in bytes
+    pub peak_heap_bytes: u64,
+    /// The most memory the process has had resident since it started, in
+    /// bytes, where known
+    pub peak_rss: Option<u64>,
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Allocations:  {} ({} bytes)",
+            self.allocations, self.allocated_bytes
+        )?;
+        writeln!(f, "Peak heap:    {} bytes", self.peak_heap_bytes)?;
+        match self.peak_rss {
+            Some(bytes) => write!(f, "Peak RSS:     {} bytes", bytes),
+            None => write!(f, "Peak RSS:     unknown"),
+        }
+    }
+}
+
+/// Measures the memory used from when it is started until it is finished.
+#[derive(Debug)]
+pub struct Profiler {
+    allocations: u64,
+    allocated_bytes: u64,
+}
+
+impl Profiler {
+    /// Starts measuring. Only one profiler should run at a time, as each
+    /// starts the peak heap afresh.
+    pub fn start() -> Self {
+        reset_peak_heap();
+        let (allocations, allocated_bytes) = allocation_counts();
+        Profiler {
+            allocations,
+            allocated_bytes,
+        }
+    }
+
+    /// The memory used since [`Profiler::start`].
+    pub fn finish(&self) -> Profile {
+        let (allocations, allocated_bytes) = allocation_counts();
+        Profile {
+            allocations: allocations - self.allocations,
+            allocated_bytes: allocated_bytes - self.allocated_bytes,
+            peak_heap_bytes: heap_bytes().1,
+            peak_rss: peak_rss(),
+        }
+    }
+}
+
+/// The most memory the process has had resident since it started, in
+/// bytes, if the system says.
+pub fn peak_rss() -> Option<u64> {
+    parse_peak_rss(&fs::read_to_string("/proc/self/status").ok()?)
+}
+
+/// Reads the `VmHWM` line of a Linux `/proc/<pid>/status` file.
+fn parse_peak_rss(status: &str) -> Option<u64> {
+    let line = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?;
+    let kilobytes = line.trim().strip_suffix("kB")?.trim().parse::<u64>().ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peak_rss_is_read_from_proc_status() {
+        let status =
+            "Name:\tsearcher\nVmPeak:\t  20000 kB\nVmHWM:\t    8192 kB\nVmRSS:\t 4096 kB\n";
+        assert_eq!(parse_peak_rss(status), Some(8192 * 1024));
+        assert_eq!(parse_peak_rss("Name:\tsearcher\n"), None);
+        if cfg!(target_os = "linux") {
+            assert!(peak_rss().is_some_and(|bytes| bytes > 0));
+        }
+
+        let profile = Profile {
+            peak_rss: None,
+            ..Profile::default()
+        };
+        assert!(profile.to_string().ends_with("Peak RSS:     unknown"));
+    }
+}
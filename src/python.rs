@@ -0,0 +1,163 @@
+//! Python bindings.
+//!
+//! Available with the `python` cargo feature. Build and install the
+//! extension module into the active virtualenv with
+//! [maturin](https://www.maturin.rs/):
+//!
+//! ```text
+//! maturin develop --release
+//! ```
+//!
+//! The module is importable as `searcher`:
+//!
+//! ```text
+//! import searcher
+//!
+//! matcher = searcher.Matcher(r"ERROR|WARN", regex=True)
+//! for m in searcher.search_file("app.log", matcher):
+//!     print(m.line_number, m.content)
+//! ```
+
+use crate::{Matcher, SearchOptions, search_path, search_str};
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+use std::path::PathBuf;
+
+/// A matching line, exposed to Python as `searcher.Match`.
+///
+/// Behaves like a frozen dataclass: fields are read-only attributes, and
+/// instances compare equal when their fields do.
+#[pyclass(
+    name = "Match",
+    module = "searcher",
+    frozen,
+    get_all,
+    eq,
+    skip_from_py_object
+)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PyMatch {
+    /// 1-based line number
+    pub line_number: usize,
+    /// The line, without its terminator
+    pub content: String,
+}
+
+#[pymethods]
+impl PyMatch {
+    #[new]
+    fn new(line_number: usize, content: String) -> Self {
+        PyMatch {
+            line_number,
+            content,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Match(line_number={}, content={:?})",
+            self.line_number, self.content
+        )
+    }
+}
+
+/// A compiled pattern, exposed to Python as `searcher.Matcher`.
+#[pyclass(name = "Matcher", module = "searcher", frozen)]
+pub struct PyMatcher {
+    matcher: Matcher,
+}
+
+#[pymethods]
+impl PyMatcher {
+    /// Compiles `pattern`; raises `ValueError` if it is not a valid regex.
+    #[new]
+    #[pyo3(signature = (pattern, ignore_case = false, regex = false))]
+    fn new(pattern: &str, ignore_case: bool, regex: bool) -> PyResult<Self> {
+        let matcher = Matcher::new(pattern, ignore_case, regex)
+            .map_err(|error| PyValueError::new_err(format!("{:#}", error)))?;
+        Ok(PyMatcher { matcher })
+    }
+
+    /// Whether `line` matches the pattern.
+    fn is_match(&self, line: &str) -> bool {
+        self.matcher.is_match(line)
+    }
+}
+
+/// Searches the lines of `text`, returning a list of `Match` objects.
+#[pyfunction]
+fn search_lines(text: &str, matcher: &PyMatcher) -> Vec<PyMatch> {
+    search_str(text, &matcher.matcher)
+        .into_iter()
+        .map(|m| PyMatch {
+            line_number: m.line_number,
+            content: m.content,
+        })
+        .collect()
+}
+
+/// Searches the file at `path`; raises `OSError` if it cannot be read.
+///
+/// Lines that are not valid UTF-8 are decoded with replacement characters.
+#[pyfunction]
+fn search_file(py: Python<'_>, path: PathBuf, matcher: &PyMatcher) -> PyResult<Vec<PyMatch>> {
+    // Release the GIL while reading so other Python threads keep running
+    let matches = py
+        .detach(|| search_path(&path, &matcher.matcher, &SearchOptions::default()))
+        .map_err(|error| PyOSError::new_err(format!("{:#}", error)))?;
+    Ok(matches
+        .iter()
+        .map(|m| PyMatch {
+            line_number: m.line_number,
+            content: m.content_lossy().into_owned(),
+        })
+        .collect())
+}
+
+/// The `searcher` Python module.
+#[pymodule]
+#[pyo3(name = "searcher")]
+fn searcher_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyMatch>()?;
+    module.add_class::<PyMatcher>()?;
+    module.add_function(wrap_pyfunction!(search_lines, module)?)?;
+    module.add_function(wrap_pyfunction!(search_file, module)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyDict;
+
+    #[test]
+    fn test_module_from_python() {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::new(py, "searcher").unwrap();
+            searcher_module(&module).unwrap();
+            let locals = PyDict::new(py);
+            locals.set_item("searcher", module).unwrap();
+
+            py.run(
+                c"matcher = searcher.Matcher('rust', ignore_case=True)\n\
+                  found = searcher.search_lines('Rust\\nGo\\nrustc\\n', matcher)\n\
+                  assert [m.line_number for m in found] == [1, 3]\n\
+                  assert found[0] == searcher.Match(1, 'Rust')\n\
+                  assert repr(found[0]) == \"Match(line_number=1, content=\\\"Rust\\\")\"\n",
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+        });
+    }
+
+    #[test]
+    fn test_invalid_regex_raises_value_error() {
+        Python::initialize();
+        Python::attach(|py| {
+            let error = PyMatcher::new("(", false, true).err().unwrap();
+            assert!(error.is_instance_of::<PyValueError>(py));
+        });
+    }
+}
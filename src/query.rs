@@ -0,0 +1,681 @@
+//! Boolean combinations of patterns.
+//!
+//! A [`CompositeMatcher`] is a small tree of [`Matcher`]s joined with AND, OR,
+//! and NOT. It can be built directly or parsed from a mini query language:
+//!
+//! ```text
+//! error AND NOT retry
+//! (timeout OR refused) AND "db pool"
+//! ```
+//!
+//! - `AND`, `OR`, and `NOT` must be written in uppercase
+//! - `NOT` binds tightest, then `AND`, then `OR`
+//! - Terms written next to each other are joined with an implicit `AND`
+//! - Double quotes group words into a single term (`\"` escapes a quote)
+//! - Parentheses override precedence
+//! - `re:` makes a term a regex whatever the other terms are. It runs to the
+//!   next whitespace or unmatched `)`, so `re:(a|b)x` is one term whose
+//!   parentheses are the regex's own; `re:"a b"` quotes one with spaces
+//!
+//! # Examples
+//!
+//! ```
+//! use searcher_cli_starter::query::CompositeMatcher;
+//!
+//! let query = CompositeMatcher::parse("error AND NOT retry", false, false).unwrap();
+//! assert!(query.is_match("error: disk full"));
+//! assert!(!query.is_match("error: timeout, retry scheduled"));
+//! ```
+//!
+//! [`build_matcher`] combines a pattern with the extra pattern lists from
+//! [`MatcherOptions`], the way the command line does.
+
+use crate::Matcher;
+use anyhow::{Result, bail};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A tree of patterns combined with boolean operators.
+///
+/// Leaves are ordinary [`Matcher`]s, so every term honours the same
+/// case-insensitivity and regex settings as a single-pattern search.
+pub enum CompositeMatcher {
+    /// A single pattern
+    Pattern(Matcher),
+    /// Matches when every child matches (an empty list always matches)
+    All(Vec<CompositeMatcher>),
+    /// Matches when at least one child matches (an empty list never matches)
+    Any(Vec<CompositeMatcher>),
+    /// Matches when the child does not match
+    Not(Box<CompositeMatcher>),
+}
+
+impl CompositeMatcher {
+    /// Parses a boolean query such as `error AND NOT retry`.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query expression
+    /// * `ignore_case` - Whether each term matches case-insensitively
+    /// * `use_regex` - Whether each term is a regular expression
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query is empty, has unbalanced parentheses or
+    /// quotes, misplaces an operator, or contains an invalid regex term.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::query::CompositeMatcher;
+    ///
+    /// let query = CompositeMatcher::parse("(warn OR error) db", true, false).unwrap();
+    /// assert!(query.is_match("ERROR in db layer"));
+    /// assert!(!query.is_match("error in cache layer"));
+    ///
+    /// assert!(CompositeMatcher::parse("error AND", false, false).is_err());
+    /// ```
+    pub fn parse(query: &str, ignore_case: bool, use_regex: bool) -> Result<Self> {
+        Self::parse_terms(query, ignore_case, use_regex, false)
+    }
+
+    /// Like [`CompositeMatcher::parse`], with every term matching only whole
+    /// words if `word` is set.
+    fn parse_terms(query: &str, ignore_case: bool, use_regex: bool, word: bool) -> Result<Self> {
+        let tokens = tokenize(query)?;
+        if tokens.is_empty() {
+            bail!("Invalid query: expression is empty");
+        }
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            ignore_case,
+            use_regex,
+            word,
+        };
+        let matcher = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            bail!("Invalid query: unexpected {}", token.describe());
+        }
+        Ok(matcher)
+    }
+
+    /// Copies the query without recompiling any of its patterns; see
+    /// [`Matcher::clone_compiled`].
+    pub fn clone_compiled(&self) -> Self {
+        match self {
+            CompositeMatcher::Pattern(matcher) => {
+                CompositeMatcher::Pattern(matcher.clone_compiled())
+            }
+            CompositeMatcher::All(children) => {
+                CompositeMatcher::All(children.iter().map(Self::clone_compiled).collect())
+            }
+            CompositeMatcher::Any(children) => {
+                CompositeMatcher::Any(children.iter().map(Self::clone_compiled).collect())
+            }
+            CompositeMatcher::Not(child) => CompositeMatcher::Not(Box::new(child.clone_compiled())),
+        }
+    }
+
+    /// Appends an unambiguous encoding of the query to `out`, for
+    /// [`Matcher::fingerprint`].
+    pub(crate) fn describe(&self, out: &mut Vec<u8>) {
+        let (tag, children) = match self {
+            CompositeMatcher::Pattern(matcher) => return matcher.describe(out),
+            CompositeMatcher::Not(child) => {
+                out.push(b'!');
+                return child.describe(out);
+            }
+            CompositeMatcher::All(children) => (b'&', children),
+            CompositeMatcher::Any(children) => (b'|', children),
+        };
+        out.push(tag);
+        out.extend_from_slice(&(children.len() as u64).to_le_bytes());
+        for child in children {
+            child.describe(out);
+        }
+    }
+
+    /// Checks if the given line satisfies the query.
+    pub fn is_match(&self, line: &str) -> bool {
+        match self {
+            CompositeMatcher::Pattern(matcher) => matcher.is_match(line),
+            CompositeMatcher::All(children) => children.iter().all(|c| c.is_match(line)),
+            CompositeMatcher::Any(children) => children.iter().any(|c| c.is_match(line)),
+            CompositeMatcher::Not(child) => !child.is_match(line),
+        }
+    }
+
+    /// Checks if the given raw line satisfies the query.
+    pub fn is_match_bytes(&self, line: &[u8]) -> bool {
+        match self {
+            CompositeMatcher::Pattern(matcher) => matcher.is_match_bytes(line),
+            CompositeMatcher::All(children) => children.iter().all(|c| c.is_match_bytes(line)),
+            CompositeMatcher::Any(children) => children.iter().any(|c| c.is_match_bytes(line)),
+            CompositeMatcher::Not(child) => !child.is_match_bytes(line),
+        }
+    }
+}
+
+/// How to interpret a search pattern and which extra patterns to combine with it.
+///
+/// This mirrors the matching flags of the command line, so library users get
+/// the same behavior as `searcher --query` or `--all-of` / `--any-of` /
+/// `--none-of` through [`build_matcher`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct MatcherOptions {
+    /// Whether every pattern matches case-insensitively
+    pub ignore_case: bool,
+    /// Whether every pattern is a regular expression
+    pub regex: bool,
+    /// Whether every pattern is plain text even if `regex` is set, like
+    /// `--fixed-strings`, so `.`, `*`, or `(` match only themselves
+    pub fixed_strings: bool,
+    /// Whether the main pattern is a boolean query such as `error AND NOT retry`
+    pub query: bool,
+    /// Whether every pattern only matches whole words, like `-w`; see
+    /// [`word_pattern`]
+    pub word_regexp: bool,
+    /// Patterns that must all match as well
+    pub all_of: Vec<String>,
+    /// Patterns of which at least one must match as well (ignored when empty)
+    pub any_of: Vec<String>,
+    /// Patterns that must not match
+    pub none_of: Vec<String>,
+}
+
+impl MatcherOptions {
+    /// Whether patterns are regular expressions: `regex` is set and
+    /// `fixed_strings` is not.
+    pub fn uses_regex(&self) -> bool {
+        self.regex && !self.fixed_strings
+    }
+}
+
+/// `pattern` as a regex that only matches whole words: not preceded or
+/// followed by a letter, digit, or `_`. A plain-text `pattern` is escaped
+/// first.
+///
+/// Unlike wrapping the pattern in `\b`, this also works for patterns that
+/// start or end with punctuation, such as `-v` or `init()`. Word characters
+/// are those of Unicode, so `ğ` or `λ` are part of a word.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::Matcher;
+/// use searcher_cli_starter::query::word_pattern;
+///
+/// let matcher = Matcher::new(&word_pattern("cat", false), false, true).unwrap();
+/// assert!(matcher.is_match("the cat sat"));
+/// assert!(!matcher.is_match("concatenate"));
+/// ```
+pub fn word_pattern(pattern: &str, use_regex: bool) -> String {
+    let pattern = if use_regex {
+        pattern.to_string()
+    } else {
+        regex::escape(pattern)
+    };
+    format!(r"\b{{start-half}}(?:{})\b{{end-half}}", pattern)
+}
+
+/// A matcher for one pattern or query term, only matching whole words if
+/// `word` is set.
+fn term_matcher(pattern: &str, ignore_case: bool, use_regex: bool, word: bool) -> Result<Matcher> {
+    if word {
+        Matcher::new(&word_pattern(pattern, use_regex), ignore_case, true)
+    } else {
+        Matcher::new(pattern, ignore_case, use_regex)
+    }
+}
+
+/// Builds the matcher for `pattern` combined with the extra patterns in `options`.
+///
+/// Without a query or extra patterns this is a plain [`Matcher::new`];
+/// otherwise the parts are joined into a [`CompositeMatcher`]. Patterns are
+/// regexes only if [`MatcherOptions::uses_regex`] says so.
+///
+/// # Errors
+///
+/// Returns an error if the query cannot be parsed or any regex is invalid.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::query::{MatcherOptions, build_matcher};
+///
+/// let options = MatcherOptions {
+///     none_of: vec!["retry".to_string()],
+///     ..MatcherOptions::default()
+/// };
+/// let matcher = build_matcher("error", &options).unwrap();
+/// assert!(matcher.is_match("error: disk full"));
+/// assert!(!matcher.is_match("error, retry in 5s"));
+/// ```
+pub fn build_matcher(pattern: &str, options: &MatcherOptions) -> Result<Matcher> {
+    let uses_combinators =
+        !options.all_of.is_empty() || !options.any_of.is_empty() || !options.none_of.is_empty();
+    let regex = options.uses_regex();
+    let word = options.word_regexp;
+    if !options.query && !uses_combinators {
+        return term_matcher(pattern, options.ignore_case, regex, word);
+    }
+
+    let term = |p: &str| -> Result<CompositeMatcher> {
+        Ok(CompositeMatcher::Pattern(term_matcher(
+            p,
+            options.ignore_case,
+            regex,
+            word,
+        )?))
+    };
+    let terms =
+        |ps: &[String]| -> Result<Vec<CompositeMatcher>> { ps.iter().map(|p| term(p)).collect() };
+
+    let mut required = vec![if options.query {
+        CompositeMatcher::parse_terms(pattern, options.ignore_case, regex, word)?
+    } else {
+        term(pattern)?
+    }];
+    required.extend(terms(&options.all_of)?);
+    if !options.any_of.is_empty() {
+        required.push(CompositeMatcher::Any(terms(&options.any_of)?));
+    }
+    if !options.none_of.is_empty() {
+        required.push(CompositeMatcher::Not(Box::new(CompositeMatcher::Any(
+            terms(&options.none_of)?,
+        ))));
+    }
+
+    Ok(CompositeMatcher::All(required).into())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    Term(String),
+    /// A `re:` term, always a regex
+    Regex(String),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::And => "`AND`".to_string(),
+            Token::Or => "`OR`".to_string(),
+            Token::Not => "`NOT`".to_string(),
+            Token::Open => "`(`".to_string(),
+            Token::Close => "`)`".to_string(),
+            Token::Term(term) => format!("term `{}`", term),
+            Token::Regex(term) => format!("term `re:{}`", term),
+        }
+    }
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                tokens.push(Token::Term(quoted_term(&mut chars)?));
+            }
+            'r' if chars.clone().take(3).eq("re:".chars()) => {
+                chars.nth(2);
+                let term = if chars.peek() == Some(&'"') {
+                    chars.next();
+                    quoted_term(&mut chars)?
+                } else {
+                    regex_term(&mut chars)
+                };
+                tokens.push(Token::Regex(term));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Term(word),
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Reads a quoted term up to its closing quote, the opening one having been
+/// read already.
+fn quoted_term(chars: &mut Peekable<Chars>) -> Result<String> {
+    let mut term = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'"') => {
+                term.push('"');
+                chars.next();
+            }
+            '"' => return Ok(term),
+            c => term.push(c),
+        }
+    }
+    bail!("Invalid query: unterminated quoted term")
+}
+
+/// Reads an unquoted regex term up to whitespace or a `)` it did not open,
+/// leaving escaped characters and those in a character class alone.
+fn regex_term(chars: &mut Peekable<Chars>) -> String {
+    let mut term = String::new();
+    let mut depth = 0usize;
+    let mut in_class = false;
+    while let Some(&c) = chars.peek() {
+        if !in_class && (c.is_whitespace() || (c == ')' && depth == 0)) {
+            break;
+        }
+        chars.next();
+        term.push(c);
+        match c {
+            '\\' => term.extend(chars.next()),
+            '[' => in_class = true,
+            ']' => in_class = false,
+            '(' if !in_class => depth += 1,
+            ')' if !in_class => depth -= 1,
+            _ => {}
+        }
+    }
+    term
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    ignore_case: bool,
+    use_regex: bool,
+    word: bool,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<CompositeMatcher> {
+        let mut children = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            children.push(self.parse_and()?);
+        }
+        Ok(collapse(children, CompositeMatcher::Any))
+    }
+
+    fn parse_and(&mut self) -> Result<CompositeMatcher> {
+        let mut children = vec![self.parse_unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                    children.push(self.parse_unary()?);
+                }
+                // Adjacent terms are joined with an implicit AND
+                Some(Token::Term(_) | Token::Regex(_) | Token::Not | Token::Open) => {
+                    children.push(self.parse_unary()?);
+                }
+                _ => break,
+            }
+        }
+        Ok(collapse(children, CompositeMatcher::All))
+    }
+
+    fn parse_unary(&mut self) -> Result<CompositeMatcher> {
+        match self.next() {
+            Some(Token::Not) => Ok(CompositeMatcher::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Open) => {
+                let inner = self.parse_or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(inner),
+                    _ => bail!("Invalid query: missing `)`"),
+                }
+            }
+            Some(Token::Term(term)) => Ok(CompositeMatcher::Pattern(term_matcher(
+                &term,
+                self.ignore_case,
+                self.use_regex,
+                self.word,
+            )?)),
+            Some(Token::Regex(term)) => Ok(CompositeMatcher::Pattern(term_matcher(
+                &term,
+                self.ignore_case,
+                true,
+                self.word,
+            )?)),
+            Some(token) => bail!("Invalid query: unexpected {}", token.describe()),
+            None => bail!("Invalid query: expression ends unexpectedly"),
+        }
+    }
+}
+
+fn collapse(
+    mut children: Vec<CompositeMatcher>,
+    combine: fn(Vec<CompositeMatcher>) -> CompositeMatcher,
+) -> CompositeMatcher {
+    if children.len() == 1 {
+        children.remove(0)
+    } else {
+        combine(children)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(query: &str) -> CompositeMatcher {
+        CompositeMatcher::parse(query, false, false).unwrap()
+    }
+
+    #[test]
+    fn test_and_not() {
+        let query = parse("error AND NOT retry");
+        assert!(query.is_match("error: disk full"));
+        assert!(!query.is_match("error, retry in 5s"));
+        assert!(!query.is_match("all good"));
+    }
+
+    #[test]
+    fn test_or() {
+        let query = parse("timeout OR refused");
+        assert!(query.is_match("connection refused"));
+        assert!(query.is_match("read timeout"));
+        assert!(!query.is_match("ok"));
+    }
+
+    #[test]
+    fn test_precedence_and_binds_tighter_than_or() {
+        let query = parse("a AND b OR c");
+        assert!(query.is_match("c"));
+        assert!(query.is_match("a b"));
+        assert!(!query.is_match("a"));
+    }
+
+    #[test]
+    fn test_parentheses_and_implicit_and() {
+        let query = parse("(warn OR error) db");
+        assert!(query.is_match("warn: db slow"));
+        assert!(!query.is_match("warn: cache slow"));
+    }
+
+    #[test]
+    fn test_quoted_terms() {
+        let query = parse(r#""connection refused" AND NOT "say \"hi\"""#);
+        assert!(query.is_match("connection refused by peer"));
+        assert!(!query.is_match("connection was refused"));
+        assert!(!query.is_match(r#"connection refused, say "hi""#));
+    }
+
+    #[test]
+    fn test_lowercase_keywords_are_terms() {
+        let query = parse("and");
+        assert!(query.is_match("this and that"));
+    }
+
+    #[test]
+    fn test_regex_terms_and_bytes() {
+        let query = CompositeMatcher::parse(r"code=\d+ AND NOT code=200", false, true).unwrap();
+        assert!(query.is_match_bytes(b"\xFF code=500"));
+        assert!(!query.is_match_bytes(b"code=200"));
+    }
+
+    #[test]
+    fn test_regex_terms_keep_their_parentheses() {
+        let query = parse(r"(re:(a|b)x OR re:[)]y) AND NOT re:\(z");
+        assert!(query.is_match("ax"));
+        assert!(query.is_match("bx"));
+        assert!(query.is_match(")y"));
+        assert!(!query.is_match("cx"));
+        assert!(!query.is_match("ax (z"));
+
+        let query = parse(r#"re:"^a b$" OR "re:(""#);
+        assert!(query.is_match("a b"));
+        assert!(query.is_match("a re:( literal"));
+        assert!(!query.is_match("a  b"));
+    }
+
+    #[test]
+    fn test_invalid_queries() {
+        for query in [
+            "",
+            "error AND",
+            "(error",
+            "error)",
+            "OR error",
+            "\"open",
+            "re:\"open",
+        ] {
+            assert!(
+                CompositeMatcher::parse(query, false, false).is_err(),
+                "expected error for {:?}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn test_empty_combinators() {
+        assert!(CompositeMatcher::All(Vec::new()).is_match("anything"));
+        assert!(!CompositeMatcher::Any(Vec::new()).is_match("anything"));
+    }
+
+    #[test]
+    fn test_build_matcher_plain_pattern() {
+        let matcher = build_matcher("Error", &MatcherOptions::default()).unwrap();
+        assert!(matches!(matcher, Matcher::Literal { .. }));
+    }
+
+    #[test]
+    fn test_build_matcher_combines_options() {
+        let options = MatcherOptions {
+            ignore_case: true,
+            query: true,
+            any_of: vec!["db".to_string(), "cache".to_string()],
+            none_of: vec!["retry".to_string()],
+            ..MatcherOptions::default()
+        };
+        let matcher = build_matcher("warn OR error", &options).unwrap();
+
+        assert!(matcher.is_match("ERROR in db"));
+        assert!(!matcher.is_match("error in queue"));
+        assert!(!matcher.is_match("warn: cache retry"));
+    }
+
+    #[test]
+    fn test_word_regexp_applies_to_every_term() {
+        let options = MatcherOptions {
+            word_regexp: true,
+            query: true,
+            none_of: vec!["init()".to_string()],
+            ..MatcherOptions::default()
+        };
+        let matcher = build_matcher("cat OR -v", &options).unwrap();
+        assert!(matcher.is_match("the cat sat"));
+        assert!(matcher.is_match("grep -v x"));
+        assert!(!matcher.is_match("concatenate"));
+        assert!(!matcher.is_match("grep -verbose"));
+        assert!(!matcher.is_match("cat init() here"));
+        assert!(matcher.is_match("cat reinit() here"));
+
+        let matcher = build_matcher(
+            "ğ",
+            &MatcherOptions {
+                word_regexp: true,
+                ..MatcherOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(matcher.is_match("a ğ b"));
+        assert!(!matcher.is_match("dağ"));
+    }
+
+    #[test]
+    fn test_fixed_strings_override_regex() {
+        let options = MatcherOptions {
+            regex: true,
+            fixed_strings: true,
+            all_of: vec!["(1)".to_string()],
+            ..MatcherOptions::default()
+        };
+        let matcher = build_matcher("a.c", &options).unwrap();
+
+        assert!(matcher.is_match("a.c (1)"));
+        assert!(!matcher.is_match("abc (1)"));
+        assert!(!options.uses_regex());
+    }
+
+    #[test]
+    fn test_fingerprint_tells_queries_apart() {
+        let fingerprint = |query: &str| {
+            let options = MatcherOptions {
+                query: true,
+                ..MatcherOptions::default()
+            };
+            build_matcher(query, &options).unwrap().fingerprint()
+        };
+
+        assert_eq!(fingerprint("a AND b"), fingerprint("a   AND b"));
+        assert_ne!(fingerprint("a AND b"), fingerprint("a OR b"));
+        assert_ne!(fingerprint("a AND b"), fingerprint("a AND NOT b"));
+        assert_ne!(fingerprint("a AND b"), fingerprint("(a AND b) AND c"));
+        assert_ne!(fingerprint("ab"), fingerprint("a AND b"));
+    }
+}
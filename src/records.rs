@@ -0,0 +1,226 @@
+//! Matching multi-line records instead of single lines.
+//!
+//! Some inputs are made of entries that span several lines: stack traces
+//! separated by blank lines, mail headers, `key: value` blocks. Searching
+//! them line by line finds the line that matched but not the entry it
+//! belongs to. [`search_records`] splits the input into [`Record`]s at the
+//! lines a separator matches, such as `^$` for blank lines, and matches each
+//! record as a whole, so a pattern can also span its lines.
+//!
+//! ```
+//! use searcher_cli_starter::records::search_records;
+//! use searcher_cli_starter::{Matcher, SearchOptions};
+//!
+//! let input = "Error: timeout\n  at fetch\n\nError: refused\n  at connect\n";
+//! let matcher = Matcher::new(r"refused\n  at connect", false, true).unwrap();
+//! let separator = Matcher::new("^$", false, true).unwrap();
+//! let records = search_records(input.as_bytes(), &matcher, &separator, &SearchOptions::default())
+//!     .unwrap();
+//!
+//! assert_eq!(records.len(), 1);
+//! assert_eq!(records[0].number, 2);
+//! assert_eq!(records[0].line_number, 4);
+//! assert_eq!(records[0].content, b"Error: refused\n  at connect");
+//! ```
+
+use crate::diagnostic::FileContext;
+use crate::lines::LineReader;
+use crate::{Matcher, SearchOptions, file_error, line_content};
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::Read;
+use std::ops::ControlFlow;
+use std::path::Path;
+
+/// A matching record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Record {
+    /// The 1-based number of the record among the records of the input
+    pub number: usize,
+    /// The line number of the first line of the record
+    pub line_number: usize,
+    /// The number of lines in the record
+    pub line_count: usize,
+    /// The lines of the record without their terminators, joined by `\n`
+    pub content: Vec<u8>,
+}
+
+impl Record {
+    /// The line number of the last line of the record.
+    pub fn last_line_number(&self) -> usize {
+        self.line_number + self.line_count - 1
+    }
+
+    /// The lines of the record.
+    pub fn lines(&self) -> impl Iterator<Item = &[u8]> {
+        self.content.split(|&byte| byte == b'\n')
+    }
+}
+
+/// Searches through a reader for records that `matcher` matches, where the
+/// records are the runs of lines between lines matching `separator`.
+///
+/// Separator lines belong to no record, and runs of them do not make empty
+/// records. Only `options.line_terminator`, `line_offset`, `cancel`, and
+/// `timeout` are used; the other options are about single lines.
+///
+/// # Errors
+///
+/// Returns an error if reading from `reader` fails, [`Cancelled`] if
+/// `options.cancel` is cancelled, or [`TimedOut`] once `options.timeout`
+/// has passed.
+///
+/// [`Cancelled`]: crate::cancel::Cancelled
+/// [`TimedOut`]: crate::cancel::TimedOut
+pub fn search_records<R: Read>(
+    reader: R,
+    matcher: &Matcher,
+    separator: &Matcher,
+    options: &SearchOptions,
+) -> Result<Vec<Record>> {
+    let mut records = Vec::new();
+    search_records_with(reader, matcher, separator, options, |record| {
+        records.push(record);
+        ControlFlow::Continue(())
+    })?;
+    Ok(records)
+}
+
+/// Searches through a reader like [`search_records`], handing each matching
+/// record to `visit` as soon as it is complete.
+///
+/// Returning [`ControlFlow::Break`] from `visit` stops reading. Only the
+/// record being read is kept in memory.
+///
+/// # Errors
+///
+/// Returns the errors of [`search_records`]. Records visited before the
+/// error are not undone.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::records::search_records_with;
+/// use searcher_cli_starter::{Matcher, SearchOptions};
+/// use std::ops::ControlFlow;
+///
+/// let input = "id: 1\nstate: failed\n---\nid: 2\nstate: ok\n---\nid: 3\nstate: failed\n";
+/// let matcher = Matcher::new("failed", false, false).unwrap();
+/// let separator = Matcher::new("^---$", false, true).unwrap();
+/// let mut first = Vec::new();
+/// search_records_with(input.as_bytes(), &matcher, &separator, &SearchOptions::default(), |record| {
+///     first.push(record.number);
+///     ControlFlow::Break(())
+/// })
+/// .unwrap();
+///
+/// assert_eq!(first, [1]);
+/// ```
+pub fn search_records_with<R, F>(
+    reader: R,
+    matcher: &Matcher,
+    separator: &Matcher,
+    options: &SearchOptions,
+    mut visit: F,
+) -> Result<()>
+where
+    R: Read,
+    F: FnMut(Record) -> ControlFlow<()>,
+{
+    let deadline = options.deadline();
+    let mut lines = LineReader::new(reader, options.line_terminator.byte());
+    let mut record = Record {
+        number: 1,
+        line_number: 0,
+        line_count: 0,
+        content: Vec::new(),
+    };
+    let mut line_number = options.line_offset;
+
+    // Hands the record to `visit` if it matches, and starts the next one
+    let mut finish = |record: &mut Record| {
+        if record.line_count == 0 {
+            return ControlFlow::Continue(());
+        }
+        let flow = if matcher.is_match_bytes(&record.content) {
+            visit(record.clone())
+        } else {
+            ControlFlow::Continue(())
+        };
+        record.number += 1;
+        record.line_count = 0;
+        record.content.clear();
+        flow
+    };
+
+    while let Some(line) = lines.next_line()? {
+        options.check_cancelled()?;
+        options.check_deadline(deadline)?;
+        line_number += 1; // 1-based indexing
+        let line = line_content(line, options);
+        if separator.is_match_bytes(line) {
+            if finish(&mut record).is_break() {
+                return Ok(());
+            }
+            continue;
+        }
+        if record.line_count == 0 {
+            record.line_number = line_number;
+        } else {
+            record.content.push(b'\n');
+        }
+        record.content.extend_from_slice(line);
+        record.line_count += 1;
+    }
+    let _ = finish(&mut record);
+
+    Ok(())
+}
+
+/// Searches the file at `path` for matching records, as
+/// [`search_records`] does.
+///
+/// # Errors
+///
+/// Returns an error naming `path` if it cannot be opened or read, along
+/// with the errors of [`search_records`].
+pub fn search_path_records(
+    path: &Path,
+    matcher: &Matcher,
+    separator: &Matcher,
+    options: &SearchOptions,
+) -> Result<Vec<Record>> {
+    options.check_cancelled()?;
+    let file = File::open(path).with_context(|| FileContext::read(path))?;
+    search_records(file, matcher, separator, options).map_err(|error| file_error(path, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LineTerminator;
+
+    #[test]
+    fn test_records_skip_separator_runs() {
+        let matcher = Matcher::new("at ", false, false).unwrap();
+        let separator = Matcher::new("^$", false, true).unwrap();
+        let input = "\r\nfirst\r\n  at a\r\n\r\n\r\nsecond\r\n\r\nthird\r\n  at b\r\n  at c";
+        let options = SearchOptions {
+            line_terminator: LineTerminator::Crlf,
+            line_offset: 100,
+            ..SearchOptions::default()
+        };
+        let records = search_records(input.as_bytes(), &matcher, &separator, &options).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].number, 1);
+        assert_eq!(records[0].line_number, 102);
+        assert_eq!(records[0].last_line_number(), 103);
+        assert_eq!(records[1].number, 3);
+        assert_eq!(records[1].line_number, 108);
+        assert_eq!(records[1].line_count, 3);
+        let lines: Vec<&[u8]> = records[1].lines().collect();
+        assert_eq!(lines, [&b"third"[..], b"  at b", b"  at c"]);
+    }
+}
@@ -0,0 +1,145 @@
+//! Masking matches in lines, so results can be shared safely.
+//!
+//! Search results often hold what the search was for: API keys, email
+//! addresses, customer ids. A [`Redactor`] replaces the matched parts of a
+//! line with a mask such as `****`, keeping the rest as context. It takes
+//! the spans to mask rather than a pattern, so they can come from
+//! [`Matcher::match_ranges`], capture groups, or any other detector.
+//!
+//! # Examples
+//!
+//! ```
+//! use searcher_cli_starter::Matcher;
+//! use searcher_cli_starter::redact::Redactor;
+//!
+//! let matcher = Matcher::new(r"\w+@\w+\.com", false, true).unwrap();
+//! let line = b"from ann@example.com to bob@example.com";
+//! let redacted = Redactor::default().redact(line, &matcher.match_ranges(line));
+//! assert_eq!(&*redacted, b"from **** to ****");
+//! ```
+
+use crate::{Matcher, SearchMatchBytes};
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// The mask of [`Redactor::default`].
+pub const DEFAULT_MASK: &str = "****";
+
+/// Replaces spans of lines with a mask.
+///
+/// The mask has the same length whatever it replaces, so the output does
+/// not give away how long a secret is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redactor {
+    mask: Vec<u8>,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Redactor::new(DEFAULT_MASK)
+    }
+}
+
+impl Redactor {
+    /// Creates a redactor replacing each span with `mask`.
+    pub fn new(mask: impl Into<Vec<u8>>) -> Self {
+        Redactor { mask: mask.into() }
+    }
+
+    /// The text written in place of each span.
+    pub fn mask(&self) -> &[u8] {
+        &self.mask
+    }
+
+    /// `line` with every span in `spans` replaced by the mask.
+    ///
+    /// Spans may come in any order. Overlapping or adjacent spans are masked
+    /// once, as a single span, and empty spans are ignored. Spans reaching
+    /// past the end of `line` are cut short at it. Returns `line` borrowed
+    /// when there is nothing to mask.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::redact::Redactor;
+    ///
+    /// let redactor = Redactor::new("[redacted]");
+    /// assert_eq!(&*redactor.redact(b"key=abc123", &[4..10]), b"key=[redacted]");
+    /// assert_eq!(&*redactor.redact(b"aaaa", &[2..4, 0..1, 1..2]), b"[redacted]");
+    /// ```
+    pub fn redact<'a>(&self, line: &'a [u8], spans: &[Range<usize>]) -> Cow<'a, [u8]> {
+        let mut spans: Vec<Range<usize>> = spans
+            .iter()
+            .map(|span| span.start.min(line.len())..span.end.min(line.len()))
+            .filter(|span| span.start < span.end)
+            .collect();
+        if spans.is_empty() {
+            return Cow::Borrowed(line);
+        }
+        spans.sort_by_key(|span| span.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(spans.len());
+        for span in spans {
+            match merged.last_mut() {
+                Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+                _ => merged.push(span),
+            }
+        }
+
+        let mut redacted = Vec::with_capacity(line.len());
+        let mut written = 0;
+        for span in merged {
+            redacted.extend_from_slice(&line[written..span.start]);
+            redacted.extend_from_slice(&self.mask);
+            written = span.end;
+        }
+        redacted.extend_from_slice(&line[written..]);
+        Cow::Owned(redacted)
+    }
+
+    /// Masks the matches of `matcher` in each line of `matches`, as found by
+    /// [`Matcher::match_ranges`]. Composite matchers have no match spans, so
+    /// their lines are left as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::redact::Redactor;
+    /// use searcher_cli_starter::{Matcher, search_lines_bytes};
+    /// use std::io::Cursor;
+    ///
+    /// let matcher = Matcher::new(r"\d{3}-\d{4}", false, true).unwrap();
+    /// let mut matches = search_lines_bytes(Cursor::new("call 555-0100\nbye"), &matcher).unwrap();
+    /// Redactor::default().redact_matches(&matcher, &mut matches);
+    ///
+    /// assert_eq!(matches[0].content, b"call ****");
+    /// ```
+    pub fn redact_matches(&self, matcher: &Matcher, matches: &mut [SearchMatchBytes]) {
+        for search_match in matches {
+            let spans = matcher.match_ranges(&search_match.content);
+            if let Cow::Owned(redacted) = self.redact(&search_match.content, &spans) {
+                search_match.content = redacted;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_merges_and_clamps_spans() {
+        let redactor = Redactor::default();
+        assert!(matches!(redactor.redact(b"plain", &[]), Cow::Borrowed(_)));
+        assert!(matches!(
+            redactor.redact(b"plain", &[2..2, 9..12]),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(&*redactor.redact(b"abcdef", &[0..2, 1..1]), b"****cdef");
+        assert_eq!(&*redactor.redact(b"abcdef", &[4..9, 9..12]), b"abcd****");
+        assert_eq!(&*redactor.redact(b"abcdef", &[1..3, 2..4]), b"a****ef");
+        assert_eq!(&*redactor.redact(b"abcdef", &[3..4, 1..3]), b"a****ef");
+        assert_eq!(&*redactor.redact(b"abcdef", &[0..1, 5..6]), b"****bcde****");
+        assert_eq!(&*Redactor::new("").redact(b"abcdef", &[1..3, 3..5]), b"af");
+    }
+}
@@ -0,0 +1,548 @@
+//! Search-and-replace over lines of text.
+//!
+//! A [`Replacer`] rewrites every match of a [`Matcher`] in a line. Applied to a
+//! whole file with [`Replacer::replace_text`], it also records which lines
+//! changed so callers can preview the edit with [`unified_diff`] before
+//! writing anything back to disk. [`write_replaced`] then writes the result,
+//! optionally keeping a backup of the original file.
+//!
+//! When a template is not enough, [`replace_lines_with`] computes each
+//! replacement with a closure instead.
+//!
+//! # Examples
+//!
+//! ```
+//! use searcher_cli_starter::Matcher;
+//! use searcher_cli_starter::replace::{Replacer, unified_diff};
+//!
+//! let matcher = Matcher::new(r"v(\d+)", false, true).unwrap();
+//! let replacer = Replacer::new(&matcher, "version $1").unwrap();
+//!
+//! let text = b"name = demo\nrelease v2\n";
+//! let replaced = replacer.replace_text(text);
+//! assert_eq!(replaced.output, b"name = demo\nrelease version 2\n");
+//!
+//! let diff = unified_diff("Cargo.toml", text, &replaced.changes, 3);
+//! assert!(diff.contains("-release v2\n+release version 2\n"));
+//! ```
+
+use crate::{Matcher, SearchMatchBytes};
+use anyhow::{Context, Result, bail};
+use regex::bytes::{NoExpand, Regex};
+
+pub use regex::bytes::Captures;
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Rewrites matches of a pattern with a replacement string.
+///
+/// For regex matchers the replacement may refer to capture groups with
+/// `$1` or `${name}`. For literal matchers the replacement is inserted
+/// verbatim.
+pub struct Replacer {
+    regex: Regex,
+    replacement: Vec<u8>,
+    expand: bool,
+}
+
+/// A single line changed by a [`Replacer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineChange {
+    /// The 1-based line number of the changed line
+    pub line_number: usize,
+    /// The line before replacement, without its terminator
+    pub before: Vec<u8>,
+    /// The line after replacement, without its terminator
+    pub after: Vec<u8>,
+}
+
+/// The result of running a [`Replacer`] over a whole text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replacement {
+    /// The full text with all replacements applied
+    pub output: Vec<u8>,
+    /// Every line that changed, in order
+    pub changes: Vec<LineChange>,
+}
+
+impl Replacer {
+    /// Creates a replacer for the matches of `matcher`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for composite (boolean query) matchers, which have no
+    /// single match span that could be replaced.
+    pub fn new(matcher: &Matcher, replacement: &str) -> Result<Self> {
+        Ok(Replacer {
+            regex: replacement_regex(matcher)?,
+            replacement: replacement.as_bytes().to_vec(),
+            expand: matches!(matcher, Matcher::Regex { .. }),
+        })
+    }
+
+    /// Replaces every match in a single line.
+    ///
+    /// Returns the line unchanged (borrowed) when nothing matched.
+    pub fn replace_line<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.expand {
+            self.regex.replace_all(line, self.replacement.as_slice())
+        } else {
+            self.regex
+                .replace_all(line, NoExpand(self.replacement.as_slice()))
+        }
+    }
+
+    /// Replaces every match in the content of each search result, in place.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::{Matcher, search_lines_bytes};
+    /// use searcher_cli_starter::replace::Replacer;
+    /// use std::io::Cursor;
+    ///
+    /// let matcher = Matcher::new("cat", false, false).unwrap();
+    /// let mut matches = search_lines_bytes(Cursor::new("cat\ndog\ncatalog"), &matcher).unwrap();
+    /// Replacer::new(&matcher, "bird").unwrap().replace_matches(&mut matches);
+    ///
+    /// assert_eq!(matches[0].content, b"bird");
+    /// assert_eq!(matches[1].content, b"birdalog");
+    /// ```
+    pub fn replace_matches(&self, matches: &mut [SearchMatchBytes]) {
+        for search_match in matches {
+            if let Cow::Owned(replaced) = self.replace_line(&search_match.content) {
+                search_match.content = replaced;
+            }
+        }
+    }
+
+    /// Replaces matches line by line throughout `text`.
+    ///
+    /// Line terminators (`\n` or `\r\n`) are preserved exactly, so only the
+    /// matched spans differ between the input and the output.
+    pub fn replace_text(&self, text: &[u8]) -> Replacement {
+        replace_each_line(text, |line| self.replace_line(line))
+    }
+}
+
+/// Replaces every match of `matcher` line by line throughout `text`, with
+/// whatever `replace` returns for the match's captures.
+///
+/// Group 0 of the captures is the whole match; regex matchers also have
+/// their capture groups, so `replace` can compute a new value from them,
+/// e.g. to bump a version number. Lines and terminators are handled as
+/// [`Replacer::replace_text`] does.
+///
+/// # Errors
+///
+/// Returns an error for composite (boolean query) matchers, as
+/// [`Replacer::new`] does.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::Matcher;
+/// use searcher_cli_starter::replace::{Captures, replace_lines_with};
+///
+/// let matcher = Matcher::new(r"version = (\d+)", false, true).unwrap();
+/// let text = b"name = demo\nversion = 41\n";
+/// let replaced = replace_lines_with(&matcher, text, |caps: &Captures| {
+///     let version: u32 = std::str::from_utf8(&caps[1]).unwrap().parse().unwrap();
+///     format!("version = {}", version + 1)
+/// })
+/// .unwrap();
+///
+/// assert_eq!(replaced.output, b"name = demo\nversion = 42\n");
+/// assert_eq!(replaced.changes[0].line_number, 2);
+/// ```
+pub fn replace_lines_with<F>(matcher: &Matcher, text: &[u8], mut replace: F) -> Result<Replacement>
+where
+    F: FnMut(&Captures) -> String,
+{
+    let regex = replacement_regex(matcher)?;
+    Ok(replace_each_line(text, |line| {
+        regex.replace_all(line, |caps: &Captures| replace(caps))
+    }))
+}
+
+/// The regex finding the spans of `matcher` to replace: the matcher's own
+/// for regex matchers, and the escaped pattern for literal ones.
+fn replacement_regex(matcher: &Matcher) -> Result<Regex> {
+    match matcher {
+        Matcher::Literal {
+            pattern,
+            ignore_case,
+        } => {
+            let escaped = regex::escape(pattern);
+            let pattern = if *ignore_case {
+                format!("(?i){}", escaped)
+            } else {
+                escaped
+            };
+            Regex::new(&pattern).context("Invalid replacement pattern")
+        }
+        Matcher::Regex { bytes_regex, .. } => Ok(bytes_regex.clone()),
+        Matcher::Composite(_) => bail!("Replacement is not supported with boolean queries"),
+    }
+}
+
+/// Runs `replace_line` on each line of `text` without its terminator,
+/// recording the lines it changed.
+fn replace_each_line<'a>(
+    text: &'a [u8],
+    mut replace_line: impl FnMut(&'a [u8]) -> Cow<'a, [u8]>,
+) -> Replacement {
+    let mut output = Vec::with_capacity(text.len());
+    let mut changes = Vec::new();
+
+    for (index, raw_line) in text.split_inclusive(|&b| b == b'\n').enumerate() {
+        let (line, terminator) = split_terminator(raw_line);
+        let replaced = replace_line(line);
+        if let Cow::Owned(after) = &replaced
+            && after.as_slice() != line
+        {
+            changes.push(LineChange {
+                line_number: index + 1,
+                before: line.to_vec(),
+                after: after.clone(),
+            });
+        }
+        output.extend_from_slice(&replaced);
+        output.extend_from_slice(terminator);
+    }
+
+    Replacement { output, changes }
+}
+
+/// Writes replaced contents back to `path`, optionally backing up the original.
+///
+/// With a `backup_suffix` such as `.bak`, the original file is first copied
+/// to the same path with the suffix appended. Symlinks are resolved first, so
+/// the target file is rewritten in place (the link stays a link) and the
+/// backup is placed next to the target. The file is overwritten rather than
+/// recreated, so its permissions are preserved; the backup copies them too.
+///
+/// Returns the path of the backup, if one was written.
+///
+/// # Errors
+///
+/// Returns an error if the path cannot be resolved, or if writing the backup
+/// or the new contents fails. When the backup fails, the original file is
+/// left untouched.
+pub fn write_replaced(
+    path: &Path,
+    contents: &[u8],
+    backup_suffix: Option<&str>,
+) -> Result<Option<PathBuf>> {
+    let target = fs::canonicalize(path)
+        .with_context(|| format!("Could not resolve `{}`", path.display()))?;
+
+    let backup = match backup_suffix {
+        Some(suffix) => {
+            let mut backup_name = OsString::from(target.as_os_str());
+            backup_name.push(suffix);
+            let backup = PathBuf::from(backup_name);
+            fs::copy(&target, &backup)
+                .with_context(|| format!("Could not write backup `{}`", backup.display()))?;
+            Some(backup)
+        }
+        None => None,
+    };
+
+    fs::write(&target, contents)
+        .with_context(|| format!("Could not write file `{}`", path.display()))?;
+    Ok(backup)
+}
+
+/// Splits a line into its content and its `\n` / `\r\n` terminator.
+fn split_terminator(line: &[u8]) -> (&[u8], &[u8]) {
+    let content_len = if line.ends_with(b"\r\n") {
+        line.len() - 2
+    } else if line.ends_with(b"\n") {
+        line.len() - 1
+    } else {
+        line.len()
+    };
+    line.split_at(content_len)
+}
+
+/// Renders the changes to `original` as a unified diff.
+///
+/// `context` lines of unchanged text are shown around each change, and
+/// nearby changes are merged into a single hunk. Returns an empty string
+/// when there are no changes.
+pub fn unified_diff(path: &str, original: &[u8], changes: &[LineChange], context: usize) -> String {
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let lines: Vec<&[u8]> = original
+        .split_inclusive(|&b| b == b'\n')
+        .map(|line| split_terminator(line).0)
+        .collect();
+
+    // Group changes whose context windows touch into hunks
+    let mut hunks: Vec<Vec<&LineChange>> = Vec::new();
+    for change in changes {
+        match hunks.last_mut() {
+            Some(hunk)
+                if change.line_number <= hunk.last().unwrap().line_number + 2 * context + 1 =>
+            {
+                hunk.push(change)
+            }
+            _ => hunks.push(vec![change]),
+        }
+    }
+
+    let mut diff = format!("--- a/{}\n+++ b/{}\n", path, path);
+    // Replacements containing newlines shift later hunks in the new file
+    let mut offset: isize = 0;
+    for hunk in hunks {
+        let first = hunk[0].line_number;
+        let last = hunk[hunk.len() - 1].line_number;
+        let start = first.saturating_sub(context).max(1);
+        let end = (last + context).min(lines.len());
+
+        let mut body = String::new();
+        let mut new_count = 0;
+        let mut pending = hunk.iter().peekable();
+        for line_number in start..=end {
+            match pending.peek() {
+                Some(change) if change.line_number == line_number => {
+                    body.push_str(&format!("-{}\n", String::from_utf8_lossy(&change.before)));
+                    for added in change.after.split(|&b| b == b'\n') {
+                        body.push_str(&format!("+{}\n", String::from_utf8_lossy(added)));
+                        new_count += 1;
+                    }
+                    pending.next();
+                }
+                _ => {
+                    body.push_str(&format!(
+                        " {}\n",
+                        String::from_utf8_lossy(lines[line_number - 1])
+                    ));
+                    new_count += 1;
+                }
+            }
+        }
+
+        let old_count = end - start + 1;
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            start,
+            old_count,
+            start.saturating_add_signed(offset),
+            new_count
+        ));
+        offset += new_count as isize - old_count as isize;
+        diff.push_str(&body);
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal_replacement_is_verbatim() {
+        let matcher = Matcher::new("a.b", false, false).unwrap();
+        let replacer = Replacer::new(&matcher, "$1").unwrap();
+
+        assert_eq!(replacer.replace_line(b"a.b axb a.b"), &b"$1 axb $1"[..]);
+    }
+
+    #[test]
+    fn test_literal_ignore_case_replacement() {
+        let matcher = Matcher::new("rust", true, false).unwrap();
+        let replacer = Replacer::new(&matcher, "Rust").unwrap();
+
+        assert_eq!(
+            replacer.replace_line(b"RUST and rust"),
+            &b"Rust and Rust"[..]
+        );
+    }
+
+    #[test]
+    fn test_regex_replacement_expands_groups() {
+        let matcher = Matcher::new(r"(?P<k>\w+)=(\d+)", false, true).unwrap();
+        let replacer = Replacer::new(&matcher, "$2=${k}").unwrap();
+
+        assert_eq!(replacer.replace_line(b"x=1 y=2"), &b"1=x 2=y"[..]);
+    }
+
+    #[test]
+    fn test_composite_matcher_rejected() {
+        let composite = crate::query::CompositeMatcher::parse("a AND b", false, false).unwrap();
+        assert!(Replacer::new(&Matcher::from(composite), "x").is_err());
+    }
+
+    #[test]
+    fn test_replace_text_preserves_terminators() {
+        let matcher = Matcher::new("old", false, false).unwrap();
+        let replacer = Replacer::new(&matcher, "new").unwrap();
+        let replaced = replacer.replace_text(b"old\r\nkeep\nold");
+
+        assert_eq!(replaced.output, b"new\r\nkeep\nnew");
+        assert_eq!(replaced.changes.len(), 2);
+        assert_eq!(replaced.changes[0].line_number, 1);
+        assert_eq!(replaced.changes[1].line_number, 3);
+    }
+
+    #[test]
+    fn test_replace_lines_with_computes_each_replacement() {
+        let matcher = Matcher::new("ab", true, false).unwrap();
+        let mut seen = Vec::new();
+        let replaced = replace_lines_with(&matcher, b"ab AB\r\nx\nAb", |caps| {
+            seen.push(caps[0].to_vec());
+            format!("<{}>", seen.len())
+        })
+        .unwrap();
+
+        assert_eq!(replaced.output, b"<1> <2>\r\nx\n<3>");
+        assert_eq!(seen, [&b"ab"[..], b"AB", b"Ab"]);
+        assert_eq!(replaced.changes.len(), 2);
+
+        let composite = crate::query::CompositeMatcher::parse("a AND b", false, false).unwrap();
+        assert!(replace_lines_with(&Matcher::from(composite), b"ab", |_| String::new()).is_err());
+    }
+
+    #[test]
+    fn test_replace_text_same_value_is_not_a_change() {
+        let matcher = Matcher::new("same", false, false).unwrap();
+        let replacer = Replacer::new(&matcher, "same").unwrap();
+
+        assert!(replacer.replace_text(b"same\n").changes.is_empty());
+    }
+
+    #[test]
+    fn test_unified_diff_hunks() {
+        let text = b"1\n2\nx\n4\n5\n6\n7\n8\n9\nx\n";
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let replacer = Replacer::new(&matcher, "y").unwrap();
+        let replaced = replacer.replace_text(text);
+
+        let diff = unified_diff("f.txt", text, &replaced.changes, 1);
+        assert_eq!(
+            diff,
+            "--- a/f.txt\n+++ b/f.txt\n\
+             @@ -2,3 +2,3 @@\n 2\n-x\n+y\n 4\n\
+             @@ -9,2 +9,2 @@\n 9\n-x\n+y\n"
+        );
+    }
+
+    #[test]
+    fn test_unified_diff_merges_close_changes() {
+        let text = b"x\na\nx\n";
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let replacer = Replacer::new(&matcher, "y").unwrap();
+        let replaced = replacer.replace_text(text);
+
+        let diff = unified_diff("f.txt", text, &replaced.changes, 3);
+        assert_eq!(diff.matches("@@ -").count(), 1);
+        assert!(diff.contains("@@ -1,3 +1,3 @@\n"));
+    }
+
+    #[test]
+    fn test_unified_diff_empty_without_changes() {
+        assert_eq!(unified_diff("f.txt", b"a\n", &[], 3), "");
+    }
+
+    #[test]
+    fn test_unified_diff_tracks_added_lines() {
+        let text = b"x\n1\n2\n3\n4\n5\n6\n7\nx\n";
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let replacer = Replacer::new(&matcher, "y\nz").unwrap();
+        let replaced = replacer.replace_text(text);
+
+        let diff = unified_diff("f.txt", text, &replaced.changes, 1);
+        assert!(diff.contains("@@ -1,2 +1,3 @@\n-x\n+y\n+z\n 1\n"));
+        assert!(diff.contains("@@ -8,2 +9,3 @@\n 7\n-x\n+y\n+z\n"));
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("searcher_replace_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_write_replaced_with_backup() {
+        let path = temp_path("backup.txt");
+        fs::write(&path, "old").unwrap();
+
+        let backup = write_replaced(&path, b"new", Some(".bak"))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            backup.file_name().unwrap(),
+            path.with_extension("txt.bak").file_name().unwrap()
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "old");
+        fs::remove_file(path).ok();
+        fs::remove_file(backup).ok();
+    }
+
+    #[test]
+    fn test_write_replaced_without_backup() {
+        let path = temp_path("nobackup.txt");
+        fs::write(&path, "old").unwrap();
+
+        assert_eq!(write_replaced(&path, b"new", None).unwrap(), None);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        fs::remove_file(path).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_replaced_preserves_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_path("perms.sh");
+        fs::write(&path, "echo old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+
+        let backup = write_replaced(&path, b"echo new", Some(".orig"))
+            .unwrap()
+            .unwrap();
+
+        let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&path), 0o750);
+        assert_eq!(mode(&backup), 0o750);
+        fs::remove_file(path).ok();
+        fs::remove_file(backup).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_replaced_follows_symlinks() {
+        let target = temp_path("target.txt");
+        let link = temp_path("link.txt");
+        fs::write(&target, "old").unwrap();
+        fs::remove_file(&link).ok();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let backup = write_replaced(&link, b"new", Some(".bak"))
+            .unwrap()
+            .unwrap();
+
+        assert!(
+            fs::symlink_metadata(&link)
+                .unwrap()
+                .file_type()
+                .is_symlink()
+        );
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert_eq!(
+            backup,
+            fs::canonicalize(&target).unwrap().with_extension("txt.bak")
+        );
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "old");
+        fs::remove_file(link).ok();
+        fs::remove_file(target).ok();
+        fs::remove_file(backup).ok();
+    }
+}
@@ -0,0 +1,448 @@
+//! A JSON-RPC 2.0 protocol over stdio, for IDE extensions.
+//!
+//! `searcher serve --stdio` runs as a long-lived backend the way a language
+//! server does: messages are JSON-RPC 2.0 objects framed by a
+//! `Content-Length` header, so the client libraries written for the
+//! Language Server Protocol can talk to it. The methods are:
+//!
+//! - `initialize`: returns the server's name, version, and capabilities.
+//! - `search`: takes a [`SearchRequest`] as its params. Each matching line
+//!   is sent as a `search/match` notification and each file that could not
+//!   be searched as `search/error`, both carrying the search's request `id`;
+//!   the response is `{"files": N, "matches": M}` once the search is done.
+//! - `cancel`: takes `{"id": ...}` and stops that search, whose response is
+//!   then an error with code `-32800` (request cancelled).
+//! - `shutdown`: cancels every search still running, responds with `null`,
+//!   and ends [`serve`].
+//!
+//! Searches run in the background, so `cancel` and other requests are read
+//! while one is in progress. Available with the `server` cargo feature.
+//!
+//! ```text
+//! Content-Length: 71\r\n
+//! \r\n
+//! {"jsonrpc":"2.0","id":1,"method":"search","params":{"pattern":"TODO","paths":["src"]}}
+//! ```
+
+use crate::cancel::CancellationToken;
+use crate::events::SearchEvent;
+use crate::server::{SearchRequest, SearchServer, with_path};
+use anyhow::{Context, Result, bail};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+/// The JSON was not valid.
+const PARSE_ERROR: i64 = -32700;
+/// The message was not a JSON-RPC request.
+const INVALID_REQUEST: i64 = -32600;
+/// No such method.
+const METHOD_NOT_FOUND: i64 = -32601;
+/// The params did not fit the method, or the search could not start.
+const INVALID_PARAMS: i64 = -32602;
+/// The request was cancelled before it finished.
+const REQUEST_CANCELLED: i64 = -32800;
+
+/// The largest message body read, in bytes.
+const MAX_MESSAGE_BYTES: usize = 1024 * 1024;
+
+/// Reads one `Content-Length` framed message from `input`, or `None` at the
+/// end of the input.
+///
+/// # Errors
+///
+/// Returns an error if reading fails, the header has no valid
+/// `Content-Length`, or it is over 1 MiB.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::rpc::{read_message, write_message};
+/// use serde_json::json;
+///
+/// let mut framed = Vec::new();
+/// write_message(&mut framed, &json!({"jsonrpc": "2.0", "method": "shutdown", "id": 1})).unwrap();
+/// assert!(framed.starts_with(b"Content-Length: "));
+///
+/// let mut input = framed.as_slice();
+/// let message = read_message(&mut input).unwrap().unwrap();
+/// assert_eq!(message, r#"{"id":1,"jsonrpc":"2.0","method":"shutdown"}"#);
+/// assert_eq!(read_message(&mut input).unwrap(), None);
+/// ```
+pub fn read_message(input: &mut impl BufRead) -> Result<Option<String>> {
+    let mut length = None;
+    let mut header = String::new();
+    loop {
+        header.clear();
+        if input.read_line(&mut header)? == 0 {
+            if length.is_none() {
+                return Ok(None);
+            }
+            bail!("The input ended inside a message header");
+        }
+        let line = header.trim_end();
+        if line.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            // Blank lines between messages
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            let value = value.trim();
+            length = Some(
+                value
+                    .parse::<usize>()
+                    .with_context(|| format!("Invalid Content-Length `{}`", value))?,
+            );
+        }
+    }
+
+    let length = length.unwrap_or_default();
+    if length > MAX_MESSAGE_BYTES {
+        bail!(
+            "A message of {} bytes is over the limit of {} bytes",
+            length,
+            MAX_MESSAGE_BYTES
+        );
+    }
+    let mut body = vec![0; length];
+    input
+        .read_exact(&mut body)
+        .context("The input ended inside a message")?;
+    String::from_utf8(body)
+        .map(Some)
+        .context("A message is not valid UTF-8")
+}
+
+/// Writes `message` to `output` with its `Content-Length` header.
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// Answers JSON-RPC messages read from `input` on `output` until the client
+/// sends `shutdown`, which cancels the searches still running, or the input
+/// ends, which lets them finish.
+///
+/// # Errors
+///
+/// Returns an error if reading `input` or writing `output` fails.
+pub fn serve(
+    server: &SearchServer,
+    mut input: impl BufRead,
+    output: impl Write + Send,
+) -> Result<()> {
+    let output = Mutex::new(output);
+    let send = |message: Value| -> io::Result<()> {
+        let mut output = output.lock().unwrap_or_else(PoisonError::into_inner);
+        write_message(&mut *output, &message)
+    };
+    // The cancellation token of each running search, by request id
+    let running: Mutex<HashMap<String, CancellationToken>> = Mutex::new(HashMap::new());
+    let cancel_all = || {
+        let running = running.lock().unwrap_or_else(PoisonError::into_inner);
+        running.values().for_each(CancellationToken::cancel);
+    };
+
+    thread::scope(|scope| {
+        let served = (|| -> Result<()> {
+            while let Some(text) = read_message(&mut input)? {
+                let message: Value = match serde_json::from_str(&text) {
+                    Ok(message) => message,
+                    Err(error) => {
+                        send(error_response(
+                            &Value::Null,
+                            PARSE_ERROR,
+                            &error.to_string(),
+                        ))?;
+                        continue;
+                    }
+                };
+                let id = message.get("id").cloned();
+                let Some(method) = message.get("method").and_then(Value::as_str) else {
+                    let id = id.unwrap_or(Value::Null);
+                    send(error_response(&id, INVALID_REQUEST, "Not a request"))?;
+                    continue;
+                };
+                let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+                match method {
+                    "initialize" => {
+                        let result = json!({
+                            "name": "searcher",
+                            "version": env!("CARGO_PKG_VERSION"),
+                            "capabilities": { "search": true, "cancel": true, "index": true },
+                        });
+                        respond(&send, id, Ok(result))?;
+                    }
+                    "search" => {
+                        let Some(id) = id else {
+                            // A search without an id could never be answered
+                            continue;
+                        };
+                        let request: SearchRequest = match serde_json::from_value(params) {
+                            Ok(request) => request,
+                            Err(error) => {
+                                let message = format!("Invalid search: {}", error);
+                                send(error_response(&id, INVALID_PARAMS, &message))?;
+                                continue;
+                            }
+                        };
+                        let token = CancellationToken::new();
+                        let events =
+                            match server.clone().with_cancel(token.clone()).search(&request) {
+                                Ok(events) => events,
+                                Err(error) => {
+                                    let message = format!("{:#}", error);
+                                    send(error_response(&id, INVALID_PARAMS, &message))?;
+                                    continue;
+                                }
+                            };
+                        running
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .insert(id.to_string(), token.clone());
+
+                        let send = &send;
+                        let running = &running;
+                        scope.spawn(move || {
+                            let finished = stream_results(send, &id, events, &token);
+                            running
+                                .lock()
+                                .unwrap_or_else(PoisonError::into_inner)
+                                .remove(&id.to_string());
+                            finished
+                        });
+                    }
+                    "cancel" => {
+                        let target = params.get("id").map(Value::to_string);
+                        let token = target.and_then(|target| {
+                            let running = running.lock().unwrap_or_else(PoisonError::into_inner);
+                            running.get(&target).cloned()
+                        });
+                        if let Some(token) = &token {
+                            token.cancel();
+                        }
+                        respond(&send, id, Ok(json!(token.is_some())))?;
+                    }
+                    "shutdown" => {
+                        cancel_all();
+                        respond(&send, id, Ok(Value::Null))?;
+                        break;
+                    }
+                    _ => {
+                        let message = format!("Unknown method `{}`", method);
+                        respond(&send, id, Err((METHOD_NOT_FOUND, message)))?;
+                    }
+                }
+            }
+            Ok(())
+        })();
+        // Searches still running after a broken message are of no use
+        if served.is_err() {
+            cancel_all();
+        }
+        served
+    })
+}
+
+/// Sends the notifications of one search, then its response.
+fn stream_results(
+    send: &impl Fn(Value) -> io::Result<()>,
+    id: &Value,
+    events: std::sync::mpsc::Receiver<SearchEvent>,
+    token: &CancellationToken,
+) -> io::Result<()> {
+    for event in events {
+        match event {
+            SearchEvent::FileStart { .. } | SearchEvent::FileEnd { .. } => {}
+            SearchEvent::Match { path, search_match } => send(json!({
+                "jsonrpc": "2.0",
+                "method": "search/match",
+                "params": with_path(
+                    json!({
+                        "id": id,
+                        "line_number": search_match.line_number,
+                        "content": search_match.content_lossy(),
+                    }),
+                    &path,
+                ),
+            }))?,
+            SearchEvent::Error { path, error } => send(json!({
+                "jsonrpc": "2.0",
+                "method": "search/error",
+                "params": with_path(
+                    json!({ "id": id, "message": format!("{:#}", error) }),
+                    &path,
+                ),
+            }))?,
+            SearchEvent::Done { files, matches } => {
+                if token.is_cancelled() {
+                    return send(error_response(id, REQUEST_CANCELLED, "Search cancelled"));
+                }
+                return send(json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "result": { "files": files, "matches": matches },
+                }));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sends the response to a request, unless it was a notification (no `id`).
+fn respond(
+    send: &impl Fn(Value) -> io::Result<()>,
+    id: Option<Value>,
+    result: Result<Value, (i64, String)>,
+) -> io::Result<()> {
+    let Some(id) = id else {
+        return Ok(());
+    };
+    match result {
+        Ok(result) => send(json!({ "jsonrpc": "2.0", "id": id, "result": result })),
+        Err((code, message)) => send(error_response(&id, code, &message)),
+    }
+}
+
+/// A JSON-RPC error response.
+fn error_response(id: &Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames `messages`, runs them through [`serve`], and returns the
+    /// messages written back.
+    fn exchange(messages: &[Value]) -> Vec<Value> {
+        let mut input = Vec::new();
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+        let mut output = Vec::new();
+        serve(&SearchServer::new(), input.as_slice(), &mut output).unwrap();
+
+        let mut output = output.as_slice();
+        let mut replies = Vec::new();
+        while let Some(text) = read_message(&mut output).unwrap() {
+            replies.push(serde_json::from_str(&text).unwrap());
+        }
+        replies
+    }
+
+    #[test]
+    fn test_oversized_messages_are_refused() {
+        let mut input = &b"Content-Length: 99999999999999\r\n\r\n{}"[..];
+        let error = read_message(&mut input).unwrap_err();
+        assert!(error.to_string().contains("over the limit"), "{error}");
+    }
+
+    #[test]
+    fn test_search_sends_matches_then_the_response() {
+        let replies = exchange(&[
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize"}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "search",
+                   "params": {"pattern": "Rust", "paths": ["tests/fixtures/sample.txt"]}}),
+        ]);
+
+        assert_eq!(replies[0]["id"], 1);
+        assert_eq!(replies[0]["result"]["name"], "searcher");
+        let lines: Vec<&Value> = replies[1..4]
+            .iter()
+            .map(|reply| &reply["params"]["line_number"])
+            .collect();
+        assert_eq!(lines, [2, 3, 5]);
+        assert!(
+            replies[1..4]
+                .iter()
+                .all(|reply| reply["method"] == "search/match")
+        );
+        assert_eq!(
+            replies[4],
+            json!({"jsonrpc": "2.0", "id": 2, "result": {"files": 1, "matches": 3}})
+        );
+    }
+
+    #[test]
+    fn test_errors_and_shutdown() {
+        let replies = exchange(&[
+            json!({"jsonrpc": "2.0", "id": 1, "method": "nope"}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "search",
+                   "params": {"pattern": "(", "paths": ["."], "match": {"regex": true}}}),
+            json!({"jsonrpc": "2.0", "id": 3, "method": "cancel", "params": {"id": 99}}),
+            json!({"jsonrpc": "2.0", "id": 4, "method": "shutdown"}),
+            json!({"jsonrpc": "2.0", "id": 5, "method": "initialize"}),
+        ]);
+
+        assert_eq!(replies[0]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(replies[1]["error"]["code"], INVALID_PARAMS);
+        assert_eq!(replies[2]["result"], false);
+        assert_eq!(
+            replies[3],
+            json!({"jsonrpc": "2.0", "id": 4, "result": null})
+        );
+        // Nothing is read after `shutdown`
+        assert_eq!(replies.len(), 4);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cancel_stops_a_running_search() {
+        use std::io::BufReader;
+
+        let (client_reader, server_writer) = io::pipe().unwrap();
+        let (server_reader, mut client_writer) = io::pipe().unwrap();
+        let serving = thread::spawn(move || {
+            serve(
+                &SearchServer::new(),
+                BufReader::new(server_reader),
+                server_writer,
+            )
+        });
+        let mut replies = BufReader::new(client_reader);
+        let mut next = || -> Value {
+            serde_json::from_str(&read_message(&mut replies).unwrap().unwrap()).unwrap()
+        };
+
+        // Split on NUL, `/dev/zero` is endless empty lines, so the search only
+        // ends when cancelled, which is checked between lines
+        let search = json!({"jsonrpc": "2.0", "id": "s", "method": "search",
+                            "params": {"pattern": "x", "paths": ["/dev/zero"],
+                                       "search": {"line_terminator": "nul"}}});
+        write_message(&mut client_writer, &search).unwrap();
+        let cancel =
+            json!({"jsonrpc": "2.0", "id": "c", "method": "cancel", "params": {"id": "s"}});
+        // The search may not be registered yet, so retry until it is found
+        loop {
+            write_message(&mut client_writer, &cancel).unwrap();
+            let reply = next();
+            if reply["id"] == "c" && reply["result"] == true {
+                break;
+            }
+            assert_eq!(reply["result"], false, "{reply}");
+        }
+
+        let reply = next();
+        assert_eq!(reply["id"], "s");
+        assert_eq!(reply["error"]["code"], REQUEST_CANCELLED);
+        drop(client_writer);
+        serving.join().unwrap().unwrap();
+    }
+}
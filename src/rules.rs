@@ -0,0 +1,407 @@
+//! Named rulesets: many patterns with metadata, checked in one pass.
+//!
+//! A ruleset file lists rules, each with an id, a pattern, a severity, and a
+//! message. `searcher lint --rules rules.toml src` reports every line that
+//! breaks a rule, which makes a lightweight scanner for leaked secrets or
+//! code smells:
+//!
+//! ```toml
+//! [[rules]]
+//! id = "no-dbg"
+//! pattern = 'dbg!\('
+//! regex = true
+//! severity = "error"
+//! message = "Remove `dbg!` before merging"
+//!
+//! [[rules]]
+//! id = "aws-key"
+//! pattern = 'AKIA[0-9A-Z]{16}'
+//! regex = true
+//! ```
+//!
+//! The same rules can be written in YAML (in a `.yaml` or `.yml` file):
+//!
+//! ```yaml
+//! rules:
+//!   - id: no-dbg
+//!     pattern: 'dbg!\('
+//!     regex: true
+//!     severity: error
+//! ```
+//!
+//! A [`Linter`] combines the patterns of all rules into one regex the way a
+//! [`PatternSet`](crate::multi::PatternSet) does, so each file is read once
+//! however many rules there are; only lines that match some rule are checked
+//! against the rules one by one.
+//!
+//! Available with the `rules` cargo feature, which the CLI enables.
+
+use crate::multi::Combined;
+use crate::{Matcher, SearchMatchBytes, SearchOptions, search_path};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+/// How serious a rule's findings are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Informational; never fails a lint run
+    Note,
+    /// The default
+    #[default]
+    Warning,
+    /// Must be fixed
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A single rule of a [`RuleSet`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// A unique, stable identifier, e.g. `no-dbg`
+    pub id: String,
+    /// The pattern that lines breaking the rule contain
+    pub pattern: String,
+    /// Interpret `pattern` as a regular expression
+    #[serde(default)]
+    pub regex: bool,
+    /// Match `pattern` case-insensitively
+    #[serde(default)]
+    pub ignore_case: bool,
+    /// How serious a finding is
+    #[serde(default)]
+    pub severity: Severity,
+    /// What to tell the reader of a finding; defaults to naming the pattern
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl Rule {
+    /// The message reported for each finding.
+    pub fn message(&self) -> String {
+        match &self.message {
+            Some(message) => message.clone(),
+            None => format!("Line matches `{}`", self.pattern),
+        }
+    }
+
+    /// The rule's pattern as regex syntax, with its case sensitivity.
+    pub(crate) fn regex_source(&self) -> String {
+        let pattern = if self.regex {
+            self.pattern.clone()
+        } else {
+            regex::escape(&self.pattern)
+        };
+        let flags = if self.ignore_case { "i" } else { "" };
+        format!("(?{}:{})", flags, pattern)
+    }
+}
+
+/// The rules of a ruleset file.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::rules::{RuleSet, Severity};
+///
+/// let rules = RuleSet::from_yaml("
+/// rules:
+///   - id: todo
+///     pattern: TODO
+///     severity: note
+/// ").unwrap();
+///
+/// assert_eq!(rules.rules[0].severity, Severity::Note);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSet {
+    /// The rules, in the order findings are reported
+    pub rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    /// Parses and validates rules written in TOML.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the text is not a valid ruleset.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let rules: RuleSet = toml::from_str(text).context("Invalid rules file")?;
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    /// Parses and validates rules written in YAML.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the text is not a valid ruleset.
+    pub fn from_yaml(text: &str) -> Result<Self> {
+        let rules: RuleSet = serde_yaml::from_str(text).context("Invalid rules file")?;
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    /// Reads a ruleset file: YAML for `.yaml` and `.yml` files, TOML otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the file if it cannot be read or is invalid.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Could not read rules file `{}`", path.display()))?;
+        let yaml = path
+            .extension()
+            .is_some_and(|extension| extension == "yaml" || extension == "yml");
+        let rules = if yaml {
+            Self::from_yaml(&text)
+        } else {
+            Self::from_toml(&text)
+        };
+        rules.with_context(|| format!("Could not load rules file `{}`", path.display()))
+    }
+
+    /// Checks that there is at least one rule, that ids are unique, and
+    /// that every pattern compiles.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error describing the first problem found.
+    pub fn validate(&self) -> Result<()> {
+        if self.rules.is_empty() {
+            bail!("The ruleset has no rules");
+        }
+        let mut ids = HashSet::new();
+        for rule in &self.rules {
+            if rule.id.is_empty() {
+                bail!("A rule has an empty `id`");
+            }
+            if !ids.insert(rule.id.as_str()) {
+                bail!("Rule id `{}` is used more than once", rule.id);
+            }
+            if rule.pattern.is_empty() {
+                bail!("Rule `{}` has an empty `pattern`", rule.id);
+            }
+            Matcher::new(&rule.pattern, rule.ignore_case, rule.regex)
+                .with_context(|| format!("Rule `{}` has an invalid pattern", rule.id))?;
+        }
+        Ok(())
+    }
+
+    /// Compiles the rules for checking files.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a pattern does not compile, or if the combined
+    /// patterns exceed the regex size limit.
+    pub fn compile(&self) -> Result<Linter> {
+        // Each source sets its own case sensitivity
+        let sources: Vec<String> = self.rules.iter().map(Rule::regex_source).collect();
+        let matchers = self
+            .rules
+            .iter()
+            .map(|rule| Matcher::new(&rule.pattern, rule.ignore_case, rule.regex))
+            .collect::<Result<_>>()?;
+        let combined = Combined::new(&sources, false, matchers)
+            .context("Could not combine the rules into one pattern")?;
+        Ok(Linter {
+            rules: self.rules.clone(),
+            combined,
+        })
+    }
+}
+
+/// A line that breaks a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// The index of the rule in [`Linter::rules`]
+    pub rule: usize,
+    /// 1-based line number
+    pub line_number: usize,
+    /// The byte range of the rule's first match within `content`
+    pub range: Range<usize>,
+    /// The line, without its terminator
+    pub content: Vec<u8>,
+}
+
+impl Finding {
+    /// The 1-based byte column where the match starts.
+    pub fn column(&self) -> usize {
+        self.range.start + 1
+    }
+}
+
+/// Compiled rules, ready to check files.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::rules::RuleSet;
+/// use searcher_cli_starter::{Matcher, search_lines_bytes};
+/// use std::io::Cursor;
+///
+/// let linter = RuleSet::from_toml(r#"
+///     [[rules]]
+///     id = "todo"
+///     pattern = "TODO"
+///
+///     [[rules]]
+///     id = "unwrap"
+///     pattern = ".unwrap()"
+/// "#).unwrap().compile().unwrap();
+///
+/// let source = "let x = y.unwrap(); // TODO\nlet z = 1;\n";
+/// let lines = search_lines_bytes(Cursor::new(source), linter.matcher()).unwrap();
+/// let findings = linter.findings(&lines);
+///
+/// assert_eq!(findings.len(), 2);
+/// assert_eq!(linter.rules()[findings[0].rule].id, "todo");
+/// assert_eq!(findings[1].column(), 10);
+/// ```
+pub struct Linter {
+    rules: Vec<Rule>,
+    /// The rules' patterns, combined as for a
+    /// [`PatternSet`](crate::multi::PatternSet)
+    combined: Combined,
+}
+
+impl Linter {
+    /// The rules, in the order given.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// A matcher for lines that break at least one rule.
+    pub fn matcher(&self) -> &Matcher {
+        self.combined.matcher()
+    }
+
+    /// Turns lines matched by [`Linter::matcher`] into one finding per rule
+    /// each line breaks, ordered by line and then by rule.
+    pub fn findings(&self, lines: &[SearchMatchBytes]) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for line in lines {
+            for rule in self.combined.matching_patterns(&line.content) {
+                let range = self
+                    .combined
+                    .pattern_matcher(rule)
+                    .match_ranges(&line.content)
+                    .into_iter()
+                    .next()
+                    .unwrap_or(0..0);
+                findings.push(Finding {
+                    rule,
+                    line_number: line.line_number,
+                    range,
+                    content: line.content.clone(),
+                });
+            }
+        }
+        findings
+    }
+
+    /// Checks the file at `path` against every rule, reading it once.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`search_path`].
+    pub fn check_path(&self, path: &Path, options: &SearchOptions) -> Result<Vec<Finding>> {
+        let lines = search_path(path, self.matcher(), options)?;
+        Ok(self.findings(&lines))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_lines_bytes;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_toml_and_yaml_rules_match() {
+        let toml = RuleSet::from_toml(
+            r#"
+            [[rules]]
+            id = "secret"
+            pattern = 'AKIA[0-9A-Z]{4}'
+            regex = true
+            severity = "error"
+            message = "AWS key"
+            "#,
+        )
+        .unwrap();
+        let yaml = RuleSet::from_yaml(
+            "rules:\n  - id: secret\n    pattern: 'AKIA[0-9A-Z]{4}'\n    regex: true\n    severity: error\n    message: AWS key\n",
+        )
+        .unwrap();
+
+        assert_eq!(toml, yaml);
+        assert_eq!(toml.rules[0].message(), "AWS key");
+    }
+
+    #[test]
+    fn test_invalid_rulesets() {
+        let invalid = [
+            "rules = []",
+            "[[rules]]\nid = \"a\"\npattern = \"\"",
+            "[[rules]]\nid = \"a\"\npattern = \"x\"\n[[rules]]\nid = \"a\"\npattern = \"y\"",
+            "[[rules]]\nid = \"a\"\npattern = \"(\"\nregex = true",
+            "[[rules]]\nid = \"a\"\npattern = \"x\"\nseverity = \"fatal\"",
+            "[[rules]]\nid = \"a\"\npattern = \"x\"\nlevel = \"error\"",
+        ];
+        for text in invalid {
+            assert!(RuleSet::from_toml(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_findings_per_rule() {
+        let linter = RuleSet::from_toml(
+            r#"
+            [[rules]]
+            id = "upper"
+            pattern = "ERROR"
+
+            [[rules]]
+            id = "any-case"
+            pattern = "error"
+            ignore_case = true
+
+            [[rules]]
+            id = "literal"
+            pattern = "a.b"
+            "#,
+        )
+        .unwrap()
+        .compile()
+        .unwrap();
+        let lines = search_lines_bytes(
+            Cursor::new("ok\nan ERROR\nerror: axb\na.b\n"),
+            linter.matcher(),
+        )
+        .unwrap();
+
+        let findings: Vec<(usize, usize, usize)> = linter
+            .findings(&lines)
+            .iter()
+            .map(|finding| (finding.line_number, finding.rule, finding.column()))
+            .collect();
+        assert_eq!(findings, [(2, 0, 4), (2, 1, 4), (3, 1, 1), (4, 2, 1)]);
+    }
+}
@@ -0,0 +1,343 @@
+//! SARIF 2.1.0 reports, for code scanning integrations.
+//!
+//! [SARIF](https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html)
+//! is the format GitHub Code Scanning and most static analysis dashboards
+//! import. A [`SarifLog`] describes the rules that were checked, one per
+//! pattern, and a result for every matching line, located by file, line, and
+//! column:
+//!
+//! ```
+//! use searcher_cli_starter::sarif::{Level, SarifLog, SarifRule};
+//! use searcher_cli_starter::{Matcher, search_str};
+//! use std::path::Path;
+//!
+//! let matcher = Matcher::new("dbg!(", false, false).unwrap();
+//! let mut log = SarifLog::new(vec![SarifRule {
+//!     id: String::from("no-dbg"),
+//!     message: String::from("Remove `dbg!` before merging"),
+//!     level: Level::Error,
+//! }]);
+//! log.add_matches(0, Path::new("src/main.rs"), &search_str("    dbg!(x);", &matcher), &matcher);
+//!
+//! let mut output = Vec::new();
+//! log.write_to(&mut output).unwrap();
+//! let json = String::from_utf8(output).unwrap();
+//! assert!(json.contains(r#""ruleId": "no-dbg""#));
+//! ```
+//!
+//! Columns are counted in Unicode code points, as declared by the run's
+//! `columnKind`. Available with the `sarif` cargo feature, which the CLI
+//! enables.
+
+use crate::{MatchedLine, Matcher};
+use anyhow::Result;
+use serde_json::{Value, json};
+use std::fmt;
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Component, Path, Prefix};
+
+/// The SARIF `$schema` of the reports.
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// How serious a rule's findings are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Level {
+    /// Informational
+    Note,
+    /// Worth fixing; the default
+    #[default]
+    Warning,
+    /// Must be fixed
+    Error,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Note => "note",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        })
+    }
+}
+
+/// A rule checked by the search, reported in the tool's rule list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SarifRule {
+    /// A stable identifier, e.g. `no-dbg`
+    pub id: String,
+    /// The message shown for each result of the rule
+    pub message: String,
+    /// The severity of each result
+    pub level: Level,
+}
+
+/// A SARIF log with a single run of `searcher`.
+#[derive(Debug, Clone)]
+pub struct SarifLog {
+    rules: Vec<SarifRule>,
+    results: Vec<Value>,
+}
+
+impl SarifLog {
+    /// Creates a log for the given rules, with no results yet.
+    pub fn new(rules: Vec<SarifRule>) -> Self {
+        SarifLog {
+            rules,
+            results: Vec::new(),
+        }
+    }
+
+    /// The number of results added so far.
+    pub fn result_count(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Adds a result of the rule at `rule_index` for every line in `matches`.
+    ///
+    /// The region starts at the first match found by `matcher` on the line
+    /// (see [`Matcher::match_ranges`]), or covers the whole line for
+    /// composite matchers. The line itself is included as the snippet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rule_index` is not the index of a rule given to
+    /// [`SarifLog::new`].
+    pub fn add_matches<M: MatchedLine>(
+        &mut self,
+        rule_index: usize,
+        path: &Path,
+        matches: &[M],
+        matcher: &Matcher,
+    ) {
+        for search_match in matches {
+            let content = search_match.content_bytes();
+            let range = matcher.match_ranges(content).into_iter().next();
+            self.add_result(rule_index, path, search_match.line_number(), content, range);
+        }
+    }
+
+    /// Adds a single result of the rule at `rule_index` on line
+    /// `line_number` of `path`, whose text is `content`.
+    ///
+    /// `range` is the byte range of the match within `content`; without one
+    /// the region covers the whole line.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rule_index` is not the index of a rule given to
+    /// [`SarifLog::new`].
+    pub fn add_result(
+        &mut self,
+        rule_index: usize,
+        path: &Path,
+        line_number: usize,
+        content: &[u8],
+        range: Option<Range<usize>>,
+    ) {
+        let level = self.rules[rule_index].level;
+        self.add_result_with_level(rule_index, level, path, line_number, content, range);
+    }
+
+    /// Adds a single result like [`SarifLog::add_result`], but at `level`
+    /// rather than the rule's level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rule_index` is not the index of a rule given to
+    /// [`SarifLog::new`].
+    pub fn add_result_with_level(
+        &mut self,
+        rule_index: usize,
+        level: Level,
+        path: &Path,
+        line_number: usize,
+        content: &[u8],
+        range: Option<Range<usize>>,
+    ) {
+        let rule = &self.rules[rule_index];
+        let mut region = json!({
+            "startLine": line_number,
+            "snippet": { "text": String::from_utf8_lossy(content) },
+        });
+        if let Some(range) = range {
+            region["startColumn"] = json!(code_points(&content[..range.start]) + 1);
+            region["endColumn"] = json!(code_points(&content[..range.end]) + 1);
+        }
+        self.results.push(json!({
+            "ruleId": rule.id,
+            "ruleIndex": rule_index,
+            "level": level.to_string(),
+            "message": { "text": rule.message },
+            "locations": [{
+                "physicalLocation": {
+                    "artifactLocation": { "uri": path_to_uri(path) },
+                    "region": region,
+                },
+            }],
+        }));
+    }
+
+    /// Builds the SARIF document.
+    pub fn to_json(&self) -> Value {
+        let rules: Vec<Value> = self
+            .rules
+            .iter()
+            .map(|rule| {
+                json!({
+                    "id": rule.id,
+                    "shortDescription": { "text": rule.message },
+                    "defaultConfiguration": { "level": rule.level.to_string() },
+                })
+            })
+            .collect();
+        json!({
+            "$schema": SCHEMA,
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "searcher",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                        "rules": rules,
+                    },
+                },
+                "columnKind": "unicodeCodePoints",
+                "results": self.results,
+            }],
+        })
+    }
+
+    /// Writes the SARIF document as pretty-printed JSON, ending in a newline.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to `writer` fails.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        serde_json::to_writer_pretty(&mut writer, &self.to_json())?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+fn code_points(bytes: &[u8]) -> usize {
+    String::from_utf8_lossy(bytes).chars().count()
+}
+
+/// Turns a path into a SARIF artifact URI: relative paths stay relative
+/// with `/` separators, rooted ones become `file://` URIs. A Windows drive
+/// is kept as the first segment (`file:///C:/src/a.rs`) and the server of a
+/// UNC path becomes the authority (`file://server/share/a.rs`).
+fn path_to_uri(path: &Path) -> String {
+    let mut authority = String::new();
+    let mut segments = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(prefix) => match prefix.kind() {
+                Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => {
+                    segments.push(format!("{}:", char::from(letter)))
+                }
+                Prefix::UNC(server, share) | Prefix::VerbatimUNC(server, share) => {
+                    authority = percent_encode(server.as_encoded_bytes());
+                    segments.push(percent_encode(share.as_encoded_bytes()));
+                }
+                Prefix::Verbatim(name) | Prefix::DeviceNS(name) => {
+                    segments.push(percent_encode(name.as_encoded_bytes()))
+                }
+            },
+            Component::Normal(name) => segments.push(percent_encode(name.as_encoded_bytes())),
+            Component::ParentDir => segments.push(String::from("..")),
+            Component::CurDir | Component::RootDir => {}
+        }
+    }
+    let joined = segments.join("/");
+    if path.has_root() {
+        format!("file://{}/{}", authority, joined)
+    } else {
+        joined
+    }
+}
+
+/// Percent-encodes a file name's bytes, which need not be valid UTF-8.
+fn percent_encode(name: &[u8]) -> String {
+    let mut encoded = String::new();
+    for &byte in name {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearchMatch;
+
+    #[test]
+    fn test_results_locate_the_first_match() {
+        let matcher = Matcher::new("bug", false, false).unwrap();
+        let mut log = SarifLog::new(vec![SarifRule {
+            id: String::from("bug"),
+            message: String::from("Matches `bug`"),
+            level: Level::Warning,
+        }]);
+        let matches = vec![SearchMatch {
+            line_number: 4,
+            content: String::from("é bug bug"),
+        }];
+        log.add_matches(0, Path::new("src/my file.rs"), &matches, &matcher);
+
+        let json = log.to_json();
+        let run = &json["runs"][0];
+        assert_eq!(json["version"], "2.1.0");
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "bug");
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "bug");
+        assert_eq!(result["level"], "warning");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/my%20file.rs");
+        assert_eq!(location["region"]["startLine"], 4);
+        assert_eq!(location["region"]["startColumn"], 3);
+        assert_eq!(location["region"]["endColumn"], 6);
+        assert_eq!(log.result_count(), 1);
+    }
+
+    #[test]
+    fn test_path_to_uri() {
+        assert_eq!(path_to_uri(Path::new("./a/b.rs")), "a/b.rs");
+        assert_eq!(path_to_uri(Path::new("../a#1.rs")), "../a%231.rs");
+        assert_eq!(
+            path_to_uri(Path::new("/var/log/x.log")),
+            "file:///var/log/x.log"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let name = std::ffi::OsStr::from_bytes(b"caf\xe9.log");
+            assert_eq!(path_to_uri(Path::new(name)), "caf%E9.log");
+        }
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_path_to_uri_keeps_the_drive() {
+        assert_eq!(
+            path_to_uri(Path::new(r"C:\src\a.rs")),
+            "file:///C:/src/a.rs"
+        );
+        assert_eq!(
+            path_to_uri(Path::new(r"\\?\C:\src\a.rs")),
+            "file:///C:/src/a.rs"
+        );
+        assert_eq!(
+            path_to_uri(Path::new(r"\\server\share\a b.rs")),
+            "file://server/share/a%20b.rs"
+        );
+        assert_eq!(path_to_uri(Path::new(r"src\a.rs")), "src/a.rs");
+    }
+}
@@ -0,0 +1,622 @@
+//! Telling comments, string literals, and code apart.
+//!
+//! A [`Lexer`] splits each line of a source file into regions of
+//! [`Scope::Comments`], [`Scope::Strings`], and [`Scope::Code`], carrying
+//! block comments and multi-line strings over from one line to the next. It
+//! knows only enough about each [`Language`] to find comment markers and
+//! quotes, so it is fast but can be fooled, e.g. by the Rust character
+//! literal `'"'`, whose `"` is read as the start of a string, or by a `#`
+//! inside a shell word.
+//!
+//! HTML and XML files are split the same way into comments, quoted
+//! attribute values, and tags, which count as code, plus a fourth scope,
+//! [`Scope::Text`], for the text between the tags, so searching a
+//! documentation dump does not match inside tag and attribute names. The
+//! contents of HTML `<script>` and `<style>` elements are code, and CDATA
+//! sections are text. Entities such as `&amp;` are left as they are.
+//!
+//! This is what `--only` uses to search, say, TODOs in comments only:
+//! [`SearchOptions::scope`](crate::SearchOptions::scope) makes
+//! [`search_path`](crate::search_path) match each line with everything
+//! outside the scope blanked out by [`Lexer::mask`].
+//!
+//! ```
+//! use searcher_cli_starter::scope::{Language, Lexer, Scope};
+//!
+//! let rust = Language::from_path("main.rs".as_ref()).unwrap();
+//! let mut lexer = Lexer::new(rust);
+//! assert_eq!(lexer.mask(br#"let s = "TODO"; // TODO: fix"#, Scope::Comments),
+//!            b"                // TODO: fix");
+//! assert_eq!(lexer.mask(b"/* starts here", Scope::Code), b"              ");
+//! assert_eq!(lexer.mask(b"ends here */ x", Scope::Code), b"             x");
+//!
+//! let html = Language::from_path("index.html".as_ref()).unwrap();
+//! let mut lexer = Lexer::new(html);
+//! assert_eq!(lexer.mask(br#"<a title="Home">Home</a>"#, Scope::Text),
+//!            b"                Home    ");
+//! ```
+
+use std::ops::Range;
+use std::path::Path;
+
+/// A kind of region in source code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Scope {
+    /// Line and block comments, including their markers
+    Comments,
+    /// String literals, including their quotes
+    Strings,
+    /// Everything else
+    Code,
+    /// Text between the tags of HTML and XML, including CDATA sections;
+    /// code has none
+    Text,
+}
+
+/// The quotes of one kind of string literal.
+#[derive(Debug)]
+struct Quote {
+    delimiter: &'static str,
+    /// Whether a backslash escapes the next byte
+    escapes: bool,
+    /// Whether the literal can span lines; otherwise it ends with the line
+    multiline: bool,
+}
+
+const fn quote(delimiter: &'static str, escapes: bool, multiline: bool) -> Quote {
+    Quote {
+        delimiter,
+        escapes,
+        multiline,
+    }
+}
+
+/// How a markup language writes its tags.
+#[derive(Debug)]
+struct Markup {
+    /// Elements whose content is code rather than text, e.g. `script`
+    raw_elements: &'static [&'static str],
+}
+
+/// How comments and strings are written in a language.
+#[derive(Debug)]
+pub struct Language {
+    /// The language's name, e.g. `Rust`
+    pub name: &'static str,
+    extensions: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    /// Whether block comments nest, as in Rust
+    nested_comments: bool,
+    /// Longer delimiters first, so `"""` is not read as `"` twice; the
+    /// quotes of attribute values in markup
+    quotes: &'static [Quote],
+    /// How tags are written, for HTML and XML
+    markup: Option<Markup>,
+}
+
+const C_QUOTES: &[Quote] = &[quote("\"", true, false), quote("'", true, false)];
+const MARKUP_QUOTES: &[Quote] = &[quote("\"", false, true), quote("'", false, true)];
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        name: "Rust",
+        extensions: &["rs"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        nested_comments: true,
+        quotes: &[quote("\"", true, true)],
+        markup: None,
+    },
+    Language {
+        name: "C",
+        extensions: &[
+            "c", "h", "cc", "cpp", "cxx", "hpp", "hh", "java", "cs", "kt", "swift", "scala",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        nested_comments: false,
+        quotes: C_QUOTES,
+        markup: None,
+    },
+    Language {
+        name: "JavaScript",
+        extensions: &["js", "jsx", "mjs", "cjs", "ts", "tsx"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        nested_comments: false,
+        quotes: &[
+            quote("\"", true, false),
+            quote("'", true, false),
+            quote("`", true, true),
+        ],
+        markup: None,
+    },
+    Language {
+        name: "Go",
+        extensions: &["go"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        nested_comments: false,
+        quotes: &[
+            quote("\"", true, false),
+            quote("'", true, false),
+            quote("`", false, true),
+        ],
+        markup: None,
+    },
+    Language {
+        name: "Python",
+        extensions: &["py", "pyi"],
+        line_comments: &["#"],
+        block_comment: None,
+        nested_comments: false,
+        quotes: &[
+            quote("\"\"\"", true, true),
+            quote("'''", true, true),
+            quote("\"", true, false),
+            quote("'", true, false),
+        ],
+        markup: None,
+    },
+    Language {
+        name: "Shell",
+        extensions: &["sh", "bash", "zsh"],
+        line_comments: &["#"],
+        block_comment: None,
+        nested_comments: false,
+        quotes: &[quote("\"", true, true), quote("'", false, true)],
+        markup: None,
+    },
+    Language {
+        name: "Ruby",
+        extensions: &["rb"],
+        line_comments: &["#"],
+        block_comment: None,
+        nested_comments: false,
+        quotes: &[quote("\"", true, true), quote("'", true, true)],
+        markup: None,
+    },
+    Language {
+        name: "TOML",
+        extensions: &["toml"],
+        line_comments: &["#"],
+        block_comment: None,
+        nested_comments: false,
+        quotes: &[
+            quote("\"\"\"", true, true),
+            quote("'''", false, true),
+            quote("\"", true, false),
+            quote("'", false, false),
+        ],
+        markup: None,
+    },
+    Language {
+        name: "YAML",
+        extensions: &["yml", "yaml"],
+        line_comments: &["#"],
+        block_comment: None,
+        nested_comments: false,
+        quotes: &[quote("\"", true, false), quote("'", false, false)],
+        markup: None,
+    },
+    Language {
+        name: "SQL",
+        extensions: &["sql"],
+        line_comments: &["--"],
+        block_comment: Some(("/*", "*/")),
+        nested_comments: false,
+        quotes: &[quote("'", false, true), quote("\"", false, false)],
+        markup: None,
+    },
+    Language {
+        name: "CSS",
+        extensions: &["css"],
+        line_comments: &[],
+        block_comment: Some(("/*", "*/")),
+        nested_comments: false,
+        quotes: C_QUOTES,
+        markup: None,
+    },
+    Language {
+        name: "HTML",
+        extensions: &["html", "htm", "xhtml"],
+        line_comments: &[],
+        block_comment: Some(("<!--", "-->")),
+        nested_comments: false,
+        quotes: MARKUP_QUOTES,
+        markup: Some(Markup {
+            raw_elements: &["script", "style"],
+        }),
+    },
+    Language {
+        name: "XML",
+        extensions: &["xml", "svg", "xsd", "xsl", "xslt", "rss", "atom", "plist"],
+        line_comments: &[],
+        block_comment: Some(("<!--", "-->")),
+        nested_comments: false,
+        quotes: MARKUP_QUOTES,
+        markup: Some(Markup { raw_elements: &[] }),
+    },
+];
+
+impl Language {
+    /// The language of the file at `path`, going by its extension, or `None`
+    /// if it is not one the lexer knows.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::scope::Language;
+    ///
+    /// assert_eq!(Language::from_path("src/app.TSX".as_ref()).unwrap().name, "JavaScript");
+    /// assert!(Language::from_path("notes.txt".as_ref()).is_none());
+    /// ```
+    pub fn from_path(path: &Path) -> Option<&'static Language> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        LANGUAGES
+            .iter()
+            .find(|language| language.extensions.contains(&extension.as_str()))
+    }
+
+    /// Whether this is a markup language, HTML or XML, whose files have
+    /// [`Scope::Text`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::scope::Language;
+    ///
+    /// assert!(Language::from_path("feed.rss".as_ref()).unwrap().is_markup());
+    /// assert!(!Language::from_path("main.go".as_ref()).unwrap().is_markup());
+    /// ```
+    pub fn is_markup(&self) -> bool {
+        self.markup.is_some()
+    }
+}
+
+/// Where the lexer is at the end of a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Code,
+    /// Inside this many levels of block comment
+    Comment(usize),
+    /// Inside a string opened by `language.quotes[index]`
+    String(usize),
+    /// Between tags, in markup
+    Text,
+    /// Inside a tag, after which the content of raw element `name` starts
+    /// if there is one
+    Tag(Option<&'static str>),
+    /// Inside an attribute value opened by `language.quotes[index]`, in a
+    /// tag as [`State::Tag`]
+    Attribute(usize, Option<&'static str>),
+    /// Inside the content of raw element `name`, up to its end tag
+    Raw(&'static str),
+    /// Inside a CDATA section
+    Cdata,
+}
+
+/// Classifies the lines of one file, in order.
+#[derive(Debug)]
+pub struct Lexer {
+    language: &'static Language,
+    state: State,
+    /// The line returned by [`Lexer::mask`]
+    masked: Vec<u8>,
+}
+
+impl Lexer {
+    /// Creates a lexer for a file in `language`, starting in code, or in
+    /// text for markup.
+    pub fn new(language: &'static Language) -> Self {
+        Lexer {
+            language,
+            state: if language.is_markup() {
+                State::Text
+            } else {
+                State::Code
+            },
+            masked: Vec::new(),
+        }
+    }
+
+    /// Splits the next line (without its terminator) into regions, merging
+    /// neighbouring regions of the same scope.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::scope::{Language, Lexer, Scope};
+    ///
+    /// let mut lexer = Lexer::new(Language::from_path("a.py".as_ref()).unwrap());
+    /// assert_eq!(
+    ///     lexer.regions(b"x = 'a#b'  # note"),
+    ///     [(0..4, Scope::Code), (4..9, Scope::Strings), (9..11, Scope::Code), (11..17, Scope::Comments)]
+    /// );
+    /// ```
+    pub fn regions(&mut self, line: &[u8]) -> Vec<(Range<usize>, Scope)> {
+        let mut regions: Vec<(Range<usize>, Scope)> = Vec::new();
+        self.lex(line, |range, scope| match regions.last_mut() {
+            Some((last, last_scope)) if *last_scope == scope && last.end == range.start => {
+                last.end = range.end;
+            }
+            _ => regions.push((range, scope)),
+        });
+        regions
+    }
+
+    /// Returns the next line (without its terminator) with every byte
+    /// outside `scope` replaced by a space, so that matching it finds only
+    /// text in that scope, at the same offsets as in the original line.
+    pub fn mask(&mut self, line: &[u8], scope: Scope) -> &[u8] {
+        let mut masked = std::mem::take(&mut self.masked);
+        masked.clear();
+        self.lex(line, |range, region| {
+            if region == scope {
+                masked.extend_from_slice(&line[range]);
+            } else {
+                masked.resize(masked.len() + range.len(), b' ');
+            }
+        });
+        self.masked = masked;
+        &self.masked
+    }
+
+    /// Hands each region of `line` to `emit` in order, updating the state.
+    fn lex(&mut self, line: &[u8], mut emit: impl FnMut(Range<usize>, Scope)) {
+        let language = self.language;
+        if let Some(markup) = &language.markup {
+            return self.lex_markup(markup, line, emit);
+        }
+        let mut i = 0;
+        while i < line.len() {
+            let rest = &line[i..];
+            match self.state {
+                State::Code => {
+                    if language
+                        .line_comments
+                        .iter()
+                        .any(|marker| rest.starts_with(marker.as_bytes()))
+                    {
+                        emit(i..line.len(), Scope::Comments);
+                        i = line.len();
+                    } else if let Some((open, _)) = language.block_comment
+                        && rest.starts_with(open.as_bytes())
+                    {
+                        self.state = State::Comment(1);
+                        emit(i..i + open.len(), Scope::Comments);
+                        i += open.len();
+                    } else if let Some(index) = language
+                        .quotes
+                        .iter()
+                        .position(|quote| rest.starts_with(quote.delimiter.as_bytes()))
+                    {
+                        self.state = State::String(index);
+                        let len = language.quotes[index].delimiter.len();
+                        emit(i..i + len, Scope::Strings);
+                        i += len;
+                    } else {
+                        emit(i..i + 1, Scope::Code);
+                        i += 1;
+                    }
+                }
+                State::Comment(depth) => {
+                    let (open, close) = language.block_comment.expect("in a block comment");
+                    let len = if rest.starts_with(close.as_bytes()) {
+                        self.state = match depth - 1 {
+                            0 => State::Code,
+                            depth => State::Comment(depth),
+                        };
+                        close.len()
+                    } else if language.nested_comments && rest.starts_with(open.as_bytes()) {
+                        self.state = State::Comment(depth + 1);
+                        open.len()
+                    } else {
+                        1
+                    };
+                    emit(i..i + len, Scope::Comments);
+                    i += len;
+                }
+                State::String(index) => {
+                    let quote = &language.quotes[index];
+                    let len = if quote.escapes && rest[0] == b'\\' {
+                        rest.len().min(2)
+                    } else if rest.starts_with(quote.delimiter.as_bytes()) {
+                        self.state = State::Code;
+                        quote.delimiter.len()
+                    } else {
+                        1
+                    };
+                    emit(i..i + len, Scope::Strings);
+                    i += len;
+                }
+                State::Text
+                | State::Tag(_)
+                | State::Attribute(..)
+                | State::Raw(_)
+                | State::Cdata => unreachable!("markup state in code"),
+            }
+        }
+        if let State::String(index) = self.state
+            && !language.quotes[index].multiline
+        {
+            self.state = State::Code;
+        }
+    }
+
+    /// [`Lexer::lex`] for HTML and XML.
+    fn lex_markup(
+        &mut self,
+        markup: &Markup,
+        line: &[u8],
+        mut emit: impl FnMut(Range<usize>, Scope),
+    ) {
+        let quotes = self.language.quotes;
+        let mut i = 0;
+        while i < line.len() {
+            let rest = &line[i..];
+            let (len, scope) = match self.state {
+                State::Text => {
+                    if rest.starts_with(b"<!--") {
+                        self.state = State::Comment(1);
+                        (4, Scope::Comments)
+                    } else if rest.starts_with(b"<![CDATA[") {
+                        self.state = State::Cdata;
+                        (9, Scope::Code)
+                    } else if rest[0] == b'<'
+                        && rest.get(1).is_some_and(|&byte| {
+                            byte.is_ascii_alphabetic() || matches!(byte, b'/' | b'!' | b'?')
+                        })
+                    {
+                        let raw = markup.raw_elements.iter().copied().find(|name| {
+                            starts_with_ignore_case(&rest[1..], name)
+                                && rest.get(1 + name.len()).is_none_or(|&byte| {
+                                    byte.is_ascii_whitespace() || matches!(byte, b'>' | b'/')
+                                })
+                        });
+                        self.state = State::Tag(raw);
+                        (1, Scope::Code)
+                    } else {
+                        (1, Scope::Text)
+                    }
+                }
+                State::Tag(raw) => {
+                    if rest[0] == b'>' {
+                        self.state = raw.map_or(State::Text, State::Raw);
+                        (1, Scope::Code)
+                    } else if let Some(index) = quotes
+                        .iter()
+                        .position(|quote| rest.starts_with(quote.delimiter.as_bytes()))
+                    {
+                        self.state = State::Attribute(index, raw);
+                        (quotes[index].delimiter.len(), Scope::Strings)
+                    } else {
+                        (1, Scope::Code)
+                    }
+                }
+                State::Attribute(index, raw) => {
+                    let delimiter = quotes[index].delimiter;
+                    if rest.starts_with(delimiter.as_bytes()) {
+                        self.state = State::Tag(raw);
+                        (delimiter.len(), Scope::Strings)
+                    } else {
+                        (1, Scope::Strings)
+                    }
+                }
+                State::Raw(name) => {
+                    if rest.starts_with(b"</") && starts_with_ignore_case(&rest[2..], name) {
+                        self.state = State::Tag(None);
+                        (2 + name.len(), Scope::Code)
+                    } else {
+                        (1, Scope::Code)
+                    }
+                }
+                State::Cdata => {
+                    if rest.starts_with(b"]]>") {
+                        self.state = State::Text;
+                        (3, Scope::Code)
+                    } else {
+                        (1, Scope::Text)
+                    }
+                }
+                State::Comment(_) => {
+                    if rest.starts_with(b"-->") {
+                        self.state = State::Text;
+                        (3, Scope::Comments)
+                    } else {
+                        (1, Scope::Comments)
+                    }
+                }
+                State::Code | State::String(_) => unreachable!("code state in markup"),
+            };
+            emit(i..i + len, scope);
+            i += len;
+        }
+    }
+}
+
+/// Whether `haystack` starts with `prefix`, ignoring ASCII case.
+fn starts_with_ignore_case(haystack: &[u8], prefix: &str) -> bool {
+    haystack.len() >= prefix.len()
+        && haystack[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lexer(path: &str) -> Lexer {
+        Lexer::new(Language::from_path(path.as_ref()).unwrap())
+    }
+
+    #[test]
+    fn test_state_carries_across_lines() {
+        let mut rust = lexer("lib.rs");
+        assert_eq!(rust.mask(b"/* a /* b */", Scope::Code), b"            ");
+        // Still inside the outer comment
+        assert_eq!(rust.mask(b"c */ d", Scope::Code), b"     d");
+        assert_eq!(
+            rust.mask(b"let s = \"one", Scope::Strings),
+            b"        \"one"
+        );
+        assert_eq!(rust.mask(b"two\" + x", Scope::Strings), b"two\"    ");
+
+        let mut python = lexer("a.py");
+        assert_eq!(
+            python.mask(b"'''doc # not", Scope::Comments),
+            b"            "
+        );
+        assert_eq!(
+            python.mask(b"end''' # yes", Scope::Comments),
+            b"       # yes"
+        );
+    }
+
+    #[test]
+    fn test_escapes_and_single_line_strings() {
+        let mut c = lexer("main.c");
+        assert_eq!(
+            c.regions(br#"puts("a\"// b"); // c"#),
+            [
+                (0..5, Scope::Code),
+                (5..14, Scope::Strings),
+                (14..17, Scope::Code),
+                (17..21, Scope::Comments),
+            ]
+        );
+        // An unterminated string ends with its line
+        assert_eq!(c.regions(b"'x"), [(0..2, Scope::Strings)]);
+        assert_eq!(c.regions(b"y"), [(0..1, Scope::Code)]);
+    }
+
+    #[test]
+    fn test_markup_text_between_tags() {
+        let mut html = lexer("page.HTML");
+        assert_eq!(
+            html.regions(br#"<p class="x">a < b<!-- c"#),
+            [
+                (0..9, Scope::Code),
+                (9..12, Scope::Strings),
+                (12..13, Scope::Code),
+                (13..18, Scope::Text),
+                (18..24, Scope::Comments),
+            ]
+        );
+        assert_eq!(html.mask(b"--> d <br", Scope::Text), b"    d    ");
+        // The tag goes on, and `>` in an attribute value does not end it
+        assert_eq!(html.mask(b"title='>'>e", Scope::Text), b"          e");
+        assert_eq!(
+            html.mask(b"<SCRIPT>if (a<b) x()</script>f", Scope::Text),
+            b"                             f"
+        );
+
+        let mut xml = lexer("doc.xml");
+        assert_eq!(
+            xml.mask(b"<![CDATA[<g>]]><script>h</script>", Scope::Text),
+            b"         <g>           h         "
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! A searcher that reuses its buffers from one input to the next.
+//!
+//! [`search_lines`](crate::search_lines) allocates a fresh block buffer for
+//! every input, which adds up when searching thousands of small files. A
+//! [`Searcher`] keeps the buffer of its [`LineReader`] instead, so after the
+//! first input it only allocates for the matches it reports. The matches are
+//! appended to a `Vec` supplied by the caller, which can be cleared and reused
+//! too. [`Searcher::search_path`] does the same for files searched as
+//! [`search_path`](crate::search_path) searches them, and is what the
+//! `searcher` binary searches each file with.
+//!
+//! ```
+//! use searcher_cli_starter::Matcher;
+//! use searcher_cli_starter::searcher::Searcher;
+//!
+//! let mut searcher = Searcher::new(Matcher::new("error", false, false).unwrap());
+//! let mut matches = Vec::new();
+//!
+//! for input in ["ok\nerror: disk\n", "error: net\nok\n"] {
+//!     matches.clear();
+//!     searcher.search_into(input.as_bytes(), &mut matches).unwrap();
+//!     assert_eq!(matches.len(), 1);
+//! }
+//! assert_eq!(matches[0].content, "error: net");
+//! ```
+
+use crate::lines::LineReader;
+use crate::source::{FileSource, search_input_in};
+use crate::{Matcher, SearchMatch, SearchMatchBytes, SearchOptions, search_lines_in};
+use anyhow::Result;
+use std::borrow::Borrow;
+use std::io::Read;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Searches inputs line-by-line like [`search_lines`](crate::search_lines),
+/// keeping its buffer between searches.
+///
+/// A searcher is meant for one thread at a time. To search in parallel, give
+/// each worker its own `Searcher` over one shared `Arc<Matcher>`, or over a
+/// `&Matcher` they all borrow, so the pattern is compiled only once.
+pub struct Searcher<M = Arc<Matcher>> {
+    matcher: M,
+    /// The block buffer, handed from one `LineReader` to the next
+    buffer: Vec<u8>,
+}
+
+impl<M: Borrow<Matcher>> Searcher<M> {
+    /// Creates a searcher for `matcher`. The buffer is allocated by the
+    /// first search.
+    ///
+    /// The matcher can be passed by value, borrowed, or shared between
+    /// searchers as an `Arc<Matcher>`.
+    pub fn new(matcher: M) -> Self {
+        Searcher {
+            matcher,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// The matcher lines are tested against.
+    pub fn matcher(&self) -> &Matcher {
+        self.matcher.borrow()
+    }
+
+    /// Searches `reader` and appends the matching lines to `matches`.
+    ///
+    /// Lines are split and numbered exactly as [`search_lines`] does: `\n`
+    /// and `\r\n` are stripped and line numbers start at 1 for every input.
+    /// Existing entries in `matches` are kept, so clear it first when reusing
+    /// it for another input.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading from `reader` fails or a line is not valid
+    /// UTF-8. Matches found before the error stay in `matches`.
+    ///
+    /// [`search_lines`]: crate::search_lines
+    pub fn search_into<R: Read>(
+        &mut self,
+        reader: R,
+        matches: &mut Vec<SearchMatch>,
+    ) -> Result<()> {
+        let mut lines = LineReader::with_buffer(reader, b'\n', std::mem::take(&mut self.buffer));
+        let result = search_lines_in(&mut lines, self.matcher.borrow(), |search_match| {
+            matches.push(search_match.to_owned());
+            ControlFlow::Continue(())
+        });
+        self.buffer = lines.into_buffer();
+        result
+    }
+
+    /// Searches the file at `path` as [`search_path`] does, with all of
+    /// `options`, and appends the matching lines to `matches`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`search_path`]. Matches found before the error
+    /// stay in `matches`.
+    ///
+    /// [`search_path`]: crate::search_path
+    pub fn search_path(
+        &mut self,
+        path: &Path,
+        options: &SearchOptions,
+        matches: &mut Vec<SearchMatchBytes>,
+    ) -> Result<()> {
+        let source = FileSource::new(path);
+        search_input_in(
+            &source,
+            self.matcher.borrow(),
+            options,
+            &mut self.buffer,
+            |search_match| {
+                matches.push(search_match);
+                ControlFlow::Continue(())
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_lines;
+
+    #[test]
+    fn test_search_into_agrees_with_search_lines() {
+        let mut searcher = Searcher::new(Matcher::new("a", false, false).unwrap());
+        let mut matches = Vec::new();
+
+        for input in [
+            "",
+            "a",
+            "abcdefgh\nb\r\naa\r\n",
+            "x\n\nlong line with an a in it\na\r",
+            &"a".repeat(100_000),
+        ] {
+            matches.clear();
+            searcher
+                .search_into(input.as_bytes(), &mut matches)
+                .unwrap();
+            assert_eq!(
+                matches,
+                search_lines(input.as_bytes(), searcher.matcher()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_path_agrees_with_search_path() {
+        let matcher = Matcher::new("e", false, false).unwrap();
+        let mut searcher = Searcher::new(&matcher);
+        let options = SearchOptions {
+            line_ranges: vec!["2-".parse().unwrap()],
+            ..SearchOptions::default()
+        };
+        let mut matches = Vec::new();
+
+        for path in ["tests/fixtures/sample.txt", "tests/fixtures/notes.txt"] {
+            let path = Path::new(path);
+            matches.clear();
+            searcher.search_path(path, &options, &mut matches).unwrap();
+            assert!(!matches.is_empty());
+            assert_eq!(
+                matches,
+                crate::search_path(path, &matcher, &options).unwrap()
+            );
+        }
+        let error = searcher
+            .search_path(Path::new("missing.txt"), &options, &mut matches)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Could not read file `missing.txt`");
+    }
+
+    #[test]
+    fn test_searchers_share_one_matcher_across_threads() {
+        let matcher = Arc::new(Matcher::new(r"id=\d+", false, true).unwrap());
+        let inputs = ["id=1\nx\n", "id=2\nid=3\n", "none\n"];
+
+        let counts: Vec<usize> = std::thread::scope(|scope| {
+            let workers: Vec<_> = inputs
+                .iter()
+                .map(|input| {
+                    let mut searcher = Searcher::new(Arc::clone(&matcher));
+                    scope.spawn(move || {
+                        let mut matches = Vec::new();
+                        searcher
+                            .search_into(input.as_bytes(), &mut matches)
+                            .unwrap();
+                        matches.len()
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+
+        assert_eq!(counts, [1, 2, 0]);
+        assert_eq!(Arc::strong_count(&matcher), 1);
+    }
+
+    #[test]
+    fn test_search_into_appends_and_rejects_invalid_utf8() {
+        let mut searcher = Searcher::new(Matcher::new("a", false, false).unwrap());
+        let mut matches = Vec::new();
+        searcher.search_into(&b"a\n"[..], &mut matches).unwrap();
+
+        let error = searcher
+            .search_into(&b"a\n\xff\na\n"[..], &mut matches)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Line 2 is not valid UTF-8");
+        assert_eq!(matches.len(), 2);
+        assert!(matches.iter().all(|m| m.line_number == 1));
+    }
+}
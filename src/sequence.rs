@@ -0,0 +1,251 @@
+//! Judging matches by the lines that follow them.
+//!
+//! Whether a log line matters often depends on what comes next: an `ERROR`
+//! followed a few lines later by `retry succeeded` was handled. These
+//! searches read the input once, holding each match back only until the
+//! lines it waits for have been read, so memory stays bounded by the
+//! window rather than the input.
+//!
+//! [`search_reader_not_within`] keeps the matches that another pattern does
+//! not follow within a number of lines.
+//!
+//! ```
+//! use searcher_cli_starter::sequence::search_reader_not_within;
+//! use searcher_cli_starter::{Matcher, SearchOptions};
+//!
+//! let input = "ERROR a\nretry succeeded\nERROR b\nok\nok\nretry succeeded\n";
+//! let matcher = Matcher::new("ERROR", false, false).unwrap();
+//! let handled = Matcher::new("retry succeeded", false, false).unwrap();
+//! let matches =
+//!     search_reader_not_within(input.as_bytes(), &matcher, &handled, 2, &SearchOptions::default())
+//!         .unwrap();
+//!
+//! assert_eq!(matches.len(), 1);
+//! assert_eq!(matches[0].content, b"ERROR b");
+//! ```
+//!
+//! [`search_reader_pairs`] does the opposite, keeping the matches that
+//! another pattern follows, together with the line that follows them, e.g.
+//! to pair requests with their responses or locks with their unlocks.
+
+use crate::{
+    Matcher, SearchMatchBytes, SearchOptions, file_error, open_window, search_reader_passthru,
+};
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::io::Read;
+use std::ops::ControlFlow;
+use std::path::Path;
+
+/// A match and the line after it that matched the pattern it was waiting
+/// for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pair {
+    /// The match
+    pub first: SearchMatchBytes,
+    /// The first line after it matching the other pattern
+    pub then: SearchMatchBytes,
+}
+
+/// Searches through a reader like [`search_reader`](crate::search_reader),
+/// dropping each match that a line matching `other` follows within `within`
+/// lines.
+///
+/// A line matching `other` only counts for the matches before it, so a line
+/// matching both patterns is still reported unless another follows it.
+/// Lines are read as [`search_reader_passthru`] reads them, so
+/// `options.scope`, `dedupe`, and `timeout` are not used.
+///
+/// # Errors
+///
+/// Returns the errors of [`search_reader_passthru`].
+pub fn search_reader_not_within<R: Read>(
+    reader: R,
+    matcher: &Matcher,
+    other: &Matcher,
+    within: usize,
+    options: &SearchOptions,
+) -> Result<Vec<SearchMatchBytes>> {
+    let mut matches = Vec::new();
+    // Matches whose window has not been read to the end yet
+    let mut pending: VecDeque<SearchMatchBytes> = VecDeque::new();
+    search_reader_passthru(reader, matcher, options, |line_number, line, is_match| {
+        while let Some(search_match) =
+            pending.pop_front_if(|search_match| line_number - search_match.line_number > within)
+        {
+            matches.push(search_match);
+        }
+        if other.is_match_bytes(line) {
+            pending.clear();
+        }
+        if is_match {
+            pending.push_back(SearchMatchBytes {
+                line_number,
+                content: line.to_vec(),
+            });
+        }
+        ControlFlow::Continue(())
+    })?;
+    matches.extend(pending);
+    Ok(matches)
+}
+
+/// Searches through a reader for the matches that a line matching `then`
+/// follows within `within` lines, pairing each with the first such line.
+///
+/// Matches waiting at the same time are all paired with the same line, so
+/// in `lock`, `lock`, `unlock` both locks pair with the unlock. A line
+/// matching both patterns only completes the matches before it. Lines are
+/// read as by [`search_reader_not_within`].
+///
+/// # Errors
+///
+/// Returns the errors of [`search_reader_passthru`].
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::sequence::search_reader_pairs;
+/// use searcher_cli_starter::{Matcher, SearchOptions};
+///
+/// let input = "GET /a\nGET /b\n200 /b\nnoise\nnoise\n200 /a\n";
+/// let request = Matcher::new("GET", false, false).unwrap();
+/// let response = Matcher::new("200", false, false).unwrap();
+/// let pairs = search_reader_pairs(input.as_bytes(), &request, &response, 1, &SearchOptions::default())
+///     .unwrap();
+///
+/// // `GET /a` waited too long for its response
+/// assert_eq!(pairs.len(), 1);
+/// assert_eq!(pairs[0].first.line_number, 2);
+/// assert_eq!(pairs[0].then.content, b"200 /b");
+/// ```
+pub fn search_reader_pairs<R: Read>(
+    reader: R,
+    matcher: &Matcher,
+    then: &Matcher,
+    within: usize,
+    options: &SearchOptions,
+) -> Result<Vec<Pair>> {
+    let mut pairs = Vec::new();
+    // Matches still waiting for a line matching `then`
+    let mut pending: VecDeque<SearchMatchBytes> = VecDeque::new();
+    search_reader_passthru(reader, matcher, options, |line_number, line, is_match| {
+        while pending
+            .pop_front_if(|search_match| line_number - search_match.line_number > within)
+            .is_some()
+        {}
+        if !pending.is_empty() && then.is_match_bytes(line) {
+            let then = SearchMatchBytes {
+                line_number,
+                content: line.to_vec(),
+            };
+            pairs.extend(pending.drain(..).map(|first| Pair {
+                first,
+                then: then.clone(),
+            }));
+        }
+        if is_match {
+            pending.push_back(SearchMatchBytes {
+                line_number,
+                content: line.to_vec(),
+            });
+        }
+        ControlFlow::Continue(())
+    })?;
+    Ok(pairs)
+}
+
+/// Searches the file at `path` like [`search_reader_not_within`].
+///
+/// `options.byte_window` is used as [`search_path`](crate::search_path)
+/// uses it, but `options.preprocessor` is not.
+///
+/// # Errors
+///
+/// Returns an error naming `path` if it cannot be opened or read, along
+/// with the errors of [`search_reader_not_within`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use searcher_cli_starter::sequence::search_path_not_within;
+/// use searcher_cli_starter::{Matcher, SearchOptions};
+///
+/// let matcher = Matcher::new("ERROR", false, false).unwrap();
+/// let handled = Matcher::new("retry succeeded", false, false).unwrap();
+/// let options = SearchOptions::default();
+/// for unhandled in search_path_not_within("app.log".as_ref(), &matcher, &handled, 5, &options).unwrap() {
+///     println!("{}: {}", unhandled.line_number, unhandled.content_lossy());
+/// }
+/// ```
+pub fn search_path_not_within(
+    path: &Path,
+    matcher: &Matcher,
+    other: &Matcher,
+    within: usize,
+    options: &SearchOptions,
+) -> Result<Vec<SearchMatchBytes>> {
+    options.check_cancelled()?;
+    let file = open_window(path, options)?;
+    search_reader_not_within(file, matcher, other, within, options)
+        .map_err(|error| file_error(path, error))
+}
+
+/// Searches the file at `path` like [`search_reader_pairs`], reading it as
+/// [`search_path_not_within`] does.
+///
+/// # Errors
+///
+/// Returns an error naming `path` if it cannot be opened or read, along
+/// with the errors of [`search_reader_pairs`].
+pub fn search_path_pairs(
+    path: &Path,
+    matcher: &Matcher,
+    then: &Matcher,
+    within: usize,
+    options: &SearchOptions,
+) -> Result<Vec<Pair>> {
+    options.check_cancelled()?;
+    let file = open_window(path, options)?;
+    search_reader_pairs(file, matcher, then, within, options)
+        .map_err(|error| file_error(path, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_not_within_counts_only_following_lines() {
+        let matcher = Matcher::new("E", false, false).unwrap();
+        let other = Matcher::new("ok", false, false).unwrap();
+        // Lines 1 and 2 are followed by `ok` on line 3, line 1 at the edge
+        // of its window; line 4 matches both and is dropped by line 5
+        let input = "E1\nE2\nok\nE ok\nok\nx\nE3\n";
+        let options = SearchOptions::default();
+        let matches = search_reader_not_within(input.as_bytes(), &matcher, &other, 2, &options);
+        let lines: Vec<usize> = matches.unwrap().iter().map(|m| m.line_number).collect();
+        assert_eq!(lines, [7]);
+
+        let matches = search_reader_not_within(input.as_bytes(), &matcher, &other, 0, &options);
+        let lines: Vec<usize> = matches.unwrap().iter().map(|m| m.line_number).collect();
+        assert_eq!(lines, [1, 2, 4, 7]);
+    }
+
+    #[test]
+    fn test_pairs_share_the_completing_line() {
+        let matcher = Matcher::new("lock", false, false).unwrap();
+        let then = Matcher::new("unlock", false, false).unwrap();
+        let input = "lock a\nlock b\nunlock\nunlock\nlock c\n";
+        let options = SearchOptions::default();
+        let pairs = search_reader_pairs(input.as_bytes(), &matcher, &then, 5, &options).unwrap();
+        let lines: Vec<(usize, usize)> = pairs
+            .iter()
+            .map(|pair| (pair.first.line_number, pair.then.line_number))
+            .collect();
+        // `unlock` matches `lock` too, so line 3 waits for the unlock on
+        // line 4, and line 4 for one that never comes
+        assert_eq!(lines, [(1, 3), (2, 3), (3, 4)]);
+    }
+}
@@ -0,0 +1,689 @@
+//! Answering search requests from a long-running process.
+//!
+//! Starting `searcher` for every keystroke of an editor plugin pays for
+//! process startup, compiling the pattern, and loading the index each time.
+//! A [`SearchServer`] stays up instead: `searcher serve --socket PATH`
+//! accepts connections on a Unix socket and answers one JSON request per
+//! line, keeping the compiled matchers and indexes of earlier requests warm.
+//! With the `serve-http` feature, `searcher serve --http ADDR` takes the
+//! same requests as `POST /search` (see [`SearchServer::serve_http`]).
+//!
+//! A request uses the field names of `searcher run` job files (`pattern`,
+//! `paths`, `match`, and `search`), plus an optional `id` that is echoed
+//! back and an optional trigram `index` to pick the files from:
+//!
+//! ```json
+//! {"id": 1, "pattern": "TODO", "paths": ["src"], "match": {"ignore_case": true}}
+//! ```
+//!
+//! The answer is one JSON object per line: a `match` for each matching line,
+//! an `error` for each file that could not be searched, and a final `done`:
+//!
+//! ```json
+//! {"content":"// todo: docs","id":1,"line_number":3,"path":"src/lib.rs","type":"match"}
+//! {"files":12,"id":1,"matches":1,"type":"done"}
+//! ```
+//!
+//! File names that are not valid UTF-8 get a lossy `path` and, to tell them
+//! apart, their bytes in base64 as `path_base64`.
+//!
+//! A request that cannot be run at all, e.g. because its pattern is invalid,
+//! is answered with a single `error` without a `path`. Available with the
+//! `server` cargo feature, which the CLI enables.
+
+use crate::cancel::CancellationToken;
+use crate::events::{SearchEvent, spawn_search};
+use crate::index::{Index, required_literals};
+use crate::query::{MatcherOptions, build_matcher};
+use crate::{Matcher, SearchOptions};
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(feature = "serve-http")]
+use {std::io::Read, std::sync::mpsc};
+#[cfg(any(unix, feature = "serve-http"))]
+use {std::thread, std::time::Duration};
+
+/// How many compiled matchers are kept before the cache is cleared.
+const MAX_CACHED_MATCHERS: usize = 64;
+
+/// The largest HTTP request body read, in bytes; larger ones get a `413`.
+#[cfg(feature = "serve-http")]
+const MAX_REQUEST_BYTES: u64 = 1024 * 1024;
+
+/// How many HTTP requests are answered at once. Further ones wait until an
+/// answer is done.
+#[cfg(feature = "serve-http")]
+const MAX_HTTP_ANSWERS: usize = 16;
+
+/// An index with the modification time of its file when it was read.
+type LoadedIndex = (SystemTime, Arc<Index>);
+
+/// One search request.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::server::SearchRequest;
+///
+/// let request: SearchRequest = serde_json::from_str(
+///     r#"{"id": "a", "pattern": "TODO", "paths": ["src"], "match": {"regex": true}}"#,
+/// )
+/// .unwrap();
+/// assert!(request.matcher.regex);
+/// assert!(serde_json::from_str::<SearchRequest>(r#"{"pattern": "x", "path": []}"#).is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SearchRequest {
+    /// Any JSON value, repeated in every line of the answer
+    #[serde(default)]
+    pub id: Value,
+    /// The pattern to look for
+    pub pattern: String,
+    /// The files or directories to search
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+    /// A trigram index written by `searcher index build`; when given, the
+    /// files to search are the index's candidates instead of `paths`
+    #[serde(default)]
+    pub index: Option<PathBuf>,
+    /// How to interpret the pattern and which patterns to combine with it
+    #[serde(default, rename = "match")]
+    pub matcher: MatcherOptions,
+    /// How to split the input into lines
+    #[serde(default)]
+    pub search: SearchOptions,
+}
+
+/// Runs search requests, caching what can be reused between them.
+///
+/// Clones share their caches, so one server can answer several connections
+/// from different threads.
+#[derive(Clone, Default)]
+pub struct SearchServer {
+    /// Compiled matchers by pattern and matcher options
+    matchers: Arc<Mutex<HashMap<String, Arc<Matcher>>>>,
+    /// Loaded indexes by path, with the index file's modification time
+    indexes: Arc<Mutex<HashMap<PathBuf, LoadedIndex>>>,
+    /// Stops every running search once cancelled
+    cancel: Option<CancellationToken>,
+}
+
+impl SearchServer {
+    /// Creates a server with empty caches.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops running searches, and [`SearchServer::serve`], once `token` is
+    /// cancelled.
+    pub fn with_cancel(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Starts the search for `request` on a background thread, returning
+    /// the channel its events arrive on (see [`spawn_search`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pattern is invalid, neither `paths` nor
+    /// `index` is given, or the index cannot be read.
+    pub fn search(&self, request: &SearchRequest) -> Result<Receiver<SearchEvent>> {
+        let matcher = self.matcher(&request.pattern, &request.matcher)?;
+        let paths = match &request.index {
+            Some(path) => {
+                let literals = required_literals(&request.pattern, &request.matcher);
+                self.index(path)?.candidates(&literals).files
+            }
+            None if request.paths.is_empty() => bail!("A request needs `paths` or an `index`"),
+            None => request.paths.clone(),
+        };
+        let options = SearchOptions {
+            cancel: self.cancel.clone(),
+            ..request.search.clone()
+        };
+        Ok(spawn_search(paths, matcher, options))
+    }
+
+    /// Answers one request line, writing the answer to `output` as JSON
+    /// lines. The search stops early if writing fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error only if writing to `output` fails; problems with the
+    /// request are written as an `error` answer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use searcher_cli_starter::server::SearchServer;
+    ///
+    /// let server = SearchServer::new();
+    /// let mut output = Vec::new();
+    /// server
+    ///     .answer(r#"{"id": 7, "pattern": "fox", "paths": ["tests/fixtures"]}"#, &mut output)
+    ///     .unwrap();
+    ///
+    /// let output = String::from_utf8(output).unwrap();
+    /// let last = output.lines().last().unwrap();
+    /// assert_eq!(last, r#"{"files":2,"id":7,"matches":1,"type":"done"}"#);
+    /// ```
+    pub fn answer(&self, line: &str, output: &mut impl Write) -> io::Result<()> {
+        match self.start(line) {
+            Ok((id, events)) => write_answer(&id, events, output),
+            Err(error) => {
+                write_json(output, error)?;
+                output.flush()
+            }
+        }
+    }
+
+    /// Parses a request and starts its search, returning its `id` with the
+    /// channel of events, or the `error` answer if it cannot run.
+    fn start(&self, text: &str) -> Result<(Value, Receiver<SearchEvent>), Value> {
+        let request: SearchRequest = serde_json::from_str(text).map_err(|error| {
+            let message = format!("Invalid request: {}", error);
+            json!({ "type": "error", "message": message })
+        })?;
+        match self.search(&request) {
+            Ok(events) => Ok((request.id, events)),
+            Err(error) => {
+                let message = format!("{:#}", error);
+                Err(json!({ "id": request.id, "type": "error", "message": message }))
+            }
+        }
+    }
+
+    /// Answers every request line read from `input` until it ends.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading `input` or writing `output` fails.
+    pub fn answer_all(&self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                self.answer(&line, &mut output)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Accepts connections on `listener` until the server's cancellation
+    /// token is cancelled, answering each on its own thread with
+    /// [`SearchServer::answer_all`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if accepting a connection fails.
+    #[cfg(unix)]
+    pub fn serve(&self, listener: &UnixListener) -> Result<()> {
+        // Polled, so that cancelling is noticed without a new connection
+        listener
+            .set_nonblocking(true)
+            .context("Could not listen on the socket")?;
+        while !self
+            .cancel
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+        {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let server = self.clone();
+                    thread::spawn(move || server.answer_stream(stream));
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                Err(error) => return Err(error).context("Could not accept a connection"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers the requests of one connection until the client hangs up.
+    #[cfg(unix)]
+    fn answer_stream(&self, stream: UnixStream) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        self.answer_all(reader, BufWriter::new(stream))
+    }
+
+    /// Answers HTTP requests received by `http` until the server's
+    /// cancellation token is cancelled, each on its own thread, with at
+    /// most 16 answered at once.
+    ///
+    /// `POST /search` takes one request as its body and streams the answer
+    /// back as `application/x-ndjson`, one JSON object per line, as
+    /// [`SearchServer::answer`] writes it. A body that is not a valid
+    /// request, or whose search cannot start, gets a `400` with the `error`
+    /// answer, and one over 1 MiB a `413`. Other paths get a `404`, and
+    /// other methods a `405`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if receiving a request fails.
+    #[cfg(feature = "serve-http")]
+    pub fn serve_http(&self, http: &tiny_http::Server) -> Result<()> {
+        // Each answer holds one of these permits until it is done
+        let (release, permits) = mpsc::sync_channel(MAX_HTTP_ANSWERS);
+        for _ in 0..MAX_HTTP_ANSWERS {
+            release
+                .send(())
+                .expect("the channel has room for every permit");
+        }
+        while !self
+            .cancel
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+        {
+            if permits.recv_timeout(Duration::from_millis(50)).is_err() {
+                continue;
+            }
+            let request = http
+                .recv_timeout(Duration::from_millis(50))
+                .context("Could not receive a request")?;
+            let Some(request) = request else {
+                release.send(()).ok();
+                continue;
+            };
+            let server = self.clone();
+            let release = release.clone();
+            thread::spawn(move || {
+                server.answer_http(request).ok();
+                release.send(()).ok();
+            });
+        }
+        Ok(())
+    }
+
+    /// Answers one HTTP request for [`SearchServer::serve_http`].
+    #[cfg(feature = "serve-http")]
+    fn answer_http(&self, mut request: tiny_http::Request) -> io::Result<()> {
+        use tiny_http::{Header, Method, Response};
+
+        let content_type = Header::from_bytes("Content-Type", "application/x-ndjson")
+            .expect("the header is valid");
+        let error = |status: u16, answer: Value| {
+            Response::from_string(format!("{}\n", answer))
+                .with_status_code(status)
+                .with_header(content_type.clone())
+        };
+        if request.url() != "/search" {
+            let answer = json!({ "type": "error", "message": "Not found" });
+            return request.respond(error(404, answer));
+        }
+        if *request.method() != Method::Post {
+            let answer = json!({ "type": "error", "message": "Use POST" });
+            let allow = Header::from_bytes("Allow", "POST").expect("the header is valid");
+            return request.respond(error(405, answer).with_header(allow));
+        }
+        let too_large = || {
+            let message = format!("Request bodies are limited to {} bytes", MAX_REQUEST_BYTES);
+            error(413, json!({ "type": "error", "message": message }))
+        };
+        if request
+            .body_length()
+            .is_some_and(|length| length as u64 > MAX_REQUEST_BYTES)
+        {
+            return request.respond(too_large());
+        }
+        // A byte past the limit tells a body sent without a length apart
+        let mut body = Vec::new();
+        let mut limited = request.as_reader().take(MAX_REQUEST_BYTES + 1);
+        if let Err(read) = limited.read_to_end(&mut body) {
+            let message = format!("Invalid request: {}", read);
+            return request.respond(error(400, json!({ "type": "error", "message": message })));
+        }
+        if body.len() as u64 > MAX_REQUEST_BYTES {
+            return request.respond(too_large());
+        }
+        let Ok(body) = String::from_utf8(body) else {
+            let message = "Invalid request: the body is not valid UTF-8";
+            return request.respond(error(400, json!({ "type": "error", "message": message })));
+        };
+        let (id, events) = match self.start(&body) {
+            Ok(started) => started,
+            Err(answer) => return request.respond(error(400, answer)),
+        };
+
+        // Streamed through a pipe: the response reads what the search writes,
+        // and a client hanging up closes the pipe, which stops the search
+        let (reader, mut writer) = io::pipe()?;
+        thread::spawn(move || write_answer(&id, events, &mut writer));
+        let response = Response::new(200.into(), vec![content_type], reader, None, None);
+        request.respond(response)
+    }
+
+    /// The compiled matcher for `pattern`, built on first use.
+    fn matcher(&self, pattern: &str, options: &MatcherOptions) -> Result<Arc<Matcher>> {
+        let key = json!([pattern, options]).to_string();
+        let mut matchers = self.matchers.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(matcher) = matchers.get(&key) {
+            return Ok(Arc::clone(matcher));
+        }
+        let matcher = Arc::new(build_matcher(pattern, options)?);
+        if matchers.len() >= MAX_CACHED_MATCHERS {
+            matchers.clear();
+        }
+        matchers.insert(key, Arc::clone(&matcher));
+        Ok(matcher)
+    }
+
+    /// The index at `path`, read again only when the file changed.
+    fn index(&self, path: &Path) -> Result<Arc<Index>> {
+        let modified = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .with_context(|| format!("Could not read index `{}`", path.display()))?;
+        let mut indexes = self.indexes.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((loaded, index)) = indexes.get(path)
+            && *loaded == modified
+        {
+            return Ok(Arc::clone(index));
+        }
+        let index = Arc::new(Index::read_from(path)?);
+        indexes.insert(path.to_path_buf(), (modified, Arc::clone(&index)));
+        Ok(index)
+    }
+}
+
+/// Writes a `match` or `error` line for every event of a search, then its
+/// `done` line. Returning early drops `events`, which stops the search.
+fn write_answer(
+    id: &Value,
+    events: Receiver<SearchEvent>,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    for event in events {
+        match event {
+            SearchEvent::FileStart { .. } | SearchEvent::FileEnd { .. } => {}
+            SearchEvent::Match { path, search_match } => write_json(
+                output,
+                with_path(
+                    json!({
+                        "id": id,
+                        "type": "match",
+                        "line_number": search_match.line_number,
+                        "content": search_match.content_lossy(),
+                    }),
+                    &path,
+                ),
+            )?,
+            SearchEvent::Error { path, error } => write_json(
+                output,
+                with_path(
+                    json!({
+                        "id": id,
+                        "type": "error",
+                        "message": format!("{:#}", error),
+                    }),
+                    &path,
+                ),
+            )?,
+            SearchEvent::Done { files, matches } => write_json(
+                output,
+                json!({ "id": id, "type": "done", "files": files, "matches": matches }),
+            )?,
+        }
+    }
+    output.flush()
+}
+
+/// Adds `path` to the JSON object `value`. File names that are not valid
+/// UTF-8 are written lossily, with `U+FFFD` in place of the bad bytes, so
+/// `path_base64` carries their exact bytes as well.
+pub(crate) fn with_path(mut value: Value, path: &Path) -> Value {
+    let bytes = path.as_os_str().as_encoded_bytes();
+    value["path"] = json!(String::from_utf8_lossy(bytes));
+    if std::str::from_utf8(bytes).is_err() {
+        value["path_base64"] = json!(base64(bytes));
+    }
+    value
+}
+
+/// Encodes `bytes` as standard, padded base64.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(ALPHABET[(group >> (18 - 6 * i)) as usize & 63]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Writes `value` as one line of JSON.
+fn write_json(output: &mut impl Write, value: Value) -> io::Result<()> {
+    serde_json::to_writer(&mut *output, &value)?;
+    output.write_all(b"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The answer lines to `request`, parsed.
+    fn answer(server: &SearchServer, request: &str) -> Vec<Value> {
+        let mut output = Vec::new();
+        server.answer(request, &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_answer_streams_matches_then_done() {
+        let server = SearchServer::new();
+        let lines = answer(
+            &server,
+            r#"{"id": "q", "pattern": "rust", "paths": ["tests/fixtures/sample.txt"],
+                "match": {"ignore_case": true}, "search": {"line_ranges": ["1-3"]}}"#,
+        );
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["type"], "match");
+        assert_eq!(lines[0]["id"], "q");
+        assert_eq!(lines[0]["line_number"], 2);
+        assert_eq!(lines[1]["content"], "Hello world from Rust");
+        assert_eq!(
+            lines[2],
+            json!({"id": "q", "type": "done", "files": 1, "matches": 2})
+        );
+    }
+
+    #[test]
+    fn test_bad_requests_are_answered_with_errors() {
+        let server = SearchServer::new();
+
+        let lines = answer(&server, "not json");
+        assert_eq!(lines[0]["type"], "error");
+        assert!(
+            lines[0]["message"]
+                .as_str()
+                .unwrap()
+                .starts_with("Invalid request")
+        );
+
+        let lines = answer(
+            &server,
+            r#"{"id": 1, "pattern": "(", "paths": ["."], "match": {"regex": true}}"#,
+        );
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["id"], 1);
+        assert_eq!(lines[0]["type"], "error");
+
+        let lines = answer(&server, r#"{"pattern": "x"}"#);
+        assert_eq!(lines[0]["message"], "A request needs `paths` or an `index`");
+
+        let lines = answer(&server, r#"{"pattern": "x", "paths": ["missing.txt"]}"#);
+        assert_eq!(lines[0]["path"], "missing.txt");
+        assert_eq!(lines[1]["type"], "done");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_paths_are_also_sent_as_base64() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = std::env::temp_dir().join("searcher_server_non_utf8");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(std::ffi::OsStr::from_bytes(b"caf\xe9.txt"));
+        fs::write(&file, "rust\n").unwrap();
+
+        let request = json!({"pattern": "rust", "paths": [dir]}).to_string();
+        let lines = answer(&SearchServer::new(), &request);
+        let path = lines[0]["path"].as_str().unwrap();
+        assert!(path.ends_with("caf\u{fffd}.txt"));
+        assert_eq!(
+            lines[0]["path_base64"],
+            base64(file.as_os_str().as_encoded_bytes())
+        );
+
+        assert_eq!(
+            ["", "f", "fo", "foo", "foob"].map(|text| base64(text.as_bytes())),
+            ["", "Zg==", "Zm8=", "Zm9v", "Zm9vYg=="]
+        );
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_matchers_are_reused() {
+        let server = SearchServer::new();
+        let options = MatcherOptions::default();
+        let first = server.matcher("a", &options).unwrap();
+        let again = server.matcher("a", &options).unwrap();
+        let other = server
+            .matcher(
+                "a",
+                &MatcherOptions {
+                    ignore_case: true,
+                    ..MatcherOptions::default()
+                },
+            )
+            .unwrap();
+
+        assert!(Arc::ptr_eq(&first, &again));
+        assert!(!Arc::ptr_eq(&first, &other));
+    }
+
+    #[cfg(feature = "serve-http")]
+    #[test]
+    fn test_serve_http_streams_answers() {
+        use std::io::Read;
+        use std::net::TcpStream;
+
+        let http = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let address = http.server_addr().to_ip().unwrap();
+        let token = CancellationToken::new();
+        let server = SearchServer::new().with_cancel(token.clone());
+        let serving = thread::spawn(move || server.serve_http(&http));
+
+        let post = move |path: &str, body: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(
+                stream,
+                "POST {} HTTP/1.1\r\nHost: x\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+                path,
+                body.len(),
+                body
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let response = post(
+            "/search",
+            r#"{"pattern": "fox", "paths": ["tests/fixtures"]}"#,
+        );
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.contains("application/x-ndjson"));
+        assert!(response.contains(r#""line_number":1,"path":"tests/fixtures/sample.txt""#));
+        assert!(response.contains(r#""type":"done""#));
+
+        let response = post("/search", r#"{"pattern": "fox"}"#);
+        assert!(response.starts_with("HTTP/1.1 400"), "{response}");
+        assert!(response.contains("A request needs `paths` or an `index`"));
+        assert!(post("/", "").starts_with("HTTP/1.1 404"));
+
+        // Too large a body is refused before it is read
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "POST /search HTTP/1.1\r\nHost: x\r\nConnection: close\r\nContent-Length: {}\r\n\r\n",
+            MAX_REQUEST_BYTES + 1
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).ok();
+        assert!(response.starts_with("HTTP/1.1 413"), "{response}");
+
+        // More requests than can be answered at once all get their answer
+        let posting: Vec<_> = (0..MAX_HTTP_ANSWERS * 2)
+            .map(|_| {
+                thread::spawn(move || {
+                    post(
+                        "/search",
+                        r#"{"pattern": "fox", "paths": ["tests/fixtures"]}"#,
+                    )
+                })
+            })
+            .collect();
+        for posted in posting {
+            assert!(posted.join().unwrap().starts_with("HTTP/1.1 200"));
+        }
+
+        token.cancel();
+        serving.join().unwrap().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_serve_answers_over_a_socket() {
+        use std::io::Read;
+
+        let path = std::env::temp_dir().join(format!("searcher_serve_{}.sock", std::process::id()));
+        fs::remove_file(&path).ok();
+        let listener = UnixListener::bind(&path).unwrap();
+        let token = CancellationToken::new();
+        let server = SearchServer::new().with_cancel(token.clone());
+        let serving = thread::spawn(move || server.serve(&listener));
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream
+            .write_all(b"{\"pattern\": \"fox\", \"paths\": [\"tests/fixtures\"]}\n")
+            .unwrap();
+        stream.shutdown(std::net::Shutdown::Write).unwrap();
+        let mut answer = String::new();
+        stream.read_to_string(&mut answer).unwrap();
+        assert!(answer.contains(r#""type":"match""#));
+        assert!(answer.ends_with("\"type\":\"done\"}\n"));
+
+        token.cancel();
+        serving.join().unwrap().unwrap();
+        fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,148 @@
+//! Grouping matching lines into sessions by a key they contain.
+//!
+//! Services handling many requests at once interleave their log lines, so
+//! the lines of one request are scattered between those of others.
+//! [`Sessions`] pulls a key such as a request or thread id out of each line
+//! with a regex capture group and gathers the lines of every key, in the
+//! order the keys were first seen. This is what `searcher find
+//! --correlate-by` prints.
+//!
+//! ```
+//! use searcher_cli_starter::sessions::Sessions;
+//! use searcher_cli_starter::{Matcher, search_str};
+//! use std::path::Path;
+//!
+//! let log = "req=a start\nreq=b start\nreq=a done\nidle\nreq=b done\n";
+//! let matcher = Matcher::new("req=", false, false).unwrap();
+//!
+//! let mut sessions = Sessions::new(r"req=(\w+)").unwrap();
+//! sessions.add_matches(Path::new("app.log"), &search_str(log, &matcher));
+//!
+//! let keys: Vec<&str> = sessions.sessions().iter().map(|session| session.key.as_str()).collect();
+//! assert_eq!(keys, ["a", "b"]);
+//! assert_eq!(sessions.sessions()[0].lines[1].1.content, b"req=a done");
+//! ```
+
+use crate::histogram::KeyPattern;
+use crate::{MatchedLine, SearchMatchBytes};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The lines of one key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    /// The value the capture group extracted
+    pub key: String,
+    /// Each line with the file it came from, in the order they were added
+    pub lines: Vec<(PathBuf, SearchMatchBytes)>,
+}
+
+/// Gathers lines into a [`Session`] per value of a capture group.
+///
+/// The key of a line is taken as by
+/// [`Histogram`](crate::histogram::Histogram): the group named `key`, else
+/// the first capture group. Lines without one are counted as
+/// [`Sessions::unkeyed`]. Every line added is kept until the sessions are
+/// printed, since a session can go on until the end of the input.
+#[derive(Debug, Clone)]
+pub struct Sessions {
+    key: KeyPattern,
+    sessions: Vec<Session>,
+    /// The index in `sessions` of each key
+    index: HashMap<String, usize>,
+    unkeyed: usize,
+}
+
+impl Sessions {
+    /// Creates an empty set of sessions keyed by a capture group of
+    /// `pattern`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `pattern` is not a valid regex or has no capture
+    /// group.
+    pub fn new(pattern: &str) -> Result<Self> {
+        Ok(Sessions {
+            key: KeyPattern::new(pattern, "correlate by")?,
+            sessions: Vec::new(),
+            index: HashMap::new(),
+            unkeyed: 0,
+        })
+    }
+
+    /// Adds one line of the file at `path` to the session of its key.
+    pub fn add(&mut self, path: &Path, line_number: usize, line: &[u8]) {
+        let Some(key) = self.key.value(line) else {
+            self.unkeyed += 1;
+            return;
+        };
+        let key = String::from_utf8_lossy(key);
+        let index = match self.index.get(&*key) {
+            Some(&index) => index,
+            None => {
+                self.index.insert(key.to_string(), self.sessions.len());
+                self.sessions.push(Session {
+                    key: key.into_owned(),
+                    lines: Vec::new(),
+                });
+                self.sessions.len() - 1
+            }
+        };
+        self.sessions[index].lines.push((
+            path.to_path_buf(),
+            SearchMatchBytes {
+                line_number,
+                content: line.to_vec(),
+            },
+        ));
+    }
+
+    /// Adds every line in `matches`, found in the file at `path`.
+    pub fn add_matches<M: MatchedLine>(&mut self, path: &Path, matches: &[M]) {
+        for search_match in matches {
+            self.add(
+                path,
+                search_match.line_number(),
+                search_match.content_bytes(),
+            );
+        }
+    }
+
+    /// The sessions, in the order their keys were first seen.
+    pub fn sessions(&self) -> &[Session] {
+        &self.sessions
+    }
+
+    /// The number of lines added that had no key.
+    pub fn unkeyed(&self) -> usize {
+        self.unkeyed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_span_files() {
+        let mut sessions = Sessions::new(r"\[(\d+)\] (?P<key>\w+)").unwrap();
+        sessions.add(Path::new("a.log"), 1, b"[1] bob login");
+        sessions.add(Path::new("a.log"), 2, b"[2] amy login");
+        sessions.add(Path::new("a.log"), 3, b"no key");
+        sessions.add(Path::new("b.log"), 1, b"[3] bob logout");
+
+        let sessions_found = sessions.sessions();
+        assert_eq!(sessions_found.len(), 2);
+        assert_eq!(sessions_found[0].key, "bob");
+        let lines: Vec<(&Path, usize)> = sessions_found[0]
+            .lines
+            .iter()
+            .map(|(path, line)| (path.as_path(), line.line_number))
+            .collect();
+        assert_eq!(lines, [(Path::new("a.log"), 1), (Path::new("b.log"), 1)]);
+        assert_eq!(sessions.unkeyed(), 1);
+
+        assert!(Sessions::new(r"\w+").is_err());
+    }
+}
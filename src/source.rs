@@ -0,0 +1,570 @@
+//! Where the input of a search comes from.
+//!
+//! [`search_path`](crate::search_path) reads files and
+//! [`search_reader`](crate::search_reader) any reader, but a reader has no
+//! name to pick a language by for `options.scope`, no size to find a
+//! [`ByteWindow::Tail`] in, and no path to report errors under. An
+//! [`InputSource`] carries all three with the contents, so
+//! [`search_input`] can search files, stdin, buffers in memory, members of
+//! tar archives, or sources of an embedder's own, such as blobs in a
+//! database, the way `search_path` searches files.
+//!
+//! ```
+//! use searcher_cli_starter::source::{MemorySource, search_input};
+//! use searcher_cli_starter::{Matcher, SearchOptions};
+//!
+//! let source = MemorySource::new("notes.txt", &b"ok\nerror: disk full\n"[..]);
+//! let matcher = Matcher::new("error", false, false).unwrap();
+//! let matches = search_input(&source, &matcher, &SearchOptions::default()).unwrap();
+//!
+//! assert_eq!(matches[0].line_number, 2);
+//! ```
+
+use crate::diagnostic::FileContext;
+use crate::{ByteWindow, Matcher, SearchMatchBytes, SearchOptions, file_error, search_source_with};
+use anyhow::{Context, Result, bail};
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// What is known about the contents of an [`InputSource`] without reading
+/// them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourceMetadata {
+    /// The length of the contents in bytes, if known in advance
+    pub len: Option<u64>,
+    /// When the contents last changed, if known
+    pub modified: Option<SystemTime>,
+}
+
+/// Something that can be searched: a name and contents that can be read.
+///
+/// Only [`name`](Self::name) and [`open`](Self::open) are required. The
+/// other methods let a source be searched more cheaply or more fully, e.g.
+/// [`open_at`](Self::open_at) lets a [`ByteWindow::Tail`] start reading
+/// near the end instead of skipping over the rest.
+pub trait InputSource {
+    /// The name matches and errors are reported under. Its extension picks
+    /// the language for `options.scope`.
+    fn name(&self) -> &Path;
+
+    /// Opens the contents for reading from the start.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the contents cannot be opened.
+    fn open(&self) -> io::Result<Box<dyn Read + '_>>;
+
+    /// What is known about the contents; nothing, unless the source says.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if looking the metadata up fails.
+    fn metadata(&self) -> io::Result<SourceMetadata> {
+        Ok(SourceMetadata::default())
+    }
+
+    /// Opens the contents for reading from byte `offset`. By default they
+    /// are opened from the start and read up to it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the contents cannot be opened or read.
+    fn open_at(&self, offset: u64) -> io::Result<Box<dyn Read + '_>> {
+        let mut reader = self.open()?;
+        io::copy(&mut (&mut reader).take(offset), &mut io::sink())?;
+        Ok(reader)
+    }
+
+    /// The file holding the contents, if they are a whole file on disk,
+    /// so that `options.preprocessor` can run on it.
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// A file on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSource {
+    path: PathBuf,
+}
+
+impl FileSource {
+    /// The file at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileSource { path: path.into() }
+    }
+}
+
+impl InputSource for FileSource {
+    fn name(&self) -> &Path {
+        &self.path
+    }
+
+    fn open(&self) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(File::open(&self.path)?))
+    }
+
+    fn metadata(&self) -> io::Result<SourceMetadata> {
+        let metadata = fs::metadata(&self.path)?;
+        Ok(SourceMetadata {
+            len: Some(metadata.len()),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    fn open_at(&self, offset: u64) -> io::Result<Box<dyn Read + '_>> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(Box::new(file))
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+/// The standard input of the process, named `<stdin>`. It can only be read
+/// once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StdinSource;
+
+impl InputSource for StdinSource {
+    fn name(&self) -> &Path {
+        Path::new("<stdin>")
+    }
+
+    fn open(&self) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(io::stdin().lock()))
+    }
+}
+
+/// Contents already in memory, under a name of the caller's choosing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemorySource<'a> {
+    name: PathBuf,
+    data: Cow<'a, [u8]>,
+}
+
+impl<'a> MemorySource<'a> {
+    /// `data`, borrowed or owned, named `name`.
+    pub fn new(name: impl Into<PathBuf>, data: impl Into<Cow<'a, [u8]>>) -> Self {
+        MemorySource {
+            name: name.into(),
+            data: data.into(),
+        }
+    }
+}
+
+impl InputSource for MemorySource<'_> {
+    fn name(&self) -> &Path {
+        &self.name
+    }
+
+    fn open(&self) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(&*self.data))
+    }
+
+    fn metadata(&self) -> io::Result<SourceMetadata> {
+        Ok(SourceMetadata {
+            len: Some(self.data.len() as u64),
+            modified: None,
+        })
+    }
+
+    fn open_at(&self, offset: u64) -> io::Result<Box<dyn Read + '_>> {
+        let start =
+            usize::try_from(offset).map_or(self.data.len(), |offset| offset.min(self.data.len()));
+        Ok(Box::new(&self.data[start..]))
+    }
+}
+
+/// A file inside an uncompressed tar archive, read in place.
+///
+/// Members are named by their path inside the archive joined to the
+/// archive's, e.g. `logs.tar/app/today.log`. Use [`tar_members`] to list
+/// them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TarMember {
+    archive: PathBuf,
+    name: PathBuf,
+    /// Where the member's contents start in the archive
+    offset: u64,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl TarMember {
+    /// The archive the member is in.
+    pub fn archive(&self) -> &Path {
+        &self.archive
+    }
+}
+
+impl InputSource for TarMember {
+    fn name(&self) -> &Path {
+        &self.name
+    }
+
+    fn open(&self) -> io::Result<Box<dyn Read + '_>> {
+        self.open_at(0)
+    }
+
+    fn metadata(&self) -> io::Result<SourceMetadata> {
+        Ok(SourceMetadata {
+            len: Some(self.len),
+            modified: self.modified,
+        })
+    }
+
+    fn open_at(&self, offset: u64) -> io::Result<Box<dyn Read + '_>> {
+        let mut file = File::open(&self.archive)?;
+        let offset = offset.min(self.len);
+        file.seek(SeekFrom::Start(self.offset + offset))?;
+        Ok(Box::new(file.take(self.len - offset)))
+    }
+}
+
+/// The size of a tar header and of the blocks member contents are padded to.
+const TAR_BLOCK: u64 = 512;
+
+/// Lists the regular files in the uncompressed tar archive at `path`, in
+/// the order they are stored. Long names written by GNU tar or in pax
+/// headers are read; directories, links, and other special members are
+/// left out.
+///
+/// # Errors
+///
+/// Returns an error naming `path` if it cannot be read or is not a tar
+/// archive.
+///
+/// # Examples
+///
+/// ```no_run
+/// use searcher_cli_starter::source::{search_input, tar_members};
+/// use searcher_cli_starter::{Matcher, SearchOptions};
+/// use std::path::Path;
+///
+/// let matcher = Matcher::new("ERROR", false, false).unwrap();
+/// for member in tar_members(Path::new("logs.tar")).unwrap() {
+///     for found in search_input(&member, &matcher, &SearchOptions::default()).unwrap() {
+///         println!("{}:{}", found.line_number, found.content_lossy());
+///     }
+/// }
+/// ```
+pub fn tar_members(path: &Path) -> Result<Vec<TarMember>> {
+    read_tar_members(path).with_context(|| FileContext::read(path))
+}
+
+fn read_tar_members(path: &Path) -> Result<Vec<TarMember>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut members = Vec::new();
+    let mut offset = 0;
+    // The name a GNU or pax header gave the next member
+    let mut long_name: Option<Vec<u8>> = None;
+    loop {
+        let mut header = [0; TAR_BLOCK as usize];
+        match file.read_exact(&mut header) {
+            Ok(()) => {}
+            // Some writers leave out the closing zero blocks
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof && offset > 0 => break,
+            Err(error) => return Err(error.into()),
+        }
+        if header.iter().all(|&byte| byte == 0) {
+            break;
+        }
+        let len = tar_number(&header[124..136])?;
+        let data = offset + TAR_BLOCK;
+        let next = data + len.div_ceil(TAR_BLOCK) * TAR_BLOCK;
+        match header[156] {
+            b'L' => long_name = Some(until_nul(&read_data(&mut file, len)?).to_vec()),
+            b'x' => {
+                if let Some(name) = pax_path(&read_data(&mut file, len)?) {
+                    long_name = Some(name);
+                }
+            }
+            b'0' | 0 | b'7' => {
+                let name = long_name.take().unwrap_or_else(|| {
+                    let mut name = Vec::new();
+                    // The ustar prefix holds the start of names over 100 bytes
+                    if &header[257..262] == b"ustar" && header[345] != 0 {
+                        name.extend_from_slice(until_nul(&header[345..500]));
+                        name.push(b'/');
+                    }
+                    name.extend_from_slice(until_nul(&header[..100]));
+                    name
+                });
+                let name = String::from_utf8_lossy(&name);
+                let modified = tar_number(&header[136..148])
+                    .ok()
+                    .map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds));
+                members.push(TarMember {
+                    archive: path.to_path_buf(),
+                    name: path.join(name.trim_start_matches('/')),
+                    offset: data,
+                    len,
+                    modified,
+                });
+            }
+            _ => long_name = None,
+        }
+        file.seek(SeekFrom::Start(next))?;
+        offset = next;
+    }
+    Ok(members)
+}
+
+/// Reads the `len` bytes of data after a header.
+fn read_data(file: &mut impl Read, len: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    file.take(len).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// `field` up to its first NUL byte.
+fn until_nul(field: &[u8]) -> &[u8] {
+    field.split(|&byte| byte == 0).next().unwrap_or_default()
+}
+
+/// A number in a tar header: octal digits, or big-endian binary when the
+/// first byte has its high bit set.
+fn tar_number(field: &[u8]) -> Result<u64> {
+    if field[0] & 0x80 != 0 {
+        let value = field[1..]
+            .iter()
+            .try_fold(u64::from(field[0] & 0x7f), |value, &byte| {
+                value.checked_mul(256).map(|value| value + u64::from(byte))
+            });
+        return value.context("not a tar archive: a size is too large");
+    }
+    let digits = String::from_utf8_lossy(until_nul(field));
+    let digits = digits.trim_matches(' ');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    match u64::from_str_radix(digits, 8) {
+        Ok(number) => Ok(number),
+        Err(_) => bail!("not a tar archive: `{}` is not an octal number", digits),
+    }
+}
+
+/// The `path` of the pax extended header records in `data`, each written
+/// as `LENGTH KEY=VALUE\n`.
+fn pax_path(data: &[u8]) -> Option<Vec<u8>> {
+    let mut rest = data;
+    while !rest.is_empty() {
+        let space = rest.iter().position(|&byte| byte == b' ')?;
+        let len: usize = std::str::from_utf8(&rest[..space]).ok()?.parse().ok()?;
+        let record = rest.get(space + 1..len)?;
+        let record = record.strip_suffix(b"\n").unwrap_or(record);
+        if let Some(path) = record.strip_prefix(b"path=") {
+            return Some(path.to_vec());
+        }
+        rest = &rest[len..];
+    }
+    None
+}
+
+/// Opens `source` at the start of `window`, ending with it, as
+/// [`SearchOptions::byte_window`] asks.
+fn open_window<'s>(
+    source: &'s dyn InputSource,
+    window: ByteWindow,
+    terminator: u8,
+) -> io::Result<Box<dyn Read + 's>> {
+    match window {
+        ByteWindow::Head(len) => Ok(Box::new(source.open()?.take(len))),
+        ByteWindow::Tail(len) => match source.metadata()?.len {
+            Some(size) if size <= len => source.open(),
+            // Start on the byte before the tail, so that a line starting
+            // right at it is kept
+            Some(size) => {
+                let mut reader = BufReader::new(source.open_at(size - len - 1)?);
+                reader.skip_until(terminator)?;
+                Ok(Box::new(reader))
+            }
+            None => {
+                // Without a length the tail is only known at the end
+                let mut data = Vec::new();
+                source.open()?.read_to_end(&mut data)?;
+                let keep = usize::try_from(len).unwrap_or(usize::MAX);
+                if data.len() <= keep {
+                    return Ok(Box::new(io::Cursor::new(data)));
+                }
+                let mut tail = io::Cursor::new(data.split_off(data.len() - keep - 1));
+                tail.skip_until(terminator)?;
+                Ok(Box::new(tail))
+            }
+        },
+    }
+}
+
+/// Searches `source` as [`search_path`](crate::search_path) searches a
+/// file: with `options.scope` picked by its name, `options.byte_window`,
+/// and, for sources that are [files on disk](InputSource::path),
+/// `options.preprocessor`.
+///
+/// # Errors
+///
+/// Returns an error naming the source if it cannot be opened or read, if it
+/// takes longer than `options.timeout`, if the preprocessor fails, or
+/// [`Cancelled`](crate::cancel::Cancelled) if `options.cancel` is cancelled.
+pub fn search_input(
+    source: &dyn InputSource,
+    matcher: &Matcher,
+    options: &SearchOptions,
+) -> Result<Vec<SearchMatchBytes>> {
+    let mut matches = Vec::new();
+    search_input_with(source, matcher, options, |search_match| {
+        matches.push(search_match);
+        ControlFlow::Continue(())
+    })?;
+    Ok(matches)
+}
+
+/// Searches `source` like [`search_input`], handing each match to `visit`
+/// as soon as it is found as [`search_reader_with`](crate::search_reader_with)
+/// does.
+///
+/// # Errors
+///
+/// Returns the errors of [`search_input`]. Matches visited before the
+/// error are not undone.
+pub fn search_input_with<F>(
+    source: &dyn InputSource,
+    matcher: &Matcher,
+    options: &SearchOptions,
+    visit: F,
+) -> Result<()>
+where
+    F: FnMut(SearchMatchBytes) -> ControlFlow<()>,
+{
+    search_input_in(source, matcher, options, &mut Vec::new(), visit)
+}
+
+/// Searches `source` like [`search_input_with`], reading its lines into
+/// `buffer` and handing it back for the next input afterwards.
+pub(crate) fn search_input_in<F>(
+    source: &dyn InputSource,
+    matcher: &Matcher,
+    options: &SearchOptions,
+    buffer: &mut Vec<u8>,
+    mut visit: F,
+) -> Result<()>
+where
+    F: FnMut(SearchMatchBytes) -> ControlFlow<()>,
+{
+    options.check_cancelled()?;
+    let name = source.name();
+    if let Some(preprocessor) = &options.preprocessor
+        && let Some(path) = source.path()
+        && preprocessor.applies_to(path)
+    {
+        // Searched to the end first, so that the command does not fail with
+        // a broken pipe
+        for search_match in preprocessor.search(path, matcher, options)? {
+            if visit(search_match).is_break() {
+                break;
+            }
+        }
+        return Ok(());
+    }
+    let reader = match options.byte_window {
+        None => source.open(),
+        Some(window) => open_window(source, window, options.line_terminator.byte()),
+    }
+    .with_context(|| FileContext::read(name))?;
+    search_source_with(name, reader, matcher, options, buffer, visit)
+        .map_err(|error| file_error(name, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tar header for a member of `len` bytes named `name`, of type `kind`.
+    fn tar_header(name: &[u8], len: usize, kind: u8) -> Vec<u8> {
+        let mut header = vec![0; TAR_BLOCK as usize];
+        header[..name.len()].copy_from_slice(name);
+        header[124..135].copy_from_slice(format!("{:011o}", len).as_bytes());
+        header[136..147].copy_from_slice(b"00000000012");
+        header[156] = kind;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header
+    }
+
+    fn padded(data: &[u8]) -> Vec<u8> {
+        let mut data = data.to_vec();
+        data.resize(data.len().div_ceil(512) * 512, 0);
+        data
+    }
+
+    #[test]
+    fn test_tar_members_are_searched_in_place() {
+        let long_name = format!("logs/{}.log", "x".repeat(120));
+        let pax = format!("{} path={}\n", long_name.len() + 10, long_name);
+        assert_eq!(pax.len(), long_name.len() + 10);
+
+        let mut archive = Vec::new();
+        archive.extend(tar_header(b"logs/", 0, b'5'));
+        archive.extend(tar_header(b"logs/a.log", 9, b'0'));
+        archive.extend(padded(b"ok\nerror\n"));
+        archive.extend(tar_header(b"PaxHeader", pax.len(), b'x'));
+        archive.extend(padded(pax.as_bytes()));
+        archive.extend(tar_header(b"truncated", 14, b'0'));
+        archive.extend(padded(b"error\nok\nerror"));
+        archive.extend([0; 1024]);
+        let path = std::env::temp_dir().join(format!("searcher_tar_{}.tar", std::process::id()));
+        fs::write(&path, archive).unwrap();
+
+        let members = tar_members(&path).unwrap();
+        let names: Vec<&Path> = members.iter().map(|member| member.name()).collect();
+        assert_eq!(names, [path.join("logs/a.log"), path.join(&long_name)]);
+        assert_eq!(
+            members[0].metadata().unwrap().modified,
+            Some(UNIX_EPOCH + Duration::from_secs(10))
+        );
+
+        let matcher = Matcher::new("error", false, false).unwrap();
+        let options = SearchOptions {
+            byte_window: Some(ByteWindow::Tail(7)),
+            ..SearchOptions::default()
+        };
+        let matches = search_input(&members[1], &matcher, &options).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].content, b"error");
+        fs::remove_file(path).ok();
+
+        assert!(tar_members(Path::new("tests/fixtures/sample.txt")).is_err());
+    }
+
+    /// A source that only says how to read it.
+    struct Chunks(&'static [u8]);
+
+    impl InputSource for Chunks {
+        fn name(&self) -> &Path {
+            Path::new("chunks.rs")
+        }
+
+        fn open(&self) -> io::Result<Box<dyn Read + '_>> {
+            Ok(Box::new(self.0))
+        }
+    }
+
+    #[test]
+    fn test_tail_of_a_source_without_a_length() {
+        let source = Chunks(b"// error one\nerror two // error three\n");
+        let matcher = Matcher::new("error", false, false).unwrap();
+        let options = SearchOptions {
+            byte_window: Some(ByteWindow::Tail(26)),
+            scope: Some(crate::scope::Scope::Comments),
+            ..SearchOptions::default()
+        };
+        let matches = search_input(&source, &matcher, &options).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line_number, 1);
+    }
+}
@@ -0,0 +1,365 @@
+//! Searching one large file on several threads.
+//!
+//! Searching files in parallel keeps one core busy per file, so a single
+//! multi-gigabyte log is still searched by one. [`search_path_split`] cuts
+//! such a file into ranges of about [`Split::range_size`] bytes, each ending
+//! just after a line terminator, and searches the ranges on
+//! [`Split::threads`], each taking the next range not yet taken as
+//! it finishes one. Each range counts the lines it reads, so the matches are
+//! put back together in order with the line numbers of a search of the
+//! whole file.
+//!
+//! [`share_threads`] keeps the two apart: a single file is split, while
+//! several are searched a file per thread and not split, so a parallel
+//! search never starts threads within threads.
+//!
+//! Only searches that judge every line on its own can be split: see
+//! [`can_split`]. Others, and files no larger than one range, are searched
+//! by [`search_path`] as usual.
+//!
+//! ```no_run
+//! use searcher_cli_starter::split::{Split, search_path_split};
+//! use searcher_cli_starter::threads::Threads;
+//! use searcher_cli_starter::{Matcher, SearchOptions};
+//!
+//! let matcher = Matcher::new("OutOfMemoryError", false, false).unwrap();
+//! let split = Split::new(Threads::available());
+//! let matches = search_path_split("huge.log".as_ref(), &matcher, &SearchOptions::default(), &split).unwrap();
+//! println!("{} matching lines", matches.len());
+//! ```
+
+use crate::diagnostic::FileContext;
+use crate::threads::Threads;
+use crate::{
+    Matcher, SearchMatchBytes, SearchOptions, file_error, search_path, search_reader_until,
+};
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+
+/// How [`search_path_split`] splits a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Split {
+    /// The threads searching the ranges, one range each at a time
+    pub threads: Threads,
+    /// Files larger than this many bytes are split into ranges of about
+    /// this size
+    pub range_size: u64,
+}
+
+impl Split {
+    /// The range size of [`Split::new`]: 64 MiB, large enough that the
+    /// ranges take much longer to search than to hand out.
+    pub const DEFAULT_RANGE_SIZE: u64 = 64 * 1024 * 1024;
+
+    /// Splits files into ranges of [`Split::DEFAULT_RANGE_SIZE`], searched
+    /// on `threads`.
+    pub fn new(threads: Threads) -> Self {
+        Split {
+            threads,
+            range_size: Self::DEFAULT_RANGE_SIZE,
+        }
+    }
+}
+
+/// Shares `threads` between the files of `paths` and the ranges of each, so
+/// that no more of them search at once than `threads.count`, and each is
+/// pinned once: a single file is split into ranges of about `range_size`
+/// bytes searched on all of them, while several files, or a directory, are
+/// searched a file per thread and not split.
+///
+/// Returns the threads to search the files on, if they are searched in
+/// parallel, and the [`Split`] to search each of them with, if any.
+pub fn share_threads<'a>(
+    threads: &'a Threads,
+    range_size: u64,
+    paths: &[PathBuf],
+) -> (Option<&'a Threads>, Option<Split>) {
+    match paths {
+        [path] if path.is_file() => (
+            None,
+            Some(Split {
+                threads: threads.clone(),
+                range_size,
+            }),
+        ),
+        _ => (Some(threads), None),
+    }
+}
+
+/// Whether a search with `options` gives the same matches when the file is
+/// searched in ranges: every line must be judged on its own, without its
+/// line number or what came before it. Line ranges, line and match filters,
+/// scopes, deduplication, preprocessors, and byte windows all rule it out.
+pub fn can_split(options: &SearchOptions) -> bool {
+    options.line_ranges.is_empty()
+        && options.filters.is_empty()
+        && options.match_filters.is_empty()
+        && options.preprocessor.is_none()
+        && options.scope.is_none()
+        && options.dedupe.is_none()
+        && options.byte_window.is_none()
+}
+
+/// Searches the file at `path` as [`search_path`] does, but in ranges
+/// searched on several threads when it is larger than `split.range_size`
+/// and [`can_split`] allows it. The matches are the same either way.
+///
+/// With `options.timeout`, the time limit is for the whole file, as with
+/// [`search_path`]: every range gives up once it has passed.
+///
+/// # Errors
+///
+/// Returns the errors of [`search_path`]; if several ranges fail, the
+/// error of the first.
+pub fn search_path_split(
+    path: &Path,
+    matcher: &Matcher,
+    options: &SearchOptions,
+    split: &Split,
+) -> Result<Vec<SearchMatchBytes>> {
+    let range_size = split.range_size.max(1);
+    if split.threads.count.get() == 1 || !can_split(options) {
+        return search_path(path, matcher, options);
+    }
+    let metadata = fs::metadata(path).with_context(|| FileContext::read(path))?;
+    if !metadata.is_file() || metadata.len() <= range_size {
+        return search_path(path, matcher, options);
+    }
+    options.check_cancelled()?;
+    let deadline = options.deadline();
+    let terminator = options.line_terminator.byte();
+    let ranges = File::open(path)
+        .and_then(|mut file| line_ranges(&mut file, metadata.len(), range_size, terminator))
+        .with_context(|| FileContext::read(path))?;
+
+    let next = &AtomicUsize::new(0);
+    let failed = &AtomicBool::new(false);
+    let ranges = &ranges;
+    let mut results: Vec<(usize, Result<RangeMatches>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..split.threads.count.get().min(ranges.len()))
+            .map(|thread| {
+                scope.spawn(move || {
+                    split.threads.pin(thread);
+                    let mut results = Vec::new();
+                    while !failed.load(Ordering::Relaxed) {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(range) = ranges.get(index) else {
+                            break;
+                        };
+                        let result = search_range(path, range, matcher, options, deadline);
+                        failed.fetch_or(result.is_err(), Ordering::Relaxed);
+                        results.push((index, result));
+                    }
+                    results
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+    results.sort_by_key(|&(index, _)| index);
+
+    let mut matches = Vec::new();
+    let mut lines_before = 0;
+    for (_, result) in results {
+        let (range_matches, lines) = result.map_err(|error| file_error(path, error))?;
+        matches.extend(range_matches.into_iter().map(|mut search_match| {
+            search_match.line_number += lines_before;
+            search_match
+        }));
+        lines_before += lines;
+    }
+    Ok(matches)
+}
+
+/// The matches of a range, and the number of lines in it.
+type RangeMatches = (Vec<SearchMatchBytes>, usize);
+
+/// Searches `range` of the file at `path` until `deadline`, returning its
+/// matches, numbered from the start of the range, and the number of line
+/// terminators in it.
+fn search_range(
+    path: &Path,
+    range: &Range<u64>,
+    matcher: &Matcher,
+    options: &SearchOptions,
+    deadline: Option<Instant>,
+) -> Result<RangeMatches> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(range.start))?;
+    let mut reader = CountLines {
+        reader: file.take(range.end - range.start),
+        terminator: options.line_terminator.byte(),
+        lines: 0,
+    };
+    let matches = search_reader_until(&mut reader, matcher, options, deadline)?;
+    Ok((matches, reader.lines))
+}
+
+/// Cuts the `len` bytes of `file` into ranges of about `range_size` bytes,
+/// each but the last ending just after a `terminator`. A line longer than a
+/// range is kept whole, so there may be fewer ranges than asked for.
+fn line_ranges(
+    file: &mut File,
+    len: u64,
+    range_size: u64,
+    terminator: u8,
+) -> io::Result<Vec<Range<u64>>> {
+    let count = len.div_ceil(range_size);
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for i in 1..count {
+        let target = len / count * i;
+        if target <= start {
+            continue;
+        }
+        let end = next_line_start(file, target, terminator)?.unwrap_or(len);
+        if end >= len {
+            break;
+        }
+        ranges.push(start..end);
+        start = end;
+    }
+    ranges.push(start..len);
+    Ok(ranges)
+}
+
+/// Where the first line of `file` starting at or after `offset`, which must
+/// not be 0, starts, if one does.
+fn next_line_start(file: &mut File, offset: u64, terminator: u8) -> io::Result<Option<u64>> {
+    // A line starts at `offset` if the byte before it ends one
+    let mut position = offset - 1;
+    file.seek(SeekFrom::Start(position))?;
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if let Some(index) = memchr::memchr(terminator, &buffer[..read]) {
+            return Ok(Some(position + index as u64 + 1));
+        }
+        position += read as u64;
+    }
+}
+
+/// A reader counting the line terminators read through it.
+struct CountLines<R> {
+    reader: R,
+    terminator: u8,
+    lines: usize,
+}
+
+impl<R: Read> Read for CountLines<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buffer)?;
+        self.lines += memchr::memchr_iter(self.terminator, &buffer[..read]).count();
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_search_matches_whole_file_search() {
+        let path = std::env::temp_dir().join("searcher_split_search.log");
+        let mut text = String::new();
+        for i in 0..500 {
+            text.push_str(&format!("line {} {}\n", i, "x".repeat(i % 37)));
+        }
+        // A line longer than a range, and no terminator at the end
+        text.push_str(&format!("line long {}\nline 7 last", "y".repeat(400)));
+        fs::write(&path, &text).unwrap();
+
+        let ranges = line_ranges(
+            &mut File::open(&path).unwrap(),
+            text.len() as u64,
+            100,
+            b'\n',
+        )
+        .unwrap();
+        assert!(ranges.len() > 100);
+        assert_eq!(ranges.first().unwrap().start, 0);
+        assert_eq!(ranges.last().unwrap().end, text.len() as u64);
+        for pair in ranges.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+            assert_eq!(text.as_bytes()[pair[0].end as usize - 1], b'\n');
+        }
+
+        let matcher = Matcher::new("line 7", false, false).unwrap();
+        let split = Split {
+            threads: Threads::new(std::num::NonZeroUsize::new(4).unwrap()),
+            range_size: 100,
+        };
+        let options = SearchOptions::default();
+        let whole = search_path(&path, &matcher, &options).unwrap();
+        assert_eq!(whole.len(), 12);
+        assert_eq!(
+            search_path_split(&path, &matcher, &options, &split).unwrap(),
+            whole
+        );
+
+        let options = SearchOptions {
+            line_offset: 1000,
+            ..SearchOptions::default()
+        };
+        let split_matches = search_path_split(&path, &matcher, &options, &split).unwrap();
+        assert_eq!(
+            split_matches,
+            search_path(&path, &matcher, &options).unwrap()
+        );
+        assert_eq!(split_matches.last().unwrap().line_number, 1502);
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_ranges_share_one_deadline() {
+        let path = Path::new("tests/fixtures/sample.txt");
+        let len = fs::metadata(path).unwrap().len();
+        let matcher = Matcher::new("Rust", false, false).unwrap();
+        let options = SearchOptions {
+            timeout: Some(std::time::Duration::from_secs(60)),
+            ..SearchOptions::default()
+        };
+
+        // A range started after the deadline gives up, whatever is left of
+        // the time limit had it started the clock itself
+        let error =
+            search_range(path, &(0..len), &matcher, &options, Some(Instant::now())).unwrap_err();
+        assert!(error.is::<crate::TimedOut>());
+        assert!(search_range(path, &(0..len), &matcher, &options, options.deadline()).is_ok());
+    }
+
+    #[test]
+    fn test_share_threads_never_starts_more_than_its_count() {
+        let threads = Threads::new(std::num::NonZeroUsize::new(4).unwrap());
+        let file = PathBuf::from("tests/fixtures/sample.txt");
+        let dir = PathBuf::from("tests/fixtures");
+
+        // One file: split on all four threads, with none for the walk
+        let (walk, split) = share_threads(&threads, 100, std::slice::from_ref(&file));
+        assert_eq!(walk, None);
+        assert_eq!(split.unwrap().threads.count.get(), 4);
+
+        // Several files, or a directory: a file per thread, none split
+        for paths in [vec![file.clone(), file], vec![dir]] {
+            let (walk, split) = share_threads(&threads, 100, &paths);
+            assert_eq!(walk.unwrap().count.get(), 4);
+            assert_eq!(split, None);
+        }
+    }
+}
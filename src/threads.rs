@@ -0,0 +1,199 @@
+//! How many threads a parallel search uses, and which CPUs they run on.
+//!
+//! [`each_file_parallel`](crate::walk::each_file_parallel) and
+//! [`search_path_split`](crate::split::search_path_split) start
+//! [`Threads::count`] threads each. By default the operating system moves
+//! them between CPUs as it likes; on a machine dedicated to crunching logs,
+//! pinning each to a CPU of its own with [`Threads::pinned_to`] keeps its
+//! caches warm, and pinning them all to the CPUs of one NUMA node keeps the
+//! search next to its memory. Pinning needs Linux and the `affinity`
+//! feature.
+//!
+//! ```
+//! use searcher_cli_starter::threads::{Threads, parse_cpu_list};
+//! use std::num::NonZeroUsize;
+//!
+//! assert_eq!(parse_cpu_list("0-3,8").unwrap(), [0, 1, 2, 3, 8]);
+//!
+//! let threads = Threads::new(NonZeroUsize::new(4).unwrap());
+//! assert!(threads.cpus.is_empty());
+//! ```
+
+use anyhow::{Context, Result, bail};
+use std::io;
+use std::num::NonZeroUsize;
+use std::thread;
+
+/// The threads of a parallel search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Threads {
+    /// How many threads are started
+    pub count: NonZeroUsize,
+    /// The CPUs the threads are pinned to, one each in turn, so the `i`th
+    /// thread started runs on `cpus[i % cpus.len()]`; when empty, threads
+    /// are not pinned
+    pub cpus: Vec<usize>,
+}
+
+impl Threads {
+    /// `count` threads, not pinned.
+    pub fn new(count: NonZeroUsize) -> Self {
+        Threads {
+            count,
+            cpus: Vec::new(),
+        }
+    }
+
+    /// One thread per CPU this process may run on, not pinned.
+    pub fn available() -> Self {
+        Threads::new(thread::available_parallelism().unwrap_or(NonZeroUsize::MIN))
+    }
+
+    /// Pins the threads to `cpus`, one each in turn.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] where threads
+    /// cannot be pinned, and of kind [`io::ErrorKind::InvalidInput`] if
+    /// `cpus` is empty or names a CPU this process may not run on.
+    pub fn pinned_to(mut self, cpus: Vec<usize>) -> io::Result<Self> {
+        if cpus.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no CPUs to pin threads to",
+            ));
+        }
+        let allowed = affinity::allowed()?;
+        if let Some(cpu) = cpus.iter().find(|cpu| !allowed.contains(cpu)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("CPU {} is not available to this process", cpu),
+            ));
+        }
+        self.cpus = cpus;
+        Ok(self)
+    }
+
+    /// Pins the calling thread, the `index`th one started, to its CPU, if
+    /// the threads are pinned. A thread that cannot be pinned runs unpinned,
+    /// as pinning only changes how fast it runs.
+    pub(crate) fn pin(&self, index: usize) {
+        if !self.cpus.is_empty() {
+            affinity::pin(self.cpus[index % self.cpus.len()]);
+        }
+    }
+}
+
+impl Default for Threads {
+    fn default() -> Self {
+        Threads::available()
+    }
+}
+
+impl From<NonZeroUsize> for Threads {
+    fn from(count: NonZeroUsize) -> Self {
+        Threads::new(count)
+    }
+}
+
+#[cfg(all(feature = "affinity", any(target_os = "linux", target_os = "android")))]
+mod affinity {
+    use nix::sched::{CpuSet, sched_getaffinity, sched_setaffinity};
+    use nix::unistd::Pid;
+    use std::io;
+
+    /// The CPUs the calling thread may run on.
+    pub fn allowed() -> io::Result<Vec<usize>> {
+        let set = sched_getaffinity(Pid::from_raw(0))?;
+        Ok((0..CpuSet::count())
+            .filter(|&cpu| set.is_set(cpu).unwrap_or(false))
+            .collect())
+    }
+
+    /// Pins the calling thread to `cpu`.
+    pub fn pin(cpu: usize) {
+        let mut set = CpuSet::new();
+        if set.set(cpu).is_ok() {
+            sched_setaffinity(Pid::from_raw(0), &set).ok();
+        }
+    }
+}
+
+#[cfg(not(all(feature = "affinity", any(target_os = "linux", target_os = "android"))))]
+mod affinity {
+    use std::io;
+
+    pub fn allowed() -> io::Result<Vec<usize>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "pinning threads to CPUs needs Linux and the `affinity` feature",
+        ))
+    }
+
+    pub fn pin(_cpu: usize) {}
+}
+
+/// One more than the highest CPU number [`parse_cpu_list`] accepts.
+const MAX_CPUS: usize = 4096;
+
+/// Reads a list of CPUs written as for `taskset --cpu-list`: numbers and
+/// inclusive ranges separated by commas, e.g. `0-7,16-23`. CPUs are kept in
+/// the order written, once each.
+///
+/// # Errors
+///
+/// Returns an error if `text` is empty or has anything but such numbers
+/// and ranges.
+pub fn parse_cpu_list(text: &str) -> Result<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in text.split(',') {
+        let part = part.trim();
+        let number = |text: &str| {
+            text.trim()
+                .parse::<usize>()
+                .with_context(|| format!("`{}` is not a CPU number", text.trim()))
+        };
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (number(first)?, number(last)?),
+            None => (number(part)?, number(part)?),
+        };
+        if first > last {
+            bail!("`{}` is not a range of CPUs, as {} > {}", part, first, last);
+        }
+        if last >= MAX_CPUS {
+            bail!(
+                "CPU {} is past the last one supported, {}",
+                last,
+                MAX_CPUS - 1
+            );
+        }
+        for cpu in first..=last {
+            if !cpus.contains(&cpu) {
+                cpus.push(cpu);
+            }
+        }
+    }
+    Ok(cpus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_lists_are_parsed_like_taskset() {
+        assert_eq!(parse_cpu_list("4, 0-2,1").unwrap(), [4, 0, 1, 2]);
+        assert!(parse_cpu_list("").is_err());
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("0-x").is_err());
+
+        let threads = Threads::new(NonZeroUsize::MIN);
+        let error = threads.clone().pinned_to(Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        let error = threads.pinned_to(vec![usize::MAX]).unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            io::ErrorKind::InvalidInput | io::ErrorKind::Unsupported
+        ));
+    }
+}
@@ -0,0 +1,571 @@
+//! Interactive search in the terminal, for `searcher tui`.
+//!
+//! The screen has a pattern input at the top, the matching lines on the
+//! left, and a preview of the selected match in its file on the right. The
+//! results update as the pattern is typed.
+//!
+//! | Key | Action |
+//! |-----|--------|
+//! | typing, `Backspace` | Edit the pattern |
+//! | `Ctrl-U` | Clear the pattern |
+//! | `Up` / `Down`, `PageUp` / `PageDown` | Move the selection |
+//! | `Enter` | Open the selected line in `$VISUAL` / `$EDITOR` |
+//! | `Esc`, `Ctrl-C` | Quit |
+//!
+//! The file list is collected once at start-up; each change to the pattern
+//! searches those files again, stopping after [`MAX_HITS`] matching lines.
+//!
+//! Available with the `tui` cargo feature, which the CLI enables.
+
+use crate::query::{MatcherOptions, build_matcher};
+use crate::walk::Walk;
+use crate::{Matcher, SearchOptions, search_path};
+use anyhow::{Context, Result, bail};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{EnterAlternateScreen, enable_raw_mode};
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The most matching lines listed for one pattern.
+pub const MAX_HITS: usize = 10_000;
+
+/// How far `PageUp` / `PageDown` move the selection.
+const PAGE: usize = 10;
+
+/// A matching line listed in the results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    /// The file the line is in
+    pub path: PathBuf,
+    /// 1-based line number
+    pub line_number: usize,
+    /// The line, without its terminator
+    pub content: Vec<u8>,
+}
+
+/// What the caller should do after a key press.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Keep running
+    Continue,
+    /// Leave the UI
+    Quit,
+    /// Open `path` in an editor at `line_number`
+    Open {
+        /// The file to open
+        path: PathBuf,
+        /// 1-based line number
+        line_number: usize,
+    },
+}
+
+/// The state of the search UI.
+///
+/// Key presses go to [`App::handle_key`] and [`App::draw`] renders the
+/// current state, so the UI can be driven without a real terminal.
+pub struct App {
+    files: Vec<PathBuf>,
+    /// Paths that could not be walked
+    walk_errors: usize,
+    /// Paths that could not be walked or searched, reported in the status line
+    unreadable: usize,
+    matcher_options: MatcherOptions,
+    search_options: SearchOptions,
+    pattern: String,
+    matcher: Option<Matcher>,
+    hits: Vec<Hit>,
+    list: ListState,
+    /// An error to show instead of the result summary
+    message: Option<String>,
+    /// The file shown in the preview and its contents
+    preview: Option<(PathBuf, io::Result<Vec<u8>>)>,
+}
+
+impl App {
+    /// Creates the UI state for searching the files below `paths`.
+    pub fn new(
+        paths: &[PathBuf],
+        matcher_options: MatcherOptions,
+        search_options: SearchOptions,
+    ) -> Self {
+        let mut walk_errors = 0;
+        let files = Walk::new(paths)
+            .filter_map(|entry| entry.map_err(|_| walk_errors += 1).ok())
+            .collect();
+        App {
+            files,
+            walk_errors,
+            unreadable: walk_errors,
+            matcher_options,
+            search_options,
+            pattern: String::new(),
+            matcher: None,
+            hits: Vec::new(),
+            list: ListState::default(),
+            message: None,
+            preview: None,
+        }
+    }
+
+    /// The pattern typed so far.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// The matching lines of the current pattern.
+    pub fn hits(&self) -> &[Hit] {
+        &self.hits
+    }
+
+    /// The selected matching line, if any.
+    pub fn selected(&self) -> Option<&Hit> {
+        self.list.selected().and_then(|index| self.hits.get(index))
+    }
+
+    /// Replaces the pattern and searches again.
+    ///
+    /// An invalid pattern keeps the previous results and shows the error.
+    pub fn set_pattern(&mut self, pattern: &str) {
+        self.pattern = pattern.to_string();
+        self.message = None;
+        if pattern.is_empty() {
+            self.matcher = None;
+            self.hits.clear();
+            self.list.select(None);
+            return;
+        }
+        match build_matcher(pattern, &self.matcher_options) {
+            Ok(matcher) => {
+                self.search(&matcher);
+                self.matcher = Some(matcher);
+            }
+            Err(error) => self.message = Some(format!("{:#}", error)),
+        }
+    }
+
+    /// Shows `message` in the status line until the pattern changes.
+    pub fn set_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    fn search(&mut self, matcher: &Matcher) {
+        self.hits.clear();
+        self.unreadable = self.walk_errors;
+        for path in &self.files {
+            let matches = match search_path(path, matcher, &self.search_options) {
+                Ok(matches) => matches,
+                Err(_) => {
+                    self.unreadable += 1;
+                    continue;
+                }
+            };
+            self.hits.extend(matches.into_iter().map(|m| Hit {
+                path: path.clone(),
+                line_number: m.line_number,
+                content: m.content,
+            }));
+            if self.hits.len() >= MAX_HITS {
+                self.hits.truncate(MAX_HITS);
+                break;
+            }
+        }
+        self.list
+            .select(if self.hits.is_empty() { None } else { Some(0) });
+    }
+
+    /// Updates the state for a key press.
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('c') if ctrl => return Action::Quit,
+            KeyCode::Char('u') if ctrl => self.set_pattern(""),
+            KeyCode::Char(c) if !ctrl => {
+                let mut pattern = self.pattern.clone();
+                pattern.push(c);
+                self.set_pattern(&pattern);
+            }
+            KeyCode::Backspace => {
+                let mut pattern = self.pattern.clone();
+                if pattern.pop().is_some() {
+                    self.set_pattern(&pattern);
+                }
+            }
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Down => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-(PAGE as isize)),
+            KeyCode::PageDown => self.move_selection(PAGE as isize),
+            KeyCode::Enter => {
+                if let Some(hit) = self.selected() {
+                    return Action::Open {
+                        path: hit.path.clone(),
+                        line_number: hit.line_number,
+                    };
+                }
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    fn move_selection(&mut self, by: isize) {
+        if let Some(index) = self.list.selected() {
+            let last = self.hits.len().saturating_sub(1);
+            self.list
+                .select(Some(index.saturating_add_signed(by).min(last)));
+        }
+    }
+
+    /// Renders the UI into `frame`.
+    pub fn draw(&mut self, frame: &mut Frame) {
+        let [input_area, body, status_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [results_area, preview_area] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(body);
+
+        self.draw_input(frame, input_area);
+        self.draw_results(frame, results_area);
+        self.draw_preview(frame, preview_area);
+        frame.render_widget(Paragraph::new(self.status()), status_area);
+    }
+
+    fn draw_input(&self, frame: &mut Frame, area: Rect) {
+        let kind = if self.matcher_options.query {
+            "query"
+        } else if self.matcher_options.uses_regex() {
+            "regex"
+        } else {
+            "literal"
+        };
+        let input = Paragraph::new(self.pattern.as_str())
+            .block(Block::bordered().title(format!(" Pattern ({}) ", kind)));
+        frame.render_widget(input, area);
+        let typed = u16::try_from(self.pattern.chars().count()).unwrap_or(u16::MAX);
+        frame.set_cursor_position(Position::new(
+            area.x.saturating_add(1).saturating_add(typed),
+            area.y + 1,
+        ));
+    }
+
+    fn draw_results(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .hits
+            .iter()
+            .map(|hit| {
+                let mut spans = vec![
+                    Span::styled(hit.path.display().to_string(), Color::Magenta),
+                    Span::raw(":"),
+                    Span::styled(hit.line_number.to_string(), Color::Green),
+                    Span::raw(": "),
+                ];
+                spans.extend(highlight(self.matcher.as_ref(), &hit.content));
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(format!(" Results ({}) ", self.hits.len())))
+            .highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(list, area, &mut self.list);
+    }
+
+    fn draw_preview(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(hit) = self.selected().cloned() else {
+            frame.render_widget(Block::bordered().title(" Preview "), area);
+            return;
+        };
+        if self
+            .preview
+            .as_ref()
+            .is_none_or(|(path, _)| *path != hit.path)
+        {
+            self.preview = Some((hit.path.clone(), fs::read(&hit.path)));
+        }
+        let block = Block::bordered().title(format!(" {} ", hit.path.display()));
+        let contents = match &self.preview {
+            Some((_, Ok(contents))) => contents,
+            Some((_, Err(error))) => {
+                frame.render_widget(Paragraph::new(error.to_string()).block(block), area);
+                return;
+            }
+            None => return,
+        };
+
+        // Center the selected line in the visible part of the file
+        let height = usize::from(area.height.saturating_sub(2));
+        let first = hit.line_number.saturating_sub(height / 2).max(1);
+        let terminator = self.search_options.line_terminator.byte();
+        let lines: Vec<Line> = contents
+            .split(|&byte| byte == terminator)
+            .enumerate()
+            .skip(first - 1)
+            .take(height)
+            .map(|(index, line)| {
+                let line_number = index + 1;
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                let mut spans = vec![Span::styled(
+                    format!("{:>5} ", line_number),
+                    Color::DarkGray,
+                )];
+                spans.extend(highlight(self.matcher.as_ref(), line));
+                let line = Line::from(spans);
+                if line_number == hit.line_number {
+                    line.bold()
+                } else {
+                    line
+                }
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn status(&self) -> Line<'static> {
+        if let Some(message) = &self.message {
+            return Line::styled(message.clone(), Color::Red);
+        }
+        let files = self
+            .hits
+            .iter()
+            .map(|hit| &hit.path)
+            .collect::<std::collections::HashSet<_>>()
+            .len();
+        let mut status = format!(
+            "{}{} matching {} in {} {}",
+            if self.hits.len() == MAX_HITS {
+                "first "
+            } else {
+                ""
+            },
+            self.hits.len(),
+            if self.hits.len() == 1 {
+                "line"
+            } else {
+                "lines"
+            },
+            files,
+            if files == 1 { "file" } else { "files" },
+        );
+        if self.unreadable > 0 {
+            status.push_str(&format!(", {} unreadable", self.unreadable));
+        }
+        status.push_str(" | Enter: open in editor, Esc: quit");
+        Line::raw(status)
+    }
+}
+
+/// Splits `line` into spans with the matches of `matcher` highlighted.
+fn highlight(matcher: Option<&Matcher>, line: &[u8]) -> Vec<Span<'static>> {
+    let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).replace('\t', "    ");
+    let ranges = matcher.map_or_else(Vec::new, |matcher| matcher.match_ranges(line));
+    let mut spans = Vec::new();
+    let mut end = 0;
+    for range in ranges.into_iter().filter(|range| !range.is_empty()) {
+        spans.push(Span::raw(text(&line[end..range.start])));
+        spans.push(Span::styled(
+            text(&line[range.clone()]),
+            Style::new().fg(Color::Red).bold(),
+        ));
+        end = range.end;
+    }
+    spans.push(Span::raw(text(&line[end..])));
+    spans
+}
+
+/// Builds the command that opens `path` at `line_number` in `editor`.
+///
+/// `editor` may include arguments, as in `EDITOR="code --wait"`. The line
+/// is passed as `+N`, which vi, Vim, Neovim, Emacs, nano, and micro accept.
+pub fn editor_command(editor: &str, path: &Path, line_number: usize) -> Result<Command> {
+    let mut words = editor.split_whitespace();
+    let Some(program) = words.next() else {
+        bail!("No editor configured; set $VISUAL or $EDITOR");
+    };
+    let mut command = Command::new(program);
+    command
+        .args(words)
+        .arg(format!("+{}", line_number))
+        .arg(path);
+    Ok(command)
+}
+
+/// Runs the UI in the terminal until the user quits.
+///
+/// Opening a match suspends the UI while `$VISUAL` (or `$EDITOR`, or `vi`)
+/// runs and resumes it afterwards.
+///
+/// # Errors
+///
+/// Returns an error if the terminal cannot be set up or read from.
+pub fn run(app: &mut App) -> Result<()> {
+    let mut terminal = ratatui::try_init().context("Could not start the terminal UI")?;
+    let result = event_loop(&mut terminal, app);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> Result<()> {
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match app.handle_key(key) {
+            Action::Continue => {}
+            Action::Quit => return Ok(()),
+            Action::Open { path, line_number } => {
+                ratatui::try_restore()?;
+                let opened = open_in_editor(&path, line_number);
+                enable_raw_mode()?;
+                execute!(io::stdout(), EnterAlternateScreen)?;
+                terminal.clear()?;
+                if let Err(error) = opened {
+                    app.set_message(format!("{:#}", error));
+                }
+            }
+        }
+    }
+}
+
+fn open_in_editor(path: &Path, line_number: usize) -> Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+    let status = editor_command(&editor, path, line_number)?
+        .status()
+        .with_context(|| format!("Could not run editor `{}`", editor))?;
+    if !status.success() {
+        bail!("Editor `{}` exited with {}", editor, status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    fn app() -> App {
+        App::new(
+            &[PathBuf::from("tests/fixtures")],
+            MatcherOptions::default(),
+            SearchOptions::default(),
+        )
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+    }
+
+    #[test]
+    fn test_typing_updates_results() {
+        let mut app = app();
+        type_text(&mut app, "Rust");
+
+        assert_eq!(app.pattern(), "Rust");
+        let sample: Vec<usize> = app
+            .hits()
+            .iter()
+            .filter(|hit| hit.path.ends_with("sample.txt"))
+            .map(|hit| hit.line_number)
+            .collect();
+        assert_eq!(sample, [2, 3, 5]);
+
+        app.handle_key(KeyEvent::from(KeyCode::Backspace));
+        assert_eq!(app.pattern(), "Rus");
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL));
+        assert!(app.hits().is_empty());
+        assert!(app.selected().is_none());
+    }
+
+    #[test]
+    fn test_enter_opens_the_selected_line() {
+        let mut app = App::new(
+            &[PathBuf::from("tests/fixtures/sample.txt")],
+            MatcherOptions::default(),
+            SearchOptions::default(),
+        );
+        type_text(&mut app, "Rust");
+        app.handle_key(KeyEvent::from(KeyCode::Down));
+        app.handle_key(KeyEvent::from(KeyCode::PageDown));
+
+        assert_eq!(
+            app.handle_key(KeyEvent::from(KeyCode::Enter)),
+            Action::Open {
+                path: PathBuf::from("tests/fixtures/sample.txt"),
+                line_number: 5,
+            }
+        );
+        assert_eq!(app.handle_key(KeyEvent::from(KeyCode::Esc)), Action::Quit);
+    }
+
+    #[test]
+    fn test_invalid_regex_keeps_results() {
+        let mut app = App::new(
+            &[PathBuf::from("tests/fixtures/sample.txt")],
+            MatcherOptions {
+                regex: true,
+                ..MatcherOptions::default()
+            },
+            SearchOptions::default(),
+        );
+        type_text(&mut app, "Rust(");
+
+        assert_eq!(app.hits().len(), 3);
+        assert!(app.status().to_string().contains("Invalid regex pattern"));
+    }
+
+    #[test]
+    fn test_draw_shows_results_and_preview() {
+        let mut app = App::new(
+            &[PathBuf::from("tests/fixtures/sample.txt")],
+            MatcherOptions::default(),
+            SearchOptions::default(),
+        );
+        type_text(&mut app, "Hello");
+        let mut terminal = Terminal::new(TestBackend::new(100, 12)).unwrap();
+
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("Pattern (literal)"));
+        assert!(screen.contains("tests/fixtures/sample.txt:3: Hello world"));
+        assert!(screen.contains("    3 Hello world from Rust"));
+        assert!(screen.contains("1 matching line in 1 file"));
+    }
+
+    #[test]
+    fn test_editor_command() {
+        let command = editor_command("code --wait", Path::new("src/lib.rs"), 12).unwrap();
+
+        assert_eq!(command.get_program(), "code");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, ["--wait", "+12", "src/lib.rs"]);
+        assert!(editor_command(" ", Path::new("a"), 1).is_err());
+    }
+}
@@ -0,0 +1,455 @@
+//! The file systems a [`Walk`](crate::walk::Walk) can walk.
+//!
+//! A walk lists directories, reads metadata, and reads ignore files through
+//! a [`Vfs`], which is the real file system ([`StdFs`]) unless
+//! [`Walk::with_fs`](crate::walk::Walk::with_fs) gives it another. A tree
+//! held in memory ([`MemoryFs`]), or one file system laid over another
+//! ([`Overlay`]), can then be searched without writing it to disk, e.g. in
+//! tests or by programs that keep their documents in memory. [`VfsFile`]
+//! searches a file found this way as an
+//! [`InputSource`](crate::source::InputSource).
+//!
+//! ```
+//! use searcher_cli_starter::source::search_input;
+//! use searcher_cli_starter::vfs::{MemoryFs, VfsFile};
+//! use searcher_cli_starter::walk::Walk;
+//! use searcher_cli_starter::{Matcher, SearchOptions};
+//! use std::sync::Arc;
+//!
+//! let fs = Arc::new(
+//!     MemoryFs::new()
+//!         .with_file("logs/app.log", "ok\nerror: disk full\n")
+//!         .with_file("logs/old/app.log", "error: network\n"),
+//! );
+//! let matcher = Matcher::new("error", false, false).unwrap();
+//!
+//! let mut found = Vec::new();
+//! for path in Walk::new(["logs"]).with_fs(fs.clone()).flatten() {
+//!     let matches = search_input(&VfsFile::new(&*fs, &path), &matcher, &SearchOptions::default()).unwrap();
+//!     found.push((path, matches.len()));
+//! }
+//! assert_eq!(found, [("logs/app.log".into(), 1), ("logs/old/app.log".into(), 1)]);
+//! ```
+
+use crate::source::{InputSource, SourceMetadata};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+/// What kind of entry a path names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    /// A regular file
+    File,
+    /// A directory
+    Dir,
+    /// A symbolic link, as listed in its directory
+    Symlink,
+    /// Anything else, e.g. a socket or a device
+    Other,
+}
+
+/// An entry of a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The directory's path joined with the entry's name
+    pub path: PathBuf,
+    /// What the entry is, without following symbolic links
+    pub kind: FileKind,
+}
+
+/// What a walk needs to know about a file or directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    /// What it is, after following symbolic links
+    pub kind: FileKind,
+    /// Its length in bytes
+    pub len: u64,
+    /// When it was last modified, if known
+    pub modified: Option<SystemTime>,
+    /// The ID of the device holding it, telling file systems apart for
+    /// [`Walk::one_file_system`](crate::walk::Walk::one_file_system)
+    pub device: Option<u64>,
+}
+
+/// A file system to walk and search.
+///
+/// Paths are given as the walk found them, joined onto the paths it
+/// started from. Errors are [`io::Error`]s as from [`std::fs`]; a path
+/// that does not exist should fail with [`io::ErrorKind::NotFound`].
+pub trait Vfs {
+    /// The entries of the directory `dir`, in any order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `dir` cannot be listed.
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<Entry>>;
+
+    /// The metadata of `path`, following symbolic links.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `path` does not exist or cannot be looked up.
+    fn metadata(&self, path: &Path) -> io::Result<Metadata>;
+
+    /// Opens the file at `path` for reading.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>>;
+
+    /// The whole contents of the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or read.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.open(path)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Whether `path` exists.
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    /// The path `path` names without `.`, `..`, or symbolic links, for
+    /// telling whether two paths name the same directory. By default
+    /// `path` is taken to be canonical already.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `path` cannot be resolved.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+}
+
+/// The real file system, through [`std::fs`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StdFs;
+
+impl StdFs {
+    fn kind(file_type: fs::FileType) -> FileKind {
+        if file_type.is_dir() {
+            FileKind::Dir
+        } else if file_type.is_file() {
+            FileKind::File
+        } else if file_type.is_symlink() {
+            FileKind::Symlink
+        } else {
+            FileKind::Other
+        }
+    }
+}
+
+impl Vfs for StdFs {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            entries.push(Entry {
+                path: entry.path(),
+                kind: Self::kind(entry.file_type()?),
+            });
+        }
+        Ok(entries)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let metadata = fs::metadata(path)?;
+        Ok(Metadata {
+            kind: Self::kind(metadata.file_type()),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            device: device(&metadata),
+        })
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(fs::File::open(path)?))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+}
+
+/// The ID of the device holding a file, telling file systems apart.
+#[cfg(unix)]
+fn device(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// A tree of files held in memory.
+///
+/// Directories are made for the parents of every file added, and `.`
+/// components are ignored, so `./logs/a.log` and `logs/a.log` are the same
+/// file. An empty path, or `.`, is the top directory. Files have no
+/// modification time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryFs {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+    dirs: BTreeSet<PathBuf>,
+}
+
+impl MemoryFs {
+    /// An empty tree, holding only the top directory.
+    pub fn new() -> Self {
+        MemoryFs {
+            files: BTreeMap::new(),
+            dirs: BTreeSet::from([PathBuf::new()]),
+        }
+    }
+
+    /// Adds the file `path` holding `contents`, replacing any file there.
+    pub fn insert(&mut self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) {
+        let path = normalize(path.as_ref());
+        self.dirs
+            .extend(path.ancestors().skip(1).map(Path::to_path_buf));
+        self.files.insert(path, contents.into());
+    }
+
+    /// The tree with the file `path` added, as by [`MemoryFs::insert`].
+    pub fn with_file(mut self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) -> Self {
+        self.insert(path, contents);
+        self
+    }
+
+    /// The tree with the directory `path` and its parents added, e.g. to
+    /// have an empty directory.
+    pub fn with_dir(mut self, path: impl AsRef<Path>) -> Self {
+        let path = normalize(path.as_ref());
+        self.dirs.extend(path.ancestors().map(Path::to_path_buf));
+        self
+    }
+}
+
+/// `path` without its `.` components.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("`{}` does not exist", path.display()),
+    )
+}
+
+impl Vfs for MemoryFs {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<Entry>> {
+        let normalized = normalize(dir);
+        if !self.dirs.contains(&normalized) {
+            if self.files.contains_key(&normalized) {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotADirectory,
+                    format!("`{}` is not a directory", dir.display()),
+                ));
+            }
+            return Err(not_found(dir));
+        }
+        let is_child = |path: &Path| path != normalized && path.parent() == Some(&normalized);
+        let dirs = self.dirs.iter().filter(|path| is_child(path));
+        let files = self.files.keys().filter(|path| is_child(path));
+        let entry = |path: &PathBuf, kind| Entry {
+            path: dir.join(path.file_name().unwrap_or_default()),
+            kind,
+        };
+        Ok(dirs
+            .map(|path| entry(path, FileKind::Dir))
+            .chain(files.map(|path| entry(path, FileKind::File)))
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let normalized = normalize(path);
+        let (kind, len) = match self.files.get(&normalized) {
+            Some(contents) => (FileKind::File, contents.len() as u64),
+            None if self.dirs.contains(&normalized) => (FileKind::Dir, 0),
+            None => return Err(not_found(path)),
+        };
+        Ok(Metadata {
+            kind,
+            len,
+            modified: None,
+            device: None,
+        })
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        match self.files.get(&normalize(path)) {
+            Some(contents) => Ok(Box::new(contents.as_slice())),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(normalize(path))
+    }
+}
+
+/// One file system laid over another, like an overlay mount: paths are
+/// looked up in `upper` first and in `lower` if `upper` does not have them,
+/// and directories list the entries of both.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Overlay<U, L> {
+    /// The file system whose files win
+    pub upper: U,
+    /// The file system underneath
+    pub lower: L,
+}
+
+impl<U: Vfs, L: Vfs> Overlay<U, L> {
+    /// `upper` laid over `lower`.
+    pub fn new(upper: U, lower: L) -> Self {
+        Overlay { upper, lower }
+    }
+
+    /// The result of `upper`, or of `lower` if `upper` does not have the
+    /// path.
+    fn either<'a, T>(
+        &'a self,
+        upper: impl FnOnce(&'a U) -> io::Result<T>,
+        lower: impl FnOnce(&'a L) -> io::Result<T>,
+    ) -> io::Result<T> {
+        match upper(&self.upper) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => lower(&self.lower),
+            result => result,
+        }
+    }
+}
+
+impl<U: Vfs, L: Vfs> Vfs for Overlay<U, L> {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<Entry>> {
+        let upper = match self.upper.read_dir(dir) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return self.lower.read_dir(dir);
+            }
+            upper => upper?,
+        };
+        let lower = match self.lower.read_dir(dir) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Vec::new(),
+            lower => lower?,
+        };
+        let names: BTreeSet<PathBuf> = upper.iter().map(|entry| entry.path.clone()).collect();
+        let mut entries = upper;
+        entries.extend(
+            lower
+                .into_iter()
+                .filter(|entry| !names.contains(&entry.path)),
+        );
+        Ok(entries)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.either(|upper| upper.metadata(path), |lower| lower.metadata(path))
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        self.either(|upper| upper.open(path), |lower| lower.open(path))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.either(
+            |upper| upper.canonicalize(path),
+            |lower| lower.canonicalize(path),
+        )
+    }
+}
+
+/// A file of a [`Vfs`], to search with
+/// [`search_input`](crate::source::search_input).
+#[derive(Clone, Copy)]
+pub struct VfsFile<'a> {
+    fs: &'a dyn Vfs,
+    path: &'a Path,
+}
+
+impl<'a> VfsFile<'a> {
+    /// The file at `path` in `fs`.
+    pub fn new(fs: &'a dyn Vfs, path: &'a Path) -> Self {
+        VfsFile { fs, path }
+    }
+}
+
+impl InputSource for VfsFile<'_> {
+    fn name(&self) -> &Path {
+        self.path
+    }
+
+    fn open(&self) -> io::Result<Box<dyn Read + '_>> {
+        self.fs.open(self.path)
+    }
+
+    fn metadata(&self) -> io::Result<SourceMetadata> {
+        let metadata = self.fs.metadata(self.path)?;
+        Ok(SourceMetadata {
+            len: Some(metadata.len),
+            modified: metadata.modified,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlay_prefers_the_upper_layer() {
+        let lower = MemoryFs::new()
+            .with_file("a.txt", "lower a")
+            .with_file("sub/b.txt", "lower b");
+        let upper = MemoryFs::new()
+            .with_file("./a.txt", "upper a")
+            .with_dir("empty");
+        let overlay = Overlay::new(upper, lower);
+
+        let mut names: Vec<PathBuf> = overlay
+            .read_dir(Path::new("."))
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                Path::new("./a.txt"),
+                Path::new("./empty"),
+                Path::new("./sub")
+            ]
+        );
+        assert_eq!(overlay.read(Path::new("a.txt")).unwrap(), b"upper a");
+        assert_eq!(overlay.read(Path::new("sub/b.txt")).unwrap(), b"lower b");
+        assert_eq!(
+            overlay.metadata(Path::new("sub")).unwrap().kind,
+            FileKind::Dir
+        );
+
+        let error = overlay.read_dir(Path::new("a.txt")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotADirectory);
+        let error = overlay.open(Path::new("missing")).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,1105 @@
+//! Expanding command-line paths into the files to search.
+//!
+//! [`Walk`] yields every file named on the command line and, for
+//! directories, every file below them. Entries are visited in file name
+//! order so output is stable from run to run. Symbolic links to files are
+//! searched, but symbolic links to directories are not followed, which keeps
+//! link cycles from turning into endless walks. With
+//! [`Walk::one_file_system`], mount points below a directory are not
+//! entered either.
+//!
+//! With [`Walk::ignore_files`], what `.searcherignore` files, and inside
+//! git repositories `.gitignore` files, list is left out as well; see the
+//! [`ignore`](crate::ignore) module. Paths given to the walk are never
+//! ignored.
+//!
+//! The walk reads the real file system unless [`Walk::with_fs`] gives it
+//! another [`Vfs`], such as a tree held in memory; see the
+//! [`vfs`](crate::vfs) module.
+//!
+//! Paths longer than Windows' `MAX_PATH` are walked like any other, and so
+//! are roots given in the verbatim `\\?\C:\...` form; file names that
+//! are not valid UTF-8 are yielded unchanged.
+//!
+//! Errors are yielded alongside the files instead of ending the walk, so one
+//! unreadable directory does not hide the rest of the tree. With
+//! [`Walk::with_max_filesize`], files that are too large are skipped before
+//! they are opened, and yielded as errors too, so the caller can say what
+//! it left out. [`Walk::newer_than`] and [`Walk::older_than`] leave out
+//! files by modification time without a word, as they are not searched by
+//! design:
+//!
+//! ```no_run
+//! use searcher_cli_starter::walk::Walk;
+//!
+//! for entry in Walk::new(["logs"]) {
+//!     match entry {
+//!         Ok(path) => println!("{}", path.display()),
+//!         Err(error) => eprintln!("skipped: {}", error),
+//!     }
+//! }
+//! ```
+
+use crate::cancel::Cancelled;
+use crate::ignore::{IGNORE_FILE_NAME, IgnoreFile};
+use crate::threads::Threads;
+use crate::vfs::{FileKind, StdFs, Vfs};
+use anyhow::Result;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::io;
+use std::iter::Enumerate;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, mpsc};
+use std::thread::Scope;
+use std::time::SystemTime;
+#[cfg(feature = "timestamps")]
+use {anyhow::bail, std::time::Duration};
+
+/// A file or directory that could not be read.
+#[derive(Debug)]
+pub struct FileError {
+    /// The path that failed
+    pub path: PathBuf,
+    /// What went wrong
+    pub error: io::Error,
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+impl std::error::Error for FileError {}
+
+/// An iterator over the files below a list of paths.
+pub struct Walk {
+    /// The file system walked
+    fs: Arc<dyn Vfs + Send + Sync>,
+    /// Paths waiting to be visited, in order
+    pending: VecDeque<PathBuf>,
+    /// The directories being walked, innermost last
+    stack: Vec<DirEntries>,
+    /// Errors to yield before going on, e.g. for unreadable ignore files
+    errors: VecDeque<FileError>,
+    /// Files larger than this many bytes are skipped
+    max_filesize: Option<u64>,
+    /// Files last modified before this are skipped
+    newer_than: Option<SystemTime>,
+    /// Files last modified at or after this are skipped
+    older_than: Option<SystemTime>,
+    /// Whether directories on other file systems are skipped
+    one_file_system: bool,
+    /// The device of the command-line directory being walked, with
+    /// `one_file_system`
+    root_device: Option<u64>,
+    /// Directories left out, as given to [`Walk::skip_dir`]
+    skipped_dirs: Vec<PathBuf>,
+    /// Whether ignore files are read, with [`Walk::ignore_files`]
+    ignore_files: bool,
+    /// Ignore files applied below every command-line directory
+    global_ignores: Vec<IgnoreFile>,
+}
+
+/// The entries of a directory being walked.
+struct DirEntries {
+    /// The entries left to visit, with whether each is a directory
+    entries: VecDeque<(PathBuf, bool)>,
+    /// The ignore files read for the directory, outermost first; for a
+    /// command-line directory, also those of its parents and the global ones
+    ignores: Vec<IgnoreLevel>,
+    /// Whether the directory is inside a git repository, so that its
+    /// `.gitignore` counts
+    in_git: bool,
+}
+
+/// Ignore files that apply to the paths below a directory.
+struct IgnoreLevel {
+    /// The directory the paths are below, as walked
+    dir: PathBuf,
+    /// Where `dir` is below the directory holding the files, when they are
+    /// those of a parent of a command-line directory
+    prefix: PathBuf,
+    /// The files, the one taking precedence first
+    files: Vec<IgnoreFile>,
+}
+
+impl IgnoreLevel {
+    /// Whether the files ignore `path`, as [`IgnoreFile::matched`].
+    fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.dir).ok()?;
+        let relative = if self.prefix.as_os_str().is_empty() {
+            relative.to_path_buf()
+        } else {
+            self.prefix.join(relative)
+        };
+        self.files
+            .iter()
+            .find_map(|file| file.matched(&relative, is_dir))
+    }
+}
+
+impl Walk {
+    /// Creates a walk over `paths`, in the order given.
+    ///
+    /// Paths that are not directories are yielded as they are, even if they
+    /// do not exist, so that opening them reports the real error.
+    pub fn new<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Self {
+        Walk {
+            fs: Arc::new(StdFs),
+            pending: paths
+                .into_iter()
+                .map(|p| p.as_ref().to_path_buf())
+                .collect(),
+            stack: Vec::new(),
+            errors: VecDeque::new(),
+            max_filesize: None,
+            newer_than: None,
+            older_than: None,
+            one_file_system: false,
+            root_device: None,
+            skipped_dirs: Vec::new(),
+            ignore_files: false,
+            global_ignores: Vec::new(),
+        }
+    }
+
+    /// Walks the file system `fs` instead of the real one, e.g. a
+    /// [`MemoryFs`](crate::vfs::MemoryFs) in tests. Ignore files are read
+    /// from it too; the files found are searched with
+    /// [`VfsFile`](crate::vfs::VfsFile).
+    pub fn with_fs(mut self, fs: Arc<dyn Vfs + Send + Sync>) -> Self {
+        self.fs = fs;
+        self
+    }
+
+    /// Skips files larger than `limit` bytes, e.g. core dumps or datasets
+    /// in a tree of sources, yielding a [`FileError`] of kind
+    /// [`io::ErrorKind::FileTooLarge`] for each instead. Only the file's
+    /// metadata is read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use searcher_cli_starter::walk::Walk;
+    /// use std::io::ErrorKind;
+    ///
+    /// for entry in Walk::new(["build"]).with_max_filesize(50 * 1024 * 1024) {
+    ///     match entry {
+    ///         Ok(path) => println!("{}", path.display()),
+    ///         Err(error) if error.error.kind() == ErrorKind::FileTooLarge => {}
+    ///         Err(error) => eprintln!("skipped: {}", error),
+    ///     }
+    /// }
+    /// ```
+    pub fn with_max_filesize(mut self, limit: u64) -> Self {
+        self.max_filesize = Some(limit);
+        self
+    }
+
+    /// Skips files last modified before `time`, e.g. to search only the
+    /// logs written in the past two days. Files whose modification time
+    /// cannot be read are kept.
+    pub fn newer_than(mut self, time: SystemTime) -> Self {
+        self.newer_than = Some(time);
+        self
+    }
+
+    /// Skips files last modified at or after `time`, written like for
+    /// [`Walk::newer_than`].
+    pub fn older_than(mut self, time: SystemTime) -> Self {
+        self.older_than = Some(time);
+        self
+    }
+
+    /// Doesn't descend into directories on a different file system than the
+    /// command-line path they are below, e.g. network mounts or `/proc`
+    /// when walking `/`. File systems are told apart by device ID, which is
+    /// only available on Unix; elsewhere this does nothing.
+    pub fn one_file_system(mut self) -> Self {
+        self.one_file_system = true;
+        self
+    }
+
+    /// Leaves out the directory `dir` and everything below it, e.g. a cache
+    /// kept inside the tree being searched. Directories are compared by
+    /// their canonical path, so `dir` may be written differently from the
+    /// paths being walked, and may not exist yet when the walk starts.
+    pub fn skip_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.skipped_dirs.push(dir.into());
+        self
+    }
+
+    /// Leaves out what the `.searcherignore` files in the walked directories
+    /// and their parents list, and inside git repositories what their
+    /// `.gitignore` files list. An ignore file that cannot be read or
+    /// parsed is yielded as a [`FileError`], and the walk goes on without
+    /// it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use searcher_cli_starter::walk::Walk;
+    ///
+    /// // With `*.gz` in `/var/log/archive/.searcherignore`
+    /// for path in Walk::new(["/var/log/archive"]).ignore_files().flatten() {
+    ///     assert!(path.extension().is_none_or(|extension| extension != "gz"));
+    /// }
+    /// ```
+    pub fn ignore_files(mut self) -> Self {
+        self.ignore_files = true;
+        self
+    }
+
+    /// Also leaves out what `ignore` lists, with its patterns relative to
+    /// each command-line directory, and below the ignore files read there.
+    pub fn with_global_ignore(mut self, ignore: IgnoreFile) -> Self {
+        self.global_ignores.push(ignore);
+        self
+    }
+
+    /// The ignore files that apply below the command-line directory `dir`
+    /// before its own: the global ones, then those of its parents, and
+    /// whether it is inside a git repository.
+    fn root_ignores(&mut self, dir: &Path) -> (Vec<IgnoreLevel>, bool) {
+        let mut levels = Vec::new();
+        if !self.global_ignores.is_empty() {
+            levels.push(IgnoreLevel {
+                dir: dir.to_path_buf(),
+                prefix: PathBuf::new(),
+                files: self.global_ignores.clone(),
+            });
+        }
+        if !self.ignore_files {
+            return (levels, false);
+        }
+        let Ok(absolute) = std::path::absolute(dir) else {
+            return (levels, false);
+        };
+        let git_top = absolute
+            .ancestors()
+            .find(|parent| self.fs.exists(&parent.join(".git")));
+        let parents: Vec<&Path> = absolute.ancestors().skip(1).collect();
+        for parent in parents.into_iter().rev() {
+            let in_git = git_top.is_some_and(|top| parent.starts_with(top));
+            let files = self.read_ignores(parent, in_git);
+            if !files.is_empty() {
+                levels.push(IgnoreLevel {
+                    dir: dir.to_path_buf(),
+                    prefix: absolute
+                        .strip_prefix(parent)
+                        .unwrap_or(&absolute)
+                        .to_path_buf(),
+                    files,
+                });
+            }
+        }
+        (levels, git_top.is_some())
+    }
+
+    /// Reads the ignore files of `dir`, queueing errors for those that
+    /// cannot be read.
+    fn read_ignores(&mut self, dir: &Path, in_git: bool) -> Vec<IgnoreFile> {
+        let names = if in_git {
+            &[IGNORE_FILE_NAME, ".gitignore"][..]
+        } else {
+            &[IGNORE_FILE_NAME]
+        };
+        let mut files = Vec::new();
+        for name in names {
+            let path = dir.join(name);
+            match IgnoreFile::read_in(&*self.fs, &path) {
+                Ok(file) => files.extend(file),
+                Err(error) => self.errors.push_back(FileError { path, error }),
+            }
+        }
+        files
+    }
+
+    /// Whether the ignore files in `levels`, or those of the directories
+    /// being walked, leave out `path`.
+    fn is_ignored(&self, path: &Path, is_dir: bool, levels: &[IgnoreLevel]) -> bool {
+        let outer = self
+            .stack
+            .iter()
+            .rev()
+            .flat_map(|dir| dir.ignores.iter().rev());
+        levels
+            .iter()
+            .rev()
+            .chain(outer)
+            .find_map(|level| level.matched(path, is_dir))
+            .unwrap_or(false)
+    }
+
+    /// Whether `dir` is one of the directories given to [`Walk::skip_dir`].
+    fn is_skipped(&self, dir: &Path) -> bool {
+        self.skipped_dirs.iter().any(|skipped| {
+            // Only resolve the paths of directories that could be it
+            skipped.file_name() == dir.file_name()
+                && self
+                    .fs
+                    .canonicalize(skipped)
+                    .is_ok_and(|skipped| self.fs.canonicalize(dir).is_ok_and(|dir| dir == skipped))
+        })
+    }
+
+    /// Whether files are filtered on their metadata.
+    fn checks_metadata(&self) -> bool {
+        self.max_filesize.is_some() || self.newer_than.is_some() || self.older_than.is_some()
+    }
+
+    /// Lists the entries of `dir` that should be visited, sorted by name.
+    /// With a `root_device`, directories on other devices are left out.
+    fn read_dir(&self, dir: &Path) -> io::Result<VecDeque<(PathBuf, bool)>> {
+        let mut entries = Vec::new();
+        for entry in self.fs.read_dir(dir)? {
+            let path = entry.path;
+            match entry.kind {
+                FileKind::Dir => {
+                    if self.root_device.is_some()
+                        && self.fs.metadata(&path).ok().and_then(|m| m.device) != self.root_device
+                    {
+                        continue;
+                    }
+                    entries.push((path, true));
+                }
+                // Follow links to files only; a link to a directory could loop
+                FileKind::Symlink => {
+                    if self
+                        .fs
+                        .metadata(&path)
+                        .is_ok_and(|m| m.kind == FileKind::File)
+                    {
+                        entries.push((path, false));
+                    }
+                }
+                FileKind::File => entries.push((path, false)),
+                FileKind::Other => {}
+            }
+        }
+        entries.sort();
+        Ok(entries.into())
+    }
+
+    /// Visits `path`: directories are queued for expansion, files returned.
+    /// `root` tells command-line paths apart from the ones found below them.
+    fn visit(
+        &mut self,
+        path: PathBuf,
+        is_dir: bool,
+        root: bool,
+    ) -> Option<Result<PathBuf, FileError>> {
+        if !is_dir {
+            if self.checks_metadata()
+                && let Ok(metadata) = self.fs.metadata(&path)
+            {
+                if let Some(limit) = self.max_filesize
+                    && metadata.len > limit
+                {
+                    let error = io::Error::new(
+                        io::ErrorKind::FileTooLarge,
+                        format!("larger than {} bytes", limit),
+                    );
+                    return Some(Err(FileError { path, error }));
+                }
+                if let Some(modified) = metadata.modified
+                    && (self.newer_than.is_some_and(|time| modified < time)
+                        || self.older_than.is_some_and(|time| modified >= time))
+                {
+                    return None;
+                }
+            }
+            return Some(Ok(path));
+        }
+        if self.is_skipped(&path) {
+            return None;
+        }
+        match self.read_dir(&path) {
+            Ok(mut entries) => {
+                let (mut ignores, in_git) = if root {
+                    self.root_ignores(&path)
+                } else {
+                    let in_git = self.stack.last().is_some_and(|parent| parent.in_git)
+                        || (self.ignore_files && self.fs.exists(&path.join(".git")));
+                    (Vec::new(), in_git)
+                };
+                if self.ignore_files {
+                    let files = self.read_ignores(&path, in_git);
+                    if !files.is_empty() {
+                        ignores.push(IgnoreLevel {
+                            dir: path,
+                            prefix: PathBuf::new(),
+                            files,
+                        });
+                    }
+                }
+                if !ignores.is_empty() || self.stack.iter().any(|dir| !dir.ignores.is_empty()) {
+                    entries.retain(|(entry, is_dir)| !self.is_ignored(entry, *is_dir, &ignores));
+                }
+                self.stack.push(DirEntries {
+                    entries,
+                    ignores,
+                    in_git,
+                });
+                None
+            }
+            Err(error) => Some(Err(FileError { path, error })),
+        }
+    }
+}
+
+impl Iterator for Walk {
+    type Item = Result<PathBuf, FileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(error) = self.errors.pop_front() {
+                return Some(Err(error));
+            }
+            if let Some(dir) = self.stack.last_mut() {
+                match dir.entries.pop_front() {
+                    Some((path, is_dir)) => {
+                        if let Some(item) = self.visit(path, is_dir, false) {
+                            return Some(item);
+                        }
+                    }
+                    None => {
+                        self.stack.pop();
+                    }
+                }
+                continue;
+            }
+
+            // Command-line arguments are followed even when they are symlinks
+            let path = self.pending.pop_front()?;
+            let metadata = self.fs.metadata(&path).ok();
+            let is_dir = metadata.is_some_and(|m| m.kind == FileKind::Dir);
+            if self.one_file_system && is_dir {
+                self.root_device = metadata.and_then(|m| m.device);
+            }
+            if let Some(item) = self.visit(path, is_dir, true) {
+                return Some(item);
+            }
+        }
+    }
+}
+
+/// Calls `read` on every file below `paths`, yielding the files it succeeded on.
+///
+/// Directories that cannot be listed and files for which `read` fails are
+/// passed to `on_error` and skipped, so one bad file does not end the whole
+/// search. If `read` fails with [`Cancelled`], the walk stops instead.
+///
+/// # Examples
+///
+/// ```no_run
+/// use searcher_cli_starter::walk::each_file;
+/// use searcher_cli_starter::{Matcher, SearchOptions, search_path};
+///
+/// let matcher = Matcher::new("TODO", false, false).unwrap();
+/// let options = SearchOptions::default();
+/// let searches = each_file(
+///     &["src".into()],
+///     |path| search_path(path, &matcher, &options),
+///     |error| eprintln!("skipped: {:#}", error),
+/// );
+/// for (path, matches) in searches {
+///     println!("{}: {}", path.display(), matches.len());
+/// }
+/// ```
+pub fn each_file<'a, T>(
+    paths: &[PathBuf],
+    read: impl FnMut(&Path) -> Result<T> + 'a,
+    on_error: impl FnMut(anyhow::Error) + 'a,
+) -> impl Iterator<Item = (PathBuf, T)> + 'a {
+    each_file_in(Walk::new(paths), read, on_error)
+}
+
+/// Like [`each_file`], but over the files of a [`Walk`] set up by the
+/// caller, e.g. with [`Walk::with_max_filesize`].
+pub fn each_file_in<'a, T>(
+    walk: Walk,
+    mut read: impl FnMut(&Path) -> Result<T> + 'a,
+    mut on_error: impl FnMut(anyhow::Error) + 'a,
+) -> impl Iterator<Item = (PathBuf, T)> + 'a {
+    walk.map_while(move |entry| {
+        let result = entry
+            .map_err(anyhow::Error::from)
+            .and_then(|path| read(&path).map(|value| (path, value)));
+        match result {
+            Ok(item) => Some(Some(item)),
+            Err(error) if error.is::<Cancelled>() => None,
+            Err(error) => {
+                on_error(error);
+                Some(None)
+            }
+        }
+    })
+    .flatten()
+}
+
+/// Like [`each_file_in`], but calls `read` on several files at once, on the
+/// `threads` spawned in `scope`. The files are still yielded in the order
+/// the walk found them, and errors passed to `on_error` in that order too,
+/// so the output built from them is the same from run to run and the same
+/// as without threads.
+///
+/// Each thread takes [`BATCH`] files from the walk at a time and sends their
+/// results back together, so threads searching many small files do not
+/// contend for the walk and the results for every one. A file's result is
+/// held back until those of the files found before it are yielded, so one
+/// slow file holds back, in memory, the results of those searched
+/// meanwhile. If `read` fails with [`Cancelled`], or the iterator is
+/// dropped, no more files are read.
+///
+/// # Examples
+///
+/// ```no_run
+/// use searcher_cli_starter::threads::Threads;
+/// use searcher_cli_starter::walk::{Walk, each_file_parallel};
+/// use searcher_cli_starter::{Matcher, SearchOptions, search_path};
+/// use std::thread;
+///
+/// let matcher = Matcher::new("TODO", false, false).unwrap();
+/// let options = SearchOptions::default();
+/// thread::scope(|scope| {
+///     let searches = each_file_parallel(
+///         scope,
+///         Walk::new(["src"]),
+///         &Threads::available(),
+///         |path| search_path(path, &matcher, &options),
+///         |error| eprintln!("skipped: {:#}", error),
+///     );
+///     for (path, matches) in searches {
+///         println!("{}: {}", path.display(), matches.len());
+///     }
+/// });
+/// ```
+pub fn each_file_parallel<'scope, T, R, E>(
+    scope: &'scope Scope<'scope, '_>,
+    walk: Walk,
+    threads: &Threads,
+    read: R,
+    on_error: E,
+) -> impl Iterator<Item = (PathBuf, T)> + use<T, R, E>
+where
+    T: Send + 'scope,
+    R: Fn(&Path) -> Result<T> + Send + Sync + 'scope,
+    E: FnMut(anyhow::Error),
+{
+    // The walk numbers the files as they are taken, which is the order
+    // their results are yielded in
+    let walk = Arc::new(Mutex::new(walk.enumerate()));
+    let read = Arc::new(read);
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, results) = mpsc::channel();
+    for thread in 0..threads.count.get() {
+        let walk: Arc<Mutex<Enumerate<Walk>>> = Arc::clone(&walk);
+        let read = Arc::clone(&read);
+        let stop = Arc::clone(&stop);
+        let sender = sender.clone();
+        let threads = threads.clone();
+        scope.spawn(move || {
+            threads.pin(thread);
+            while !stop.load(Ordering::Relaxed) {
+                let batch: Vec<_> = walk
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .by_ref()
+                    .take(BATCH)
+                    .collect();
+                if batch.is_empty() {
+                    break;
+                }
+                let mut results = Vec::with_capacity(batch.len());
+                for (index, entry) in batch {
+                    let result = entry
+                        .map_err(anyhow::Error::from)
+                        .and_then(|path| read(&path).map(|value| (path, value)));
+                    let cancelled = result.as_ref().is_err_and(|error| error.is::<Cancelled>());
+                    results.push((index, result));
+                    if cancelled {
+                        // The files after it are not yielded, so not read
+                        stop.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                }
+                if sender.send(results).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    InOrder {
+        results: Some(results),
+        held: BTreeMap::new(),
+        next: 0,
+        stop,
+        on_error,
+    }
+}
+
+/// How many files a thread of [`each_file_parallel`] takes from the walk
+/// at a time.
+pub const BATCH: usize = 8;
+
+/// A file, or the error in its place, as [`each_file_in`] reads it.
+type FileResult<T> = Result<(PathBuf, T)>;
+
+/// The results of a batch of files, each with its number in the walk.
+type Batch<T> = Vec<(usize, FileResult<T>)>;
+
+/// The results of [`each_file_parallel`], put back in the walk's order.
+struct InOrder<T, E> {
+    /// The numbered results from the threads, until the walk is cancelled
+    results: Option<mpsc::Receiver<Batch<T>>>,
+    /// Results that came in before those of files found earlier
+    held: BTreeMap<usize, FileResult<T>>,
+    /// The number of the file to yield next
+    next: usize,
+    /// Tells the threads to stop taking files
+    stop: Arc<AtomicBool>,
+    on_error: E,
+}
+
+impl<T, E: FnMut(anyhow::Error)> Iterator for InOrder<T, E> {
+    type Item = (PathBuf, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(result) = self.held.remove(&self.next) else {
+                let batch = self.results.as_ref()?.recv().ok()?;
+                self.held.extend(batch);
+                continue;
+            };
+            self.next += 1;
+            match result {
+                Ok(item) => return Some(item),
+                Err(error) if error.is::<Cancelled>() => {
+                    // Files found after it are not yielded, as by `each_file_in`
+                    self.stop.store(true, Ordering::Relaxed);
+                    self.results = None;
+                    self.held.clear();
+                    return None;
+                }
+                Err(error) => (self.on_error)(error),
+            }
+        }
+    }
+}
+
+impl<T, E> Drop for InOrder<T, E> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Reads a `--newer-than` / `--older-than` bound: an age before `now` such
+/// as `30m`, `12h`, `2d`, or `1w` (units `s`, `m`, `h`, `d`, and `w`), or a
+/// UTC time written as for [`TimeRange::since`], e.g. `2024-01-01`.
+///
+/// # Errors
+///
+/// Returns an error if `text` is neither.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::walk::parse_file_time;
+/// use std::time::{Duration, SystemTime, UNIX_EPOCH};
+///
+/// let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+/// assert_eq!(parse_file_time("2d", now).unwrap(), now - Duration::from_secs(2 * 86400));
+/// assert_eq!(parse_file_time("1970-01-02", now).unwrap(), UNIX_EPOCH + Duration::from_secs(86400));
+/// assert!(parse_file_time("2 days", now).is_err());
+/// ```
+///
+/// [`TimeRange::since`]: crate::filter::TimeRange::since
+#[cfg(feature = "timestamps")]
+pub fn parse_file_time(text: &str, now: SystemTime) -> Result<SystemTime> {
+    let text = text.trim();
+    if let Some((i, unit)) = text.char_indices().last()
+        && let Ok(count) = text[..i].parse::<u64>()
+    {
+        let seconds = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => bail!("`{}` has an unknown unit (use s, m, h, d, or w)", text),
+        };
+        let age = count
+            .checked_mul(seconds)
+            .map(Duration::from_secs)
+            .and_then(|age| now.checked_sub(age));
+        return match age {
+            Some(time) => Ok(time),
+            None => bail!("`{}` is too long ago", text),
+        };
+    }
+    match crate::filter::parse_bound(text) {
+        Ok(time) => Ok(time.assume_utc().into()),
+        Err(_) => bail!(
+            "`{}` is not an age such as `2d` or a date such as `2024-01-01`",
+            text
+        ),
+    }
+}
+
+/// Whether `paths` can produce more than one file, so results should be
+/// labelled with the file they came from.
+pub fn spans_multiple_files(paths: &[PathBuf]) -> bool {
+    paths.len() > 1 || paths.iter().any(|path| path.is_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemoryFs;
+    use std::fs;
+
+    fn fixture(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        fs::remove_dir_all(&root).ok();
+        fs::create_dir_all(root.join("b/nested")).unwrap();
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::write(root.join("b/nested/deep.txt"), "").unwrap();
+        fs::write(root.join("b/two.txt"), "").unwrap();
+        fs::write(root.join("a/one.txt"), "").unwrap();
+        fs::write(root.join("top.txt"), "").unwrap();
+        root
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_walk_reaches_past_max_path() {
+        let root =
+            std::env::temp_dir().join(format!("searcher_walk_long_paths_{}", std::process::id()));
+        let mut deep = root.clone();
+        while deep.as_os_str().len() < 300 {
+            deep.push("a_rather_long_directory_name");
+        }
+        fs::create_dir_all(&deep).unwrap();
+        fs::write(deep.join("deep.txt"), "").unwrap();
+
+        let found: Vec<PathBuf> = Walk::new([&root]).map(Result::unwrap).collect();
+        assert_eq!(found, [deep.join("deep.txt")]);
+
+        // Paths given in the `\\?\` form are walked too
+        let verbatim = PathBuf::from(format!(
+            r"\\?\{}",
+            std::path::absolute(&root).unwrap().display()
+        ));
+        let found: Vec<PathBuf> = Walk::new([&verbatim]).map(Result::unwrap).collect();
+        assert_eq!(found.len(), 1);
+        assert!(found[0].starts_with(&verbatim));
+
+        fs::remove_dir_all(root).ok();
+    }
+
+    fn relative(root: &Path, walk: Walk) -> Vec<String> {
+        walk.map(|entry| {
+            let path = entry.unwrap();
+            path.strip_prefix(root)
+                .unwrap_or(&path)
+                .display()
+                .to_string()
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_walk_sorted_depth_first() {
+        let root = fixture("searcher_walk_sorted");
+        let files = relative(&root, Walk::new([&root]));
+
+        assert_eq!(
+            files,
+            ["a/one.txt", "b/nested/deep.txt", "b/two.txt", "top.txt"]
+        );
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_walk_keeps_argument_order() {
+        let root = fixture("searcher_walk_order");
+        let files = relative(&root, Walk::new([root.join("top.txt"), root.join("a")]));
+
+        assert_eq!(files, ["top.txt", "a/one.txt"]);
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_walk_leaves_out_skipped_dirs() {
+        let root = fixture("searcher_walk_skip_dir");
+        // Written differently from the walked path, and the name of a file too
+        let walk = Walk::new([&root])
+            .skip_dir(root.join("a/../b/nested"))
+            .skip_dir(root.join("top.txt"));
+
+        assert_eq!(relative(&root, walk), ["a/one.txt", "b/two.txt", "top.txt"]);
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_walk_reads_ignore_files() {
+        let root = fixture("searcher_walk_ignore_files");
+        fs::write(root.join(".searcherignore"), "*.txt\n!one.txt\n").unwrap();
+        fs::write(root.join("b/.searcherignore"), "!two.txt\n").unwrap();
+        fs::write(root.join(".gitignore"), "a/\n").unwrap();
+
+        let files = relative(&root, Walk::new([&root]).ignore_files());
+        assert_eq!(
+            files,
+            [
+                ".gitignore",
+                ".searcherignore",
+                "a/one.txt",
+                "b/.searcherignore",
+                "b/two.txt"
+            ]
+        );
+
+        // A parent's ignore file applies below it, and `.gitignore` only in a repository
+        fs::create_dir(root.join(".git")).unwrap();
+        let files = relative(&root, Walk::new([&root, &root.join("b")]).ignore_files());
+        assert_eq!(
+            files,
+            [
+                ".gitignore",
+                ".searcherignore",
+                "b/.searcherignore",
+                "b/two.txt",
+                "b/.searcherignore",
+                "b/two.txt"
+            ]
+        );
+
+        let global = IgnoreFile::parse("one.txt").unwrap();
+        let files = relative(
+            &root,
+            Walk::new([root.join("a")]).with_global_ignore(global),
+        );
+        assert!(files.is_empty());
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_walk_yields_invalid_ignore_files() {
+        let root = fixture("searcher_walk_invalid_ignore");
+        fs::write(root.join("a/.searcherignore"), "[z-a\n").unwrap();
+
+        let entries: Vec<_> = Walk::new([root.join("a")]).ignore_files().collect();
+        assert_eq!(entries.len(), 3);
+        let error = entries[0].as_ref().unwrap_err();
+        assert_eq!(error.path, root.join("a/.searcherignore"));
+        assert_eq!(error.error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(entries[2].as_ref().unwrap(), &root.join("a/one.txt"));
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_each_file_skips_failures() {
+        let root = fixture("searcher_walk_each_file");
+        let mut errors = Vec::new();
+        let sizes: Vec<_> = each_file(
+            &[root.join("missing.txt"), root.join("a")],
+            |path| Ok(fs::metadata(path)?.len()),
+            |error| errors.push(error.to_string()),
+        )
+        .collect();
+
+        assert_eq!(sizes, [(root.join("a/one.txt"), 0)]);
+        assert_eq!(errors.len(), 1);
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_each_file_stops_when_cancelled() {
+        let root = fixture("searcher_walk_cancelled");
+        let mut visited = 0;
+        let found: Vec<_> = each_file(
+            std::slice::from_ref(&root),
+            |_| {
+                visited += 1;
+                if visited == 2 {
+                    Err(Cancelled.into())
+                } else {
+                    Ok(())
+                }
+            },
+            |error| panic!("unexpected error: {}", error),
+        )
+        .collect();
+
+        assert_eq!(found, [(root.join("a/one.txt"), ())]);
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_each_file_parallel_keeps_the_walk_order() {
+        let fs = Arc::new((0..20).fold(MemoryFs::new(), |fs, i| {
+            fs.with_file(format!("logs/{:02}.log", i), vec![b'x'; 20 - i])
+        }));
+        let walk = || Walk::new(["logs", "logs/missing.log"]).with_fs(fs.clone());
+        let read = |path: &Path| {
+            let data = fs.read(path)?;
+            if data.len() == 5 {
+                return Err(anyhow::anyhow!("unlucky"));
+            }
+            // The files found first take the longest
+            std::thread::sleep(std::time::Duration::from_millis(data.len() as u64));
+            Ok(data.len())
+        };
+        let workers = Threads::new(std::num::NonZeroUsize::new(4).unwrap());
+
+        let mut errors = Vec::new();
+        let serial: Vec<_> =
+            each_file_in(walk(), read, |error| errors.push(error.to_string())).collect();
+        let mut parallel_errors = Vec::new();
+        let parallel: Vec<_> = std::thread::scope(|scope| {
+            each_file_parallel(scope, walk(), &workers, read, |error| {
+                parallel_errors.push(error.to_string())
+            })
+            .collect()
+        });
+        assert_eq!(serial.len(), 19);
+        assert_eq!(parallel, serial);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(parallel_errors, errors);
+
+        // Files found after a cancelled one are not yielded
+        let found: Vec<_> = std::thread::scope(|scope| {
+            let read = |path: &Path| match fs.read(path)?.len() {
+                18 => Err(Cancelled.into()),
+                len => Ok(len),
+            };
+            each_file_parallel(scope, walk(), &workers, read, |error| panic!("{}", error)).collect()
+        });
+        assert_eq!(
+            found,
+            [("logs/00.log".into(), 20), ("logs/01.log".into(), 19)]
+        );
+    }
+
+    #[test]
+    fn test_walk_skips_files_over_max_filesize() {
+        let root = fixture("searcher_walk_max_filesize");
+        fs::write(root.join("a/big.bin"), [0; 100]).unwrap();
+        fs::write(root.join("a/one.txt"), [b'x'; 10]).unwrap();
+
+        let entries: Vec<_> = Walk::new([root.join("a")]).with_max_filesize(10).collect();
+        let too_large = entries[0].as_ref().unwrap_err();
+        assert_eq!(too_large.path, root.join("a/big.bin"));
+        assert_eq!(too_large.error.kind(), io::ErrorKind::FileTooLarge);
+        assert_eq!(entries[1].as_ref().unwrap(), &root.join("a/one.txt"));
+        assert_eq!(entries.len(), 2);
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_walk_filters_on_modification_time() {
+        let root = fixture("searcher_walk_modified");
+        let now = SystemTime::now();
+        let old = fs::File::options()
+            .write(true)
+            .open(root.join("a/one.txt"))
+            .unwrap();
+        old.set_modified(now - std::time::Duration::from_secs(3600))
+            .unwrap();
+
+        let minute_ago = now - std::time::Duration::from_secs(60);
+        let recent = relative(&root, Walk::new([&root]).newer_than(minute_ago));
+        assert_eq!(recent, ["b/nested/deep.txt", "b/two.txt", "top.txt"]);
+        let stale = relative(&root, Walk::new([&root]).older_than(minute_ago));
+        assert_eq!(stale, ["a/one.txt"]);
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_one_file_system_walks_a_single_file_system_fully() {
+        let root = fixture("searcher_walk_one_file_system");
+        let files = relative(&root, Walk::new([&root]).one_file_system());
+
+        assert_eq!(
+            files,
+            ["a/one.txt", "b/nested/deep.txt", "b/two.txt", "top.txt"]
+        );
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_walk_runs_over_another_file_system() {
+        let fs = MemoryFs::new()
+            .with_file("tree/.searcherignore", "*.tmp\n")
+            .with_file("tree/b/big.log", [b'x'; 100])
+            .with_file("tree/b/scratch.tmp", "")
+            .with_file("tree/a/one.txt", "one")
+            .with_file("tree/skipped/two.txt", "two")
+            .with_dir("tree/empty");
+        let entries: Vec<_> = Walk::new(["tree", "tree/missing.txt"])
+            .with_fs(Arc::new(fs))
+            .ignore_files()
+            .with_max_filesize(10)
+            .skip_dir("./tree/skipped")
+            .collect();
+
+        assert_eq!(entries.len(), 4);
+        assert_eq!(
+            entries[0].as_ref().unwrap(),
+            Path::new("tree/.searcherignore")
+        );
+        assert_eq!(entries[1].as_ref().unwrap(), Path::new("tree/a/one.txt"));
+        let too_large = entries[2].as_ref().unwrap_err();
+        assert_eq!(too_large.path, Path::new("tree/b/big.log"));
+        assert_eq!(too_large.error.kind(), io::ErrorKind::FileTooLarge);
+        assert_eq!(entries[3].as_ref().unwrap(), Path::new("tree/missing.txt"));
+    }
+
+    #[test]
+    fn test_walk_yields_missing_files() {
+        let files: Vec<_> = Walk::new(["does/not/exist.txt"]).collect();
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].as_ref().unwrap(), Path::new("does/not/exist.txt"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_does_not_follow_directory_symlinks() {
+        let root = fixture("searcher_walk_symlink");
+        std::os::unix::fs::symlink(&root, root.join("a/loop")).unwrap();
+        std::os::unix::fs::symlink(root.join("top.txt"), root.join("a/link.txt")).unwrap();
+
+        let files = relative(&root, Walk::new([root.join("a")]));
+
+        assert_eq!(files, ["a/link.txt", "a/one.txt"]);
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_reports_unreadable_directory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = fixture("searcher_walk_unreadable");
+        let locked = root.join("b");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        let readable = fs::read_dir(&locked).is_ok(); // true when running as root
+
+        let entries: Vec<_> = Walk::new([&root]).collect();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+        if !readable {
+            let errors: Vec<_> = entries.iter().filter_map(|e| e.as_ref().err()).collect();
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].path, locked);
+            assert_eq!(entries.len(), 3);
+        }
+        fs::remove_dir_all(root).ok();
+    }
+}
@@ -0,0 +1,142 @@
+//! JavaScript bindings for browser-based tools.
+//!
+//! Available with the `wasm` cargo feature. Build the library for
+//! `wasm32-unknown-unknown` without the CLI and generate the JavaScript glue
+//! with `wasm-bindgen`:
+//!
+//! ```text
+//! cargo build --lib --target wasm32-unknown-unknown --no-default-features --features wasm
+//! ```
+//!
+//! From JavaScript, [`search`] takes the text, the pattern, and an optional
+//! options object, and returns an array of `{ lineNumber, content }` objects:
+//!
+//! ```text
+//! const matches = search(logText, "timeout|refused", { regex: true, ignoreCase: true });
+//! ```
+//!
+//! Only in-memory text can be searched there; the path-based functions need
+//! a filesystem, which the browser does not provide.
+
+use crate::query::{MatcherOptions, build_matcher};
+use crate::{LineTerminator, SearchMatchBytes, SearchOptions, search_reader};
+use anyhow::Result;
+use js_sys::{Array, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+/// The options understood by [`search`], read from a JavaScript object.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct WasmOptions {
+    ignore_case: bool,
+    regex: bool,
+    query: bool,
+    crlf: bool,
+}
+
+impl WasmOptions {
+    /// Reads the known boolean fields of `options`; missing fields are `false`.
+    fn from_js(options: &JsValue) -> Self {
+        let flag = |name: &str| {
+            options.is_object()
+                && Reflect::get(options, &JsValue::from_str(name))
+                    .ok()
+                    .and_then(|value| value.as_bool())
+                    .unwrap_or(false)
+        };
+        WasmOptions {
+            ignore_case: flag("ignoreCase"),
+            regex: flag("regex"),
+            query: flag("query"),
+            crlf: flag("crlf"),
+        }
+    }
+}
+
+/// Searches `text` for lines matching `pattern`.
+///
+/// `options` may be `undefined` or an object with any of the boolean fields
+/// `ignoreCase`, `regex`, `query` (boolean query syntax), and `crlf`.
+///
+/// # Errors
+///
+/// Throws a JavaScript `Error` if the pattern or query is invalid.
+#[wasm_bindgen]
+pub fn search(text: &str, pattern: &str, options: JsValue) -> Result<JsValue, JsError> {
+    let matches = search_text(text, pattern, &WasmOptions::from_js(&options))
+        .map_err(|error| JsError::new(&format!("{:#}", error)))?;
+
+    let results = Array::new();
+    for search_match in &matches {
+        let object = Object::new();
+        Reflect::set(
+            &object,
+            &JsValue::from_str("lineNumber"),
+            &JsValue::from_f64(search_match.line_number as f64),
+        )
+        .map_err(|_| JsError::new("Could not build result object"))?;
+        Reflect::set(
+            &object,
+            &JsValue::from_str("content"),
+            &JsValue::from_str(&search_match.content_lossy()),
+        )
+        .map_err(|_| JsError::new("Could not build result object"))?;
+        results.push(&object);
+    }
+    Ok(results.into())
+}
+
+/// The JavaScript-independent part of [`search`].
+fn search_text(text: &str, pattern: &str, options: &WasmOptions) -> Result<Vec<SearchMatchBytes>> {
+    let matcher = build_matcher(
+        pattern,
+        &MatcherOptions {
+            ignore_case: options.ignore_case,
+            regex: options.regex,
+            query: options.query,
+            ..MatcherOptions::default()
+        },
+    )?;
+    let search_options = SearchOptions {
+        line_terminator: if options.crlf {
+            LineTerminator::Crlf
+        } else {
+            LineTerminator::Lf
+        },
+        ..SearchOptions::default()
+    };
+    search_reader(text.as_bytes(), &matcher, &search_options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_text_options() {
+        let options = WasmOptions {
+            ignore_case: true,
+            query: true,
+            crlf: true,
+            ..WasmOptions::default()
+        };
+        let matches = search_text(
+            "ok\r\nERROR db\r\nerror cache\r\n",
+            "error AND db",
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line_number, 2);
+        assert_eq!(matches[0].content, b"ERROR db");
+    }
+
+    #[test]
+    fn test_search_text_invalid_pattern() {
+        let options = WasmOptions {
+            regex: true,
+            ..WasmOptions::default()
+        };
+        assert!(search_text("text", "(", &options).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! Telling new matches from ones already reported, for `searcher watch`.
+//!
+//! Watch mode searches a file again every time it changes. Printing every
+//! match each time would bury the interesting part, so a [`MatchTracker`]
+//! remembers what each file matched last time and hands back only the
+//! matches that were not there before.
+//!
+//! Matches are compared by content, not line number: inserting a line at the
+//! top of a file shifts every line below it, but those matches are not new.
+
+use crate::SearchMatchBytes;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Remembers the matches of each file between searches.
+///
+/// # Examples
+///
+/// ```
+/// use searcher_cli_starter::SearchMatchBytes;
+/// use searcher_cli_starter::watch::MatchTracker;
+/// use std::path::Path;
+///
+/// let line = |line_number, content: &str| SearchMatchBytes {
+///     line_number,
+///     content: content.as_bytes().to_vec(),
+/// };
+/// let mut tracker = MatchTracker::new();
+/// let path = Path::new("app.log");
+///
+/// tracker.update(path, vec![line(1, "error: a")]);
+/// let new = tracker.update(path, vec![line(2, "error: a"), line(3, "error: b")]);
+///
+/// assert_eq!(new, [line(3, "error: b")]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MatchTracker {
+    /// For each file, how many times each matching line occurred
+    seen: HashMap<PathBuf, HashMap<Vec<u8>, usize>>,
+}
+
+impl MatchTracker {
+    /// Creates a tracker that has not seen any file.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the current matches of `path`, returning those that are new
+    /// since the last update for the same path.
+    ///
+    /// A line that occurs more often than before counts as new for each
+    /// extra occurrence. Every match of a file seen for the first time is new.
+    pub fn update(&mut self, path: &Path, matches: Vec<SearchMatchBytes>) -> Vec<SearchMatchBytes> {
+        let mut previous = self.seen.remove(path).unwrap_or_default();
+        let mut current: HashMap<Vec<u8>, usize> = HashMap::new();
+        let mut new = Vec::new();
+
+        for search_match in matches {
+            *current.entry(search_match.content.clone()).or_default() += 1;
+            match previous.get_mut(&search_match.content) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => new.push(search_match),
+            }
+        }
+
+        self.seen.insert(path.to_path_buf(), current);
+        new
+    }
+
+    /// Forgets `path`, e.g. after it was deleted, so that all its matches
+    /// are new if it comes back.
+    pub fn forget(&mut self, path: &Path) {
+        self.seen.remove(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(contents: &[&str]) -> Vec<SearchMatchBytes> {
+        contents
+            .iter()
+            .enumerate()
+            .map(|(i, content)| SearchMatchBytes {
+                line_number: i + 1,
+                content: content.as_bytes().to_vec(),
+            })
+            .collect()
+    }
+
+    fn contents(matches: &[SearchMatchBytes]) -> Vec<String> {
+        matches
+            .iter()
+            .map(|m| m.content_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_update_reports_only_new_lines() {
+        let mut tracker = MatchTracker::new();
+        let path = Path::new("app.log");
+
+        assert_eq!(
+            contents(&tracker.update(path, lines(&["a", "b"]))),
+            ["a", "b"]
+        );
+        assert!(tracker.update(path, lines(&["b", "a"])).is_empty());
+        assert_eq!(
+            contents(&tracker.update(path, lines(&["a", "b", "a"]))),
+            ["a"]
+        );
+        assert_eq!(contents(&tracker.update(path, lines(&["c"]))), ["c"]);
+    }
+
+    #[test]
+    fn test_files_are_tracked_separately() {
+        let mut tracker = MatchTracker::new();
+        tracker.update(Path::new("one.log"), lines(&["a"]));
+
+        assert_eq!(
+            contents(&tracker.update(Path::new("two.log"), lines(&["a"]))),
+            ["a"]
+        );
+
+        tracker.forget(Path::new("one.log"));
+        assert_eq!(
+            contents(&tracker.update(Path::new("one.log"), lines(&["a"]))),
+            ["a"]
+        );
+    }
+}